@@ -0,0 +1,198 @@
+//! Classification of columns holding sensitive data, e.g. personally identifiable information.
+//!
+//! Columns are tagged in their comment, with `@pii`, `@secret`, `@internal` or any other
+//! `@name`, or in a sidecar file listing one column per line:
+//!
+//! ```text
+//! # table.column: classifications
+//! customer.email: pii
+//! customer.api_key: secret, internal
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Classification {
+    /// Personally identifiable information
+    Pii,
+    /// Credentials and keys
+    Secret,
+    /// Data not to be shared outside the organization
+    Internal,
+    Other(String),
+}
+
+/// Classifications of the columns of a schema, by table and column
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Classifications {
+    columns: BTreeMap<(String, String), BTreeSet<Classification>>,
+}
+
+impl Classification {
+    /// Parse a classification name, case-insensitively
+    pub fn from_name(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "pii" => Self::Pii,
+            "secret" => Self::Secret,
+            "internal" => Self::Internal,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+
+    /// Classifications tagged in a column comment as `@name` words
+    pub fn from_comment(comment: &str) -> Vec<Self> {
+        let mut classifications = Vec::new();
+        for word in comment.split_whitespace() {
+            let name = match word.strip_prefix('@') {
+                Some(name) => name.trim_end_matches(|c: char| !c.is_alphanumeric()),
+                None => continue,
+            };
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+            let classification = Self::from_name(name);
+            if valid && !classifications.contains(&classification) {
+                classifications.push(classification);
+            }
+        }
+        classifications
+    }
+}
+
+impl Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pii => write!(f, "pii"),
+            Self::Secret => write!(f, "secret"),
+            Self::Internal => write!(f, "internal"),
+            Self::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Classifications {
+    /// Tag a column with a classification
+    pub fn tag<T, C>(mut self, table: T, column: C, classification: Classification) -> Self
+    where
+        T: Into<String>,
+        C: Into<String>,
+    {
+        self.columns
+            .entry((table.into(), column.into()))
+            .or_default()
+            .insert(classification);
+        self
+    }
+
+    /// Parse a sidecar file of `table.column: classification, ...` lines,
+    /// ignoring blank lines and lines starting with `#`
+    pub fn parse(source: &str) -> std::io::Result<Self> {
+        let mut classifications = Self::default();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line.split_once(':').and_then(|(column, tags)| {
+                let (table, column) = column.trim().rsplit_once('.')?;
+                Some((table.trim(), column.trim(), tags))
+            });
+            let (table, column, tags) = match parsed {
+                Some((table, column, tags)) if !table.is_empty() && !column.is_empty() => {
+                    (table, column, tags)
+                }
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "Line {}: expected `table.column: classification, ...`, found '{}'",
+                            i + 1,
+                            line
+                        ),
+                    ))
+                }
+            };
+            for tag in tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+                classifications =
+                    classifications.tag(table, column, Classification::from_name(tag));
+            }
+        }
+        Ok(classifications)
+    }
+
+    /// Read a sidecar file, see [`Classifications::parse`]
+    pub fn from_file<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Tags of both, e.g. the ones read from comments and the ones of a sidecar file
+    pub fn merge(mut self, other: Self) -> Self {
+        for (column, classifications) in other.columns {
+            self.columns
+                .entry(column)
+                .or_default()
+                .extend(classifications);
+        }
+        self
+    }
+
+    /// Classifications of a column, empty if unclassified
+    pub fn get(&self, table: &str, column: &str) -> Vec<Classification> {
+        self.columns
+            .get(&(table.to_owned(), column.to_owned()))
+            .map(|classifications| classifications.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Classified columns of a table, with their classifications
+    pub fn table(&self, table: &str) -> Vec<(String, Vec<Classification>)> {
+        self.columns
+            .iter()
+            .filter(|((t, _), _)| t == table)
+            .map(|((_, column), classifications)| {
+                (column.clone(), classifications.iter().cloned().collect())
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_comment() {
+        assert_eq!(
+            Classification::from_comment("Email of the customer @PII, see @gdpr-art17."),
+            vec![
+                Classification::Pii,
+                Classification::Other("gdpr-art17".to_owned())
+            ]
+        );
+        assert!(Classification::from_comment("Contact at support@example.com").is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        let classifications = Classifications::parse(
+            "# sensitive columns\ncustomer.email: pii\n\ncustomer.api_key: secret, internal\n",
+        )
+        .unwrap();
+        assert_eq!(
+            classifications.get("customer", "api_key"),
+            vec![Classification::Secret, Classification::Internal]
+        );
+        assert_eq!(classifications.table("customer").len(), 2);
+        assert!(Classifications::parse("customer: pii").is_err());
+    }
+}
@@ -0,0 +1,538 @@
+//! Render a discovered schema into browsable Markdown or HTML pages, one per table, cross-linked
+//! through their foreign keys. Build a [`SchemaDocs`] from the schema discovered on any backend,
+//! e.g. with `SchemaDocs::from(&schema)`, or export it as a flat data dictionary.
+
+use crate::classification::{Classification, Classifications};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Documented schema, independent of the backend it was discovered on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchemaDocs {
+    pub name: String,
+    pub tables: Vec<TableDocs>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableDocs {
+    pub name: String,
+    pub comment: Option<String>,
+    pub columns: Vec<ColumnDocs>,
+    pub indexes: Vec<IndexDocs>,
+    pub foreign_keys: Vec<ForeignKeyDocs>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColumnDocs {
+    pub name: String,
+    pub col_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub comment: Option<String>,
+    pub classifications: Vec<Classification>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexDocs {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub primary: bool,
+    pub unique: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ForeignKeyDocs {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    pub on_update: Option<String>,
+    pub on_delete: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+/// Flat data dictionary format, see [`SchemaDocs::data_dictionary`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DictionaryFormat {
+    /// Comma-separated values, quoted as in RFC 4180
+    Csv,
+    /// Tab-separated values, tabs and line breaks in values replaced with spaces
+    Tsv,
+}
+
+/// A rendered page, at `path` relative to the root of the site
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocPage {
+    pub path: String,
+    pub content: String,
+}
+
+impl DocFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl SchemaDocs {
+    /// Add the classifications of a sidecar file to the ones tagged in column comments
+    pub fn classify(mut self, classifications: &Classifications) -> Self {
+        for table in self.tables.iter_mut() {
+            for column in table.columns.iter_mut() {
+                for classification in classifications.get(&table.name, &column.name) {
+                    if !column.classifications.contains(&classification) {
+                        column.classifications.push(classification);
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Render the index page listing the tables, then one page per table
+    pub fn render(&self, format: DocFormat) -> Vec<DocPage> {
+        let mut pages = vec![DocPage {
+            path: format!("index.{}", format.extension()),
+            content: self.render_index(format),
+        }];
+        for table in self.tables.iter() {
+            pages.push(DocPage {
+                path: page_path(&table.name, format),
+                content: self.render_table(table, format),
+            });
+        }
+        pages
+    }
+
+    /// Render the pages into `dir`, creating it if needed
+    pub fn write_to<P>(&self, dir: P, format: DocFormat) -> std::io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        for page in self.render(format) {
+            std::fs::write(dir.join(page.path), page.content)?;
+        }
+        Ok(())
+    }
+
+    /// Export one row per column, with the table, column, type, nullability, default and comment,
+    /// preceded by a header row
+    pub fn data_dictionary(&self, format: DictionaryFormat) -> String {
+        let mut dictionary = String::new();
+        let mut push_row = |row: [&str; 7]| {
+            let row: Vec<String> = row
+                .iter()
+                .map(|field| match format {
+                    DictionaryFormat::Csv => {
+                        if field.contains([',', '"', '\n', '\r']) {
+                            format!("\"{}\"", field.replace('"', "\"\""))
+                        } else {
+                            field.to_string()
+                        }
+                    }
+                    DictionaryFormat::Tsv => field.replace(['\t', '\n', '\r'], " "),
+                })
+                .collect();
+            let separator = match format {
+                DictionaryFormat::Csv => ",",
+                DictionaryFormat::Tsv => "\t",
+            };
+            dictionary.push_str(&row.join(separator));
+            dictionary.push_str("\r\n");
+        };
+        push_row([
+            "table",
+            "column",
+            "type",
+            "nullable",
+            "default",
+            "comment",
+            "classification",
+        ]);
+        for table in self.tables.iter() {
+            for column in table.columns.iter() {
+                push_row([
+                    &table.name,
+                    &column.name,
+                    &column.col_type,
+                    if column.nullable { "yes" } else { "no" },
+                    column.default.as_deref().unwrap_or_default(),
+                    column.comment.as_deref().unwrap_or_default(),
+                    &classification_list(&column.classifications),
+                ]);
+            }
+        }
+        dictionary
+    }
+
+    fn render_index(&self, format: DocFormat) -> String {
+        let mut page = Page::new(format, &format!("Schema {}", self.name));
+        page.table(
+            &["Table", "Columns", "Comment"],
+            self.tables
+                .iter()
+                .map(|table| {
+                    vec![
+                        page.link(&table.name, &page_path(&table.name, format)),
+                        page.text(&table.columns.len().to_string()),
+                        page.text(table.comment.as_deref().unwrap_or_default()),
+                    ]
+                })
+                .collect(),
+        );
+        page.finish()
+    }
+
+    fn render_table(&self, table: &TableDocs, format: DocFormat) -> String {
+        let mut page = Page::new(format, &table.name);
+        page.paragraph(&page.link(
+            &format!("Schema {}", self.name),
+            &format!("index.{}", format.extension()),
+        ));
+        if let Some(comment) = &table.comment {
+            page.paragraph(&page.text(comment));
+        }
+
+        page.heading("Columns");
+        page.table(
+            &[
+                "Column",
+                "Type",
+                "Nullable",
+                "Default",
+                "Comment",
+                "Classification",
+            ],
+            table
+                .columns
+                .iter()
+                .map(|column| {
+                    vec![
+                        page.code(&column.name),
+                        page.code(&column.col_type),
+                        page.text(if column.nullable { "yes" } else { "no" }),
+                        column
+                            .default
+                            .as_deref()
+                            .map(|default| page.code(default))
+                            .unwrap_or_default(),
+                        page.text(column.comment.as_deref().unwrap_or_default()),
+                        page.text(&classification_list(&column.classifications)),
+                    ]
+                })
+                .collect(),
+        );
+
+        if !table.indexes.is_empty() {
+            page.heading("Indexes");
+            page.table(
+                &["Index", "Columns", "Kind"],
+                table
+                    .indexes
+                    .iter()
+                    .map(|index| {
+                        let kind = match (index.primary, index.unique) {
+                            (true, _) => "primary key",
+                            (false, true) => "unique",
+                            (false, false) => "",
+                        };
+                        vec![
+                            page.code(&index.name),
+                            page.code(&index.columns.join(", ")),
+                            page.text(kind),
+                        ]
+                    })
+                    .collect(),
+            );
+        }
+
+        if !table.foreign_keys.is_empty() {
+            page.heading("Foreign Keys");
+            page.table(
+                &[
+                    "Foreign Key",
+                    "Columns",
+                    "References",
+                    "On Update",
+                    "On Delete",
+                ],
+                table
+                    .foreign_keys
+                    .iter()
+                    .map(|foreign_key| {
+                        vec![
+                            page.code(&foreign_key.name),
+                            page.code(&foreign_key.columns.join(", ")),
+                            self.table_link(
+                                &page,
+                                &foreign_key.referenced_table,
+                                &foreign_key.referenced_columns,
+                            ),
+                            page.text(foreign_key.on_update.as_deref().unwrap_or_default()),
+                            page.text(foreign_key.on_delete.as_deref().unwrap_or_default()),
+                        ]
+                    })
+                    .collect(),
+            );
+        }
+
+        let referenced_by: Vec<Vec<String>> = self
+            .tables
+            .iter()
+            .flat_map(|other| {
+                other
+                    .foreign_keys
+                    .iter()
+                    .filter(|foreign_key| foreign_key.referenced_table == table.name)
+                    .map(|foreign_key| {
+                        vec![
+                            self.table_link(&page, &other.name, &foreign_key.columns),
+                            page.code(&foreign_key.referenced_columns.join(", ")),
+                        ]
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if !referenced_by.is_empty() {
+            page.heading("Referenced By");
+            page.table(&["Table", "Columns"], referenced_by);
+        }
+        page.finish()
+    }
+
+    /// Link to the page of a table, or its plain name if it is not documented, e.g. in another schema
+    fn table_link(&self, page: &Page, table: &str, columns: &[String]) -> String {
+        let label = format!("{} ({})", table, columns.join(", "));
+        match self.tables.iter().any(|t| t.name == table) {
+            true => page.link(&label, &page_path(table, page.format)),
+            false => page.text(&label),
+        }
+    }
+}
+
+fn classification_list(classifications: &[Classification]) -> String {
+    classifications
+        .iter()
+        .map(|classification| classification.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn page_path(table: &str, format: DocFormat) -> String {
+    let name: String = table
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    format!("{}.{}", name, format.extension())
+}
+
+/// Page being rendered in either format
+struct Page {
+    format: DocFormat,
+    content: String,
+}
+
+impl Page {
+    fn new(format: DocFormat, title: &str) -> Self {
+        let mut page = Self {
+            format,
+            content: String::new(),
+        };
+        let title_text = page.text(title);
+        match format {
+            DocFormat::Markdown => {
+                let _ = write!(page.content, "# {}\n\n", title_text);
+            }
+            DocFormat::Html => {
+                let _ = write!(
+                    page.content,
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+                    title_text
+                );
+            }
+        }
+        page
+    }
+
+    fn heading(&mut self, heading: &str) {
+        let heading = self.text(heading);
+        let _ = match self.format {
+            DocFormat::Markdown => write!(self.content, "## {}\n\n", heading),
+            DocFormat::Html => writeln!(self.content, "<h2>{}</h2>", heading),
+        };
+    }
+
+    /// A paragraph of already rendered content
+    fn paragraph(&mut self, content: &str) {
+        let _ = match self.format {
+            DocFormat::Markdown => write!(self.content, "{}\n\n", content),
+            DocFormat::Html => writeln!(self.content, "<p>{}</p>", content),
+        };
+    }
+
+    /// A table of already rendered cells
+    fn table(&mut self, headers: &[&str], rows: Vec<Vec<String>>) {
+        match self.format {
+            DocFormat::Markdown => {
+                let _ = writeln!(self.content, "| {} |", headers.join(" | "));
+                let _ = writeln!(self.content, "|{}", " --- |".repeat(headers.len()));
+                for row in rows.iter() {
+                    let _ = writeln!(self.content, "| {} |", row.join(" | "));
+                }
+                self.content.push('\n');
+            }
+            DocFormat::Html => {
+                self.content.push_str("<table>\n<tr>");
+                for header in headers.iter() {
+                    let _ = write!(self.content, "<th>{}</th>", header);
+                }
+                self.content.push_str("</tr>\n");
+                for row in rows.iter() {
+                    self.content.push_str("<tr>");
+                    for cell in row.iter() {
+                        let _ = write!(self.content, "<td>{}</td>", cell);
+                    }
+                    self.content.push_str("</tr>\n");
+                }
+                self.content.push_str("</table>\n");
+            }
+        }
+    }
+
+    fn text(&self, text: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => text
+                .replace('\\', "\\\\")
+                .replace('|', "\\|")
+                .replace('\n', " "),
+            DocFormat::Html => escape_html(text),
+        }
+    }
+
+    fn code(&self, code: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => format!("`{}`", code.replace('|', "\\|").replace('\n', " ")),
+            DocFormat::Html => format!("<code>{}</code>", escape_html(code)),
+        }
+    }
+
+    fn link(&self, label: &str, href: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => format!("[{}]({})", self.text(label), href.replace(' ', "%20")),
+            DocFormat::Html => format!(
+                "<a href=\"{}\">{}</a>",
+                escape_html(href),
+                escape_html(label)
+            ),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.format == DocFormat::Html {
+            self.content.push_str("</body>\n</html>\n");
+        }
+        self.content
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_dictionary() {
+        let docs = SchemaDocs {
+            name: "bakery".to_owned(),
+            tables: vec![TableDocs {
+                name: "cake".to_owned(),
+                columns: vec![ColumnDocs {
+                    name: "name".to_owned(),
+                    col_type: "varchar(255)".to_owned(),
+                    nullable: true,
+                    default: Some("'plain'".to_owned()),
+                    comment: Some("Name, as \"shown\"\tto customers".to_owned()),
+                    classifications: vec![Classification::Pii],
+                }],
+                ..Default::default()
+            }],
+        };
+        assert_eq!(
+            docs.data_dictionary(DictionaryFormat::Csv),
+            "table,column,type,nullable,default,comment,classification\r\ncake,name,varchar(255),yes,'plain',\"Name, as \"\"shown\"\"\tto customers\",pii\r\n"
+        );
+        assert_eq!(
+            docs.data_dictionary(DictionaryFormat::Tsv),
+            "table\tcolumn\ttype\tnullable\tdefault\tcomment\tclassification\r\ncake\tname\tvarchar(255)\tyes\t'plain'\tName, as \"shown\" to customers\tpii\r\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let docs = SchemaDocs {
+            name: "bakery".to_owned(),
+            tables: vec![
+                TableDocs {
+                    name: "cake".to_owned(),
+                    comment: Some("Cakes | pastries".to_owned()),
+                    columns: vec![ColumnDocs {
+                        name: "id".to_owned(),
+                        col_type: "int".to_owned(),
+                        nullable: false,
+                        default: None,
+                        comment: None,
+                        classifications: Vec::new(),
+                    }],
+                    ..Default::default()
+                },
+                TableDocs {
+                    name: "slice".to_owned(),
+                    foreign_keys: vec![ForeignKeyDocs {
+                        name: "fk-slice-cake".to_owned(),
+                        columns: vec!["cake_id".to_owned()],
+                        referenced_table: "cake".to_owned(),
+                        referenced_columns: vec!["id".to_owned()],
+                        on_update: None,
+                        on_delete: Some("CASCADE".to_owned()),
+                    }],
+                    ..Default::default()
+                },
+            ],
+        };
+        let pages = docs.render(DocFormat::Markdown);
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| page.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["index.md", "cake.md", "slice.md"]
+        );
+        assert!(pages[0]
+            .content
+            .contains("| [cake](cake.md) | 1 | Cakes \\| pastries |"));
+        assert!(pages[1]
+            .content
+            .contains("## Referenced By\n\n| Table | Columns |\n| --- | --- |\n| [slice (cake_id)](slice.md) | `id` |"));
+        assert!(pages[2]
+            .content
+            .contains("| `fk-slice-cake` | `cake_id` | [cake (id)](cake.md) |  | CASCADE |"));
+    }
+}
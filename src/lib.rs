@@ -16,6 +16,12 @@ pub mod sqlite;
 #[cfg_attr(docsrs, doc(cfg(feature = "migration")))]
 pub mod migration;
 
+pub mod classification;
+
+#[cfg(feature = "writer")]
+#[cfg_attr(docsrs, doc(cfg(feature = "writer")))]
+pub mod docs;
+
 pub use sea_query;
 
 pub(crate) mod parser;
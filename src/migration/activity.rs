@@ -0,0 +1,84 @@
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::fmt::Display;
+
+/// A session connected to the database server, as reported by the backend
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionActivity {
+    /// Backend process id (Postgres) or connection id (MySQL)
+    pub id: i64,
+    /// Current state of the session, e.g. `active` or `Waiting for table metadata lock`
+    pub state: Option<String>,
+    /// What the session is waiting on, if anything
+    pub wait_event: Option<String>,
+    /// Sessions blocking this session, as reported by the backend
+    pub blocked_by: Option<String>,
+    /// Seconds since the current transaction (Postgres) or command (MySQL) started
+    pub elapsed_secs: Option<i64>,
+    /// The statement being executed, truncated
+    pub query: Option<String>,
+}
+
+impl Display for SessionActivity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "session {}", self.id)?;
+        if let Some(state) = &self.state {
+            write!(f, " [{}]", state)?;
+        }
+        if let Some(wait_event) = &self.wait_event {
+            write!(f, " waiting on {}", wait_event)?;
+        }
+        if let Some(blocked_by) = &self.blocked_by {
+            write!(f, " blocked by {}", blocked_by)?;
+        }
+        if let Some(elapsed_secs) = self.elapsed_secs {
+            write!(f, " for {}s", elapsed_secs)?;
+        }
+        if let Some(query) = &self.query {
+            write!(f, ": {}", query)?;
+        }
+        Ok(())
+    }
+}
+
+/// List the other sessions that are currently doing work on the database server.
+/// SQLite has no notion of server sessions, an empty list is returned.
+pub async fn session_activity(db: &DbConn) -> Result<Vec<SessionActivity>, DbErr> {
+    let db_backend = db.get_database_backend();
+    let sql = match db_backend {
+        DbBackend::MySql => {
+            r#"SELECT CAST(`ID` AS SIGNED) AS `id`, `STATE` AS `state`, NULL AS `wait_event`,
+                NULL AS `blocked_by`, CAST(`TIME` AS SIGNED) AS `elapsed_secs`,
+                LEFT(`INFO`, 200) AS `query`
+            FROM `information_schema`.`PROCESSLIST`
+            WHERE `COMMAND` <> 'Sleep' AND `ID` <> CONNECTION_ID()
+            ORDER BY `TIME` DESC"#
+        }
+        DbBackend::Postgres => {
+            r#"SELECT "pid"::bigint AS "id", "state",
+                "wait_event_type" || ':' || "wait_event" AS "wait_event",
+                NULLIF(pg_blocking_pids("pid")::text, '{}') AS "blocked_by",
+                EXTRACT(EPOCH FROM now() - "xact_start")::bigint AS "elapsed_secs",
+                LEFT("query", 200) AS "query"
+            FROM "pg_stat_activity"
+            WHERE "datname" = current_database() AND "pid" <> pg_backend_pid()
+                AND "state" IS DISTINCT FROM 'idle'
+            ORDER BY "xact_start""#
+        }
+        DbBackend::Sqlite => return Ok(Vec::new()),
+    };
+    let rows = db
+        .query_all(Statement::from_string(db_backend, sql.to_owned()))
+        .await?;
+    let mut sessions = Vec::new();
+    for row in rows.into_iter() {
+        sessions.push(SessionActivity {
+            id: row.try_get("", "id")?,
+            state: row.try_get("", "state")?,
+            wait_event: row.try_get("", "wait_event")?,
+            blocked_by: row.try_get("", "blocked_by")?,
+            elapsed_secs: row.try_get("", "elapsed_secs")?,
+            query: row.try_get("", "query")?,
+        });
+    }
+    Ok(sessions)
+}
@@ -0,0 +1,300 @@
+use super::{json_string, MigratorTrait};
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::sync::Mutex;
+use sea_orm::{DbConn, DbErr};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Environment variable holding the token of the agent API, read by the `agent` subcommand
+pub const AGENT_TOKEN_VAR: &str = "SEA_SCHEMA_AGENT_TOKEN";
+
+/// Largest request head the agent accepts
+const MAX_REQUEST_LEN: usize = 16 * 1024;
+
+/// How long a client has to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Remote execution agent, serving a small HTTP API so that deployment systems can plan and
+/// apply the migrations against the configured database without holding its credentials.
+///
+/// Every request must carry an `Authorization: Bearer <token>` header and no body. The
+/// endpoints answer with a JSON object:
+/// - `GET /status`: `migrations`, each with its `name`, `status` (`applied` or `pending`) and
+///   the `warnings` it emitted when applied
+/// - `GET /plan`: the plan of the pending migrations, see [`Plan::to_json`](super::Plan::to_json),
+///   read without installing or upgrading the history table
+/// - `POST /apply`: apply the pending migrations, answering the `applied` ones
+///
+/// Failures are answered as `{"error": "..."}`. Each connection is served by its own task, and
+/// applies wait for one another so that two never run concurrently. The API is plain HTTP, put it behind a TLS-terminating
+/// proxy when it is reachable beyond localhost.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Agent {
+    /// Address to listen on, e.g. `127.0.0.1:8750`
+    pub listen: String,
+    /// Bearer token every request must carry, redacted from the `Debug` output
+    pub token: String,
+}
+
+/// The parts of a request the agent looks at
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+}
+
+impl std::fmt::Debug for Agent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Agent")
+            .field("listen", &self.listen)
+            .field("token", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Agent {
+    pub fn new<L, T>(listen: L, token: T) -> Self
+    where
+        L: Into<String>,
+        T: Into<String>,
+    {
+        Self {
+            listen: listen.into(),
+            token: token.into(),
+        }
+    }
+
+    /// Serve the API until the listener fails
+    pub async fn serve<M>(&self, db: &DbConn) -> Result<(), DbErr>
+    where
+        M: MigratorTrait + 'static,
+    {
+        if self.token.is_empty() {
+            return Err(DbErr::Custom(
+                "The agent token must not be empty".to_owned(),
+            ));
+        }
+        let listener = TcpListener::bind(&self.listen)
+            .await
+            .map_err(|err| DbErr::Custom(format!("Cannot listen on {}: {}", self.listen, err)))?;
+        info!("Agent listening on {}", self.listen);
+        let agent = Arc::new(self.clone());
+        let apply_lock = Arc::new(Mutex::new(()));
+        loop {
+            let (stream, peer) = listener
+                .accept()
+                .await
+                .map_err(|err| DbErr::Custom(format!("Cannot accept a connection: {}", err)))?;
+            let (agent, db, apply_lock) = (agent.clone(), db.clone(), apply_lock.clone());
+            async_std::task::spawn(async move {
+                if let Err(err) = agent.handle::<M>(&db, &apply_lock, stream).await {
+                    warn!("Agent request from {} failed: {}", peer, err);
+                }
+            });
+        }
+    }
+
+    async fn handle<M>(
+        &self,
+        db: &DbConn,
+        apply_lock: &Mutex<()>,
+        mut stream: TcpStream,
+    ) -> std::io::Result<()>
+    where
+        M: MigratorTrait,
+    {
+        let head = async_std::io::timeout(READ_TIMEOUT, read_head(&mut stream)).await?;
+        let (status, body) = match parse_request(&head) {
+            Some(request) => self.respond::<M>(db, apply_lock, &request).await,
+            None => (400, error_json("Malformed request")),
+        };
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.flush().await
+    }
+
+    async fn respond<M>(
+        &self,
+        db: &DbConn,
+        apply_lock: &Mutex<()>,
+        request: &Request,
+    ) -> (u16, String)
+    where
+        M: MigratorTrait,
+    {
+        if !self.authorized(request.authorization.as_deref()) {
+            return (401, error_json("Unauthorized"));
+        }
+        info!("Agent request {} {}", request.method, request.path);
+        let res = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/status") => status_json::<M>(db).await,
+            ("GET", "/plan") => M::up_plan(db).await.map(|plan| plan.to_json()),
+            ("POST", "/apply") => {
+                let _guard = apply_lock.lock().await;
+                apply_json::<M>(db).await
+            }
+            (_, "/status" | "/plan" | "/apply") => {
+                return (405, error_json("Method not allowed"));
+            }
+            _ => return (404, error_json("Not found")),
+        };
+        match res {
+            Ok(body) => (200, body),
+            Err(err) => {
+                warn!(
+                    "Agent request {} {} failed: {}",
+                    request.method, request.path, err
+                );
+                (500, error_json(&err.to_string()))
+            }
+        }
+    }
+
+    /// Compare the bearer token in constant time
+    fn authorized(&self, authorization: Option<&str>) -> bool {
+        let token = match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+            Some(token) => token.trim().as_bytes(),
+            None => return false,
+        };
+        let expected = self.token.as_bytes();
+        token.len() == expected.len()
+            && token
+                .iter()
+                .zip(expected.iter())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+async fn status_json<M>(db: &DbConn) -> Result<String, DbErr>
+where
+    M: MigratorTrait,
+{
+    let migrations = M::read_migration_with_status(db).await?;
+    let warnings = M::history_store().warnings(db).await?;
+    let migrations: Vec<String> = migrations
+        .iter()
+        .map(|migration| {
+            let warnings: Vec<String> = warnings
+                .get(migration.name())
+                .into_iter()
+                .flatten()
+                .map(|warning| json_string(warning))
+                .collect();
+            format!(
+                r#"{{"name":{},"status":{},"warnings":[{}]}}"#,
+                json_string(migration.name()),
+                json_string(&migration.status().to_string().to_lowercase()),
+                warnings.join(",")
+            )
+        })
+        .collect();
+    Ok(format!(r#"{{"migrations":[{}]}}"#, migrations.join(",")))
+}
+
+async fn apply_json<M>(db: &DbConn) -> Result<String, DbErr>
+where
+    M: MigratorTrait,
+{
+    let before: HashSet<String> = M::get_applied_migrations(db)
+        .await?
+        .iter()
+        .map(|migration| migration.name().to_owned())
+        .collect();
+    M::up(db, None).await?;
+    // `up` may stop before the last pending migration, answer only those it applied
+    let applied: Vec<String> = M::get_applied_migrations(db)
+        .await?
+        .iter()
+        .filter(|migration| !before.contains(migration.name()))
+        .map(|migration| json_string(migration.name()))
+        .collect();
+    Ok(format!(r#"{{"applied":[{}]}}"#, applied.join(",")))
+}
+
+fn error_json(error: &str) -> String {
+    format!(r#"{{"error":{}}}"#, json_string(error))
+}
+
+/// Read the request line and headers, up to the blank line ending them
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 || head.len() + n > MAX_REQUEST_LEN {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// Parse the method, the path without query string, and the `Authorization` header
+fn parse_request(head: &str) -> Option<Request> {
+    let head = head.split("\r\n\r\n").next()?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_owned();
+    let target = request_line.next()?;
+    if !request_line.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    let path = target.split('?').next()?.to_owned();
+    let authorization = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .map(|(_, value)| value.trim().to_owned());
+    Some(Request {
+        method,
+        path,
+        authorization,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = parse_request(
+            "POST /apply?wait=1 HTTP/1.1\r\nHost: db\r\nauthorization: Bearer s3cret\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request {
+                method: "POST".to_owned(),
+                path: "/apply".to_owned(),
+                authorization: Some("Bearer s3cret".to_owned()),
+            }
+        );
+        assert_eq!(parse_request("GET /status\r\n\r\n"), None);
+
+        let agent = Agent::new("127.0.0.1:8750", "s3cret");
+        assert!(agent.authorized(request.authorization.as_deref()));
+        assert!(!agent.authorized(Some("Bearer s3cre")));
+        assert!(!agent.authorized(Some("Basic s3cret")));
+        assert!(!agent.authorized(None));
+        assert!(!format!("{:?}", agent).contains("s3cret"));
+    }
+}
@@ -0,0 +1,448 @@
+use super::{seaql_migrations, ColumnInfo};
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{DbBackend, Statement};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// Key of the `seaql_schema_meta` entry recording the format of `seaql_migrations.applied_at`
+pub const META_APPLIED_AT_FORMAT: &str = "applied_at_format";
+
+/// Integer `applied_at` values from this one on are in milliseconds: in seconds it is in the
+/// year 5138, in milliseconds in 1973
+const MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+/// How `seaql_migrations.applied_at` is stored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AppliedAtFormat {
+    /// Unix timestamp in seconds stored as a big integer, the layout of earlier versions
+    #[default]
+    UnixSeconds,
+    /// Unix timestamp in milliseconds stored as a big integer
+    UnixMillis,
+    /// Native timestamp column with millisecond precision.
+    /// [`seaql_migrations::Model::applied_at`](super::seaql_migrations::Model) reads as unix milliseconds.
+    Timestamp,
+}
+
+impl AppliedAtFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnixSeconds => "unix_seconds",
+            Self::UnixMillis => "unix_millis",
+            Self::Timestamp => "timestamp",
+        }
+    }
+
+    /// Units of a unix timestamp per second, the timestamp column is read in milliseconds
+    fn units_per_sec(&self) -> i64 {
+        match self {
+            Self::UnixSeconds => 1,
+            Self::UnixMillis | Self::Timestamp => 1000,
+        }
+    }
+
+    /// The current time, as read from `seaql_migrations.applied_at`
+    pub fn now(&self) -> i64 {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("SystemTime before UNIX EPOCH!");
+        match self {
+            Self::UnixSeconds => now.as_secs() as i64,
+            Self::UnixMillis | Self::Timestamp => now.as_millis() as i64,
+        }
+    }
+
+    /// Convert `applied_at` as read in this format to the units read in `target`
+    pub fn convert(&self, applied_at: i64, target: AppliedAtFormat) -> i64 {
+        applied_at * target.units_per_sec() / self.units_per_sec()
+    }
+
+    /// Expression reading `applied_at` as a unix timestamp
+    pub(crate) fn select_expr(&self, db_backend: DbBackend) -> SimpleExpr {
+        let col = quote(db_backend, "applied_at");
+        match self {
+            Self::UnixSeconds | Self::UnixMillis => Expr::cust(&col),
+            Self::Timestamp => Expr::cust(&from_timestamp(db_backend, &col, 1000)),
+        }
+    }
+
+    /// Statement inserting a migration record unless a record of the same version exists,
+    /// affecting no rows in that case
+    pub(crate) fn claim_stmt(
+        &self,
+        db_backend: DbBackend,
+        record: &seaql_migrations::Model,
+    ) -> Statement {
+        let mut stmt = self.insert_stmt(db_backend, record);
+        stmt.sql = match db_backend {
+            DbBackend::MySql => stmt.sql.replacen("INSERT INTO", "INSERT IGNORE INTO", 1),
+            DbBackend::Postgres | DbBackend::Sqlite => format!(
+                "{} ON CONFLICT ({}) DO NOTHING",
+                stmt.sql,
+                quote(db_backend, "version")
+            ),
+        };
+        stmt
+    }
+
+    /// Statement inserting a migration record, `applied_at` as returned by [`AppliedAtFormat::now`]
+    pub(crate) fn insert_stmt(
+        &self,
+        db_backend: DbBackend,
+        record: &seaql_migrations::Model,
+    ) -> Statement {
+        let param = |i: usize| match db_backend {
+            DbBackend::Postgres => format!("${}", i),
+            DbBackend::MySql | DbBackend::Sqlite => "?".to_owned(),
+        };
+        let applied_at_expr = match self {
+            Self::UnixSeconds | Self::UnixMillis => param(2),
+            Self::Timestamp => to_timestamp(db_backend, &param(2), 1000),
+        };
+        let columns = [
+            "version",
+            "applied_at",
+            "app_version",
+            "checksum",
+            "duration_ms",
+            "applied_by",
+            "run_id",
+            "batch",
+        ];
+        let values = (1..=columns.len())
+            .map(|i| match i {
+                2 => applied_at_expr.clone(),
+                i => param(i),
+            })
+            .collect::<Vec<_>>();
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(db_backend, "seaql_migrations"),
+            columns
+                .iter()
+                .map(|col| quote(db_backend, col))
+                .collect::<Vec<_>>()
+                .join(", "),
+            values.join(", "),
+        );
+        Statement::from_sql_and_values(
+            db_backend,
+            &sql,
+            vec![
+                record.version.clone().into(),
+                record.applied_at.into(),
+                record.app_version.clone().into(),
+                record.checksum.clone().into(),
+                record.duration_ms.into(),
+                record.applied_by.clone().into(),
+                record.run_id.clone().into(),
+                record.batch.clone().into(),
+            ],
+        )
+    }
+
+    /// Statements converting existing `seaql_migrations.applied_at` values from `self` to `target`
+    pub(crate) fn upgrade_statements(
+        &self,
+        target: AppliedAtFormat,
+        db_backend: DbBackend,
+    ) -> Vec<Statement> {
+        let table = quote(db_backend, "seaql_migrations");
+        let col = quote(db_backend, "applied_at");
+        let tmp = quote(db_backend, "applied_at_tmp");
+        let (from_units, to_units) = (self.units_per_sec(), target.units_per_sec());
+        let sqls = match (self, target) {
+            (from, to) if from == &to => Vec::new(),
+            (Self::Timestamp, _) => match db_backend {
+                DbBackend::Postgres => vec![format!(
+                    "ALTER TABLE {table} ALTER COLUMN {col} TYPE bigint USING {expr}",
+                    expr = from_timestamp(db_backend, &col, to_units),
+                )],
+                DbBackend::MySql => vec![
+                    format!(
+                        "ALTER TABLE {table} ADD COLUMN {tmp} {} NULL",
+                        target.mysql_type()
+                    ),
+                    format!(
+                        "UPDATE {table} SET {tmp} = {expr}",
+                        expr = from_timestamp(db_backend, &col, to_units),
+                    ),
+                    format!("ALTER TABLE {table} DROP COLUMN {col}"),
+                    format!(
+                        "ALTER TABLE {table} CHANGE {tmp} {col} {} NOT NULL",
+                        target.mysql_type()
+                    ),
+                ],
+                DbBackend::Sqlite => vec![format!(
+                    "UPDATE {table} SET {col} = {expr}",
+                    expr = from_timestamp(db_backend, &col, to_units),
+                )],
+            },
+            (_, Self::Timestamp) => match db_backend {
+                DbBackend::Postgres => vec![format!(
+                    "ALTER TABLE {table} ALTER COLUMN {col} TYPE timestamptz(3) USING {expr}",
+                    expr = to_timestamp(db_backend, &col, from_units),
+                )],
+                DbBackend::MySql => vec![
+                    format!(
+                        "ALTER TABLE {table} ADD COLUMN {tmp} {} NULL",
+                        target.mysql_type()
+                    ),
+                    format!(
+                        "UPDATE {table} SET {tmp} = {expr}",
+                        expr = to_timestamp(db_backend, &col, from_units),
+                    ),
+                    format!("ALTER TABLE {table} DROP COLUMN {col}"),
+                    format!(
+                        "ALTER TABLE {table} CHANGE {tmp} {col} {} NOT NULL",
+                        target.mysql_type()
+                    ),
+                ],
+                DbBackend::Sqlite => vec![format!(
+                    "UPDATE {table} SET {col} = {expr}",
+                    expr = to_timestamp(db_backend, &col, from_units),
+                )],
+            },
+            // Rescaling only values still in the source unit keeps a conversion run twice, as
+            // when interrupted before the format was recorded, from rescaling them twice
+            _ if from_units < to_units => vec![format!(
+                "UPDATE {table} SET {col} = {col} * {} WHERE {col} < {MILLIS_THRESHOLD}",
+                to_units / from_units
+            )],
+            _ => vec![format!(
+                "UPDATE {table} SET {col} = {col} / {} WHERE {col} >= {MILLIS_THRESHOLD}",
+                from_units / to_units
+            )],
+        };
+        sqls.into_iter()
+            .map(|sql| Statement::from_string(db_backend, sql))
+            .collect()
+    }
+
+    /// Column type of `applied_at` on MySQL
+    fn mysql_type(&self) -> &'static str {
+        match self {
+            Self::UnixSeconds | Self::UnixMillis => "bigint",
+            Self::Timestamp => "datetime(3)",
+        }
+    }
+
+    /// Statements resuming a conversion to `target` interrupted on MySQL, given the columns of
+    /// `seaql_migrations`, `None` if none was interrupted. Converting between an integer and a
+    /// timestamp copies `applied_at` through `applied_at_tmp` with statements MySQL cannot run
+    /// in a transaction, and the format is recorded once they all ran.
+    pub(crate) fn resume_statements(
+        &self,
+        target: AppliedAtFormat,
+        db_backend: DbBackend,
+        columns: &[ColumnInfo],
+    ) -> Option<Vec<Statement>> {
+        let copied = (*self == Self::Timestamp) != (target == Self::Timestamp);
+        if db_backend != DbBackend::MySql || !copied {
+            return None;
+        }
+        let table = quote(db_backend, "seaql_migrations");
+        let col = quote(db_backend, "applied_at");
+        let tmp = quote(db_backend, "applied_at_tmp");
+        let find = |name: &str| columns.iter().find(|column| column.name == name);
+        let sqls = match (find("applied_at"), find("applied_at_tmp")) {
+            // Interrupted while copying, the copy starts over
+            (Some(_), Some(_)) => {
+                let mut stmts = vec![Statement::from_string(
+                    db_backend,
+                    format!("ALTER TABLE {table} DROP COLUMN {tmp}"),
+                )];
+                stmts.extend(self.upgrade_statements(target, db_backend));
+                return Some(stmts);
+            }
+            // Interrupted after dropping the original column
+            (None, Some(_)) => vec![format!(
+                "ALTER TABLE {table} CHANGE {tmp} {col} {} NOT NULL",
+                target.mysql_type()
+            )],
+            // Interrupted before recording the format, the column has the target type
+            (Some(column), None)
+                if column
+                    .column_type
+                    .to_lowercase()
+                    .starts_with(target.mysql_type()) =>
+            {
+                Vec::new()
+            }
+            _ => return None,
+        };
+        Some(
+            sqls.into_iter()
+                .map(|sql| Statement::from_string(db_backend, sql))
+                .collect(),
+        )
+    }
+}
+
+impl Display for AppliedAtFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for AppliedAtFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix_seconds" => Ok(Self::UnixSeconds),
+            "unix_millis" => Ok(Self::UnixMillis),
+            "timestamp" => Ok(Self::Timestamp),
+            _ => Err(format!("Unknown applied_at format '{}'", s)),
+        }
+    }
+}
+
+pub(crate) fn quote(db_backend: DbBackend, ident: &str) -> String {
+    match db_backend {
+        DbBackend::MySql => format!("`{}`", ident.replace('`', "``")),
+        DbBackend::Postgres | DbBackend::Sqlite => format!("\"{}\"", ident.replace('"', "\"\"")),
+    }
+}
+
+/// Convert a unix timestamp expression in `units` per second into a timestamp
+fn to_timestamp(db_backend: DbBackend, expr: &str, units: i64) -> String {
+    match db_backend {
+        DbBackend::Postgres => format!("to_timestamp({} / {}.0)", expr, units),
+        DbBackend::MySql => format!("FROM_UNIXTIME({} / {})", expr, units),
+        DbBackend::Sqlite => format!(
+            "strftime('%Y-%m-%d %H:%M:%f', {} / {}.0, 'unixepoch')",
+            expr, units
+        ),
+    }
+}
+
+/// Convert a timestamp expression into a unix timestamp in `units` per second
+fn from_timestamp(db_backend: DbBackend, expr: &str, units: i64) -> String {
+    match db_backend {
+        DbBackend::Postgres => format!("(EXTRACT(EPOCH FROM {}) * {})::bigint", expr, units),
+        DbBackend::MySql => format!("CAST(UNIX_TIMESTAMP({}) * {} AS SIGNED)", expr, units),
+        DbBackend::Sqlite => format!(
+            "CAST(ROUND((julianday({}) - 2440587.5) * 86400 * {}) AS INTEGER)",
+            expr, units
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_statements() {
+        let sqls = |from: AppliedAtFormat, to, db_backend| {
+            from.upgrade_statements(to, db_backend)
+                .into_iter()
+                .map(|stmt| stmt.sql)
+                .collect::<Vec<_>>()
+        };
+        assert!(sqls(
+            AppliedAtFormat::UnixMillis,
+            AppliedAtFormat::UnixMillis,
+            DbBackend::MySql
+        )
+        .is_empty());
+        assert_eq!(
+            sqls(
+                AppliedAtFormat::UnixSeconds,
+                AppliedAtFormat::UnixMillis,
+                DbBackend::Postgres
+            ),
+            vec![
+                r#"UPDATE "seaql_migrations" SET "applied_at" = "applied_at" * 1000 WHERE "applied_at" < 100000000000"#
+            ]
+        );
+        assert_eq!(
+            sqls(
+                AppliedAtFormat::UnixMillis,
+                AppliedAtFormat::UnixSeconds,
+                DbBackend::MySql
+            ),
+            vec![
+                "UPDATE `seaql_migrations` SET `applied_at` = `applied_at` / 1000 WHERE `applied_at` >= 100000000000"
+            ]
+        );
+        assert_eq!(
+            sqls(
+                AppliedAtFormat::UnixSeconds,
+                AppliedAtFormat::Timestamp,
+                DbBackend::Postgres
+            ),
+            vec![
+                r#"ALTER TABLE "seaql_migrations" ALTER COLUMN "applied_at" TYPE timestamptz(3) USING to_timestamp("applied_at" / 1.0)"#
+            ]
+        );
+        assert_eq!(
+            sqls(
+                AppliedAtFormat::Timestamp,
+                AppliedAtFormat::UnixSeconds,
+                DbBackend::MySql
+            )
+            .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_resume_statements() {
+        let column = |name: &str, column_type: &str| ColumnInfo {
+            name: name.to_owned(),
+            column_type: column_type.to_owned(),
+            nullable: false,
+            default: None,
+        };
+        let resume = |columns: &[ColumnInfo]| {
+            AppliedAtFormat::Timestamp
+                .resume_statements(AppliedAtFormat::UnixMillis, DbBackend::MySql, columns)
+                .map(|stmts| stmts.into_iter().map(|stmt| stmt.sql).collect::<Vec<_>>())
+        };
+        assert_eq!(resume(&[column("applied_at", "datetime(3)")]), None);
+        // Interrupted while copying
+        let copying = resume(&[
+            column("applied_at", "datetime(3)"),
+            column("applied_at_tmp", "bigint"),
+        ])
+        .unwrap();
+        assert_eq!(
+            copying[0],
+            "ALTER TABLE `seaql_migrations` DROP COLUMN `applied_at_tmp`"
+        );
+        assert_eq!(copying.len(), 5);
+        // Interrupted after dropping `applied_at`
+        assert_eq!(
+            resume(&[column("version", "varchar(255)"), column("applied_at_tmp", "bigint")]),
+            Some(vec![
+                "ALTER TABLE `seaql_migrations` CHANGE `applied_at_tmp` `applied_at` bigint NOT NULL"
+                    .to_owned()
+            ])
+        );
+        // Interrupted before recording the format
+        assert_eq!(resume(&[column("applied_at", "bigint(20)")]), Some(vec![]));
+        assert_eq!(
+            AppliedAtFormat::UnixSeconds.resume_statements(
+                AppliedAtFormat::UnixMillis,
+                DbBackend::MySql,
+                &[column("applied_at_tmp", "bigint")]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_applied_at_format_from_str() {
+        for format in [
+            AppliedAtFormat::UnixSeconds,
+            AppliedAtFormat::UnixMillis,
+            AppliedAtFormat::Timestamp,
+        ] {
+            assert_eq!(format.as_str().parse(), Ok(format));
+        }
+        assert!("seconds".parse::<AppliedAtFormat>().is_err());
+    }
+}
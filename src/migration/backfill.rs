@@ -0,0 +1,589 @@
+use super::{quote, SchemaManager};
+use sea_orm::sea_query::{Alias, ColumnDef, Table, Value};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, QueryResult, Statement};
+use std::fmt::Display;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::info;
+
+/// Default table the progress of backfills is persisted in
+pub const BACKFILL_PROGRESS_TABLE: &str = "seaql_backfill_progress";
+
+/// Batched backfill of a column, e.g. populating a column added by a previous migration.
+///
+/// Rows are updated in batches of consecutive keys. The last key processed and the number of rows
+/// done are persisted in a side table after every batch, so a backfill interrupted by a restart
+/// resumes where it stopped, and an operator can follow its progress and ETA while it runs.
+/// Statements are paced by the configured [`Throttle`](super::Throttle).
+///
+/// A risky transformation can be rolled out progressively with a [`Rollout`]: each run stops
+/// once the rollout is reached, and a later run with a wider rollout resumes from there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backfill {
+    /// Name identifying the backfill in the progress table
+    pub name: String,
+    pub table: String,
+    /// Integer column the rows are processed in the order of, usually the primary key
+    pub key: String,
+    /// `SET` clause of the update, e.g. `"slug" = lower("name")`
+    pub set: String,
+    /// Condition restricting the rows to update, e.g. `"slug" IS NULL`
+    pub filter: Option<String>,
+    pub batch_size: u64,
+    pub progress_table: String,
+    pub rollout: Rollout,
+}
+
+/// How far a [`Backfill`] goes in a run, so that a transformation can be validated on a slice
+/// of the rows before completing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rollout {
+    /// Process every row and complete the backfill
+    #[default]
+    Complete,
+    /// Stop once this percentage of the rows, counted when the run started, has been done
+    Percent(u8),
+    /// Only process the rows with a key up to this one, inclusive
+    UpToKey(i64),
+}
+
+/// Progress of a backfill, as persisted in the progress table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackfillProgress {
+    pub name: String,
+    pub table: String,
+    /// Key of the last row processed, `None` before the first batch
+    pub last_key: Option<i64>,
+    pub rows_done: i64,
+    /// Rows done plus the rows left when the backfill was last started or resumed
+    pub total_rows: i64,
+    /// Time spent backfilling, excluding the time the backfill was interrupted
+    pub elapsed_ms: i64,
+    /// Unix timestamp of the last update of the progress
+    pub updated_at: i64,
+    /// Unix timestamp the backfill completed at
+    pub completed_at: Option<i64>,
+}
+
+impl Backfill {
+    pub fn new<N, T, K, S>(name: N, table: T, key: K, set: S) -> Self
+    where
+        N: Into<String>,
+        T: Into<String>,
+        K: Into<String>,
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            table: table.into(),
+            key: key.into(),
+            set: set.into(),
+            filter: None,
+            batch_size: 1000,
+            progress_table: BACKFILL_PROGRESS_TABLE.to_owned(),
+            rollout: Rollout::Complete,
+        }
+    }
+
+    /// Only update the rows matching the condition
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Into<String>,
+    {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// Update at most `batch_size` rows per statement
+    pub fn batch_size(mut self, batch_size: u64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Persist the progress in another table than [`BACKFILL_PROGRESS_TABLE`]
+    pub fn progress_table<T>(mut self, progress_table: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.progress_table = progress_table.into();
+        self
+    }
+
+    /// Stop the run once the rollout is reached instead of completing the backfill
+    pub fn rollout(mut self, rollout: Rollout) -> Self {
+        self.rollout = rollout;
+        self
+    }
+
+    /// Run the backfill to completion, or up to its [`Rollout`], resuming from the persisted
+    /// progress if any
+    pub async fn run(&self, manager: &SchemaManager<'_>) -> Result<BackfillProgress, DbErr> {
+        let db_backend = manager.get_database_backend();
+        manager
+            .create_table(progress_table_statement(&self.progress_table))
+            .await?;
+        let persisted = self.progress(manager).await?;
+        if let Some(progress) = persisted.as_ref().filter(|p| p.completed_at.is_some()) {
+            info!("Backfill '{}' has already completed", self.name);
+            return Ok(progress.clone());
+        }
+        let mut progress = match persisted.clone() {
+            Some(progress) => {
+                info!(
+                    "Resuming backfill '{}' after {} rows",
+                    self.name, progress.rows_done
+                );
+                progress
+            }
+            None => BackfillProgress {
+                name: self.name.clone(),
+                table: self.table.clone(),
+                last_key: None,
+                rows_done: 0,
+                total_rows: 0,
+                elapsed_ms: 0,
+                updated_at: now(),
+                completed_at: None,
+            },
+        };
+        let remaining = self.remaining(manager, progress.last_key).await?;
+        progress.total_rows = progress.rows_done + remaining;
+        self.save(manager, &progress, persisted.is_some()).await?;
+
+        let elapsed_before = progress.elapsed_ms;
+        let started = Instant::now();
+        let up_to = match self.rollout {
+            Rollout::UpToKey(key) => Some(key),
+            Rollout::Complete | Rollout::Percent(_) => None,
+        };
+        loop {
+            let limit = self.batch_limit(&progress);
+            if limit == 0 {
+                break;
+            }
+            let batch = manager
+                .query_one_raw(self.statement(
+                    db_backend,
+                    &format!(
+                        "SELECT COUNT(*) AS batch_rows, {} AS last_key FROM (SELECT {{key}} FROM {{table}} WHERE {{range}} ORDER BY {{key}} LIMIT {}) AS batch",
+                        cast_bigint(db_backend, "MAX({key})"),
+                        limit
+                    ),
+                    progress.last_key,
+                    up_to,
+                ))
+                .await?;
+            let (rows, last_key) = match batch {
+                Some(row) => (
+                    get_i64(&row, "batch_rows")?,
+                    row.try_get::<Option<i64>>("", "last_key")?,
+                ),
+                None => (0, None),
+            };
+            let last_key = match last_key {
+                Some(last_key) if rows > 0 => last_key,
+                _ => break,
+            };
+            manager
+                .exec_raw(self.statement(
+                    db_backend,
+                    &format!("UPDATE {{table}} SET {} WHERE {{range}}", self.set),
+                    progress.last_key,
+                    Some(last_key),
+                ))
+                .await?;
+            progress.last_key = Some(last_key);
+            progress.rows_done += rows;
+            progress.total_rows = progress.total_rows.max(progress.rows_done);
+            progress.elapsed_ms = elapsed_before + started.elapsed().as_millis() as i64;
+            progress.updated_at = now();
+            self.save(manager, &progress, true).await?;
+            info!(
+                "Backfill '{}': {} of {} rows done{}",
+                self.name,
+                progress.rows_done,
+                progress.total_rows,
+                progress
+                    .eta()
+                    .map(|eta| format!(", ETA {}s", eta.as_secs()))
+                    .unwrap_or_default()
+            );
+        }
+        if self.rollout != Rollout::Complete {
+            let remaining = self.remaining(manager, progress.last_key).await?;
+            if remaining > 0 {
+                info!(
+                    "Backfill '{}' reached its rollout of {}, {} rows done, {} rows left",
+                    self.name, self.rollout, progress.rows_done, remaining
+                );
+                return Ok(progress);
+            }
+        }
+        progress.completed_at = Some(now());
+        progress.updated_at = now();
+        self.save(manager, &progress, true).await?;
+        info!(
+            "Backfill '{}' completed, {} rows done",
+            self.name, progress.rows_done
+        );
+        Ok(progress)
+    }
+
+    /// Number of rows left after the key `after`
+    async fn remaining(
+        &self,
+        manager: &SchemaManager<'_>,
+        after: Option<i64>,
+    ) -> Result<i64, DbErr> {
+        Ok(manager
+            .query_one_raw(self.statement(
+                manager.get_database_backend(),
+                "SELECT COUNT(*) AS remaining FROM {table} WHERE {range}",
+                after,
+                None,
+            ))
+            .await?
+            .map(|row| get_i64(&row, "remaining"))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// Size of the next batch, `0` once a [`Rollout::Percent`] is reached
+    fn batch_limit(&self, progress: &BackfillProgress) -> u64 {
+        match self.rollout {
+            Rollout::Percent(percent) => {
+                let percent = percent.min(100) as i64;
+                // Round up, so that any percentage above zero processes at least one row
+                let target = (progress.total_rows * percent + 99) / 100;
+                (target - progress.rows_done).clamp(0, self.batch_size as i64) as u64
+            }
+            Rollout::Complete | Rollout::UpToKey(_) => self.batch_size,
+        }
+    }
+
+    /// Persisted progress of the backfill, `None` if it has not started yet
+    pub async fn progress(
+        &self,
+        manager: &SchemaManager<'_>,
+    ) -> Result<Option<BackfillProgress>, DbErr> {
+        let db_backend = manager.get_database_backend();
+        manager
+            .query_one_raw(Statement::from_sql_and_values(
+                db_backend,
+                &format!(
+                    "{} WHERE {} = {}",
+                    select_progress(db_backend, &self.progress_table),
+                    quote(db_backend, "name"),
+                    placeholder(db_backend, 1)
+                ),
+                vec![self.name.as_str().into()],
+            ))
+            .await?
+            .map(|row| BackfillProgress::from_row(&row))
+            .transpose()
+    }
+
+    async fn save(
+        &self,
+        manager: &SchemaManager<'_>,
+        progress: &BackfillProgress,
+        exists: bool,
+    ) -> Result<(), DbErr> {
+        let db_backend = manager.get_database_backend();
+        let columns = [
+            "table_name",
+            "last_key",
+            "rows_done",
+            "total_rows",
+            "elapsed_ms",
+            "updated_at",
+            "completed_at",
+            "name",
+        ];
+        let values: Vec<Value> = vec![
+            progress.table.as_str().into(),
+            progress.last_key.into(),
+            progress.rows_done.into(),
+            progress.total_rows.into(),
+            progress.elapsed_ms.into(),
+            progress.updated_at.into(),
+            progress.completed_at.into(),
+            progress.name.as_str().into(),
+        ];
+        let sql = match exists {
+            true => format!(
+                "UPDATE {} SET {} WHERE {} = {}",
+                quote(db_backend, &self.progress_table),
+                columns[..7]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, col)| format!(
+                        "{} = {}",
+                        quote(db_backend, col),
+                        placeholder(db_backend, i + 1)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                quote(db_backend, "name"),
+                placeholder(db_backend, 8)
+            ),
+            false => format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote(db_backend, &self.progress_table),
+                columns
+                    .iter()
+                    .map(|col| quote(db_backend, col))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                (1..=columns.len())
+                    .map(|i| placeholder(db_backend, i))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        manager
+            .exec_raw(Statement::from_sql_and_values(db_backend, &sql, values))
+            .await
+    }
+
+    /// Statement over the rows with a key in `(after, up_to]` matching the filter, replacing the
+    /// `{table}`, `{key}` and `{range}` placeholders of `template`
+    fn statement(
+        &self,
+        db_backend: DbBackend,
+        template: &str,
+        after: Option<i64>,
+        up_to: Option<i64>,
+    ) -> Statement {
+        let key = quote(db_backend, &self.key);
+        let mut conditions = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        for (op, bound) in [(">", after), ("<=", up_to)] {
+            if let Some(bound) = bound {
+                values.push(bound.into());
+                conditions.push(format!(
+                    "{} {} {}",
+                    key,
+                    op,
+                    placeholder(db_backend, values.len())
+                ));
+            }
+        }
+        if let Some(filter) = &self.filter {
+            conditions.push(format!("({})", filter));
+        }
+        if conditions.is_empty() {
+            conditions.push("1 = 1".to_owned());
+        }
+        let sql = template
+            .replace("{table}", &quote(db_backend, &self.table))
+            .replace("{key}", &key)
+            .replace("{range}", &conditions.join(" AND "));
+        Statement::from_sql_and_values(db_backend, &sql, values)
+    }
+}
+
+impl Display for Rollout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Complete => write!(f, "100%"),
+            Self::Percent(percent) => write!(f, "{}%", percent),
+            Self::UpToKey(key) => write!(f, "key {}", key),
+        }
+    }
+}
+
+impl BackfillProgress {
+    /// Estimated time left, extrapolated from the rows done so far
+    pub fn eta(&self) -> Option<Duration> {
+        if self.completed_at.is_some() {
+            return Some(Duration::ZERO);
+        }
+        if self.rows_done <= 0 {
+            return None;
+        }
+        let remaining = (self.total_rows - self.rows_done).max(0) as u128;
+        let ms = remaining * self.elapsed_ms.max(0) as u128 / self.rows_done as u128;
+        Some(Duration::from_millis(ms as u64))
+    }
+
+    fn from_row(row: &QueryResult) -> Result<Self, DbErr> {
+        Ok(Self {
+            name: row.try_get("", "name")?,
+            table: row.try_get("", "table_name")?,
+            last_key: row.try_get("", "last_key")?,
+            rows_done: row.try_get("", "rows_done")?,
+            total_rows: row.try_get("", "total_rows")?,
+            elapsed_ms: row.try_get("", "elapsed_ms")?,
+            updated_at: row.try_get("", "updated_at")?,
+            completed_at: row.try_get("", "completed_at")?,
+        })
+    }
+}
+
+/// Progress of every backfill persisted in [`BACKFILL_PROGRESS_TABLE`], e.g. for an operator
+/// to follow a backfill run by another process
+pub async fn backfill_progress(db: &DbConn) -> Result<Vec<BackfillProgress>, DbErr> {
+    let db_backend = db.get_database_backend();
+    db.query_all(Statement::from_string(
+        db_backend,
+        format!(
+            "{} ORDER BY {}",
+            select_progress(db_backend, BACKFILL_PROGRESS_TABLE),
+            quote(db_backend, "name")
+        ),
+    ))
+    .await?
+    .iter()
+    .map(BackfillProgress::from_row)
+    .collect()
+}
+
+fn progress_table_statement(progress_table: &str) -> sea_orm::sea_query::TableCreateStatement {
+    Table::create()
+        .table(Alias::new(progress_table))
+        .if_not_exists()
+        .col(
+            ColumnDef::new(Alias::new("name"))
+                .string_len(255)
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("table_name"))
+                .string_len(255)
+                .not_null(),
+        )
+        .col(ColumnDef::new(Alias::new("last_key")).big_integer().null())
+        .col(
+            ColumnDef::new(Alias::new("rows_done"))
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("total_rows"))
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("elapsed_ms"))
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("updated_at"))
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("completed_at"))
+                .big_integer()
+                .null(),
+        )
+        .to_owned()
+}
+
+fn select_progress(db_backend: DbBackend, progress_table: &str) -> String {
+    let columns = [
+        "name",
+        "table_name",
+        "last_key",
+        "rows_done",
+        "total_rows",
+        "elapsed_ms",
+        "updated_at",
+        "completed_at",
+    ];
+    format!(
+        "SELECT {} FROM {}",
+        columns
+            .iter()
+            .map(|col| quote(db_backend, col))
+            .collect::<Vec<_>>()
+            .join(", "),
+        quote(db_backend, progress_table)
+    )
+}
+
+pub(crate) fn placeholder(db_backend: DbBackend, i: usize) -> String {
+    match db_backend {
+        DbBackend::Postgres => format!("${}", i),
+        DbBackend::MySql | DbBackend::Sqlite => "?".to_owned(),
+    }
+}
+
+pub(crate) fn cast_bigint(db_backend: DbBackend, expr: &str) -> String {
+    match db_backend {
+        DbBackend::MySql => format!("CAST({} AS SIGNED)", expr),
+        DbBackend::Postgres | DbBackend::Sqlite => format!("CAST({} AS BIGINT)", expr),
+    }
+}
+
+fn get_i64(row: &QueryResult, column: &str) -> Result<i64, DbErr> {
+    row.try_get("", column)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("SystemTime before UNIX EPOCH!")
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statement() {
+        let backfill = Backfill::new("cake_slug", "cake", "id", r#""slug" = lower("name")"#)
+            .filter(r#""slug" IS NULL"#);
+        let stmt = backfill.statement(
+            DbBackend::Postgres,
+            r#"UPDATE {table} SET "slug" = lower("name") WHERE {range}"#,
+            Some(1000),
+            Some(2000),
+        );
+        assert_eq!(
+            stmt.sql,
+            r#"UPDATE "cake" SET "slug" = lower("name") WHERE "id" > $1 AND "id" <= $2 AND ("slug" IS NULL)"#
+        );
+        assert_eq!(stmt.values.unwrap().0.len(), 2);
+    }
+
+    #[test]
+    fn test_batch_limit() {
+        let backfill = Backfill::new("cake_slug", "cake", "id", r#""slug" = lower("name")"#)
+            .batch_size(100)
+            .rollout(Rollout::Percent(10));
+        let progress = |rows_done| BackfillProgress {
+            name: "cake_slug".to_owned(),
+            table: "cake".to_owned(),
+            last_key: None,
+            rows_done,
+            total_rows: 1005,
+            elapsed_ms: 0,
+            updated_at: 0,
+            completed_at: None,
+        };
+        assert_eq!(backfill.batch_limit(&progress(0)), 100);
+        assert_eq!(backfill.batch_limit(&progress(100)), 1);
+        assert_eq!(backfill.batch_limit(&progress(101)), 0);
+        let backfill = backfill.rollout(Rollout::UpToKey(500));
+        assert_eq!(backfill.batch_limit(&progress(101)), 100);
+    }
+
+    #[test]
+    fn test_eta() {
+        let progress = BackfillProgress {
+            name: "cake_slug".to_owned(),
+            table: "cake".to_owned(),
+            last_key: Some(250),
+            rows_done: 250,
+            total_rows: 1000,
+            elapsed_ms: 5000,
+            updated_at: 0,
+            completed_at: None,
+        };
+        assert_eq!(progress.eta(), Some(Duration::from_secs(15)));
+    }
+}
@@ -0,0 +1,205 @@
+use super::{quote, SchemaManager};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use sea_orm::sea_query::Value;
+use sea_orm::{DbBackend, DbErr, Statement};
+
+/// Streaming of a BLOB / `bytea` value of a single row, in chunks, so that data migrations
+/// moving or transforming binary payloads never hold an entire object in memory.
+///
+/// Reads select one chunk per query. Writes empty the value, then append one chunk per
+/// statement; each append rewrites the stored value on Postgres, keep chunks large there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlobStream {
+    pub table: String,
+    pub column: String,
+    /// Column identifying the row, e.g. its primary key
+    pub key_column: String,
+    pub key: Value,
+    /// Bytes per chunk, 1 MiB by default
+    pub chunk_size: usize,
+}
+
+impl BlobStream {
+    pub fn new<T, C, K, V>(table: T, column: C, key_column: K, key: V) -> Self
+    where
+        T: Into<String>,
+        C: Into<String>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        Self {
+            table: table.into(),
+            column: column.into(),
+            key_column: key_column.into(),
+            key: key.into(),
+            chunk_size: 1024 * 1024,
+        }
+    }
+
+    /// Read and write at most `chunk_size` bytes per statement
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Size of the value in bytes, `None` if the row does not exist or the value is `NULL`
+    pub async fn len(&self, manager: &SchemaManager<'_>) -> Result<Option<u64>, DbErr> {
+        let db_backend = manager.get_database_backend();
+        let length = match db_backend {
+            DbBackend::Postgres => "octet_length",
+            DbBackend::MySql | DbBackend::Sqlite => "length",
+        };
+        let sql = format!(
+            "SELECT {}({}) AS {} FROM {} WHERE {} = {}",
+            length,
+            quote(db_backend, &self.column),
+            quote(db_backend, "length"),
+            quote(db_backend, &self.table),
+            quote(db_backend, &self.key_column),
+            param(db_backend, 1),
+        );
+        let stmt = Statement::from_sql_and_values(db_backend, &sql, vec![self.key.clone()]);
+        let length: Option<i64> = match manager.query_one_raw(stmt).await? {
+            Some(row) => row.try_get("", "length")?,
+            None => None,
+        };
+        Ok(length.map(|length| length as u64))
+    }
+
+    /// Copy the value into `writer` chunk by chunk, returning the number of bytes copied
+    pub async fn read_to<W>(
+        &self,
+        manager: &SchemaManager<'_>,
+        writer: &mut W,
+    ) -> Result<u64, DbErr>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let db_backend = manager.get_database_backend();
+        let sql = format!(
+            "SELECT {} AS {} FROM {} WHERE {} = {}",
+            substring(db_backend, &quote(db_backend, &self.column)),
+            quote(db_backend, "chunk"),
+            quote(db_backend, &self.table),
+            quote(db_backend, &self.key_column),
+            param(db_backend, 3),
+        );
+        let mut copied = 0;
+        loop {
+            let stmt = Statement::from_sql_and_values(
+                db_backend,
+                &sql,
+                vec![
+                    (copied as i64 + 1).into(),
+                    (self.chunk_size as i64).into(),
+                    self.key.clone(),
+                ],
+            );
+            let chunk: Option<Vec<u8>> = match manager.query_one_raw(stmt).await? {
+                Some(row) => row.try_get("", "chunk")?,
+                None => None,
+            };
+            let chunk = chunk.unwrap_or_default();
+            writer.write_all(&chunk).await.map_err(io_err)?;
+            copied += chunk.len() as u64;
+            if chunk.len() < self.chunk_size {
+                break;
+            }
+        }
+        writer.flush().await.map_err(io_err)?;
+        Ok(copied)
+    }
+
+    /// Replace the value with the content of `reader` written chunk by chunk, returning
+    /// the number of bytes written
+    pub async fn write_from<R>(
+        &self,
+        manager: &SchemaManager<'_>,
+        reader: &mut R,
+    ) -> Result<u64, DbErr>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let db_backend = manager.get_database_backend();
+        let column = quote(db_backend, &self.column);
+        let update = |value: &str, key_param: usize| {
+            format!(
+                "UPDATE {} SET {} = {} WHERE {} = {}",
+                quote(db_backend, &self.table),
+                column,
+                value,
+                quote(db_backend, &self.key_column),
+                param(db_backend, key_param),
+            )
+        };
+        let empty = match db_backend {
+            DbBackend::Postgres => "''::bytea",
+            DbBackend::MySql => "''",
+            DbBackend::Sqlite => "X''",
+        };
+        manager
+            .exec_raw(Statement::from_sql_and_values(
+                db_backend,
+                &update(empty, 1),
+                vec![self.key.clone()],
+            ))
+            .await?;
+        let append = match db_backend {
+            DbBackend::MySql => format!("CONCAT({}, ?)", column),
+            DbBackend::Postgres | DbBackend::Sqlite => {
+                format!("{} || {}", column, param(db_backend, 1))
+            }
+        };
+        let append = update(&append, 2);
+        let mut written = 0;
+        let mut chunk = vec![0; self.chunk_size];
+        loop {
+            let mut filled = 0;
+            while filled < chunk.len() {
+                match reader.read(&mut chunk[filled..]).await.map_err(io_err)? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+            manager
+                .exec_raw(Statement::from_sql_and_values(
+                    db_backend,
+                    &append,
+                    vec![chunk[..filled].to_vec().into(), self.key.clone()],
+                ))
+                .await?;
+            written += filled as u64;
+            if filled < chunk.len() {
+                break;
+            }
+        }
+        Ok(written)
+    }
+}
+
+fn param(db_backend: DbBackend, i: usize) -> String {
+    match db_backend {
+        DbBackend::Postgres => format!("${}", i),
+        DbBackend::MySql | DbBackend::Sqlite => "?".to_owned(),
+    }
+}
+
+/// Expression selecting the bytes of `expr` from the first parameter, a one-based offset,
+/// for the second parameter, a length
+fn substring(db_backend: DbBackend, expr: &str) -> String {
+    match db_backend {
+        DbBackend::Postgres => format!(
+            "substring({} FROM CAST($1 AS integer) FOR CAST($2 AS integer))",
+            expr
+        ),
+        DbBackend::MySql => format!("SUBSTRING({}, ?, ?)", expr),
+        DbBackend::Sqlite => format!("substr({}, ?, ?)", expr),
+    }
+}
+
+fn io_err(err: std::io::Error) -> DbErr {
+    DbErr::Custom(format!("Fail to stream binary value: {}", err))
+}
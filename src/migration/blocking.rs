@@ -0,0 +1,226 @@
+use super::SessionActivity;
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, Statement};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// What to do when a lock-heavy DDL statement would be blocked by other sessions
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockingAction {
+    /// Poll until the blocking sessions finish, aborting once `timeout` elapses
+    Wait {
+        timeout: Duration,
+        poll_interval: Duration,
+    },
+    /// Terminate the blocking sessions. Their transactions are rolled back,
+    /// so this has to be opted into explicitly.
+    Kill,
+    /// Abort with a report of the blocking sessions
+    Abort,
+}
+
+/// Check for long-running transactions holding locks on a table before
+/// executing a lock-heavy DDL statement against it
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockingCheck {
+    /// Only sessions whose transaction has been open for at least this long are considered blocking
+    pub min_age: Duration,
+    pub action: BlockingAction,
+}
+
+impl BlockingCheck {
+    pub fn new(action: BlockingAction) -> Self {
+        Self {
+            min_age: Duration::from_secs(5),
+            action,
+        }
+    }
+
+    pub fn min_age(mut self, min_age: Duration) -> Self {
+        self.min_age = min_age;
+        self
+    }
+
+    /// Make sure no session is blocking the given statement, acting according to `action` otherwise.
+    /// `db` is the connection about to execute the statement, its own session is never blocking.
+    pub async fn ensure_clear<C>(&self, db: &C, sql: &str) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait + ?Sized,
+    {
+        let table = match lock_target(sql) {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        let start = Instant::now();
+        loop {
+            let sessions = blocking_sessions(db, &table, self.min_age).await?;
+            if sessions.is_empty() {
+                return Ok(());
+            }
+            match &self.action {
+                BlockingAction::Wait {
+                    timeout,
+                    poll_interval,
+                } => {
+                    if start.elapsed() >= *timeout {
+                        return Err(blocked_error(&table, &sessions));
+                    }
+                    info!(
+                        "Waiting for {} session(s) holding locks on table '{}'",
+                        sessions.len(),
+                        table
+                    );
+                    async_std::task::sleep(*poll_interval).await;
+                }
+                BlockingAction::Kill => {
+                    for session in sessions.iter() {
+                        warn!("Terminating blocking {}", session);
+                        kill_session(db, session.id).await?;
+                    }
+                }
+                BlockingAction::Abort => return Err(blocked_error(&table, &sessions)),
+            }
+        }
+    }
+}
+
+/// List sessions holding locks on `table` whose transaction has been open for at least `min_age`
+pub async fn blocking_sessions<C>(
+    db: &C,
+    table: &str,
+    min_age: Duration,
+) -> Result<Vec<SessionActivity>, DbErr>
+where
+    C: ConnectionTrait + ?Sized,
+{
+    let db_backend = db.get_database_backend();
+    let sql = match db_backend {
+        DbBackend::MySql => {
+            r#"SELECT DISTINCT CAST(`t`.`PROCESSLIST_ID` AS SIGNED) AS `id`,
+                `t`.`PROCESSLIST_STATE` AS `state`, `m`.`LOCK_TYPE` AS `wait_event`,
+                NULL AS `blocked_by`, CAST(`t`.`PROCESSLIST_TIME` AS SIGNED) AS `elapsed_secs`,
+                LEFT(`t`.`PROCESSLIST_INFO`, 200) AS `query`
+            FROM `performance_schema`.`metadata_locks` AS `m`
+            JOIN `performance_schema`.`threads` AS `t` ON `t`.`THREAD_ID` = `m`.`OWNER_THREAD_ID`
+            WHERE `m`.`OBJECT_TYPE` = 'TABLE' AND `m`.`OBJECT_SCHEMA` = DATABASE()
+                AND `m`.`OBJECT_NAME` = ? AND `m`.`LOCK_STATUS` = 'GRANTED'
+                AND `t`.`PROCESSLIST_ID` <> CONNECTION_ID()"#
+        }
+        DbBackend::Postgres => {
+            r#"SELECT DISTINCT "a"."pid"::bigint AS "id", "a"."state",
+                "l"."mode" AS "wait_event",
+                NULLIF(pg_blocking_pids("a"."pid")::text, '{}') AS "blocked_by",
+                EXTRACT(EPOCH FROM now() - "a"."xact_start")::bigint AS "elapsed_secs",
+                LEFT("a"."query", 200) AS "query"
+            FROM "pg_locks" AS "l"
+            JOIN "pg_stat_activity" AS "a" ON "a"."pid" = "l"."pid"
+            WHERE "l"."relation" = to_regclass(quote_ident($1)) AND "l"."granted"
+                AND "a"."pid" <> pg_backend_pid()"#
+        }
+        DbBackend::Sqlite => return Ok(Vec::new()),
+    };
+    let stmt = Statement::from_sql_and_values(db_backend, sql, vec![table.into()]);
+    let mut sessions = Vec::new();
+    for row in db.query_all(stmt).await?.into_iter() {
+        let session = SessionActivity {
+            id: row.try_get("", "id")?,
+            state: row.try_get("", "state")?,
+            wait_event: row.try_get("", "wait_event")?,
+            blocked_by: row.try_get("", "blocked_by")?,
+            elapsed_secs: row.try_get("", "elapsed_secs")?,
+            query: row.try_get("", "query")?,
+        };
+        if session.elapsed_secs.unwrap_or_default() >= min_age.as_secs() as i64 {
+            sessions.push(session);
+        }
+    }
+    Ok(sessions)
+}
+
+/// Terminate a session on the database server
+pub async fn kill_session<C>(db: &C, id: i64) -> Result<(), DbErr>
+where
+    C: ConnectionTrait + ?Sized,
+{
+    let db_backend = db.get_database_backend();
+    let sql = match db_backend {
+        DbBackend::MySql => format!("KILL {}", id),
+        DbBackend::Postgres => format!("SELECT pg_terminate_backend({})", id),
+        DbBackend::Sqlite => return Ok(()),
+    };
+    db.execute(Statement::from_string(db_backend, sql))
+        .await
+        .map(|_| ())
+}
+
+fn blocked_error(table: &str, sessions: &[SessionActivity]) -> DbErr {
+    let mut msg = format!(
+        "Table '{}' is locked by {} long-running session(s):",
+        table,
+        sessions.len()
+    );
+    for session in sessions.iter() {
+        msg.push_str(&format!("\n  {}", session));
+    }
+    DbErr::Custom(msg)
+}
+
+/// Extract the table a lock-heavy DDL statement operates on
+pub(crate) fn lock_target(sql: &str) -> Option<String> {
+    let tokens: Vec<&str> = sql.split_whitespace().collect();
+    let keyword = |i: usize| tokens.get(i).map(|t| t.to_uppercase()).unwrap_or_default();
+    let table = match (keyword(0).as_str(), keyword(1).as_str()) {
+        ("ALTER", "TABLE") | ("DROP", "TABLE") | ("TRUNCATE", "TABLE") => {
+            let mut i = 2;
+            while matches!(keyword(i).as_str(), "IF" | "EXISTS" | "ONLY") {
+                i += 1;
+            }
+            tokens.get(i)
+        }
+        ("TRUNCATE", _) => tokens.get(1),
+        ("CREATE", "INDEX" | "UNIQUE" | "FULLTEXT" | "SPATIAL") | ("DROP", "INDEX") => {
+            let on = tokens.iter().position(|t| t.eq_ignore_ascii_case("ON"))?;
+            tokens.get(on + 1)
+        }
+        _ => None,
+    }?;
+    let table = table.split('(').next()?;
+    let table = table.rsplit('.').next()?;
+    Some(table.trim_matches(|c| c == '"' || c == '`').to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_target() {
+        assert_eq!(
+            lock_target(r#"ALTER TABLE "cake" ADD COLUMN "price" integer"#),
+            Some("cake".to_owned())
+        );
+        assert_eq!(
+            lock_target("DROP TABLE IF EXISTS `cake` CASCADE"),
+            Some("cake".to_owned())
+        );
+        assert_eq!(
+            lock_target(r#"TRUNCATE TABLE "public"."cake""#),
+            Some("cake".to_owned())
+        );
+        assert_eq!(
+            lock_target(r#"CREATE INDEX "idx-cake-name" ON "cake" ("name")"#),
+            Some("cake".to_owned())
+        );
+        assert_eq!(
+            lock_target("DROP INDEX `idx-cake-name` ON `cake`"),
+            Some("cake".to_owned())
+        );
+        assert_eq!(lock_target(r#"DROP INDEX "idx-cake-name""#), None);
+        assert_eq!(
+            lock_target(
+                r#"CREATE TABLE "fruit" ("id" integer REFERENCES "cake" ON DELETE CASCADE)"#
+            ),
+            None
+        );
+        assert_eq!(lock_target(r#"SELECT * FROM "cake""#), None);
+    }
+}
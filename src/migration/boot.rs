@@ -0,0 +1,420 @@
+use super::{placeholder, quote, unix_timestamp, DurationClass, MigratorTrait};
+use sea_orm::sea_query::{Alias, ColumnDef, Table};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Table holding the lock taken by [`auto_migrate_on_boot`], a single row while held
+pub const BOOT_LOCK_TABLE: &str = "seaql_boot_lock";
+
+/// Interval between attempts to take a lock held by another instance
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What [`auto_migrate_on_boot`] does when the migrations cannot be applied
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BootFailure {
+    /// Return the error, so that the service refuses to start against an outdated schema
+    #[default]
+    Abort,
+    /// Log the error and let the service start, e.g. when its code is compatible with the
+    /// previous schema and the migrations are retried by the next instance
+    Continue,
+}
+
+/// How a service applies its migrations at startup, see [`auto_migrate_on_boot`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootPolicy {
+    /// Serialize the instances booting concurrently with a lock in [`BOOT_LOCK_TABLE`]
+    pub lock: bool,
+    /// How long to wait for the lock held by another instance
+    pub lock_timeout: Duration,
+    /// Age after which a lock is considered abandoned by a crashed instance and taken over. The
+    /// instance holding the lock renews it while applying the migrations, every third of it
+    pub stale_lock: Duration,
+    /// Only apply the pending migrations preceding the first [`DurationClass::Slow`] one, unless
+    /// [`MigratorTrait::acknowledge_slow`], leaving the others to be applied out of band
+    pub fast_only: bool,
+    /// Time limit of applying the migrations, after which the lock is left to go stale rather
+    /// than released
+    pub timeout: Option<Duration>,
+    pub on_failure: BootFailure,
+}
+
+/// What [`auto_migrate_on_boot`] did
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BootOutcome {
+    /// Migrations applied
+    pub applied: Vec<String>,
+    /// Pending migrations left to be applied out of band, see [`BootPolicy::fast_only`]
+    pub deferred: Vec<String>,
+    /// The failure the boot continued past, see [`BootFailure::Continue`]
+    pub error: Option<String>,
+}
+
+/// The lock row of [`BOOT_LOCK_TABLE`], released when the boot is done
+struct BootLock<'a> {
+    db: &'a DbConn,
+    owner: String,
+    /// Leave the row to be taken over as stale instead of deleting it on release
+    keep: AtomicBool,
+}
+
+impl Default for BootPolicy {
+    fn default() -> Self {
+        Self {
+            lock: true,
+            lock_timeout: Duration::from_secs(60),
+            stale_lock: Duration::from_secs(60 * 60),
+            fast_only: true,
+            timeout: None,
+            on_failure: BootFailure::default(),
+        }
+    }
+}
+
+impl BootPolicy {
+    /// Do not take the lock, e.g. when a single instance is ever started at a time
+    pub fn without_lock(mut self) -> Self {
+        self.lock = false;
+        self
+    }
+
+    /// Wait up to `lock_timeout` for the lock held by another instance
+    pub fn lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// Take over a lock held for longer than `stale_lock`
+    pub fn stale_lock(mut self, stale_lock: Duration) -> Self {
+        self.stale_lock = stale_lock;
+        self
+    }
+
+    /// Apply the slow migrations at boot too
+    pub fn allow_slow(mut self) -> Self {
+        self.fast_only = false;
+        self
+    }
+
+    /// Give up applying the migrations after `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn on_failure(mut self, on_failure: BootFailure) -> Self {
+        self.on_failure = on_failure;
+        self
+    }
+}
+
+/// Apply the pending migrations at the startup of a service, with the steps services tend to
+/// hand-roll:
+/// - take a lock, so that instances started together apply the migrations once, the others
+///   waiting and then finding nothing pending
+/// - check the pending migrations, returning without running the migrator if there are none
+/// - with [`BootPolicy::fast_only`], defer the slow migrations and those following them
+/// - apply the migrations within [`BootPolicy::timeout`]
+/// - release the lock, unless the migrations timed out
+///
+/// Any failure, including the lock timing out, is returned or logged according to
+/// [`BootPolicy::on_failure`].
+///
+/// When the timeout elapses, the migrator is dropped at its next await point while the
+/// statement it was executing may still run on the server, and the migration it was applying
+/// may be left half-applied on backends without transactional DDL. The lock is then kept, so
+/// that no other instance starts migrating before it goes stale after
+/// [`BootPolicy::stale_lock`]. Prefer a statement timeout in
+/// [`MigratorTrait::session_settings`] to bound individual statements.
+pub async fn auto_migrate_on_boot<M>(db: &DbConn, policy: &BootPolicy) -> Result<BootOutcome, DbErr>
+where
+    M: MigratorTrait,
+{
+    let mut outcome = BootOutcome::default();
+    let res = match policy.lock {
+        true => match BootLock::acquire(db, policy).await {
+            Ok(lock) => {
+                let res = migrate::<M>(db, policy, Some(&lock), &mut outcome).await;
+                let released = lock.release().await;
+                res.and(released)
+            }
+            Err(err) => Err(err),
+        },
+        false => migrate::<M>(db, policy, None, &mut outcome).await,
+    };
+    match (res, policy.on_failure) {
+        (Ok(()), _) => Ok(outcome),
+        (Err(err), BootFailure::Abort) => Err(err),
+        (Err(err), BootFailure::Continue) => {
+            error!("Migrations failed at boot, continuing: {}", err);
+            outcome.error = Some(err.to_string());
+            Ok(outcome)
+        }
+    }
+}
+
+async fn migrate<M>(
+    db: &DbConn,
+    policy: &BootPolicy,
+    lock: Option<&BootLock<'_>>,
+    outcome: &mut BootOutcome,
+) -> Result<(), DbErr>
+where
+    M: MigratorTrait,
+{
+    let pending = M::get_pending_migrations(db).await?;
+    if pending.is_empty() {
+        info!("No pending migrations at boot");
+        return Ok(());
+    }
+    let steps = match policy.fast_only && !M::acknowledge_slow() {
+        true => pending
+            .iter()
+            .position(|migration| migration.duration_class() == DurationClass::Slow)
+            .unwrap_or(pending.len()),
+        false => pending.len(),
+    };
+    let (apply, defer) = pending.split_at(steps);
+    outcome.deferred = defer.iter().map(|m| m.name().to_owned()).collect();
+    if !outcome.deferred.is_empty() {
+        warn!(
+            "Deferring slow migrations and the ones following them: {:?}",
+            outcome.deferred
+        );
+    }
+    if apply.is_empty() {
+        return Ok(());
+    }
+
+    info!("Applying {} pending migrations at boot", apply.len());
+    let up = async {
+        let up = M::up(db, Some(apply.len() as u32));
+        match lock {
+            Some(lock) => lock.renewing(policy.stale_lock, up).await,
+            None => up.await,
+        }
+    };
+    let res = match policy.timeout {
+        Some(timeout) => match async_std::future::timeout(timeout, up).await {
+            Ok(res) => res,
+            Err(_) => {
+                if let Some(lock) = lock {
+                    lock.keep.store(true, Ordering::Relaxed);
+                }
+                Err(DbErr::Custom(format!(
+                    "Migrations did not complete within {:?}",
+                    timeout
+                )))
+            }
+        },
+        None => up.await,
+    };
+    outcome.applied = match &res {
+        Ok(()) => apply.iter().map(|m| m.name().to_owned()).collect(),
+        // Some migrations may have been applied before the failure
+        Err(_) => {
+            let still_pending: Vec<String> = M::get_pending_migrations(db)
+                .await
+                .map(|pending| pending.iter().map(|m| m.name().to_owned()).collect())
+                .unwrap_or_else(|_| apply.iter().map(|m| m.name().to_owned()).collect());
+            apply
+                .iter()
+                .map(|m| m.name().to_owned())
+                .filter(|name| !still_pending.contains(name))
+                .collect()
+        }
+    };
+    res
+}
+
+impl<'a> BootLock<'a> {
+    /// Insert the lock row, waiting while another instance holds it and taking over a stale one
+    async fn acquire(db: &'a DbConn, policy: &BootPolicy) -> Result<BootLock<'a>, DbErr> {
+        let db_backend = db.get_database_backend();
+        let create = Table::create()
+            .table(Alias::new(BOOT_LOCK_TABLE))
+            .if_not_exists()
+            .col(
+                ColumnDef::new(Alias::new("id"))
+                    .integer()
+                    .not_null()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(Alias::new("owner")).string().not_null())
+            .col(
+                ColumnDef::new(Alias::new("acquired_at"))
+                    .big_integer()
+                    .not_null(),
+            )
+            .to_owned();
+        db.execute(db_backend.build(&create)).await?;
+
+        let owner = Uuid::new_v4().to_string();
+        let started = Instant::now();
+        loop {
+            let res = db.execute(insert_stmt(db_backend, &owner)).await?;
+            if res.rows_affected() == 1 {
+                info!("Acquired the boot lock as {}", owner);
+                return Ok(BootLock {
+                    db,
+                    owner,
+                    keep: AtomicBool::new(false),
+                });
+            }
+            let holder = db.query_one(holder_stmt(db_backend)).await?;
+            let (holder, acquired_at) = match holder {
+                Some(row) => (
+                    row.try_get::<String>("", "owner")?,
+                    row.try_get::<i64>("", "acquired_at")?,
+                ),
+                // Released in the meantime
+                None => continue,
+            };
+            let age = unix_timestamp().saturating_sub(acquired_at).max(0) as u64;
+            if age >= policy.stale_lock.as_secs() {
+                warn!("Taking over the boot lock held by {} for {}s", holder, age);
+                db.execute(delete_stmt(db_backend, &holder)).await?;
+                continue;
+            }
+            if started.elapsed() >= policy.lock_timeout {
+                return Err(DbErr::Custom(format!(
+                    "The boot lock is held by {} for {}s, gave up after {:?}",
+                    holder, age, policy.lock_timeout
+                )));
+            }
+            info!("Waiting for the boot lock held by {}", holder);
+            async_std::task::sleep(LOCK_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Wait for `fut`, renewing the lock every third of `stale_lock` so that it is not taken
+    /// over while the migrations run for longer
+    async fn renewing<F>(&self, stale_lock: Duration, fut: F) -> F::Output
+    where
+        F: Future,
+    {
+        let interval = (stale_lock / 3).max(LOCK_POLL_INTERVAL);
+        let mut fut = Box::pin(fut);
+        loop {
+            match async_std::future::timeout(interval, &mut fut).await {
+                Ok(res) => return res,
+                Err(_) => self.renew().await,
+            }
+        }
+    }
+
+    async fn renew(&self) {
+        let db_backend = self.db.get_database_backend();
+        match self.db.execute(renew_stmt(db_backend, &self.owner)).await {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("The boot lock held by {} was taken over", self.owner)
+            }
+            Ok(_) => {}
+            Err(err) => warn!("Fail to renew the boot lock: {}", err),
+        }
+    }
+
+    async fn release(self) -> Result<(), DbErr> {
+        if self.keep.load(Ordering::Relaxed) {
+            warn!(
+                "Keeping the boot lock held by {} until it goes stale",
+                self.owner
+            );
+            return Ok(());
+        }
+        let db_backend = self.db.get_database_backend();
+        self.db
+            .execute(delete_stmt(db_backend, &self.owner))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// Statement inserting the lock row, affecting no rows if it is held
+fn insert_stmt(db_backend: DbBackend, owner: &str) -> Statement {
+    let sql = format!(
+        "INTO {} ({}, {}, {}) VALUES (1, {}, {})",
+        quote(db_backend, BOOT_LOCK_TABLE),
+        quote(db_backend, "id"),
+        quote(db_backend, "owner"),
+        quote(db_backend, "acquired_at"),
+        placeholder(db_backend, 1),
+        placeholder(db_backend, 2)
+    );
+    let sql = match db_backend {
+        DbBackend::MySql => format!("INSERT IGNORE {}", sql),
+        DbBackend::Postgres | DbBackend::Sqlite => format!(
+            "INSERT {} ON CONFLICT ({}) DO NOTHING",
+            sql,
+            quote(db_backend, "id")
+        ),
+    };
+    Statement::from_sql_and_values(
+        db_backend,
+        &sql,
+        [owner.to_owned().into(), unix_timestamp().into()],
+    )
+}
+
+fn holder_stmt(db_backend: DbBackend) -> Statement {
+    Statement::from_string(
+        db_backend,
+        format!(
+            "SELECT {}, {} FROM {}",
+            quote(db_backend, "owner"),
+            quote(db_backend, "acquired_at"),
+            quote(db_backend, BOOT_LOCK_TABLE)
+        ),
+    )
+}
+
+/// Statement refreshing the acquisition time of the lock row if it is held by `owner`
+fn renew_stmt(db_backend: DbBackend, owner: &str) -> Statement {
+    Statement::from_sql_and_values(
+        db_backend,
+        &format!(
+            "UPDATE {} SET {} = {} WHERE {} = {}",
+            quote(db_backend, BOOT_LOCK_TABLE),
+            quote(db_backend, "acquired_at"),
+            placeholder(db_backend, 1),
+            quote(db_backend, "owner"),
+            placeholder(db_backend, 2)
+        ),
+        [unix_timestamp().into(), owner.to_owned().into()],
+    )
+}
+
+/// Statement deleting the lock row if it is held by `owner`
+fn delete_stmt(db_backend: DbBackend, owner: &str) -> Statement {
+    Statement::from_sql_and_values(
+        db_backend,
+        &format!(
+            "DELETE FROM {} WHERE {} = {}",
+            quote(db_backend, BOOT_LOCK_TABLE),
+            quote(db_backend, "owner"),
+            placeholder(db_backend, 1)
+        ),
+        [owner.to_owned().into()],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_stmt() {
+        assert_eq!(
+            insert_stmt(DbBackend::Postgres, "a").sql,
+            r#"INSERT INTO "seaql_boot_lock" ("id", "owner", "acquired_at") VALUES (1, $1, $2) ON CONFLICT ("id") DO NOTHING"#
+        );
+        assert_eq!(
+            insert_stmt(DbBackend::MySql, "a").sql,
+            "INSERT IGNORE INTO `seaql_boot_lock` (`id`, `owner`, `acquired_at`) VALUES (1, ?, ?)"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use super::{quote, SchemaManager};
+use sea_orm::sea_query::{Alias, Query, Value};
+use sea_orm::{DbBackend, DbErr, Statement};
+use tracing::info;
+
+/// Fast loading of large seeds and fixtures into a table.
+///
+/// Rows held in memory are inserted with batched multi-row inserts. CSV files are loaded with
+/// `COPY` on Postgres and `LOAD DATA INFILE` on MySQL, and with batched multi-row inserts on SQLite.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BulkLoader {
+    /// Table to load the rows into
+    pub table: String,
+    /// Columns of each row, in order
+    pub columns: Vec<String>,
+    /// Rows per multi-row insert, defaults to as many as the bound parameter limit of the backend allows
+    pub batch_size: Option<usize>,
+    /// Read CSV files on the client with `LOAD DATA LOCAL INFILE` on MySQL, which has to be
+    /// enabled on both the server and the driver. Otherwise the file is read by the database server.
+    pub local_infile: bool,
+}
+
+impl BulkLoader {
+    pub fn new<T, I, C>(table: T, columns: I) -> Self
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = C>,
+        C: Into<String>,
+    {
+        Self {
+            table: table.into(),
+            columns: columns.into_iter().map(Into::into).collect(),
+            batch_size: None,
+            local_infile: false,
+        }
+    }
+
+    /// Insert at most `batch_size` rows per statement
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Read CSV files on the client on MySQL
+    pub fn local_infile(mut self, local_infile: bool) -> Self {
+        self.local_infile = local_infile;
+        self
+    }
+
+    fn rows_per_batch(&self, db_backend: DbBackend) -> usize {
+        let max_params = match db_backend {
+            DbBackend::MySql | DbBackend::Postgres => 65535,
+            DbBackend::Sqlite => 999,
+        };
+        self.batch_size
+            .unwrap_or(max_params / self.columns.len().max(1))
+            .max(1)
+    }
+
+    /// Insert the rows with batched multi-row inserts
+    pub async fn load_rows<I>(&self, manager: &SchemaManager<'_>, rows: I) -> Result<(), DbErr>
+    where
+        I: IntoIterator<Item = Vec<Value>>,
+    {
+        let rows_per_batch = self.rows_per_batch(manager.get_database_backend());
+        let mut rows = rows.into_iter().peekable();
+        let mut loaded = 0;
+        while rows.peek().is_some() {
+            let mut stmt = Query::insert();
+            stmt.into_table(Alias::new(self.table.as_str()))
+                .columns(self.columns.iter().map(|col| Alias::new(col.as_str())));
+            for row in rows.by_ref().take(rows_per_batch) {
+                if row.len() != self.columns.len() {
+                    return Err(DbErr::Custom(format!(
+                        "Expected {} values per row of table '{}', got {}",
+                        self.columns.len(),
+                        self.table,
+                        row.len()
+                    )));
+                }
+                stmt.values_panic(row.into_iter().map(Into::into));
+                loaded += 1;
+            }
+            manager.exec_stmt(stmt).await?;
+        }
+        info!("Loaded {} rows into table '{}'", loaded, self.table);
+        Ok(())
+    }
+
+    /// Load a CSV file with a header row, unquoted empty fields are loaded as `NULL`
+    pub async fn load_csv(&self, manager: &SchemaManager<'_>, path: &str) -> Result<(), DbErr> {
+        let db_backend = manager.get_database_backend();
+        let columns = self
+            .columns
+            .iter()
+            .map(|col| quote(db_backend, col))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = match db_backend {
+            DbBackend::Postgres => format!(
+                "COPY {} ({}) FROM '{}' WITH (FORMAT csv, HEADER true)",
+                quote(db_backend, &self.table),
+                columns,
+                path.replace('\'', "''"),
+            ),
+            DbBackend::MySql => format!(
+                "LOAD DATA {}INFILE '{}' INTO TABLE {} FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '\"' LINES TERMINATED BY '\\n' IGNORE 1 LINES ({})",
+                if self.local_infile { "LOCAL " } else { "" },
+                path.replace('\\', "\\\\").replace('\'', "\\'"),
+                quote(db_backend, &self.table),
+                columns,
+            ),
+            DbBackend::Sqlite => {
+                let content = async_std::fs::read_to_string(path)
+                    .await
+                    .map_err(|err| DbErr::Custom(format!("Fail to read '{}': {}", path, err)))?;
+                let rows = parse_csv(&content).map_err(DbErr::Custom)?;
+                let rows = rows.into_iter().skip(1).map(|row| {
+                    row.into_iter()
+                        .map(|field| Value::String(field.map(Box::new)))
+                        .collect()
+                });
+                return self.load_rows(manager, rows).await;
+            }
+        };
+        info!("Loading '{}' into table '{}'", path, self.table);
+        manager
+            .exec_raw(Statement::from_string(db_backend, sql))
+            .await
+    }
+}
+
+/// Parse CSV content into rows of fields, `None` for unquoted empty fields
+pub(crate) fn parse_csv(content: &str) -> Result<Vec<Vec<Option<String>>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() && !quoted => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                        None => return Err("Unterminated quoted field".to_owned()),
+                    }
+                }
+            }
+            ',' => {
+                row.push(take_field(&mut field, &mut quoted));
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(take_field(&mut field, &mut quoted));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || quoted || !row.is_empty() {
+        row.push(take_field(&mut field, &mut quoted));
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn take_field(field: &mut String, quoted: &mut bool) -> Option<String> {
+    let value = match (field.is_empty(), *quoted) {
+        (true, false) => None,
+        _ => Some(std::mem::take(field)),
+    };
+    *quoted = false;
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let some = |s: &str| Some(s.to_owned());
+        assert_eq!(
+            parse_csv("id,name\r\n1,\"Cheese, \"\"Blue\"\"\"\n2,\n3,\"\"").unwrap(),
+            vec![
+                vec![some("id"), some("name")],
+                vec![some("1"), some("Cheese, \"Blue\"")],
+                vec![some("2"), None],
+                vec![some("3"), some("")],
+            ]
+        );
+        assert!(parse_csv("1,\"Cheese").is_err());
+    }
+
+    #[test]
+    fn test_rows_per_batch() {
+        let loader = BulkLoader::new("cake", ["id", "name"]);
+        assert_eq!(loader.rows_per_batch(DbBackend::Sqlite), 499);
+        assert_eq!(
+            loader.batch_size(100).rows_per_batch(DbBackend::Postgres),
+            100
+        );
+    }
+}
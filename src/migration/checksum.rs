@@ -0,0 +1,344 @@
+use sea_orm::DbErr;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::fmt::Display;
+
+/// A change to the migration set that is not an append of new migrations
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChecksumViolation {
+    /// The statements built by an existing migration changed
+    Changed {
+        version: String,
+        base: String,
+        current: String,
+    },
+    /// An existing migration is missing
+    Removed { version: String },
+    /// An existing migration moved relative to the other existing migrations
+    Reordered { version: String },
+    /// A new migration was inserted before an existing migration
+    Inserted { version: String },
+}
+
+/// Hash algorithm of migration checksums
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+/// How migration checksums are computed, see [`MigratorTrait::checksum_options`](super::MigratorTrait::checksum_options).
+///
+/// Checksums other than the default SHA-256 of the statements as built are prefixed with the
+/// algorithm, e.g. `sha512+normalized:<hex>`, so that they never match checksums computed otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChecksumOptions {
+    pub algorithm: ChecksumAlgorithm,
+    /// Hash the statements normalized with [`normalize_sql`], so that cosmetic changes to
+    /// the Rust code of a migration do not change its checksum
+    pub normalize: bool,
+}
+
+impl ChecksumAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+
+    fn hash<S>(&self, statements: &[S]) -> String
+    where
+        S: AsRef<str>,
+    {
+        fn hash<D, S>(statements: &[S]) -> String
+        where
+            D: Digest,
+            S: AsRef<str>,
+        {
+            let mut hasher = D::new();
+            for statement in statements.iter() {
+                hasher.update(statement.as_ref().as_bytes());
+                hasher.update([b'\n']);
+            }
+            hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        }
+        match self {
+            Self::Sha256 => hash::<Sha256, _>(statements),
+            Self::Sha384 => hash::<Sha384, _>(statements),
+            Self::Sha512 => hash::<Sha512, _>(statements),
+        }
+    }
+}
+
+impl ChecksumOptions {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        Self {
+            algorithm,
+            ..Default::default()
+        }
+    }
+
+    /// Hash the normalized statements
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Compute the checksum of the statements built by a migration
+    pub fn checksum<S>(&self, statements: &[S]) -> String
+    where
+        S: AsRef<str>,
+    {
+        let hex = match self.normalize {
+            true => {
+                let normalized: Vec<String> = statements
+                    .iter()
+                    .map(|statement| normalize_sql(statement.as_ref()))
+                    .collect();
+                self.algorithm.hash(&normalized)
+            }
+            false => self.algorithm.hash(statements),
+        };
+        match (self.algorithm, self.normalize) {
+            (ChecksumAlgorithm::Sha256, false) => hex,
+            (algorithm, false) => format!("{}:{}", algorithm.name(), hex),
+            (algorithm, true) => format!("{}+normalized:{}", algorithm.name(), hex),
+        }
+    }
+}
+
+/// Compute the SHA-256 checksum of the statements built by a migration
+pub fn checksum<S>(statements: &[S]) -> String
+where
+    S: AsRef<str>,
+{
+    ChecksumOptions::default().checksum(statements)
+}
+
+/// Canonical form of a statement: runs of whitespace collapsed to a single space, whitespace
+/// around parentheses and commas dropped, and unquoted keywords, types and identifiers
+/// lowercased. Quoted strings and identifiers are kept as is, their case and spacing being
+/// significant; identifiers built by sea-query are always quoted.
+pub fn normalize_sql(sql: &str) -> String {
+    let mut normalized = String::with_capacity(sql.len());
+    let mut chars = sql.trim().chars().peekable();
+    let mut pending_space = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                if pending_space && !normalized.ends_with(['(', ',']) {
+                    normalized.push(' ');
+                }
+                pending_space = false;
+                normalized.push(c);
+                while let Some(q) = chars.next() {
+                    normalized.push(q);
+                    if q == c {
+                        // A doubled quote escapes the quote
+                        if chars.peek() == Some(&c) {
+                            normalized.push(chars.next().unwrap());
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            c if c.is_whitespace() => pending_space = true,
+            '(' | ')' | ',' => {
+                pending_space = false;
+                normalized.push(c);
+            }
+            c => {
+                if pending_space && !normalized.ends_with(['(', ',']) {
+                    normalized.push(' ');
+                }
+                pending_space = false;
+                normalized.extend(c.to_lowercase());
+            }
+        }
+    }
+    normalized
+}
+
+/// Compare the (version, checksum) pairs of the current migration set against a base set,
+/// e.g. exported from the main branch, and list every change that is not an append
+pub fn append_only_violations(
+    base: &[(String, String)],
+    current: &[(String, String)],
+) -> Vec<ChecksumViolation> {
+    let position = |version: &str| current.iter().position(|(v, _)| v == version);
+    let mut violations = Vec::new();
+    let mut last_position = None;
+    for (version, base_checksum) in base.iter() {
+        match position(version) {
+            None => violations.push(ChecksumViolation::Removed {
+                version: version.clone(),
+            }),
+            Some(i) => {
+                let current_checksum = &current[i].1;
+                if current_checksum != base_checksum {
+                    violations.push(ChecksumViolation::Changed {
+                        version: version.clone(),
+                        base: base_checksum.clone(),
+                        current: current_checksum.clone(),
+                    });
+                }
+                if matches!(last_position, Some(last) if i < last) {
+                    violations.push(ChecksumViolation::Reordered {
+                        version: version.clone(),
+                    });
+                }
+                last_position = Some(last_position.map_or(i, |last: usize| last.max(i)));
+            }
+        }
+    }
+    if let Some(last) = last_position {
+        for (version, _) in current[..last].iter() {
+            if !base.iter().any(|(v, _)| v == version) {
+                violations.push(ChecksumViolation::Inserted {
+                    version: version.clone(),
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Format (version, checksum) pairs as lines of `<version> <checksum>`
+pub fn format_checksums(checksums: &[(String, String)]) -> String {
+    checksums
+        .iter()
+        .map(|(version, checksum)| format!("{} {}\n", version, checksum))
+        .collect()
+}
+
+/// Parse (version, checksum) pairs from lines of `<version> <checksum>`, as written by [`format_checksums`]
+pub fn parse_checksums(s: &str) -> Result<Vec<(String, String)>, DbErr> {
+    s.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once(char::is_whitespace) {
+            Some((version, checksum)) => Ok((version.to_owned(), checksum.trim().to_owned())),
+            None => Err(DbErr::Custom(format!(
+                "Invalid checksum line '{}', expected '<version> <checksum>'",
+                line
+            ))),
+        })
+        .collect()
+}
+
+impl Display for ChecksumViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Changed {
+                version,
+                base,
+                current,
+            } => write!(
+                f,
+                "Migration '{}' has been modified: checksum {} != {}",
+                version, current, base
+            ),
+            Self::Removed { version } => write!(f, "Migration '{}' has been removed", version),
+            Self::Reordered { version } => write!(f, "Migration '{}' has been reordered", version),
+            Self::Inserted { version } => write!(
+                f,
+                "Migration '{}' has been inserted before existing migrations",
+                version
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(v, c)| (v.to_string(), c.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_append_only_violations() {
+        let base = pairs(&[("m1", "a"), ("m2", "b"), ("m3", "c")]);
+        assert!(append_only_violations(&base, &base).is_empty());
+        assert!(append_only_violations(
+            &base,
+            &pairs(&[("m1", "a"), ("m2", "b"), ("m3", "c"), ("m4", "d")])
+        )
+        .is_empty());
+        assert_eq!(
+            append_only_violations(&base, &pairs(&[("m1", "a"), ("m2", "x"), ("m4", "d")])),
+            vec![
+                ChecksumViolation::Changed {
+                    version: "m2".to_owned(),
+                    base: "b".to_owned(),
+                    current: "x".to_owned(),
+                },
+                ChecksumViolation::Removed {
+                    version: "m3".to_owned()
+                },
+            ]
+        );
+        assert_eq!(
+            append_only_violations(
+                &base,
+                &pairs(&[("m1", "a"), ("m4", "d"), ("m3", "c"), ("m2", "b")])
+            ),
+            vec![
+                ChecksumViolation::Reordered {
+                    version: "m3".to_owned()
+                },
+                ChecksumViolation::Inserted {
+                    version: "m4".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_checksums() {
+        let checksums = pairs(&[("m1", "a"), ("m2", "b")]);
+        assert_eq!(
+            parse_checksums(&format_checksums(&checksums)).unwrap(),
+            checksums
+        );
+        assert!(parse_checksums("m1").is_err());
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(
+            checksum::<&str>(&[]),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(checksum(&["a", "b"]), checksum(&["ab"]));
+
+        let options = ChecksumOptions::new(ChecksumAlgorithm::Sha512).normalize(true);
+        assert!(options.checksum(&["a"]).starts_with("sha512+normalized:"));
+        assert_eq!(
+            options.checksum(&[r#"CREATE TABLE "cake" ( "id" integer NOT NULL )"#]),
+            options.checksum(&[r#"create  table "cake"("id" INTEGER not null)"#])
+        );
+    }
+
+    #[test]
+    fn test_normalize_sql() {
+        assert_eq!(
+            normalize_sql(
+                "  INSERT INTO \"Cake\" ( \"name\" , \"note\" )\n  VALUES ('Blue  Cheese', 'It''s  OK')  "
+            ),
+            r#"insert into "Cake"("name","note") values('Blue  Cheese','It''s  OK')"#
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use super::quote;
+use sea_orm::sea_query::{Expr, SimpleExpr, Value};
+use sea_orm::DbBackend;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Encoder and decoder of a backend-specific column type, e.g. Postgres ranges, intervals and
+/// enums or MySQL bit fields, used by data migrations binding and reading values of such columns
+pub trait ValueCodec: Debug + Send + Sync {
+    /// Expression binding `value` as a value of the column type
+    fn encode(&self, db_backend: DbBackend, value: Value) -> SimpleExpr;
+
+    /// Expression selecting `column` as a value sea-orm can decode
+    fn decode(&self, db_backend: DbBackend, column: &str) -> SimpleExpr;
+}
+
+/// Codecs registered by column type, see [`MigratorTrait::value_codecs`](super::MigratorTrait::value_codecs)
+#[derive(Clone, Debug, Default)]
+pub struct ValueCodecs {
+    codecs: HashMap<String, Arc<dyn ValueCodec>>,
+}
+
+/// Binds values cast to `column_type` and reads the column cast to `read_as`, e.g. Postgres
+/// ranges and enums bound and read as their text representation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CastCodec {
+    pub column_type: String,
+    pub read_as: String,
+}
+
+/// Postgres `interval` bound and read as a number of microseconds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IntervalCodec;
+
+/// MySQL `BIT(n)` bound and read as an unsigned integer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BitCodec;
+
+impl ValueCodecs {
+    /// Register the codec of a column type, replacing the codec registered before, if any
+    pub fn register<T, C>(mut self, column_type: T, codec: C) -> Self
+    where
+        T: Into<String>,
+        C: ValueCodec + 'static,
+    {
+        self.codecs
+            .insert(column_type.into().to_lowercase(), Arc::new(codec));
+        self
+    }
+
+    pub fn get(&self, column_type: &str) -> Option<&dyn ValueCodec> {
+        self.codecs
+            .get(&column_type.to_lowercase())
+            .map(|codec| codec.as_ref())
+    }
+
+    /// Expression binding `value` to a column of `column_type`, the plain value if no codec is registered
+    pub fn encode(&self, db_backend: DbBackend, column_type: &str, value: Value) -> SimpleExpr {
+        match self.get(column_type) {
+            Some(codec) => codec.encode(db_backend, value),
+            None => value.into(),
+        }
+    }
+
+    /// Expression selecting `column` of `column_type`, the plain column if no codec is registered
+    pub fn decode(&self, db_backend: DbBackend, column_type: &str, column: &str) -> SimpleExpr {
+        match self.get(column_type) {
+            Some(codec) => codec.decode(db_backend, column),
+            None => Expr::cust(&quote(db_backend, column)),
+        }
+    }
+}
+
+impl CastCodec {
+    /// Bind and read the column type as text
+    pub fn text<T>(column_type: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            column_type: column_type.into(),
+            read_as: "text".to_owned(),
+        }
+    }
+}
+
+impl ValueCodec for CastCodec {
+    fn encode(&self, _: DbBackend, value: Value) -> SimpleExpr {
+        Expr::cust_with_values(&format!("CAST(? AS {})", self.column_type), vec![value])
+    }
+
+    fn decode(&self, db_backend: DbBackend, column: &str) -> SimpleExpr {
+        Expr::cust(&format!(
+            "CAST({} AS {})",
+            quote(db_backend, column),
+            self.read_as
+        ))
+    }
+}
+
+impl ValueCodec for IntervalCodec {
+    fn encode(&self, _: DbBackend, value: Value) -> SimpleExpr {
+        Expr::cust_with_values("(? * INTERVAL '1 microsecond')", vec![value])
+    }
+
+    fn decode(&self, db_backend: DbBackend, column: &str) -> SimpleExpr {
+        Expr::cust(&format!(
+            "CAST(EXTRACT(EPOCH FROM {}) * 1000000 AS bigint)",
+            quote(db_backend, column)
+        ))
+    }
+}
+
+impl ValueCodec for BitCodec {
+    fn encode(&self, _: DbBackend, value: Value) -> SimpleExpr {
+        value.into()
+    }
+
+    fn decode(&self, db_backend: DbBackend, column: &str) -> SimpleExpr {
+        Expr::cust(&format!("CAST({} AS UNSIGNED)", quote(db_backend, column)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::sea_query::{Alias, PostgresQueryBuilder, Query};
+
+    #[test]
+    fn test_encode_decode() {
+        let codecs = ValueCodecs::default()
+            .register("tstzrange", CastCodec::text("tstzrange"))
+            .register("interval", IntervalCodec);
+        let db_backend = DbBackend::Postgres;
+
+        let (sql, _) = Query::insert()
+            .into_table(Alias::new("booking"))
+            .columns([
+                Alias::new("during"),
+                Alias::new("grace"),
+                Alias::new("note"),
+            ])
+            .values_panic([
+                codecs.encode(db_backend, "TSTZRANGE", "[2022-01-01,2022-01-02)".into()),
+                codecs.encode(db_backend, "interval", 90_000_000i64.into()),
+                codecs.encode(db_backend, "text", "late".into()),
+            ])
+            .build(PostgresQueryBuilder);
+        assert_eq!(
+            sql,
+            r#"INSERT INTO "booking" ("during", "grace", "note") VALUES (CAST($1 AS tstzrange), ($2 * INTERVAL '1 microsecond'), $3)"#
+        );
+
+        let (sql, _) = Query::select()
+            .expr_as(
+                codecs.decode(db_backend, "interval", "grace"),
+                Alias::new("grace"),
+            )
+            .expr_as(
+                codecs.decode(db_backend, "text", "note"),
+                Alias::new("note"),
+            )
+            .from(Alias::new("booking"))
+            .build(PostgresQueryBuilder);
+        assert_eq!(
+            sql,
+            r#"SELECT CAST(EXTRACT(EPOCH FROM "grace") * 1000000 AS bigint) AS "grace", "note" AS "note" FROM "booking""#
+        );
+    }
+}
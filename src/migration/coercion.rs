@@ -0,0 +1,486 @@
+use super::tokens;
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::fmt::Display;
+
+/// A column change MySQL would apply by silently coercing the stored values
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LossyChange {
+    pub table: String,
+    pub column: String,
+    pub reason: String,
+}
+
+/// A column redefined by an `ALTER TABLE` statement
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ColumnChange {
+    /// Name of the column before the change
+    column: String,
+    column_type: String,
+    charset: Option<String>,
+}
+
+/// Definition of a column in a MySQL type family, for comparing capacities
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MySqlType {
+    Integer {
+        bytes: u8,
+        unsigned: bool,
+    },
+    Decimal {
+        precision: u32,
+        scale: u32,
+    },
+    Float {
+        bytes: u8,
+    },
+    /// Character or binary string holding up to `capacity` characters, for `char` and
+    /// `varchar`, or bytes, for the binary and text types
+    String {
+        capacity: u64,
+        binary: bool,
+        characters: bool,
+    },
+    /// Date and time types with fractional seconds precision
+    Temporal {
+        name: String,
+        fsp: u64,
+    },
+    Other(String),
+}
+
+impl Display for LossyChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}: {}", self.table, self.column, self.reason)
+    }
+}
+
+/// Compare the live definitions of the columns changed by a MySQL `ALTER TABLE` statement with
+/// the requested ones, failing if the change may truncate or mangle stored values
+pub(crate) async fn ensure_no_lossy_change(db: &DbConn, sql: &str) -> Result<(), DbErr> {
+    let changes = lossy_changes(db, sql).await?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let mut msg =
+        "ALTER TABLE may lose data, use `SchemaManager::force_alter_table` to apply it anyway:"
+            .to_owned();
+    for change in changes.iter() {
+        msg.push_str(&format!("\n  {}", change));
+    }
+    Err(DbErr::Custom(msg))
+}
+
+/// Column changes of a MySQL `ALTER TABLE` statement which may lose data, given the live schema
+pub async fn lossy_changes(db: &DbConn, sql: &str) -> Result<Vec<LossyChange>, DbErr> {
+    let is_alter = matches!(
+        sql.trim_start().get(..5),
+        Some(keyword) if keyword.eq_ignore_ascii_case("ALTER")
+    );
+    if db.get_database_backend() != DbBackend::MySql || !is_alter {
+        return Ok(Vec::new());
+    }
+    let (table, changes, convert_to) = match parse_alter_table(sql) {
+        Some(parsed) => parsed,
+        None => return Ok(Vec::new()),
+    };
+    if changes.is_empty() && convert_to.is_none() {
+        return Ok(Vec::new());
+    }
+    let table_charset: Option<String> = db
+        .query_one(Statement::from_sql_and_values(
+            DbBackend::MySql,
+            r#"SELECT `c`.`CHARACTER_SET_NAME` AS `charset`
+            FROM `information_schema`.`TABLES` AS `t`
+            JOIN `information_schema`.`COLLATION_CHARACTER_SET_APPLICABILITY` AS `c`
+                ON `c`.`COLLATION_NAME` = `t`.`TABLE_COLLATION`
+            WHERE `t`.`TABLE_SCHEMA` = DATABASE() AND `t`.`TABLE_NAME` = ?"#,
+            vec![table.as_str().into()],
+        ))
+        .await?
+        .map(|row| row.try_get("", "charset"))
+        .transpose()?;
+    let columns = db
+        .query_all(Statement::from_sql_and_values(
+            DbBackend::MySql,
+            r#"SELECT `COLUMN_NAME` AS `column_name`, `COLUMN_TYPE` AS `column_type`,
+                `CHARACTER_SET_NAME` AS `charset`
+            FROM `information_schema`.`COLUMNS`
+            WHERE `TABLE_SCHEMA` = DATABASE() AND `TABLE_NAME` = ?"#,
+            vec![table.as_str().into()],
+        ))
+        .await?;
+    let mut lossy = Vec::new();
+    for row in columns.iter() {
+        let column: String = row.try_get("", "column_name")?;
+        let live_type: String = row.try_get("", "column_type")?;
+        let live_charset: Option<String> = row.try_get("", "charset")?;
+        let change = changes.iter().find(|change| change.column == column);
+        let reason = match (change, &convert_to) {
+            (Some(change), _) => lossy_type_change(
+                &live_type,
+                live_charset.as_deref(),
+                &change.column_type,
+                // A redefined string column takes the charset of the table unless given
+                change.charset.as_deref().or(table_charset.as_deref()),
+            ),
+            (None, Some(charset)) => lossy_type_change(
+                &live_type,
+                live_charset.as_deref(),
+                &live_type,
+                Some(charset),
+            ),
+            (None, None) => None,
+        };
+        if let Some(reason) = reason {
+            lossy.push(LossyChange {
+                table: table.clone(),
+                column,
+                reason,
+            });
+        }
+    }
+    Ok(lossy)
+}
+
+/// Why changing a MySQL column from one type and charset to another may lose data, if it may
+pub fn lossy_type_change(
+    from_type: &str,
+    from_charset: Option<&str>,
+    to_type: &str,
+    to_charset: Option<&str>,
+) -> Option<String> {
+    use MySqlType::*;
+    let (from, to) = (parse_mysql_type(from_type), parse_mysql_type(to_type));
+    let narrows = match (&from, &to) {
+        (
+            Integer {
+                bytes: a,
+                unsigned: u,
+            },
+            Integer {
+                bytes: b,
+                unsigned: v,
+            },
+        ) => b < a || (u != v && (!u || a == b)),
+        (Integer { bytes, .. }, Decimal { precision, scale }) => {
+            precision.saturating_sub(*scale) < integer_digits(*bytes)
+        }
+        // Floats represent integers exactly up to 2^24 and 2^53
+        (Integer { bytes: a, .. }, Float { bytes: b }) => *a > b - 1,
+        (
+            Decimal {
+                precision: p,
+                scale: s,
+            },
+            Decimal {
+                precision: q,
+                scale: t,
+            },
+        ) => q.saturating_sub(*t) < p.saturating_sub(*s) || t < s,
+        (Float { bytes: a }, Float { bytes: b }) => b < a,
+        (
+            String {
+                capacity: a,
+                characters: x,
+                ..
+            },
+            String {
+                capacity: b,
+                characters: y,
+                ..
+            },
+        ) => {
+            let (a, b) = (
+                string_limits(*a, *x, from_charset),
+                string_limits(*b, *y, to_charset),
+            );
+            // Between `char` and `varchar` columns only the number of characters matters
+            b.0 < a.0 || (!(*x && *y) && b.1 < a.1)
+        }
+        (Integer { .. } | Decimal { .. } | Float { .. }, String { .. }) => false,
+        (Temporal { name: a, fsp: p }, Temporal { name: b, fsp: q }) => a != b || q < p,
+        (a, b) => a != b,
+    };
+    if narrows {
+        return Some(format!(
+            "changing {} to {} may truncate values",
+            from_type, to_type
+        ));
+    }
+    let is_text = |t: &MySqlType| matches!(t, String { binary: false, .. });
+    if let (true, true, Some(from_charset), Some(to_charset)) =
+        (is_text(&from), is_text(&to), from_charset, to_charset)
+    {
+        if !charset_widens(from_charset, to_charset) {
+            return Some(format!(
+                "converting from {} to {} may mangle characters",
+                from_charset, to_charset
+            ));
+        }
+    }
+    None
+}
+
+/// Most characters and bytes a string type holds, a character taking up to as many bytes as
+/// the charset uses at most, 4 if it is unknown
+fn string_limits(capacity: u64, characters: bool, charset: Option<&str>) -> (u64, u64) {
+    match characters {
+        true => (capacity, capacity * max_char_bytes(charset)),
+        false => (capacity, capacity),
+    }
+}
+
+fn max_char_bytes(charset: Option<&str>) -> u64 {
+    match charset.map(|charset| charset.to_lowercase()).as_deref() {
+        Some("ascii" | "latin1" | "binary") => 1,
+        Some("ucs2") => 2,
+        Some("utf8" | "utf8mb3") => 3,
+        _ => 4,
+    }
+}
+
+/// Whether every character of `from` can be represented in `to`
+fn charset_widens(from: &str, to: &str) -> bool {
+    let (from, to) = (from.to_lowercase(), to.to_lowercase());
+    from == to
+        || from == "ascii"
+        || matches!(
+            (from.as_str(), to.as_str()),
+            (
+                "latin1" | "utf8" | "utf8mb3",
+                "utf8" | "utf8mb3" | "utf8mb4"
+            )
+        )
+}
+
+/// Number of decimal digits of the largest value of an integer type
+fn integer_digits(bytes: u8) -> u32 {
+    match bytes {
+        1 => 3,
+        2 => 5,
+        3 => 8,
+        4 => 10,
+        _ => 20,
+    }
+}
+
+fn parse_mysql_type(column_type: &str) -> MySqlType {
+    let column_type = column_type.to_lowercase();
+    let (name, rest) = match column_type.find(['(', ' ']) {
+        Some(i) => column_type.split_at(i),
+        None => (column_type.as_str(), ""),
+    };
+    let args: Vec<u64> = rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.split(')').next())
+        .map(|args| {
+            args.split(',')
+                .filter_map(|arg| arg.trim().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    let arg = |i: usize, default: u64| args.get(i).copied().unwrap_or(default);
+    let unsigned = rest.contains("unsigned");
+    match name {
+        "tinyint" | "bool" | "boolean" => MySqlType::Integer { bytes: 1, unsigned },
+        "smallint" => MySqlType::Integer { bytes: 2, unsigned },
+        "mediumint" => MySqlType::Integer { bytes: 3, unsigned },
+        "int" | "integer" => MySqlType::Integer { bytes: 4, unsigned },
+        "bigint" => MySqlType::Integer { bytes: 8, unsigned },
+        "decimal" | "numeric" => MySqlType::Decimal {
+            precision: arg(0, 10) as u32,
+            scale: arg(1, 0) as u32,
+        },
+        "float" => MySqlType::Float { bytes: 4 },
+        "double" | "real" => MySqlType::Float { bytes: 8 },
+        "char" | "varchar" => MySqlType::String {
+            capacity: arg(0, 1),
+            binary: false,
+            characters: true,
+        },
+        "binary" | "varbinary" => MySqlType::String {
+            capacity: arg(0, 1),
+            binary: true,
+            characters: false,
+        },
+        "tinytext" | "tinyblob" => MySqlType::String {
+            capacity: 255,
+            binary: name.ends_with("blob"),
+            characters: false,
+        },
+        "text" | "blob" => MySqlType::String {
+            capacity: 65535,
+            binary: name == "blob",
+            characters: false,
+        },
+        "mediumtext" | "mediumblob" => MySqlType::String {
+            capacity: 16777215,
+            binary: name.ends_with("blob"),
+            characters: false,
+        },
+        "longtext" | "longblob" | "json" => MySqlType::String {
+            capacity: 4294967295,
+            binary: name.ends_with("blob"),
+            characters: false,
+        },
+        "datetime" | "timestamp" | "time" => MySqlType::Temporal {
+            name: name.to_owned(),
+            fsp: arg(0, 0),
+        },
+        _ => MySqlType::Other(column_type.clone()),
+    }
+}
+
+/// Table, redefined columns and charset of `CONVERT TO CHARACTER SET` of a MySQL `ALTER TABLE` statement
+fn parse_alter_table(sql: &str) -> Option<(String, Vec<ColumnChange>, Option<String>)> {
+    let tokens = tokens(sql, true);
+    let upper: Vec<String> = tokens.iter().map(|t| t.to_uppercase()).collect();
+    if upper.get(0..2)? != ["ALTER", "TABLE"] {
+        return None;
+    }
+    let table = unquote(tokens.get(2)?);
+    let mut changes = Vec::new();
+    let mut convert_to = None;
+    // Clauses are split on top-level commas
+    let mut depth = 0;
+    let mut clauses = vec![Vec::new()];
+    for (i, token) in upper.iter().enumerate().skip(3) {
+        match token.as_str() {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                clauses.push(Vec::new());
+                continue;
+            }
+            _ => {}
+        }
+        clauses.last_mut().unwrap().push(i);
+    }
+    for clause in clauses.iter() {
+        let word = |j: usize| {
+            clause
+                .get(j)
+                .map(|&i| upper[i].as_str())
+                .unwrap_or_default()
+        };
+        let token = |j: usize| {
+            clause
+                .get(j)
+                .map(|&i| tokens[i].as_str())
+                .unwrap_or_default()
+        };
+        let mut j = match word(0) {
+            "MODIFY" | "CHANGE" => 1,
+            "CONVERT" => {
+                let j = if word(3) == "SET" { 4 } else { 3 };
+                convert_to = Some(unquote(token(j)));
+                continue;
+            }
+            _ => continue,
+        };
+        if word(j) == "COLUMN" {
+            j += 1;
+        }
+        let column = unquote(token(j));
+        j += 1;
+        if word(0) == "CHANGE" {
+            j += 1;
+        }
+        let mut column_type = word(j).to_lowercase();
+        j += 1;
+        if word(j) == "(" {
+            column_type.push('(');
+            while !matches!(word(j + 1), ")" | "") {
+                j += 1;
+                column_type.push_str(&word(j).to_lowercase());
+            }
+            column_type.push(')');
+            j += 2;
+        }
+        let mut charset = None;
+        while j < clause.len() {
+            match word(j) {
+                "UNSIGNED" => column_type.push_str(" unsigned"),
+                "CHARSET" => charset = Some(unquote(token(j + 1))),
+                "CHARACTER" if word(j + 1) == "SET" => charset = Some(unquote(token(j + 2))),
+                _ => {}
+            }
+            j += 1;
+        }
+        changes.push(ColumnChange {
+            column,
+            column_type,
+            charset,
+        });
+    }
+    Some((table, changes, convert_to))
+}
+
+fn unquote(token: &str) -> String {
+    match token.strip_prefix('`') {
+        Some(quoted) => quoted[..quoted.len() - 1].replace("``", "`"),
+        None => token.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_alter_table() {
+        let (table, changes, convert_to) = parse_alter_table(
+            "ALTER TABLE `cake` MODIFY COLUMN `name` varchar(16) NOT NULL, CHANGE `price` `cost` decimal(8, 2) unsigned, ADD COLUMN `note` text",
+        )
+        .unwrap();
+        assert_eq!(table, "cake");
+        assert_eq!(
+            changes,
+            vec![
+                ColumnChange {
+                    column: "name".to_owned(),
+                    column_type: "varchar(16)".to_owned(),
+                    charset: None,
+                },
+                ColumnChange {
+                    column: "price".to_owned(),
+                    column_type: "decimal(8,2) unsigned".to_owned(),
+                    charset: None,
+                },
+            ]
+        );
+        assert_eq!(convert_to, None);
+
+        let (_, changes, convert_to) =
+            parse_alter_table("ALTER TABLE `cake` CONVERT TO CHARACTER SET latin1").unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(convert_to.as_deref(), Some("latin1"));
+    }
+
+    #[test]
+    fn test_lossy_type_change() {
+        let utf8mb4 = Some("utf8mb4");
+        assert!(lossy_type_change("varchar(255)", utf8mb4, "varchar(16)", utf8mb4).is_some());
+        assert!(lossy_type_change("varchar(16)", utf8mb4, "text", utf8mb4).is_none());
+        assert!(lossy_type_change("text", utf8mb4, "varchar(255)", utf8mb4).is_some());
+        // 255 characters of up to 4 bytes do not fit in 255 bytes
+        assert!(lossy_type_change("varchar(255)", utf8mb4, "tinytext", utf8mb4).is_some());
+        assert!(
+            lossy_type_change("varchar(255)", Some("latin1"), "tinytext", Some("latin1")).is_none()
+        );
+        assert!(lossy_type_change("varchar(16383)", utf8mb4, "text", utf8mb4).is_none());
+        assert!(lossy_type_change("varchar(16)", utf8mb4, "varchar(16)", Some("latin1")).is_some());
+        assert!(
+            lossy_type_change("varchar(16)", Some("utf8mb3"), "varchar(16)", utf8mb4).is_none()
+        );
+        assert!(lossy_type_change("int(11)", None, "bigint", None).is_none());
+        assert!(lossy_type_change("bigint", None, "int", None).is_some());
+        assert!(lossy_type_change("int", None, "int unsigned", None).is_some());
+        assert!(lossy_type_change("smallint unsigned", None, "int", None).is_none());
+        assert!(lossy_type_change("decimal(10,2)", None, "decimal(12,4)", None).is_none());
+        assert!(lossy_type_change("decimal(10,2)", None, "decimal(10,1)", None).is_some());
+        assert!(lossy_type_change("double", None, "int", None).is_some());
+        assert!(lossy_type_change("datetime", None, "date", None).is_some());
+        assert!(lossy_type_change("datetime", None, "datetime(6)", None).is_none());
+    }
+}
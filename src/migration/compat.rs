@@ -0,0 +1,175 @@
+use super::{
+    cast_bigint, get_schema_meta_value, probe_columns, quote, seaql_migrations, AppliedAtFormat,
+    HISTORY_LAYOUT_VERSION, META_APPLIED_AT_FORMAT, META_HISTORY_LAYOUT,
+};
+use sea_orm::sea_query::{Alias, Expr, Order, Query};
+use sea_orm::{ConnectionTrait, DbConn, DbErr, QueryResult};
+use tracing::warn;
+
+/// Optional fields of [`seaql_migrations::Model`] along with the names of the columns they were
+/// stored under, newest first. A renamed column keeps its former names here, so that tables
+/// written by every earlier version remain readable.
+const HISTORY_FIELDS: [(&str, &[&str]); 6] = [
+    ("app_version", &["app_version"]),
+    ("checksum", &["checksum"]),
+    ("duration_ms", &["duration_ms"]),
+    ("applied_by", &["applied_by"]),
+    ("run_id", &["run_id"]),
+    ("batch", &["batch"]),
+];
+
+/// Layout of an existing `seaql_migrations` table, as written by any version of the migrator,
+/// including the `(version, applied_at)` layout of the upstream `sea-orm-migration`.
+///
+/// Reading the history through the layout never installs, upgrades or converts the table, so
+/// that instances of an older or newer version sharing the database can report the status
+/// without racing the instances applying the migrations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryLayout {
+    /// Columns of the table, in ordinal order
+    pub columns: Vec<String>,
+    /// How `applied_at` is stored, unix seconds unless recorded otherwise in `seaql_schema_meta`
+    pub applied_at_format: AppliedAtFormat,
+    /// Layout version recorded in `seaql_schema_meta`, `None` for tables created by the
+    /// upstream migrator or by versions predating the record
+    pub recorded_version: Option<usize>,
+}
+
+impl HistoryLayout {
+    /// Detect the layout, `None` if there is no `seaql_migrations` table
+    pub async fn detect(db: &DbConn) -> Result<Option<Self>, DbErr> {
+        let columns: Vec<String> = probe_columns(db, "seaql_migrations")
+            .await?
+            .into_iter()
+            .map(|column| column.name)
+            .collect();
+        if columns.is_empty() {
+            return Ok(None);
+        }
+        // The upstream migrator has no metadata table
+        let has_meta = !probe_columns(db, "seaql_schema_meta").await?.is_empty();
+        let (format, layout) = match has_meta {
+            true => (
+                get_schema_meta_value(db, META_APPLIED_AT_FORMAT).await?,
+                get_schema_meta_value(db, META_HISTORY_LAYOUT).await?,
+            ),
+            false => (None, None),
+        };
+        let applied_at_format = match format {
+            Some(format) => format.parse().map_err(DbErr::Custom)?,
+            None => AppliedAtFormat::UnixSeconds,
+        };
+        let recorded_version = layout.and_then(|layout| layout.parse().ok());
+        if let Some(version) = recorded_version.filter(|v| *v > HISTORY_LAYOUT_VERSION) {
+            warn!(
+                "Table 'seaql_migrations' has layout {} which is newer than {}, reading the known columns",
+                version, HISTORY_LAYOUT_VERSION
+            );
+        }
+        Ok(Some(Self {
+            columns,
+            applied_at_format,
+            recorded_version,
+        }))
+    }
+
+    /// Whether the table has the columns of the current layout
+    pub fn is_current(&self) -> bool {
+        HISTORY_FIELDS
+            .iter()
+            .all(|(_, names)| self.stored_name(names).is_some())
+    }
+
+    /// Read the applied migrations, ordered by version. The fields missing from the layout are
+    /// `None` and `applied_at` is read in the units of `applied_at_format`.
+    pub async fn read(&self, db: &DbConn) -> Result<Vec<seaql_migrations::Model>, DbErr> {
+        let db_backend = db.get_database_backend();
+        let mut stmt = Query::select();
+        stmt.expr_as(
+            Expr::cust(&quote(db_backend, "version")),
+            Alias::new("version"),
+        )
+        .expr_as(
+            self.applied_at_format.select_expr(db_backend),
+            Alias::new("applied_at"),
+        );
+        for (field, names) in HISTORY_FIELDS.iter() {
+            let name = match self.stored_name(names) {
+                Some(name) => name,
+                None => continue,
+            };
+            let col = quote(db_backend, name);
+            let expr = match *field {
+                // Integer columns of any width
+                "duration_ms" => cast_bigint(db_backend, &col),
+                _ => col,
+            };
+            stmt.expr_as(Expr::cust(&expr), Alias::new(field));
+        }
+        stmt.from(seaql_migrations::Entity)
+            .order_by(seaql_migrations::Column::Version, Order::Asc);
+        db.query_all(db_backend.build(&stmt))
+            .await?
+            .iter()
+            .map(|row| self.model(row))
+            .collect()
+    }
+
+    fn stored_name<'a>(&self, names: &[&'a str]) -> Option<&'a str> {
+        names
+            .iter()
+            .find(|name| self.columns.iter().any(|column| column == *name))
+            .copied()
+    }
+
+    fn model(&self, row: &QueryResult) -> Result<seaql_migrations::Model, DbErr> {
+        let string = |field: &str| -> Result<Option<String>, DbErr> {
+            match self.stored_name(field_names(field)) {
+                Some(_) => row.try_get("", field),
+                None => Ok(None),
+            }
+        };
+        Ok(seaql_migrations::Model {
+            version: row.try_get("", "version")?,
+            applied_at: row.try_get("", "applied_at")?,
+            app_version: string("app_version")?,
+            checksum: string("checksum")?,
+            duration_ms: match self.stored_name(field_names("duration_ms")) {
+                Some(_) => row.try_get("", "duration_ms")?,
+                None => None,
+            },
+            applied_by: string("applied_by")?,
+            run_id: string("run_id")?,
+            batch: string("batch")?,
+        })
+    }
+}
+
+fn field_names(field: &str) -> &'static [&'static str] {
+    HISTORY_FIELDS
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, names)| *names)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_layout() {
+        let layout = HistoryLayout {
+            columns: vec!["version".to_owned(), "applied_at".to_owned()],
+            applied_at_format: AppliedAtFormat::UnixSeconds,
+            recorded_version: None,
+        };
+        assert!(!layout.is_current());
+        assert_eq!(layout.stored_name(field_names("checksum")), None);
+
+        let mut columns = layout.columns.clone();
+        columns.extend(HISTORY_FIELDS.iter().map(|(field, _)| field.to_string()));
+        let layout = HistoryLayout { columns, ..layout };
+        assert!(layout.is_current());
+    }
+}
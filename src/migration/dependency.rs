@@ -0,0 +1,356 @@
+use super::{probe_tables, quote, SchemaManager, MIGRATOR_TABLE_PREFIX};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A foreign key from `table` referencing `referenced_table`
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ForeignKeyEdge {
+    pub table: String,
+    pub name: String,
+    pub referenced_table: String,
+}
+
+/// Tables of a schema along with the foreign keys between them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableGraph {
+    pub tables: Vec<String>,
+    pub foreign_keys: Vec<ForeignKeyEdge>,
+}
+
+/// Tables sorted by their foreign key dependencies
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableOrder {
+    /// Tables in dependency order, referenced tables come before the tables referencing them
+    pub sorted: Vec<String>,
+    /// Tables taking part in, or depending on, a foreign key cycle; these cannot be ordered
+    pub cyclic: Vec<String>,
+}
+
+impl TableGraph {
+    /// Discover all tables and foreign keys of the current schema
+    pub async fn discover(db: &DbConn) -> Result<Self, DbErr> {
+        let tables = probe_tables(db).await?;
+        let foreign_keys = query_foreign_keys(db, &tables).await?;
+        Ok(Self {
+            tables,
+            foreign_keys,
+        })
+    }
+
+    /// Discover the tables of a named schema and the foreign keys between them (MySQL and Postgres),
+    /// the schema being a database on MySQL
+    pub async fn discover_schema(db: &DbConn, schema: &str) -> Result<Self, DbErr> {
+        let db_backend = db.get_database_backend();
+        let (tables_sql, foreign_keys_sql) = match db_backend {
+            DbBackend::MySql => (
+                r#"SELECT `TABLE_NAME` AS `table_name`
+                FROM `information_schema`.`TABLES`
+                WHERE `TABLE_SCHEMA` = ? AND `TABLE_TYPE` = 'BASE TABLE'
+                ORDER BY `TABLE_NAME`"#,
+                r#"SELECT `TABLE_NAME` AS `table_name`, `CONSTRAINT_NAME` AS `constraint_name`,
+                    `REFERENCED_TABLE_NAME` AS `referenced_table_name`
+                FROM `information_schema`.`REFERENTIAL_CONSTRAINTS`
+                WHERE `CONSTRAINT_SCHEMA` = ? AND `UNIQUE_CONSTRAINT_SCHEMA` = `CONSTRAINT_SCHEMA`
+                ORDER BY `TABLE_NAME`, `CONSTRAINT_NAME`"#,
+            ),
+            DbBackend::Postgres => (
+                r#"SELECT "tablename"::text AS "table_name"
+                FROM "pg_tables"
+                WHERE "schemaname" = $1
+                ORDER BY "tablename""#,
+                r#"SELECT "cl"."relname"::text AS "table_name",
+                    "c"."conname"::text AS "constraint_name",
+                    "rcl"."relname"::text AS "referenced_table_name"
+                FROM "pg_constraint" AS "c"
+                JOIN "pg_class" AS "cl" ON "cl"."oid" = "c"."conrelid"
+                JOIN "pg_class" AS "rcl" ON "rcl"."oid" = "c"."confrelid"
+                JOIN "pg_namespace" AS "n" ON "n"."oid" = "cl"."relnamespace"
+                WHERE "c"."contype" = 'f' AND "n"."nspname" = $1
+                    AND "rcl"."relnamespace" = "cl"."relnamespace"
+                ORDER BY "cl"."relname", "c"."conname""#,
+            ),
+            DbBackend::Sqlite => {
+                return Err(DbErr::Custom(
+                    "Discovering a named schema is not supported on SQLite".to_owned(),
+                ))
+            }
+        };
+        let query =
+            |sql: &str| Statement::from_sql_and_values(db_backend, sql, vec![schema.into()]);
+        let tables = db
+            .query_all(query(tables_sql))
+            .await?
+            .into_iter()
+            .map(|row| row.try_get("", "table_name"))
+            .collect::<Result<Vec<String>, DbErr>>()?;
+        let mut foreign_keys = Vec::new();
+        for row in db.query_all(query(foreign_keys_sql)).await?.into_iter() {
+            foreign_keys.push(ForeignKeyEdge {
+                table: row.try_get("", "table_name")?,
+                name: row.try_get("", "constraint_name")?,
+                referenced_table: row.try_get("", "referenced_table_name")?,
+            });
+        }
+        Ok(Self {
+            tables,
+            foreign_keys,
+        })
+    }
+
+    /// Sort the tables such that every table comes after the tables it references.
+    /// Self-referencing foreign keys do not impose any ordering.
+    pub fn sort(&self) -> TableOrder {
+        let tables: BTreeSet<&str> = self.tables.iter().map(|t| t.as_str()).collect();
+        let mut dependencies: BTreeMap<&str, BTreeSet<&str>> =
+            tables.iter().map(|t| (*t, BTreeSet::new())).collect();
+        for fk in self.foreign_keys.iter() {
+            if fk.table != fk.referenced_table && tables.contains(fk.referenced_table.as_str()) {
+                if let Some(deps) = dependencies.get_mut(fk.table.as_str()) {
+                    deps.insert(fk.referenced_table.as_str());
+                }
+            }
+        }
+        let mut order = TableOrder::default();
+        let mut placed = BTreeSet::new();
+        // Preserve the discovered order among tables without mutual dependencies
+        let mut remaining: Vec<&str> = self.tables.iter().map(|t| t.as_str()).collect();
+        loop {
+            let (ready, pending): (Vec<&str>, Vec<&str>) = remaining
+                .into_iter()
+                .partition(|t| dependencies[t].iter().all(|dep| placed.contains(dep)));
+            if ready.is_empty() {
+                order.cyclic = pending.into_iter().map(|t| t.to_owned()).collect();
+                break;
+            }
+            for table in ready {
+                placed.insert(table);
+                order.sorted.push(table.to_owned());
+            }
+            remaining = pending;
+        }
+        order
+    }
+
+    /// Foreign keys of which both ends are among the given tables
+    pub fn foreign_keys_within(&self, tables: &[String]) -> Vec<&ForeignKeyEdge> {
+        self.foreign_keys
+            .iter()
+            .filter(|fk| tables.contains(&fk.table) && tables.contains(&fk.referenced_table))
+            .collect()
+    }
+}
+
+/// Table Cleanup
+impl<'c> SchemaManager<'c> {
+    /// Remove every row from the tables of the current schema, except the given tables and the
+    /// tables of the migrator, without dropping them, e.g. to clean up between test cases.
+    /// Return the emptied tables, in order.
+    ///
+    /// Tables are emptied referencing tables first: with a single `TRUNCATE` on Postgres, which
+    /// fails if a kept table references an emptied one, and with `DELETE` on MySQL and SQLite.
+    /// Tables referencing each other in a cycle are emptied in a single transaction, with the
+    /// foreign key check disabled on MySQL and deferred to the commit on SQLite.
+    pub async fn truncate_all_except(&self, tables: &[&str]) -> Result<Vec<String>, DbErr> {
+        let db_backend = self.get_database_backend();
+        let mut graph = TableGraph::discover(self.schema_connection()).await?;
+        graph.tables.retain(|table| {
+            !tables.contains(&table.as_str()) && !table.starts_with(MIGRATOR_TABLE_PREFIX)
+        });
+        let order = graph.sort();
+        let emptied: Vec<String> = order
+            .cyclic
+            .iter()
+            .chain(order.sorted.iter().rev())
+            .cloned()
+            .collect();
+        if emptied.is_empty() {
+            return Ok(emptied);
+        }
+        if db_backend == DbBackend::Postgres {
+            let tables: Vec<String> = emptied.iter().map(|t| quote(db_backend, t)).collect();
+            self.exec_raw(Statement::from_string(
+                db_backend,
+                format!("TRUNCATE TABLE {}", tables.join(", ")),
+            ))
+            .await?;
+            return Ok(emptied);
+        }
+        let delete = |table: &str| {
+            Statement::from_string(
+                db_backend,
+                format!("DELETE FROM {}", quote(db_backend, table)),
+            )
+        };
+        if order.cyclic.is_empty() {
+            for table in emptied.iter() {
+                self.exec_raw(delete(table)).await?;
+            }
+            return Ok(emptied);
+        }
+
+        // The foreign key check is a setting of the session, the transaction holds a single
+        // connection of the pool for it, unless one is already held, e.g. by the migrator
+        let own = !self.in_transaction();
+        let defer = match db_backend {
+            DbBackend::MySql => "SET FOREIGN_KEY_CHECKS = 0",
+            // Reset at the end of the transaction
+            _ => "PRAGMA defer_foreign_keys = ON",
+        };
+        if own {
+            self.begin().await?;
+        }
+        let deleted = async {
+            self.exec_raw(Statement::from_string(db_backend, defer.to_owned()))
+                .await?;
+            for table in emptied.iter() {
+                self.exec_raw(delete(table)).await?;
+            }
+            Ok(())
+        }
+        .await;
+        let restored = match db_backend {
+            // Restored before the connection returns to the pool
+            DbBackend::MySql => {
+                self.exec_raw(Statement::from_string(
+                    db_backend,
+                    "SET FOREIGN_KEY_CHECKS = 1".to_owned(),
+                ))
+                .await
+            }
+            _ => Ok(()),
+        };
+        match deleted.and(restored) {
+            Ok(()) if own => self.commit().await.map(|_| emptied),
+            Ok(()) => Ok(emptied),
+            Err(err) => {
+                if own {
+                    self.rollback().await?;
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Query the foreign keys defined on the given tables of the current schema, referencing
+/// tables of the current schema
+pub async fn query_foreign_keys(
+    db: &DbConn,
+    tables: &[String],
+) -> Result<Vec<ForeignKeyEdge>, DbErr> {
+    let db_backend = db.get_database_backend();
+    let mut foreign_keys = Vec::new();
+    match db_backend {
+        DbBackend::MySql | DbBackend::Postgres => {
+            let sql = match db_backend {
+                DbBackend::MySql => {
+                    r#"SELECT `TABLE_NAME` AS `table_name`, `CONSTRAINT_NAME` AS `constraint_name`,
+                        `REFERENCED_TABLE_NAME` AS `referenced_table_name`
+                    FROM `information_schema`.`REFERENTIAL_CONSTRAINTS`
+                    WHERE `CONSTRAINT_SCHEMA` = DATABASE()
+                        AND `UNIQUE_CONSTRAINT_SCHEMA` = `CONSTRAINT_SCHEMA`
+                    ORDER BY `TABLE_NAME`, `CONSTRAINT_NAME`"#
+                }
+                DbBackend::Postgres => {
+                    r#"SELECT "cl"."relname"::text AS "table_name",
+                        "c"."conname"::text AS "constraint_name",
+                        "rcl"."relname"::text AS "referenced_table_name"
+                    FROM "pg_constraint" AS "c"
+                    JOIN "pg_class" AS "cl" ON "cl"."oid" = "c"."conrelid"
+                    JOIN "pg_class" AS "rcl" ON "rcl"."oid" = "c"."confrelid"
+                    JOIN "pg_namespace" AS "n" ON "n"."oid" = "cl"."relnamespace"
+                    WHERE "c"."contype" = 'f' AND "n"."nspname" = CURRENT_SCHEMA()
+                        AND "rcl"."relnamespace" = "cl"."relnamespace"
+                    ORDER BY "cl"."relname", "c"."conname""#
+                }
+                DbBackend::Sqlite => unreachable!(),
+            };
+            let rows = db
+                .query_all(Statement::from_string(db_backend, sql.to_owned()))
+                .await?;
+            for row in rows.into_iter() {
+                let table: String = row.try_get("", "table_name")?;
+                if !tables.contains(&table) {
+                    continue;
+                }
+                foreign_keys.push(ForeignKeyEdge {
+                    table,
+                    name: row.try_get("", "constraint_name")?,
+                    referenced_table: row.try_get("", "referenced_table_name")?,
+                });
+            }
+        }
+        DbBackend::Sqlite => {
+            for table in tables.iter() {
+                let stmt = Statement::from_string(
+                    db_backend,
+                    format!(
+                        "PRAGMA foreign_key_list(\"{}\")",
+                        table.replace('"', "\"\"")
+                    ),
+                );
+                let mut seen = BTreeSet::new();
+                for row in db.query_all(stmt).await?.into_iter() {
+                    // Composite foreign keys span multiple rows sharing the same id
+                    let id: i32 = row.try_get("", "id")?;
+                    if !seen.insert(id) {
+                        continue;
+                    }
+                    foreign_keys.push(ForeignKeyEdge {
+                        table: table.clone(),
+                        name: id.to_string(),
+                        referenced_table: row.try_get("", "table")?,
+                    });
+                }
+            }
+        }
+    }
+    Ok(foreign_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fk(table: &str, referenced_table: &str) -> ForeignKeyEdge {
+        ForeignKeyEdge {
+            table: table.to_owned(),
+            name: format!("fk-{}-{}", table, referenced_table),
+            referenced_table: referenced_table.to_owned(),
+        }
+    }
+
+    fn graph(tables: &[&str], foreign_keys: Vec<ForeignKeyEdge>) -> TableGraph {
+        TableGraph {
+            tables: tables.iter().map(|t| t.to_string()).collect(),
+            foreign_keys,
+        }
+    }
+
+    #[test]
+    fn test_sort_tables() {
+        let order = graph(
+            &["fruit", "cake_fruit", "cake", "baker"],
+            vec![
+                fk("cake_fruit", "cake"),
+                fk("cake_fruit", "fruit"),
+                fk("cake", "baker"),
+                fk("baker", "baker"),
+            ],
+        )
+        .sort();
+        assert_eq!(order.sorted, vec!["fruit", "baker", "cake", "cake_fruit"]);
+        assert!(order.cyclic.is_empty());
+    }
+
+    #[test]
+    fn test_sort_tables_with_cycle() {
+        let order = graph(
+            &["a", "b", "c", "d"],
+            vec![fk("a", "b"), fk("b", "a"), fk("c", "a")],
+        )
+        .sort();
+        assert_eq!(order.sorted, vec!["d"]);
+        assert_eq!(order.cyclic, vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,660 @@
+use super::{lossy_type_change, ColumnInfo, SchemaManager};
+use crate::classification::{Classification, Classifications};
+use sea_orm::sea_query::{Alias, ColumnDef, Table, TableAlterStatement};
+use sea_orm::{DbBackend, DbErr};
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::io::{BufRead, Write};
+
+/// A change turning the live columns of a table into the desired ones
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnDiff {
+    Add(ColumnInfo),
+    Drop(String),
+    /// A dropped and an added column paired as a rename, with the confidence of the pairing
+    /// between 0 and 1, 1 for renames given explicitly
+    Rename {
+        from: String,
+        to: String,
+        confidence: f64,
+    },
+    /// Type, nullability or default of a column changed
+    Alter {
+        from: ColumnInfo,
+        to: ColumnInfo,
+    },
+}
+
+/// How [`diff_columns`] pairs dropped and added columns as renames
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnDiffOptions {
+    /// Minimum confidence for pairing a dropped column with an added one, above 1 to never
+    /// detect renames
+    pub rename_threshold: f64,
+    /// Renames applied regardless of the confidence, as (from, to) pairs
+    pub renames: Vec<(String, String)>,
+    /// (from, to) pairs never considered renames
+    pub not_renames: Vec<(String, String)>,
+    /// Minimum confidence of a possible rename to be submitted to a [`DiffResolver`]
+    pub decision_threshold: f64,
+    /// Classified columns, whose drop is submitted to a [`DiffResolver`]
+    pub classifications: Classifications,
+}
+
+/// A choice [`resolve_column_diff`] leaves to a [`DiffResolver`] rather than guessing
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffDecision {
+    /// Whether the dropped column `from` was renamed to the added column `to`,
+    /// rather than dropped along with its data
+    Rename {
+        table: String,
+        from: String,
+        to: String,
+        confidence: f64,
+    },
+    /// Whether to change the type of a column although it may truncate values,
+    /// rather than leaving the column unchanged
+    Narrow {
+        table: String,
+        column: String,
+        from_type: String,
+        to_type: String,
+        reason: String,
+    },
+    /// Whether to drop a classified column, rather than keeping it
+    DropClassified {
+        table: String,
+        column: String,
+        classifications: Vec<Classification>,
+    },
+}
+
+/// Resolver of the decision points of a diff, e.g. prompting a user or failing in CI.
+/// Closures taking a [`DiffDecision`] and returning `Result<bool, DbErr>` are resolvers.
+pub trait DiffResolver {
+    /// Accept the change described by the decision, or reject it. An error aborts the resolution.
+    fn resolve(&mut self, decision: &DiffDecision) -> Result<bool, DbErr>;
+}
+
+/// Fail on the first decision point, for non-interactive runs such as CI
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FailingResolver;
+
+/// Ask on the terminal, accepting on `y`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PromptResolver;
+
+impl Default for ColumnDiffOptions {
+    fn default() -> Self {
+        Self {
+            rename_threshold: 0.8,
+            renames: Vec::new(),
+            not_renames: Vec::new(),
+            decision_threshold: 0.4,
+            classifications: Classifications::default(),
+        }
+    }
+}
+
+impl ColumnDiffOptions {
+    pub fn rename_threshold(mut self, rename_threshold: f64) -> Self {
+        self.rename_threshold = rename_threshold;
+        self
+    }
+
+    pub fn decision_threshold(mut self, decision_threshold: f64) -> Self {
+        self.decision_threshold = decision_threshold;
+        self
+    }
+
+    pub fn classifications(mut self, classifications: Classifications) -> Self {
+        self.classifications = classifications;
+        self
+    }
+
+    /// Treat `from` being replaced by `to` as a rename
+    pub fn rename<F, T>(mut self, from: F, to: T) -> Self
+    where
+        F: Into<String>,
+        T: Into<String>,
+    {
+        self.renames.push((from.into(), to.into()));
+        self
+    }
+
+    /// Never treat `from` being replaced by `to` as a rename
+    pub fn not_rename<F, T>(mut self, from: F, to: T) -> Self
+    where
+        F: Into<String>,
+        T: Into<String>,
+    {
+        self.not_renames.push((from.into(), to.into()));
+        self
+    }
+}
+
+impl Display for ColumnDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add(column) => write!(f, "ADD {} {}", column.name, column.column_type),
+            Self::Drop(column) => write!(f, "DROP {}", column),
+            Self::Rename {
+                from,
+                to,
+                confidence,
+            } => write!(f, "RENAME {} TO {} ({:.0}%)", from, to, confidence * 100.0),
+            Self::Alter { from, to } => write!(
+                f,
+                "ALTER {} {} TO {}",
+                from.name, from.column_type, to.column_type
+            ),
+        }
+    }
+}
+
+impl Display for DiffDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rename {
+                table,
+                from,
+                to,
+                confidence,
+            } => write!(
+                f,
+                "Rename column {}.{} to {}, rather than dropping it? ({:.0}% confidence)",
+                table,
+                from,
+                to,
+                confidence * 100.0
+            ),
+            Self::Narrow {
+                table,
+                column,
+                from_type,
+                to_type,
+                reason,
+            } => write!(
+                f,
+                "Change column {}.{} from {} to {}? ({})",
+                table, column, from_type, to_type, reason
+            ),
+            Self::DropClassified {
+                table,
+                column,
+                classifications,
+            } => write!(
+                f,
+                "Drop column {}.{} classified as {}?",
+                table,
+                column,
+                classifications
+                    .iter()
+                    .map(|classification| classification.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl<F> DiffResolver for F
+where
+    F: FnMut(&DiffDecision) -> Result<bool, DbErr>,
+{
+    fn resolve(&mut self, decision: &DiffDecision) -> Result<bool, DbErr> {
+        self(decision)
+    }
+}
+
+impl DiffResolver for FailingResolver {
+    fn resolve(&mut self, decision: &DiffDecision) -> Result<bool, DbErr> {
+        Err(DbErr::Custom(format!(
+            "Diff needs a decision: {}",
+            decision
+        )))
+    }
+}
+
+impl DiffResolver for PromptResolver {
+    fn resolve(&mut self, decision: &DiffDecision) -> Result<bool, DbErr> {
+        let io_err = |err: std::io::Error| DbErr::Custom(format!("Fail to prompt: {}", err));
+        print!("{} [y/N] ", decision);
+        std::io::stdout().flush().map_err(io_err)?;
+        let mut answer = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .map_err(io_err)?;
+        Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+    }
+}
+
+/// Confidence of a live column removed at `from_pos` having been renamed to a desired column
+/// added at `to_pos`: columns of different types are never paired, otherwise nullability,
+/// default and position each add to the confidence
+pub fn rename_confidence(
+    from: &ColumnInfo,
+    from_pos: usize,
+    to: &ColumnInfo,
+    to_pos: usize,
+) -> f64 {
+    if !from.column_type.eq_ignore_ascii_case(&to.column_type) {
+        return 0.0;
+    }
+    let mut confidence = 0.4;
+    if from.nullable == to.nullable {
+        confidence += 0.2;
+    }
+    if from.default == to.default {
+        confidence += 0.2;
+    }
+    if from_pos == to_pos {
+        confidence += 0.2;
+    }
+    confidence
+}
+
+/// Changes turning the `live` columns of a table into the `desired` ones, in order: renames,
+/// drops, alters and adds. A dropped and an added column are paired as a rename if given in
+/// `options`, or if their [`rename_confidence`] reaches the threshold, most confident first.
+pub fn diff_columns(
+    live: &[ColumnInfo],
+    desired: &[ColumnInfo],
+    options: &ColumnDiffOptions,
+) -> Vec<ColumnDiff> {
+    let is_desired = |name: &str| desired.iter().any(|column| column.name == name);
+    let is_live = |name: &str| live.iter().any(|column| column.name == name);
+    let mut dropped: Vec<(usize, &ColumnInfo)> = live
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| !is_desired(&column.name))
+        .collect();
+    let mut added: Vec<(usize, &ColumnInfo)> = desired
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| !is_live(&column.name))
+        .collect();
+
+    let mut renames = Vec::new();
+    for (from, to) in options.renames.iter() {
+        let i = dropped.iter().position(|(_, column)| &column.name == from);
+        let j = added.iter().position(|(_, column)| &column.name == to);
+        if let (Some(i), Some(j)) = (i, j) {
+            let (_, from) = dropped.remove(i);
+            let (_, to) = added.remove(j);
+            renames.push((from, to, 1.0));
+        }
+    }
+    let candidates = rename_candidates(&dropped, &added, options, options.rename_threshold);
+    for (confidence, from, to) in candidates {
+        let i = dropped.iter().position(|(_, column)| column.name == from);
+        let j = added.iter().position(|(_, column)| column.name == to);
+        if let (Some(i), Some(j)) = (i, j) {
+            let (_, from) = dropped.remove(i);
+            let (_, to) = added.remove(j);
+            renames.push((from, to, confidence));
+        }
+    }
+
+    let mut diffs = Vec::new();
+    for (from, to, confidence) in renames.iter() {
+        diffs.push(ColumnDiff::Rename {
+            from: from.name.clone(),
+            to: to.name.clone(),
+            confidence: *confidence,
+        });
+    }
+    for (_, column) in dropped.iter() {
+        diffs.push(ColumnDiff::Drop(column.name.clone()));
+    }
+    let pairs = live
+        .iter()
+        .filter_map(|from| {
+            let to = desired.iter().find(|to| to.name == from.name)?;
+            Some((from, to))
+        })
+        .chain(renames.iter().map(|(from, to, _)| (*from, *to)));
+    for (from, to) in pairs {
+        let changed = !from.column_type.eq_ignore_ascii_case(&to.column_type)
+            || from.nullable != to.nullable
+            || from.default != to.default;
+        if changed {
+            diffs.push(ColumnDiff::Alter {
+                from: ColumnInfo {
+                    name: to.name.clone(),
+                    ..from.clone()
+                },
+                to: to.clone(),
+            });
+        }
+    }
+    for (_, column) in added.iter() {
+        diffs.push(ColumnDiff::Add((*column).clone()));
+    }
+    diffs
+}
+
+/// Dropped and added columns possibly renamed with at least the given confidence,
+/// most confident first
+fn rename_candidates(
+    dropped: &[(usize, &ColumnInfo)],
+    added: &[(usize, &ColumnInfo)],
+    options: &ColumnDiffOptions,
+    threshold: f64,
+) -> Vec<(f64, String, String)> {
+    let mut candidates = Vec::new();
+    for (from_pos, from) in dropped.iter() {
+        for (to_pos, to) in added.iter() {
+            let forbidden = options
+                .not_renames
+                .iter()
+                .any(|(f, t)| f == &from.name && t == &to.name);
+            let confidence = rename_confidence(from, *from_pos, to, *to_pos);
+            if !forbidden && confidence >= threshold {
+                candidates.push((confidence, from.name.clone(), to.name.clone()));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    candidates
+}
+
+/// Changes turning the `live` columns of a table into the `desired` ones like [`diff_columns`],
+/// without guessing: every possible rename not given in `options`, every type change which
+/// may truncate values and every drop of a classified column is submitted to the resolver.
+/// These changes are only applied once accepted, a rejected one leaves the column unchanged.
+pub fn resolve_column_diff<R>(
+    table: &str,
+    live: &[ColumnInfo],
+    desired: &[ColumnInfo],
+    options: &ColumnDiffOptions,
+    resolver: &mut R,
+) -> Result<Vec<ColumnDiff>, DbErr>
+where
+    R: DiffResolver + ?Sized,
+{
+    let mut options = options.clone();
+    loop {
+        let dropped: Vec<(usize, &ColumnInfo)> = live
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| {
+                !desired.iter().any(|c| c.name == column.name)
+                    && !options.renames.iter().any(|(from, _)| from == &column.name)
+            })
+            .collect();
+        let added: Vec<(usize, &ColumnInfo)> = desired
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| {
+                !live.iter().any(|c| c.name == column.name)
+                    && !options.renames.iter().any(|(_, to)| to == &column.name)
+            })
+            .collect();
+        let candidates = rename_candidates(&dropped, &added, &options, options.decision_threshold);
+        // Decide the most confident rename first, as accepting it rules out the others of its columns
+        let (confidence, from, to) = match candidates.into_iter().next() {
+            Some(candidate) => candidate,
+            None => break,
+        };
+        let decision = DiffDecision::Rename {
+            table: table.to_owned(),
+            from: from.clone(),
+            to: to.clone(),
+            confidence,
+        };
+        match resolver.resolve(&decision)? {
+            true => options.renames.push((from, to)),
+            false => options.not_renames.push((from, to)),
+        }
+    }
+    options.rename_threshold = f64::INFINITY;
+    let mut diffs = Vec::new();
+    for diff in diff_columns(live, desired, &options) {
+        let decision = match &diff {
+            ColumnDiff::Alter { from, to } => {
+                lossy_type_change(&from.column_type, None, &to.column_type, None).map(|reason| {
+                    DiffDecision::Narrow {
+                        table: table.to_owned(),
+                        column: to.name.clone(),
+                        from_type: from.column_type.clone(),
+                        to_type: to.column_type.clone(),
+                        reason,
+                    }
+                })
+            }
+            ColumnDiff::Drop(column) => {
+                let classifications = options.classifications.get(table, column);
+                (!classifications.is_empty()).then(|| DiffDecision::DropClassified {
+                    table: table.to_owned(),
+                    column: column.clone(),
+                    classifications,
+                })
+            }
+            _ => None,
+        };
+        if let Some(decision) = decision {
+            if !resolver.resolve(&decision)? {
+                continue;
+            }
+        }
+        diffs.push(diff);
+    }
+    Ok(diffs)
+}
+
+/// Statements applying column changes to a table. Altering a column is not supported on SQLite.
+pub fn column_diff_statements(
+    table: &str,
+    diffs: &[ColumnDiff],
+    db_backend: DbBackend,
+) -> Result<Vec<TableAlterStatement>, DbErr> {
+    let column_def = |column: &ColumnInfo| {
+        let mut def = ColumnDef::new(Alias::new(&column.name));
+        def.custom(Alias::new(&column.column_type));
+        match column.nullable {
+            true => def.null(),
+            false => def.not_null(),
+        };
+        if let Some(default) = &column.default {
+            def.extra(format!("DEFAULT {}", default));
+        }
+        def
+    };
+    let mut statements = Vec::new();
+    for diff in diffs.iter() {
+        let mut stmt = Table::alter();
+        stmt.table(Alias::new(table));
+        match diff {
+            ColumnDiff::Add(column) => stmt.add_column(&mut column_def(column)),
+            ColumnDiff::Drop(column) => stmt.drop_column(Alias::new(column)),
+            ColumnDiff::Rename { from, to, .. } => {
+                stmt.rename_column(Alias::new(from), Alias::new(to))
+            }
+            ColumnDiff::Alter { to, .. } => {
+                if db_backend == DbBackend::Sqlite {
+                    return Err(DbErr::Custom(format!(
+                        "Column '{}' of table '{}' cannot be altered on SQLite",
+                        to.name, table
+                    )));
+                }
+                stmt.modify_column(&mut column_def(to))
+            }
+        };
+        statements.push(stmt);
+    }
+    Ok(statements)
+}
+
+/// Schema Diff
+impl<'c> SchemaManager<'c> {
+    /// Changes turning the live columns of a table into the desired ones, see [`diff_columns`]
+    pub async fn diff_columns(
+        &self,
+        table: &str,
+        desired: &[ColumnInfo],
+        options: &ColumnDiffOptions,
+    ) -> Result<Vec<ColumnDiff>, DbErr> {
+        let live = self.table_columns(table).await?;
+        Ok(diff_columns(&live, desired, options))
+    }
+
+    /// Changes turning the live columns of a table into the desired ones, with the ambiguous
+    /// ones decided by the resolver, see [`resolve_column_diff`]
+    pub async fn resolve_column_diff<R>(
+        &self,
+        table: &str,
+        desired: &[ColumnInfo],
+        options: &ColumnDiffOptions,
+        resolver: &mut R,
+    ) -> Result<Vec<ColumnDiff>, DbErr>
+    where
+        R: DiffResolver + ?Sized,
+    {
+        let live = self.table_columns(table).await?;
+        resolve_column_diff(table, &live, desired, options, resolver)
+    }
+
+    /// Apply column changes to a table, e.g. computed by [`SchemaManager::diff_columns`]
+    pub async fn apply_column_diff(&self, table: &str, diffs: &[ColumnDiff]) -> Result<(), DbErr> {
+        for stmt in column_diff_statements(table, diffs, self.get_database_backend())? {
+            self.alter_table(stmt).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, column_type: &str) -> ColumnInfo {
+        ColumnInfo {
+            name: name.to_owned(),
+            column_type: column_type.to_owned(),
+            nullable: false,
+            default: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_columns() {
+        let live = vec![
+            column("id", "integer"),
+            column("name", "text"),
+            column("price", "integer"),
+        ];
+        let desired = vec![
+            column("id", "integer"),
+            column("title", "text"),
+            column("cost", "numeric"),
+        ];
+        let options = ColumnDiffOptions::default();
+        assert_eq!(
+            diff_columns(&live, &desired, &options),
+            vec![
+                ColumnDiff::Rename {
+                    from: "name".to_owned(),
+                    to: "title".to_owned(),
+                    confidence: 1.0,
+                },
+                ColumnDiff::Drop("price".to_owned()),
+                ColumnDiff::Add(column("cost", "numeric")),
+            ]
+        );
+
+        let options = ColumnDiffOptions::default()
+            .not_rename("name", "title")
+            .rename("price", "cost");
+        assert_eq!(
+            diff_columns(&live, &desired, &options),
+            vec![
+                ColumnDiff::Rename {
+                    from: "price".to_owned(),
+                    to: "cost".to_owned(),
+                    confidence: 1.0,
+                },
+                ColumnDiff::Drop("name".to_owned()),
+                ColumnDiff::Alter {
+                    from: column("cost", "integer"),
+                    to: column("cost", "numeric"),
+                },
+                ColumnDiff::Add(column("title", "text")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_column_diff() {
+        let live = vec![column("id", "bigint"), column("name", "varchar(255)")];
+        let desired = vec![column("id", "integer"), column("title", "varchar(255)")];
+        let options = ColumnDiffOptions::default();
+
+        assert!(
+            resolve_column_diff("cake", &live, &desired, &options, &mut FailingResolver).is_err()
+        );
+
+        let mut decisions = Vec::new();
+        let mut resolver = |decision: &DiffDecision| {
+            decisions.push(decision.clone());
+            Ok(true)
+        };
+        let diffs = resolve_column_diff("cake", &live, &desired, &options, &mut resolver).unwrap();
+        assert_eq!(
+            diffs,
+            vec![
+                ColumnDiff::Rename {
+                    from: "name".to_owned(),
+                    to: "title".to_owned(),
+                    confidence: 1.0,
+                },
+                ColumnDiff::Alter {
+                    from: column("id", "bigint"),
+                    to: column("id", "integer"),
+                },
+            ]
+        );
+        assert_eq!(decisions.len(), 2);
+
+        let diffs = resolve_column_diff(
+            "cake",
+            &live,
+            &desired,
+            &options,
+            &mut |_: &DiffDecision| Ok(false),
+        )
+        .unwrap();
+        assert_eq!(
+            diffs,
+            vec![
+                ColumnDiff::Drop("name".to_owned()),
+                ColumnDiff::Add(column("title", "varchar(255)")),
+            ]
+        );
+
+        let options = ColumnDiffOptions::default()
+            .not_rename("name", "title")
+            .classifications(Classifications::default().tag("cake", "name", Classification::Pii));
+        let diffs = resolve_column_diff(
+            "cake",
+            &live,
+            &desired,
+            &options,
+            &mut |decision: &DiffDecision| {
+                Ok(!matches!(decision, DiffDecision::DropClassified { .. }))
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            diffs,
+            vec![
+                ColumnDiff::Alter {
+                    from: column("id", "bigint"),
+                    to: column("id", "integer"),
+                },
+                ColumnDiff::Add(column("title", "varchar(255)")),
+            ]
+        );
+    }
+}
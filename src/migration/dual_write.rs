@@ -0,0 +1,285 @@
+use super::{quote, MigrationName, MigrationTrait, SchemaManager};
+use sea_orm::{DbBackend, DbErr, Statement};
+use tracing::info;
+
+/// Temporary triggers keeping an old and a new column of a table in sync while both are written
+/// to, e.g. across the deploys of a column rename or type change.
+///
+/// A row inserted with only one of the columns set gets the other one converted from it, and an
+/// update of either column is copied to the other one. The conversions are SQL expressions in which
+/// `{}` stands for the column converted from, e.g. `CAST({} AS BIGINT)`, and default to the value
+/// itself. On SQLite the triggers update the row by `rowid`, tables `WITHOUT ROWID` are unsupported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DualWrite {
+    pub table: String,
+    pub old_column: String,
+    pub new_column: String,
+    /// Conversion of the old column into the new one
+    pub to_new: String,
+    /// Conversion of the new column into the old one
+    pub to_old: String,
+    /// Name of the triggers, or their prefix if the backend needs several
+    pub name: String,
+}
+
+impl DualWrite {
+    pub fn new<T, O, N>(table: T, old_column: O, new_column: N) -> Self
+    where
+        T: Into<String>,
+        O: Into<String>,
+        N: Into<String>,
+    {
+        let (table, old_column, new_column) = (table.into(), old_column.into(), new_column.into());
+        Self {
+            name: format!("dual_write_{}_{}_{}", table, old_column, new_column),
+            table,
+            old_column,
+            new_column,
+            to_new: "{}".to_owned(),
+            to_old: "{}".to_owned(),
+        }
+    }
+
+    /// Convert the old column into the new one with the expression, `{}` standing for the old column
+    pub fn to_new<E>(mut self, expr: E) -> Self
+    where
+        E: Into<String>,
+    {
+        self.to_new = expr.into();
+        self
+    }
+
+    /// Convert the new column into the old one with the expression, `{}` standing for the new column
+    pub fn to_old<E>(mut self, expr: E) -> Self
+    where
+        E: Into<String>,
+    {
+        self.to_old = expr.into();
+        self
+    }
+
+    /// Name the triggers instead of `dual_write_<table>_<old column>_<new column>`
+    pub fn name<N>(mut self, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.name = name.into();
+        self
+    }
+
+    /// Statements creating the triggers
+    pub fn create_statements(&self, db_backend: DbBackend) -> Vec<Statement> {
+        let table = quote(db_backend, &self.table);
+        let old = quote(db_backend, &self.old_column);
+        let new = quote(db_backend, &self.new_column);
+        let to_new = |row: &str| self.to_new.replace("{}", &format!("{}.{}", row, old));
+        let to_old = |row: &str| self.to_old.replace("{}", &format!("{}.{}", row, new));
+        let sql = match db_backend {
+            DbBackend::Postgres => vec![
+                format!(
+                    r#"CREATE OR REPLACE FUNCTION {name}() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        IF NEW.{new} IS NULL THEN
+            NEW.{new} := {to_new};
+        ELSIF NEW.{old} IS NULL THEN
+            NEW.{old} := {to_old};
+        END IF;
+    ELSIF NEW.{old} IS DISTINCT FROM OLD.{old} THEN
+        NEW.{new} := {to_new};
+    ELSIF NEW.{new} IS DISTINCT FROM OLD.{new} THEN
+        NEW.{old} := {to_old};
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql"#,
+                    name = quote(db_backend, &self.name),
+                    old = old,
+                    new = new,
+                    to_new = to_new("NEW"),
+                    to_old = to_old("NEW"),
+                ),
+                format!(
+                    "CREATE TRIGGER {name} BEFORE INSERT OR UPDATE ON {table} FOR EACH ROW EXECUTE PROCEDURE {name}()",
+                    name = quote(db_backend, &self.name),
+                    table = table,
+                ),
+            ],
+            DbBackend::MySql => vec![
+                format!(
+                    "CREATE TRIGGER {name} BEFORE INSERT ON {table} FOR EACH ROW BEGIN IF NEW.{new} IS NULL THEN SET NEW.{new} = {to_new}; ELSEIF NEW.{old} IS NULL THEN SET NEW.{old} = {to_old}; END IF; END",
+                    name = quote(db_backend, &format!("{}_insert", self.name)),
+                    table = table,
+                    old = old,
+                    new = new,
+                    to_new = to_new("NEW"),
+                    to_old = to_old("NEW"),
+                ),
+                format!(
+                    "CREATE TRIGGER {name} BEFORE UPDATE ON {table} FOR EACH ROW BEGIN IF NOT (NEW.{old} <=> OLD.{old}) THEN SET NEW.{new} = {to_new}; ELSEIF NOT (NEW.{new} <=> OLD.{new}) THEN SET NEW.{old} = {to_old}; END IF; END",
+                    name = quote(db_backend, &format!("{}_update", self.name)),
+                    table = table,
+                    old = old,
+                    new = new,
+                    to_new = to_new("NEW"),
+                    to_old = to_old("NEW"),
+                ),
+            ],
+            DbBackend::Sqlite => vec![
+                format!(
+                    "CREATE TRIGGER {name} AFTER INSERT ON {table} FOR EACH ROW BEGIN UPDATE {table} SET {new} = {to_new} WHERE rowid = NEW.rowid AND NEW.{new} IS NULL; UPDATE {table} SET {old} = {to_old} WHERE rowid = NEW.rowid AND NEW.{new} IS NOT NULL AND NEW.{old} IS NULL; END",
+                    name = quote(db_backend, &format!("{}_insert", self.name)),
+                    table = table,
+                    old = old,
+                    new = new,
+                    to_new = to_new("NEW"),
+                    to_old = to_old("NEW"),
+                ),
+                format!(
+                    "CREATE TRIGGER {name} AFTER UPDATE OF {old} ON {table} FOR EACH ROW WHEN NEW.{old} IS NOT OLD.{old} BEGIN UPDATE {table} SET {new} = {to_new} WHERE rowid = NEW.rowid; END",
+                    name = quote(db_backend, &format!("{}_update_old", self.name)),
+                    table = table,
+                    old = old,
+                    new = new,
+                    to_new = to_new("NEW"),
+                ),
+                format!(
+                    "CREATE TRIGGER {name} AFTER UPDATE OF {new} ON {table} FOR EACH ROW WHEN NEW.{new} IS NOT OLD.{new} AND NEW.{old} IS OLD.{old} BEGIN UPDATE {table} SET {old} = {to_old} WHERE rowid = NEW.rowid; END",
+                    name = quote(db_backend, &format!("{}_update_new", self.name)),
+                    table = table,
+                    old = old,
+                    new = new,
+                    to_old = to_old("NEW"),
+                ),
+            ],
+        };
+        sql.into_iter()
+            .map(|sql| Statement::from_string(db_backend, sql))
+            .collect()
+    }
+
+    /// Statements dropping the triggers, if they exist
+    pub fn drop_statements(&self, db_backend: DbBackend) -> Vec<Statement> {
+        let sql = match db_backend {
+            DbBackend::Postgres => vec![
+                format!(
+                    "DROP TRIGGER IF EXISTS {} ON {}",
+                    quote(db_backend, &self.name),
+                    quote(db_backend, &self.table)
+                ),
+                format!(
+                    "DROP FUNCTION IF EXISTS {}()",
+                    quote(db_backend, &self.name)
+                ),
+            ],
+            DbBackend::MySql => ["insert", "update"]
+                .iter()
+                .map(|suffix| {
+                    format!(
+                        "DROP TRIGGER IF EXISTS {}",
+                        quote(db_backend, &format!("{}_{}", self.name, suffix))
+                    )
+                })
+                .collect(),
+            DbBackend::Sqlite => ["insert", "update_old", "update_new"]
+                .iter()
+                .map(|suffix| {
+                    format!(
+                        "DROP TRIGGER IF EXISTS {}",
+                        quote(db_backend, &format!("{}_{}", self.name, suffix))
+                    )
+                })
+                .collect(),
+        };
+        sql.into_iter()
+            .map(|sql| Statement::from_string(db_backend, sql))
+            .collect()
+    }
+
+    /// Create the triggers
+    pub async fn create(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        info!(
+            "Keeping columns '{}' and '{}' of table '{}' in sync",
+            self.old_column, self.new_column, self.table
+        );
+        for stmt in self.create_statements(manager.get_database_backend()) {
+            manager.exec_raw(stmt).await?;
+        }
+        Ok(())
+    }
+
+    /// Drop the triggers
+    pub async fn drop(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        info!(
+            "Dropping the triggers syncing columns '{}' and '{}' of table '{}'",
+            self.old_column, self.new_column, self.table
+        );
+        for stmt in self.drop_statements(manager.get_database_backend()) {
+            manager.exec_raw(stmt).await?;
+        }
+        Ok(())
+    }
+
+    /// Migration dropping the triggers once every deploy writes the new column,
+    /// recreating them when rolled back
+    pub fn cleanup_migration<V>(self, version: V) -> DualWriteCleanup
+    where
+        V: Into<String>,
+    {
+        DualWriteCleanup {
+            version: version.into(),
+            dual_write: self,
+        }
+    }
+}
+
+/// Migration dropping the triggers of a [`DualWrite`], see [`DualWrite::cleanup_migration`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DualWriteCleanup {
+    pub version: String,
+    pub dual_write: DualWrite,
+}
+
+impl MigrationName for DualWriteCleanup {
+    fn name(&self) -> &str {
+        &self.version
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for DualWriteCleanup {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        self.dual_write.drop(manager).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        self.dual_write.create(manager).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statements() {
+        let dual_write = DualWrite::new("cake", "price", "price_cents").to_new("{} * 100");
+        let create = dual_write.create_statements(DbBackend::MySql);
+        assert_eq!(create.len(), 2);
+        assert_eq!(
+            create[1].sql,
+            "CREATE TRIGGER `dual_write_cake_price_price_cents_update` BEFORE UPDATE ON `cake` FOR EACH ROW BEGIN IF NOT (NEW.`price` <=> OLD.`price`) THEN SET NEW.`price_cents` = NEW.`price` * 100; ELSEIF NOT (NEW.`price_cents` <=> OLD.`price_cents`) THEN SET NEW.`price` = NEW.`price_cents`; END IF; END"
+        );
+        assert_eq!(
+            dual_write
+                .drop_statements(DbBackend::Postgres)
+                .iter()
+                .map(|stmt| stmt.sql.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                r#"DROP TRIGGER IF EXISTS "dual_write_cake_price_price_cents" ON "cake""#,
+                r#"DROP FUNCTION IF EXISTS "dual_write_cake_price_price_cents"()"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use super::MigrationTrait;
+use sea_orm::DbErr;
+use std::fmt::Display;
+use tracing::warn;
+
+/// Expected duration of a migration, see [`MigrationTrait::duration_class`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DurationClass {
+    /// Completes in seconds, safe to apply anywhere, e.g. automatically at boot
+    #[default]
+    Fast,
+    /// May run for minutes or hours, e.g. rewriting a large table, applied only where
+    /// acknowledged, see [`MigratorTrait::fast_only`](super::MigratorTrait::fast_only)
+    Slow,
+}
+
+impl Display for DurationClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fast => write!(f, "fast"),
+            Self::Slow => write!(f, "slow"),
+        }
+    }
+}
+
+/// Fail if any of the migrations about to run is slow, unless slow migrations are acknowledged
+pub(crate) fn ensure_fast_only<'a, I>(migrations: I, acknowledged: bool) -> Result<(), DbErr>
+where
+    I: IntoIterator<Item = &'a dyn MigrationTrait>,
+{
+    let slow: Vec<&str> = migrations
+        .into_iter()
+        .filter(|migration| migration.duration_class() == DurationClass::Slow)
+        .map(|migration| migration.name())
+        .collect();
+    match (slow.is_empty(), acknowledged) {
+        (true, _) => Ok(()),
+        (false, true) => {
+            warn!("Running slow migrations, as acknowledged: {:?}", slow);
+            Ok(())
+        }
+        (false, false) => Err(DbErr::Custom(format!(
+            "Slow migrations {:?} are not allowed in this environment, acknowledge them to run them",
+            slow
+        ))),
+    }
+}
@@ -0,0 +1,346 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::SystemTime;
+use tracing::{error, info, warn};
+
+/// Target of the tracing events carrying migrator lifecycle events in [`LogFormat::Json`]
+pub const EVENT_TARGET: &str = "sea_schema::migration::event";
+
+static LOG_FORMAT: AtomicU8 = AtomicU8::new(0);
+
+/// How the migrator reports lifecycle events
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable messages
+    #[default]
+    Text,
+    /// One JSON object per lifecycle event, logged with the target [`EVENT_TARGET`]
+    Json,
+}
+
+/// A lifecycle event of a migrator run.
+///
+/// In [`LogFormat::Json`], every event is a JSON object with the fields `event` (the snake-cased
+/// variant name), `timestamp_ms`, `run_id` and `command`, along with the fields of the variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MigratorEvent {
+    RunStarted {
+        run_id: String,
+        command: String,
+    },
+    MigrationStarted {
+        run_id: String,
+        command: String,
+        version: String,
+    },
+    MigrationCompleted {
+        run_id: String,
+        command: String,
+        version: String,
+        duration_ms: u64,
+    },
+    /// A non-fatal warning emitted by the migration with [`SchemaManager::warn`](super::SchemaManager::warn)
+    MigrationWarning {
+        run_id: String,
+        command: String,
+        version: String,
+        message: String,
+    },
+    RunCompleted {
+        run_id: String,
+        command: String,
+        duration_ms: u64,
+    },
+    RunFailed {
+        run_id: String,
+        command: String,
+        duration_ms: u64,
+        error: String,
+    },
+    /// No migration is registered, most likely a packaging bug
+    NoMigrations {
+        run_id: String,
+        command: String,
+    },
+    /// A statement is still running, emitted by the [`Watchdog`](super::Watchdog) every heartbeat interval
+    Heartbeat {
+        run_id: String,
+        command: String,
+        statement: String,
+        elapsed_ms: u64,
+    },
+    /// A statement exceeded the threshold of the [`Watchdog`](super::Watchdog), along with the
+    /// sessions active on the database server
+    WatchdogWarning {
+        run_id: String,
+        command: String,
+        statement: String,
+        elapsed_ms: u64,
+        threshold_ms: u64,
+        sessions: Vec<String>,
+    },
+}
+
+/// Set how lifecycle events are reported by every migrator of the process
+pub fn set_log_format(log_format: LogFormat) {
+    LOG_FORMAT.store(log_format as u8, Ordering::Relaxed);
+}
+
+/// How lifecycle events are currently reported
+pub fn log_format() -> LogFormat {
+    match LOG_FORMAT.load(Ordering::Relaxed) {
+        1 => LogFormat::Json,
+        _ => LogFormat::Text,
+    }
+}
+
+impl MigratorEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RunStarted { .. } => "run_started",
+            Self::MigrationStarted { .. } => "migration_started",
+            Self::MigrationCompleted { .. } => "migration_completed",
+            Self::MigrationWarning { .. } => "migration_warning",
+            Self::RunCompleted { .. } => "run_completed",
+            Self::RunFailed { .. } => "run_failed",
+            Self::NoMigrations { .. } => "no_migrations",
+            Self::Heartbeat { .. } => "heartbeat",
+            Self::WatchdogWarning { .. } => "watchdog_warning",
+        }
+    }
+
+    /// Render the event as a single-line JSON object
+    pub fn to_json(&self, timestamp_ms: u64) -> String {
+        let (run_id, command) = match self {
+            Self::RunStarted { run_id, command }
+            | Self::NoMigrations { run_id, command }
+            | Self::MigrationStarted {
+                run_id, command, ..
+            }
+            | Self::MigrationCompleted {
+                run_id, command, ..
+            }
+            | Self::MigrationWarning {
+                run_id, command, ..
+            }
+            | Self::RunCompleted {
+                run_id, command, ..
+            }
+            | Self::RunFailed {
+                run_id, command, ..
+            }
+            | Self::Heartbeat {
+                run_id, command, ..
+            }
+            | Self::WatchdogWarning {
+                run_id, command, ..
+            } => (run_id, command),
+        };
+        let mut fields = vec![
+            format!(r#""event":{}"#, json_string(self.name())),
+            format!(r#""timestamp_ms":{}"#, timestamp_ms),
+            format!(r#""run_id":{}"#, json_string(run_id)),
+            format!(r#""command":{}"#, json_string(command)),
+        ];
+        match self {
+            Self::RunStarted { .. } | Self::NoMigrations { .. } => {}
+            Self::MigrationStarted { version, .. } => {
+                fields.push(format!(r#""version":{}"#, json_string(version)));
+            }
+            Self::MigrationCompleted {
+                version,
+                duration_ms,
+                ..
+            } => {
+                fields.push(format!(r#""version":{}"#, json_string(version)));
+                fields.push(format!(r#""duration_ms":{}"#, duration_ms));
+            }
+            Self::MigrationWarning {
+                version, message, ..
+            } => {
+                fields.push(format!(r#""version":{}"#, json_string(version)));
+                fields.push(format!(r#""message":{}"#, json_string(message)));
+            }
+            Self::RunCompleted { duration_ms, .. } => {
+                fields.push(format!(r#""duration_ms":{}"#, duration_ms));
+            }
+            Self::RunFailed {
+                duration_ms, error, ..
+            } => {
+                fields.push(format!(r#""duration_ms":{}"#, duration_ms));
+                fields.push(format!(r#""error":{}"#, json_string(error)));
+            }
+            Self::Heartbeat {
+                statement,
+                elapsed_ms,
+                ..
+            } => {
+                fields.push(format!(r#""statement":{}"#, json_string(statement)));
+                fields.push(format!(r#""elapsed_ms":{}"#, elapsed_ms));
+            }
+            Self::WatchdogWarning {
+                statement,
+                elapsed_ms,
+                threshold_ms,
+                sessions,
+                ..
+            } => {
+                let sessions: Vec<String> = sessions.iter().map(|s| json_string(s)).collect();
+                fields.push(format!(r#""statement":{}"#, json_string(statement)));
+                fields.push(format!(r#""elapsed_ms":{}"#, elapsed_ms));
+                fields.push(format!(r#""threshold_ms":{}"#, threshold_ms));
+                fields.push(format!(r#""sessions":[{}]"#, sessions.join(",")));
+            }
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Report the event in the current [`LogFormat`]
+    pub fn emit(&self) {
+        let failed = matches!(self, Self::RunFailed { .. });
+        let empty = matches!(
+            self,
+            Self::NoMigrations { .. }
+                | Self::MigrationWarning { .. }
+                | Self::WatchdogWarning { .. }
+        );
+        match (log_format(), failed) {
+            (LogFormat::Text, false) if empty => warn!("{}", self),
+            (LogFormat::Text, false) => info!("{}", self),
+            (LogFormat::Text, true) => error!("{}", self),
+            (LogFormat::Json, failed) => {
+                let timestamp_ms = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .expect("SystemTime before UNIX EPOCH!")
+                    .as_millis() as u64;
+                let json = self.to_json(timestamp_ms);
+                match (failed, empty) {
+                    (false, false) => info!(target: EVENT_TARGET, "{}", json),
+                    (false, true) => warn!(target: EVENT_TARGET, "{}", json),
+                    (true, _) => error!(target: EVENT_TARGET, "{}", json),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown log format '{}'", s)),
+        }
+    }
+}
+
+impl Display for MigratorEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RunStarted { run_id, command } => {
+                write!(f, "Migrator run '{}' started: {}", run_id, command)
+            }
+            Self::MigrationStarted {
+                command, version, ..
+            } => match command.as_str() {
+                "down" => write!(f, "Rolling back migration '{}'", version),
+                _ => write!(f, "Applying migration '{}'", version),
+            },
+            Self::MigrationCompleted {
+                command, version, ..
+            } => match command.as_str() {
+                "down" => write!(f, "Migration '{}' has been rollbacked", version),
+                _ => write!(f, "Migration '{}' has been applied", version),
+            },
+            Self::MigrationWarning {
+                version, message, ..
+            } => write!(f, "Migration '{}' warned: {}", version, message),
+            Self::RunCompleted {
+                run_id,
+                duration_ms,
+                ..
+            } => write!(
+                f,
+                "Migrator run '{}' completed in {}ms",
+                run_id, duration_ms
+            ),
+            Self::RunFailed { run_id, error, .. } => {
+                write!(f, "Migrator run '{}' failed: {}", run_id, error)
+            }
+            Self::NoMigrations { run_id, .. } => write!(
+                f,
+                "Migrator run '{}' found no registered migration, are the migrations compiled in?",
+                run_id
+            ),
+            Self::Heartbeat {
+                statement,
+                elapsed_ms,
+                ..
+            } => write!(
+                f,
+                "Statement still running after {}ms: {}",
+                elapsed_ms, statement
+            ),
+            Self::WatchdogWarning {
+                statement,
+                elapsed_ms,
+                threshold_ms,
+                sessions,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Statement running for {}ms exceeded watchdog threshold of {}ms: {}",
+                    elapsed_ms, threshold_ms, statement
+                )?;
+                match sessions.is_empty() {
+                    true => write!(f, "\nNo other active sessions reported by the database"),
+                    false => sessions
+                        .iter()
+                        .try_for_each(|session| write!(f, "\nActive {}", session)),
+                }
+            }
+        }
+    }
+}
+
+/// Quote and escape a string as a JSON string
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let event = MigratorEvent::RunFailed {
+            run_id: "0a1b".to_owned(),
+            command: "up".to_owned(),
+            duration_ms: 42,
+            error: "Execution Error: \"cake\"\n\u{1}".to_owned(),
+        };
+        assert_eq!(
+            event.to_json(1642464000000),
+            r#"{"event":"run_failed","timestamp_ms":1642464000000,"run_id":"0a1b","command":"up","duration_ms":42,"error":"Execution Error: \"cake\"\n\u0001"}"#
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use super::{BulkLoader, SchemaManager, TableGraph, TableOrder};
+use sea_orm::sea_query::Value;
+use sea_orm::{DbBackend, DbErr, Statement};
+
+/// Rows to be loaded into a table, see [`Fixtures`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum FixtureSource {
+    Rows(Vec<Vec<Value>>),
+    /// Path of a CSV file with a header row, see [`BulkLoader::load_csv`]
+    Csv(String),
+}
+
+/// A set of fixtures spanning several tables, loaded such that referenced tables are loaded
+/// before the tables referencing them, whatever order the fixtures were added in.
+///
+/// Tables without a dependency between them are loaded in the order their first fixture was
+/// added. Tables referencing each other in a cycle, and the tables depending on them, are loaded
+/// last in a single transaction, with the foreign key check disabled on MySQL and deferred to the
+/// commit on Postgres and SQLite. On Postgres the foreign keys of the cycle must be `DEFERRABLE`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fixtures {
+    pub fixtures: Vec<(BulkLoader, FixtureSource)>,
+}
+
+impl Fixtures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the rows with the loader
+    pub fn rows<I>(mut self, loader: BulkLoader, rows: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<Value>>,
+    {
+        self.fixtures
+            .push((loader, FixtureSource::Rows(rows.into_iter().collect())));
+        self
+    }
+
+    /// Load the CSV file with the loader
+    pub fn csv<P>(mut self, loader: BulkLoader, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.fixtures
+            .push((loader, FixtureSource::Csv(path.into())));
+        self
+    }
+
+    /// Order the tables of the fixtures by the foreign keys of the graph
+    pub fn order(&self, graph: &TableGraph) -> TableOrder {
+        let mut tables: Vec<String> = Vec::new();
+        for (loader, _) in self.fixtures.iter() {
+            if !tables.contains(&loader.table) {
+                tables.push(loader.table.clone());
+            }
+        }
+        TableGraph {
+            tables,
+            foreign_keys: graph.foreign_keys.clone(),
+        }
+        .sort()
+    }
+
+    /// Load the fixtures in foreign key order, discovering the foreign keys of the current schema
+    pub async fn load(&self, manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        let graph = TableGraph::discover(manager.schema_connection()).await?;
+        let order = self.order(&graph);
+        for table in order.sorted.iter() {
+            self.load_table(manager, table).await?;
+        }
+        if order.cyclic.is_empty() {
+            return Ok(());
+        }
+
+        // The foreign key check is a setting of the session, the transaction holds a single
+        // connection of the pool for it, unless one is already held, e.g. by the migrator
+        let own = !manager.in_transaction();
+        let db_backend = manager.get_database_backend();
+        let defer = match db_backend {
+            DbBackend::MySql => "SET FOREIGN_KEY_CHECKS = 0",
+            DbBackend::Postgres => "SET CONSTRAINTS ALL DEFERRED",
+            // Reset at the end of the transaction
+            DbBackend::Sqlite => "PRAGMA defer_foreign_keys = ON",
+        };
+        if own {
+            manager.begin().await?;
+        }
+        let loaded = async {
+            manager
+                .exec_raw(Statement::from_string(db_backend, defer.to_owned()))
+                .await?;
+            for table in order.cyclic.iter() {
+                self.load_table(manager, table).await?;
+            }
+            Ok(())
+        }
+        .await;
+        let restored = match db_backend {
+            // Restored before the connection returns to the pool
+            DbBackend::MySql => {
+                manager
+                    .exec_raw(Statement::from_string(
+                        db_backend,
+                        "SET FOREIGN_KEY_CHECKS = 1".to_owned(),
+                    ))
+                    .await
+            }
+            _ => Ok(()),
+        };
+        match loaded.and(restored) {
+            Ok(()) if own => manager.commit().await,
+            Ok(()) => Ok(()),
+            Err(err) => {
+                if own {
+                    manager.rollback().await?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn load_table(&self, manager: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+        for (loader, source) in self.fixtures.iter() {
+            if loader.table != table {
+                continue;
+            }
+            match source {
+                FixtureSource::Rows(rows) => {
+                    loader.load_rows(manager, rows.iter().cloned()).await?
+                }
+                FixtureSource::Csv(path) => loader.load_csv(manager, path).await?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::ForeignKeyEdge;
+
+    #[test]
+    fn test_order() {
+        let fk = |table: &str, referenced_table: &str| ForeignKeyEdge {
+            table: table.to_owned(),
+            name: format!("fk-{}-{}", table, referenced_table),
+            referenced_table: referenced_table.to_owned(),
+        };
+        let graph = TableGraph {
+            tables: vec![],
+            foreign_keys: vec![
+                fk("cake_fruit", "cake"),
+                fk("cake_fruit", "fruit"),
+                fk("cake", "baker"),
+            ],
+        };
+        let fixtures = Fixtures::new()
+            .csv(
+                BulkLoader::new("cake_fruit", ["cake_id", "fruit_id"]),
+                "cake_fruit.csv",
+            )
+            .rows(BulkLoader::new("cake", ["id", "name"]), vec![])
+            .rows(BulkLoader::new("fruit", ["id", "name"]), vec![])
+            .rows(BulkLoader::new("cake", ["id", "name"]), vec![]);
+        let order = fixtures.order(&graph);
+        assert_eq!(order.sorted, vec!["cake", "fruit", "cake_fruit"]);
+        assert!(order.cyclic.is_empty());
+    }
+}
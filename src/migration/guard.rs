@@ -0,0 +1,158 @@
+use super::quote;
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+thread_local! {
+    /// Run id and command of the migrator run being polled on this thread, if any
+    static CURRENT_RUN: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+/// Run id and command of the migrator runs in progress in this process, by the database they
+/// run against, see [`database_key`]
+static RUNS: Mutex<BTreeMap<String, (String, String)>> = Mutex::new(BTreeMap::new());
+
+/// A migrator run, marked as current on its thread while it is polled, so that the events it
+/// emits carry its run id
+pub(crate) struct InRun<F> {
+    run: Option<(String, String)>,
+    fut: Pin<Box<F>>,
+}
+
+impl<F> InRun<F> {
+    pub(crate) fn new(run_id: &str, command: &str, fut: F) -> Self {
+        Self {
+            run: Some((run_id.to_owned(), command.to_owned())),
+            fut: Box::pin(fut),
+        }
+    }
+}
+
+impl<F> Future for InRun<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let run = self.run.take();
+        let outer = CURRENT_RUN.with(|current| current.replace(run));
+        let res = self.fut.as_mut().poll(cx);
+        self.run = CURRENT_RUN.with(|current| current.replace(outer));
+        res
+    }
+}
+
+/// Run id and command of the migrator run in progress on this thread, if any
+pub(crate) fn current_run() -> Option<(String, String)> {
+    CURRENT_RUN.with(|current| current.borrow().clone())
+}
+
+/// A migrator run registered against its database until dropped, including when the run is
+/// dropped before completing
+pub(crate) struct RunMarker {
+    key: Option<String>,
+}
+
+impl Drop for RunMarker {
+    fn drop(&mut self) {
+        if let Some(key) = &self.key {
+            runs().remove(key);
+        }
+    }
+}
+
+fn runs() -> MutexGuard<'static, BTreeMap<String, (String, String)>> {
+    RUNS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Identify the database `db` connects to: its server, database and, on Postgres, schema.
+/// `None` for an in-memory SQLite database, only reachable through the pool at hand.
+async fn database_key(db: &DbConn) -> Result<Option<String>, DbErr> {
+    let db_backend = db.get_database_backend();
+    let sql = match db_backend {
+        DbBackend::MySql => "SELECT CONCAT_WS(':', @@hostname, @@port, DATABASE())",
+        DbBackend::Postgres => "SELECT CONCAT_WS(':', inet_server_addr(), inet_server_port(), current_database(), current_schema())",
+        DbBackend::Sqlite => "SELECT \"file\" FROM pragma_database_list WHERE \"name\" = 'main'",
+    };
+    let sql = format!("{} AS {}", sql, quote(db_backend, "key"));
+    let key: Option<String> = match db
+        .query_one(Statement::from_string(db_backend, sql))
+        .await?
+    {
+        Some(row) => row.try_get("", "key")?,
+        None => None,
+    };
+    Ok(key.filter(|key| !key.is_empty()))
+}
+
+/// Fail if a migrator run is in progress on the task polling `command`, or on the database of
+/// `db` from any task of this process, i.e. when called from a migration
+pub(crate) async fn ensure_not_in_run(db: &DbConn, command: &str) -> Result<(), DbErr> {
+    let key = database_key(db).await?;
+    check_not_in_run(&runs(), key.as_deref(), command)
+}
+
+/// Register a run of `command` against the database of `db`, failing if one is in progress
+pub(crate) async fn enter_run(
+    db: &DbConn,
+    run_id: &str,
+    command: &str,
+) -> Result<RunMarker, DbErr> {
+    let key = database_key(db).await?;
+    let mut runs = runs();
+    check_not_in_run(&runs, key.as_deref(), command)?;
+    if let Some(key) = &key {
+        runs.insert(key.clone(), (run_id.to_owned(), command.to_owned()));
+    }
+    Ok(RunMarker { key })
+}
+
+fn check_not_in_run(
+    runs: &BTreeMap<String, (String, String)>,
+    key: Option<&str>,
+    command: &str,
+) -> Result<(), DbErr> {
+    let run = current_run().or_else(|| key.and_then(|key| runs.get(key).cloned()));
+    match run {
+        Some((run_id, outer)) => Err(DbErr::Custom(format!(
+            "Migrator command '{}' cannot be called while migrator run '{}' ('{}') is in progress, migrations must not call migrator commands",
+            command, run_id, outer
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_check_not_in_run() {
+        let check = |key| check_not_in_run(&runs(), key, "down");
+        assert!(check(None).is_ok());
+        let nested = InRun::new("0a1b", "up", async { check(None) }).await;
+        assert!(nested.is_err());
+        assert!(check(None).is_ok());
+
+        // Detected from another task, through the database the run is registered against
+        runs().insert(
+            "db:5432:guard".to_owned(),
+            ("0a1b".to_owned(), "up".to_owned()),
+        );
+        let marker = RunMarker {
+            key: Some("db:5432:guard".to_owned()),
+        };
+        let spawned = async_std::task::spawn(async move {
+            check_not_in_run(&runs(), Some("db:5432:guard"), "down")
+        });
+        assert!(spawned.await.is_err());
+        assert!(check(Some("db:5432:other")).is_ok());
+        drop(marker);
+        assert!(check(Some("db:5432:guard")).is_ok());
+    }
+}
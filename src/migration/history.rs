@@ -0,0 +1,630 @@
+use super::{
+    cast_bigint, count_pending, get_migration_warnings, get_schema_meta_value, probe_columns,
+    record_run, record_warnings, seaql_migrations, seaql_schema_meta, set_schema_meta_value,
+    AppliedAtFormat, HistoryLayout, MigrationSummary, SchemaManager, META_APPLIED_AT_FORMAT,
+};
+use async_std::io::WriteExt;
+use sea_orm::sea_query::{Alias, ColumnDef, Expr, Order, Query, Table};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbBackend, DbConn, DbErr, EntityTrait, IdenStatic, QueryFilter,
+    QueryOrder, QuerySelect, Schema, TransactionTrait,
+};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Persistence of the applied migrations.
+///
+/// DDL is always executed against the target database, the history can be kept elsewhere,
+/// e.g. when the target is a read-only analytical database.
+#[async_trait::async_trait]
+pub trait HistoryStore: Send + Sync {
+    /// Prepare the store, upgrading it if it was created by an earlier version
+    async fn install(&self, db: &DbConn) -> Result<(), DbErr>;
+
+    /// Applied migrations, ordered by version
+    async fn applied(&self, db: &DbConn) -> Result<Vec<seaql_migrations::Model>, DbErr>;
+
+    /// Applied migrations, ordered by version, read without installing or upgrading the store,
+    /// e.g. for reporting the status from instances of another version sharing the store
+    async fn read_applied(&self, db: &DbConn) -> Result<Vec<seaql_migrations::Model>, DbErr> {
+        self.applied(db).await
+    }
+
+    /// Number of applied migrations and of the `registered` ones pending, along with the
+    /// version and `applied_at` of the last one applied, for when the full list is not needed
+    async fn summary(&self, db: &DbConn, registered: &[String]) -> Result<MigrationSummary, DbErr> {
+        let applied = self.applied(db).await?;
+        Ok(MigrationSummary {
+            applied: applied.len(),
+            pending: count_pending(registered, &applied),
+            last_applied: applied
+                .last()
+                .map(|model| (model.version.clone(), model.applied_at)),
+        })
+    }
+
+    /// Record a migration as applied
+    async fn record_applied(
+        &self,
+        db: &DbConn,
+        record: &seaql_migrations::Model,
+    ) -> Result<(), DbErr>;
+
+    /// Record a migration as applied unless it is already recorded, e.g. by another instance
+    /// applying the migrations concurrently, in which case `false` is returned
+    async fn claim(&self, db: &DbConn, record: &seaql_migrations::Model) -> Result<bool, DbErr> {
+        let applied = self.applied(db).await?;
+        if applied.iter().any(|model| model.version == record.version) {
+            return Ok(false);
+        }
+        self.record_applied(db, record).await?;
+        Ok(true)
+    }
+
+    /// Update the record of a migration claimed before it was applied, e.g. with its duration
+    async fn update_applied(
+        &self,
+        db: &DbConn,
+        record: &seaql_migrations::Model,
+    ) -> Result<(), DbErr> {
+        self.remove_applied(db, &record.version).await?;
+        self.record_applied(db, record).await
+    }
+
+    /// Forget a rolled back migration
+    async fn remove_applied(&self, db: &DbConn, version: &str) -> Result<(), DbErr>;
+
+    /// Forget every migration, after `fresh` dropped everything from the target database
+    async fn clear(&self, db: &DbConn) -> Result<(), DbErr>;
+
+    /// Record the end of a migrator run which applied or rolled back a migration, in
+    /// `seaql_schema_meta` by default
+    async fn record_run(&self, db: &DbConn, run_id: &str, run_at: i64) -> Result<(), DbErr> {
+        record_run(db, run_id, run_at).await
+    }
+
+    /// Record the warnings a migration emitted when it was applied, replacing the earlier ones,
+    /// in `seaql_schema_meta` by default. No warning forgets them, e.g. after a rollback.
+    async fn record_warnings(
+        &self,
+        db: &DbConn,
+        version: &str,
+        warnings: &[String],
+    ) -> Result<(), DbErr> {
+        record_warnings(db, version, warnings).await
+    }
+
+    /// Warnings recorded with [`HistoryStore::record_warnings`], by version
+    async fn warnings(&self, db: &DbConn) -> Result<BTreeMap<String, Vec<String>>, DbErr> {
+        get_migration_warnings(db).await
+    }
+}
+
+/// The default history store: the `seaql_migrations` table of the target database, along
+/// with the `seaql_schema_meta` table
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableHistoryStore {
+    pub applied_at_format: AppliedAtFormat,
+}
+
+/// History kept in a local file, one tab-separated line per applied migration.
+/// Neither the end of a run nor the warnings of the migrations are recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileHistoryStore {
+    pub path: PathBuf,
+}
+
+/// Key of the `seaql_schema_meta` entry recording the layout version of `seaql_migrations`
+pub const META_HISTORY_LAYOUT: &str = "history_layout";
+
+/// Columns added to `seaql_migrations` after its initial `(version, applied_at)` layout,
+/// in the order they were introduced. The layout version is the number of columns added.
+const HISTORY_COLUMNS: [seaql_migrations::Column; 6] = [
+    seaql_migrations::Column::AppVersion,
+    seaql_migrations::Column::Checksum,
+    seaql_migrations::Column::DurationMs,
+    seaql_migrations::Column::AppliedBy,
+    seaql_migrations::Column::RunId,
+    seaql_migrations::Column::Batch,
+];
+
+/// Layout version of `seaql_migrations` created by this version of the migrator
+pub const HISTORY_LAYOUT_VERSION: usize = HISTORY_COLUMNS.len();
+
+fn column_def(column: seaql_migrations::Column) -> ColumnDef {
+    let mut def = ColumnDef::new(column);
+    match column {
+        seaql_migrations::Column::DurationMs => def.big_integer(),
+        _ => def.string(),
+    };
+    def.null();
+    def
+}
+
+/// Upgrade a `seaql_migrations` table created by an earlier version of the migrator in place,
+/// adding the columns its layout lacks
+pub(crate) async fn upgrade_history_table(db: &DbConn) -> Result<(), DbErr> {
+    let stored =
+        match get_schema_meta_value(db, META_HISTORY_LAYOUT).await? {
+            Some(layout) => Some(layout.parse::<usize>().map_err(|_| {
+                DbErr::Custom(format!("Invalid migration table layout '{}'", layout))
+            })?),
+            None => None,
+        };
+    match stored {
+        Some(layout) if layout == HISTORY_LAYOUT_VERSION => return Ok(()),
+        Some(layout) if layout > HISTORY_LAYOUT_VERSION => {
+            warn!(
+                "Table 'seaql_migrations' has layout {} which is newer than {}, upgrade sea-schema",
+                layout, HISTORY_LAYOUT_VERSION
+            );
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // Columns of the recorded layout exist, the remaining ones are checked
+    // as a previous upgrade may have been interrupted
+    let manager = SchemaManager::new(db);
+    let columns: Vec<_> = HISTORY_COLUMNS
+        .into_iter()
+        .skip(stored.unwrap_or(0))
+        .collect();
+    let names: Vec<_> = columns.iter().map(|column| column.as_str()).collect();
+    let exists = manager.has_columns("seaql_migrations", &names).await?;
+    for column in columns {
+        if exists[column.as_str()] {
+            continue;
+        }
+        info!(
+            "Adding column '{}' to table 'seaql_migrations'",
+            column.as_str()
+        );
+        let mut stmt = Table::alter();
+        stmt.table(seaql_migrations::Entity)
+            .add_column(&mut column_def(column));
+        manager.exec_stmt(stmt).await?;
+    }
+    set_schema_meta_value(db, META_HISTORY_LAYOUT, &HISTORY_LAYOUT_VERSION.to_string()).await
+}
+
+/// Widen the `value` column of a `seaql_schema_meta` table created by an earlier version of
+/// the migrator, a `varchar(255)` on MySQL, to `text`
+pub(crate) async fn upgrade_schema_meta_table(db: &DbConn) -> Result<(), DbErr> {
+    if db.get_database_backend() != DbBackend::MySql {
+        return Ok(());
+    }
+    let narrow = probe_columns(db, "seaql_schema_meta")
+        .await?
+        .into_iter()
+        .any(|column| {
+            column.name == seaql_schema_meta::Column::Value.as_str()
+                && column.column_type.starts_with("varchar")
+        });
+    if narrow {
+        info!("Widening column 'value' of table 'seaql_schema_meta'");
+        let mut stmt = Table::alter();
+        stmt.table(seaql_schema_meta::Entity).modify_column(
+            ColumnDef::new(seaql_schema_meta::Column::Value)
+                .text()
+                .not_null(),
+        );
+        SchemaManager::new(db).exec_stmt(stmt).await?;
+    }
+    Ok(())
+}
+
+impl TableHistoryStore {
+    pub fn new(applied_at_format: AppliedAtFormat) -> Self {
+        Self { applied_at_format }
+    }
+}
+
+#[async_trait::async_trait]
+impl HistoryStore for TableHistoryStore {
+    /// Create the `seaql_migrations` and `seaql_schema_meta` tables, upgrade older layouts
+    /// and convert `applied_at` when the configured format differs from the stored one
+    async fn install(&self, db: &DbConn) -> Result<(), DbErr> {
+        let builder = db.get_database_backend();
+        let schema = Schema::new(builder);
+        let mut stmt = schema.create_table_from_entity(seaql_migrations::Entity);
+        stmt.if_not_exists();
+        db.execute(builder.build(&stmt)).await?;
+        let mut stmt = schema.create_table_from_entity(seaql_schema_meta::Entity);
+        stmt.if_not_exists();
+        db.execute(builder.build(&stmt)).await?;
+
+        // Migration tables created by earlier versions lack the newer columns
+        upgrade_history_table(db).await?;
+        upgrade_schema_meta_table(db).await?;
+
+        let target = self.applied_at_format;
+        let stored = get_schema_meta_value(db, META_APPLIED_AT_FORMAT).await?;
+        let current = match &stored {
+            Some(format) => format.parse().map_err(DbErr::Custom)?,
+            None => AppliedAtFormat::UnixSeconds,
+        };
+        if current != target {
+            let columns = probe_columns(db, "seaql_migrations").await?;
+            let stmts = match current.resume_statements(target, builder, &columns) {
+                Some(stmts) => {
+                    info!(
+                        "Resuming the conversion of column 'applied_at' of table 'seaql_migrations' from {} to {}",
+                        current, target
+                    );
+                    stmts
+                }
+                None => {
+                    info!(
+                        "Converting column 'applied_at' of table 'seaql_migrations' from {} to {}",
+                        current, target
+                    );
+                    current.upgrade_statements(target, builder)
+                }
+            };
+            // MySQL commits the copy through `applied_at_tmp` statement by statement and resumes
+            // it instead, the other backends convert and record the format atomically
+            if builder == DbBackend::MySql {
+                for stmt in stmts {
+                    db.execute(stmt).await?;
+                }
+            } else {
+                let txn = db.begin().await?;
+                for stmt in stmts {
+                    txn.execute(stmt).await?;
+                }
+                set_schema_meta_value(&txn, META_APPLIED_AT_FORMAT, target.as_str()).await?;
+                return txn.commit().await;
+            }
+        }
+        if stored.is_none() || current != target {
+            set_schema_meta_value(db, META_APPLIED_AT_FORMAT, target.as_str()).await?;
+        }
+        Ok(())
+    }
+
+    async fn applied(&self, db: &DbConn) -> Result<Vec<seaql_migrations::Model>, DbErr> {
+        let applied_at = self
+            .applied_at_format
+            .select_expr(db.get_database_backend());
+        seaql_migrations::Entity::find()
+            .select_only()
+            .column(seaql_migrations::Column::Version)
+            .column_as(applied_at, "applied_at")
+            .column(seaql_migrations::Column::AppVersion)
+            .column(seaql_migrations::Column::Checksum)
+            .column(seaql_migrations::Column::DurationMs)
+            .column(seaql_migrations::Column::AppliedBy)
+            .column(seaql_migrations::Column::RunId)
+            .column(seaql_migrations::Column::Batch)
+            .order_by_asc(seaql_migrations::Column::Version)
+            .into_model::<seaql_migrations::Model>()
+            .all(db)
+            .await
+    }
+
+    /// Read through the [`HistoryLayout`] of the table, whatever version of the migrator or the
+    /// upstream `sea-orm-migration` created it, `applied_at` converted to the configured format
+    async fn read_applied(&self, db: &DbConn) -> Result<Vec<seaql_migrations::Model>, DbErr> {
+        let layout = match HistoryLayout::detect(db).await? {
+            Some(layout) => layout,
+            None => return Ok(Vec::new()),
+        };
+        let mut applied = layout.read(db).await?;
+        for model in applied.iter_mut() {
+            model.applied_at = layout
+                .applied_at_format
+                .convert(model.applied_at, self.applied_at_format);
+        }
+        Ok(applied)
+    }
+
+    /// No warnings are recorded without the `seaql_schema_meta` table, e.g. when the history
+    /// was written by the upstream `sea-orm-migration`
+    async fn warnings(&self, db: &DbConn) -> Result<BTreeMap<String, Vec<String>>, DbErr> {
+        if probe_columns(db, "seaql_schema_meta").await?.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        get_migration_warnings(db).await
+    }
+
+    /// Count the records and those of the `registered` migrations, then read the last one alone
+    async fn summary(&self, db: &DbConn, registered: &[String]) -> Result<MigrationSummary, DbErr> {
+        let db_backend = db.get_database_backend();
+        let count = |registered_only: bool| {
+            let mut stmt = Query::select();
+            stmt.expr_as(
+                Expr::cust(&cast_bigint(db_backend, "COUNT(*)")),
+                Alias::new("applied"),
+            )
+            .from(seaql_migrations::Entity);
+            if registered_only {
+                stmt.and_where(seaql_migrations::Column::Version.is_in(registered.iter().cloned()));
+            }
+            async move {
+                match db.query_one(db_backend.build(&stmt)).await? {
+                    Some(row) => row.try_get::<i64>("", "applied"),
+                    None => Ok(0),
+                }
+            }
+        };
+        let applied = count(false).await?;
+        if applied == 0 {
+            return Ok(MigrationSummary {
+                pending: registered.len(),
+                ..Default::default()
+            });
+        }
+        // The history may hold versions no longer registered, which are not pending ones
+        let registered_applied = match registered.is_empty() {
+            true => 0,
+            false => count(true).await?,
+        };
+        let mut stmt = Query::select();
+        stmt.column(seaql_migrations::Column::Version)
+            .expr_as(
+                self.applied_at_format.select_expr(db_backend),
+                Alias::new("applied_at"),
+            )
+            .from(seaql_migrations::Entity)
+            .order_by(seaql_migrations::Column::Version, Order::Desc)
+            .limit(1);
+        let last = match db.query_one(db_backend.build(&stmt)).await? {
+            Some(row) => Some((row.try_get("", "version")?, row.try_get("", "applied_at")?)),
+            None => None,
+        };
+        Ok(MigrationSummary {
+            applied: applied as usize,
+            pending: registered.len().saturating_sub(registered_applied as usize),
+            last_applied: last,
+        })
+    }
+
+    async fn record_applied(
+        &self,
+        db: &DbConn,
+        record: &seaql_migrations::Model,
+    ) -> Result<(), DbErr> {
+        let stmt = self
+            .applied_at_format
+            .insert_stmt(db.get_database_backend(), record);
+        db.execute(stmt).await.map(|_| ())
+    }
+
+    /// Insert the record with a statement ignoring a conflict on the primary key `version`
+    async fn claim(&self, db: &DbConn, record: &seaql_migrations::Model) -> Result<bool, DbErr> {
+        let stmt = self
+            .applied_at_format
+            .claim_stmt(db.get_database_backend(), record);
+        Ok(db.execute(stmt).await?.rows_affected() == 1)
+    }
+
+    async fn update_applied(
+        &self,
+        db: &DbConn,
+        record: &seaql_migrations::Model,
+    ) -> Result<(), DbErr> {
+        seaql_migrations::Entity::update_many()
+            .col_expr(
+                seaql_migrations::Column::Checksum,
+                Expr::value(record.checksum.clone()),
+            )
+            .col_expr(
+                seaql_migrations::Column::DurationMs,
+                Expr::value(record.duration_ms),
+            )
+            .filter(seaql_migrations::Column::Version.eq(record.version.as_str()))
+            .exec(db)
+            .await
+            .map(|_| ())
+    }
+
+    async fn remove_applied(&self, db: &DbConn, version: &str) -> Result<(), DbErr> {
+        seaql_migrations::Entity::delete_many()
+            .filter(seaql_migrations::Column::Version.eq(version))
+            .exec(db)
+            .await
+            .map(|_| ())
+    }
+
+    async fn clear(&self, db: &DbConn) -> Result<(), DbErr> {
+        self.install(db).await?;
+        seaql_migrations::Entity::delete_many()
+            .exec(db)
+            .await
+            .map(|_| ())
+    }
+}
+
+impl FileHistoryStore {
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self { path: path.into() }
+    }
+
+    async fn read(&self) -> Result<Vec<seaql_migrations::Model>, DbErr> {
+        let content = async_std::fs::read_to_string(&self.path)
+            .await
+            .map_err(|err| self.io_err(err))?;
+        let mut records = content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(parse_record)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(DbErr::Custom)?;
+        records.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok(records)
+    }
+
+    async fn write(&self, records: &[seaql_migrations::Model]) -> Result<(), DbErr> {
+        let content: String = records
+            .iter()
+            .map(|record| format!("{}\n", format_record(record)))
+            .collect();
+        async_std::fs::write(&self.path, content)
+            .await
+            .map_err(|err| self.io_err(err))
+    }
+
+    fn io_err(&self, err: std::io::Error) -> DbErr {
+        DbErr::Custom(format!(
+            "Fail to access history file '{}': {}",
+            self.path.display(),
+            err
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl HistoryStore for FileHistoryStore {
+    async fn install(&self, _: &DbConn) -> Result<(), DbErr> {
+        async_std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map(|_| ())
+            .map_err(|err| self.io_err(err))
+    }
+
+    async fn applied(&self, _: &DbConn) -> Result<Vec<seaql_migrations::Model>, DbErr> {
+        self.read().await
+    }
+
+    async fn record_applied(
+        &self,
+        _: &DbConn,
+        record: &seaql_migrations::Model,
+    ) -> Result<(), DbErr> {
+        let mut file = async_std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|err| self.io_err(err))?;
+        file.write_all(format!("{}\n", format_record(record)).as_bytes())
+            .await
+            .map_err(|err| self.io_err(err))?;
+        file.sync_all().await.map_err(|err| self.io_err(err))
+    }
+
+    async fn remove_applied(&self, _: &DbConn, version: &str) -> Result<(), DbErr> {
+        let mut records = self.read().await?;
+        records.retain(|record| record.version != version);
+        self.write(&records).await
+    }
+
+    async fn clear(&self, _: &DbConn) -> Result<(), DbErr> {
+        self.write(&[]).await
+    }
+
+    async fn record_run(&self, _: &DbConn, _: &str, _: i64) -> Result<(), DbErr> {
+        Ok(())
+    }
+
+    async fn record_warnings(&self, _: &DbConn, _: &str, _: &[String]) -> Result<(), DbErr> {
+        Ok(())
+    }
+
+    async fn warnings(&self, _: &DbConn) -> Result<BTreeMap<String, Vec<String>>, DbErr> {
+        Ok(BTreeMap::new())
+    }
+}
+
+/// Format a record as tab-separated fields, escaping backslashes, tabs and newlines.
+/// Missing values are written as `\N`.
+fn format_record(record: &seaql_migrations::Model) -> String {
+    let field = |value: Option<String>| match value {
+        Some(value) => value
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n"),
+        None => "\\N".to_owned(),
+    };
+    [
+        field(Some(record.version.clone())),
+        field(Some(record.applied_at.to_string())),
+        field(record.app_version.clone()),
+        field(record.checksum.clone()),
+        field(record.duration_ms.map(|ms| ms.to_string())),
+        field(record.applied_by.clone()),
+        field(record.run_id.clone()),
+        field(record.batch.clone()),
+    ]
+    .join("\t")
+}
+
+fn parse_record(line: &str) -> Result<seaql_migrations::Model, String> {
+    let field = |value: &str| -> Option<String> {
+        if value == "\\N" {
+            return None;
+        }
+        let mut unescaped = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => unescaped.push('\t'),
+                    Some('n') => unescaped.push('\n'),
+                    Some(c) => unescaped.push(c),
+                    None => {}
+                },
+                c => unescaped.push(c),
+            }
+        }
+        Some(unescaped)
+    };
+    let fields: Vec<Option<String>> = line.split('\t').map(field).collect();
+    // Records written before the run id and the batch were recorded have six or seven fields
+    if !matches!(fields.len(), 6..=8) {
+        return Err(format!("Invalid history record '{}'", line));
+    }
+    let int = |i: usize| -> Result<Option<i64>, String> {
+        fields[i]
+            .as_deref()
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid history record '{}'", line))
+            })
+            .transpose()
+    };
+    Ok(seaql_migrations::Model {
+        version: fields[0]
+            .clone()
+            .ok_or_else(|| format!("Invalid history record '{}'", line))?,
+        applied_at: int(1)?.ok_or_else(|| format!("Invalid history record '{}'", line))?,
+        app_version: fields[2].clone(),
+        checksum: fields[3].clone(),
+        duration_ms: int(4)?,
+        applied_by: fields[5].clone(),
+        run_id: fields.get(6).cloned().flatten(),
+        batch: fields.get(7).cloned().flatten(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_record() {
+        let record = seaql_migrations::Model {
+            version: "m20220118_000001_create_cake_table".to_owned(),
+            applied_at: 1642464000,
+            app_version: Some("1.0\tbeta\\2".to_owned()),
+            checksum: None,
+            duration_ms: Some(12),
+            applied_by: Some("deploy\nbot".to_owned()),
+            run_id: Some("f1b2c3d4-0000-4000-8000-000000000000".to_owned()),
+            batch: Some("v1.2.0".to_owned()),
+        };
+        let line = format_record(&record);
+        assert!(!line.contains('\n'));
+        assert_eq!(parse_record(&line), Ok(record));
+        assert!(parse_record("m1\t1").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use super::{probe_columns, AppliedAtFormat, ColumnInfo, HistoryLayout};
+use sea_orm::{DbConn, DbErr};
+use std::fmt::Display;
+
+/// What keeps a `seaql_migrations` table from being shared with the upstream
+/// `sea-orm-migration`, see [`MigratorTrait::adopt_from_sea_orm`](super::MigratorTrait::adopt_from_sea_orm)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Incompatibility {
+    /// `version` or `applied_at` is missing
+    MissingColumn(String),
+    /// `version` is not a string or `applied_at` not an integer, as the upstream entity reads them
+    ColumnType { column: String, found: String },
+    /// A column the upstream migrator does not know of is required, failing its inserts
+    RequiredColumn(String),
+    /// `applied_at` is not stored in unix seconds
+    AppliedAtFormat(AppliedAtFormat),
+    /// An applied migration is not registered in the migrator
+    UnregisteredVersion(String),
+    /// An applied migration is registered at another position than it was applied in
+    OutOfOrder(String),
+}
+
+/// Result of checking a database against the upstream `sea-orm-migration` conventions
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdoptionReport {
+    /// Migrations recorded in `seaql_migrations`
+    pub applied: usize,
+    pub incompatibilities: Vec<Incompatibility>,
+}
+
+impl Display for Incompatibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingColumn(column) => write!(f, "column '{}' is missing", column),
+            Self::ColumnType { column, found } => {
+                write!(f, "column '{}' has unexpected type '{}'", column, found)
+            }
+            Self::RequiredColumn(column) => write!(
+                f,
+                "column '{}' is required, inserts of the upstream migrator fail",
+                column
+            ),
+            Self::AppliedAtFormat(format) => write!(
+                f,
+                "applied_at is stored as {}, the upstream migrator reads unix seconds",
+                format
+            ),
+            Self::UnregisteredVersion(version) => {
+                write!(f, "applied migration '{}' is not registered", version)
+            }
+            Self::OutOfOrder(version) => write!(
+                f,
+                "applied migration '{}' is registered at another position",
+                version
+            ),
+        }
+    }
+}
+
+impl AdoptionReport {
+    pub fn is_compatible(&self) -> bool {
+        self.incompatibilities.is_empty()
+    }
+}
+
+/// Check the `seaql_migrations` table, if any, and the applied versions against the migrations
+/// registered in order, under the `applied_at` format the migrator is configured with
+pub(crate) async fn check_adoption(
+    db: &DbConn,
+    registered: &[String],
+    applied_at_format: AppliedAtFormat,
+) -> Result<AdoptionReport, DbErr> {
+    let mut report = AdoptionReport::default();
+    if applied_at_format != AppliedAtFormat::UnixSeconds {
+        report
+            .incompatibilities
+            .push(Incompatibility::AppliedAtFormat(applied_at_format));
+    }
+    let layout = match HistoryLayout::detect(db).await? {
+        Some(layout) => layout,
+        None => return Ok(report),
+    };
+    if layout.applied_at_format != AppliedAtFormat::UnixSeconds
+        && layout.applied_at_format != applied_at_format
+    {
+        report
+            .incompatibilities
+            .push(Incompatibility::AppliedAtFormat(layout.applied_at_format));
+    }
+    let columns = probe_columns(db, "seaql_migrations").await?;
+    report.incompatibilities.extend(check_columns(&columns));
+    if report
+        .incompatibilities
+        .iter()
+        .any(|issue| matches!(issue, Incompatibility::MissingColumn(_)))
+    {
+        return Ok(report);
+    }
+
+    let applied = layout.read(db).await?;
+    report.applied = applied.len();
+    for (i, model) in applied.iter().enumerate() {
+        match registered.iter().position(|name| name == &model.version) {
+            Some(position) if position == i => {}
+            Some(_) => report
+                .incompatibilities
+                .push(Incompatibility::OutOfOrder(model.version.clone())),
+            None => report
+                .incompatibilities
+                .push(Incompatibility::UnregisteredVersion(model.version.clone())),
+        }
+    }
+    Ok(report)
+}
+
+/// Check the columns of `seaql_migrations` against the `(version, applied_at)` layout the
+/// upstream migrator reads and writes
+fn check_columns(columns: &[ColumnInfo]) -> Vec<Incompatibility> {
+    let mut incompatibilities = Vec::new();
+    for (name, expected) in [
+        ("version", &["char", "text"][..]),
+        ("applied_at", &["int"][..]),
+    ] {
+        match columns.iter().find(|column| column.name == name) {
+            Some(column) => {
+                let found = column.column_type.to_lowercase();
+                if !expected.iter().any(|expected| found.contains(expected)) {
+                    incompatibilities.push(Incompatibility::ColumnType {
+                        column: name.to_owned(),
+                        found: column.column_type.clone(),
+                    });
+                }
+            }
+            None => incompatibilities.push(Incompatibility::MissingColumn(name.to_owned())),
+        }
+    }
+    incompatibilities.extend(
+        columns
+            .iter()
+            .filter(|column| !matches!(column.name.as_str(), "version" | "applied_at"))
+            .filter(|column| !column.nullable && column.default.is_none())
+            .map(|column| Incompatibility::RequiredColumn(column.name.clone())),
+    );
+    incompatibilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_columns() {
+        let column = |name: &str, column_type: &str, nullable: bool| ColumnInfo {
+            name: name.to_owned(),
+            column_type: column_type.to_owned(),
+            nullable,
+            default: None,
+        };
+        // The layout created by the upstream migrator on Postgres
+        let mut columns = vec![
+            column("version", "character varying", false),
+            column("applied_at", "bigint", false),
+        ];
+        assert!(check_columns(&columns).is_empty());
+
+        columns.push(column("checksum", "character varying", true));
+        columns.push(column("tenant", "text", false));
+        assert_eq!(
+            check_columns(&columns),
+            vec![Incompatibility::RequiredColumn("tenant".to_owned())]
+        );
+
+        let columns = [column("applied_at", "timestamp(3)", false)];
+        assert_eq!(
+            check_columns(&columns),
+            vec![
+                Incompatibility::MissingColumn("version".to_owned()),
+                Incompatibility::ColumnType {
+                    column: "applied_at".to_owned(),
+                    found: "timestamp(3)".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Measures how far replicas lag behind the primary, consulted by [`Throttle`](super::Throttle)
+/// to pause work while the lag exceeds a threshold
+#[async_trait::async_trait]
+pub trait LagProbe: Debug + Send + Sync {
+    /// The current replication lag, `None` if unknown
+    async fn lag(&self, db: &DbConn) -> Result<Option<Duration>, DbErr>;
+}
+
+/// Replay lag of the most lagging replica as reported by `pg_stat_replication` on Postgres.
+/// The lag is unknown on MySQL and SQLite, where replicas report their own lag.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReplicationLagProbe;
+
+/// Replication lag reported by a user callback, e.g. reading a metric of the replicas
+#[derive(Clone)]
+pub struct CallbackLagProbe(pub Arc<dyn Fn() -> Option<Duration> + Send + Sync>);
+
+#[async_trait::async_trait]
+impl LagProbe for ReplicationLagProbe {
+    async fn lag(&self, db: &DbConn) -> Result<Option<Duration>, DbErr> {
+        let db_backend = db.get_database_backend();
+        if db_backend != DbBackend::Postgres {
+            return Ok(None);
+        }
+        let sql = r#"SELECT (EXTRACT(EPOCH FROM MAX("replay_lag")) * 1000)::bigint AS "lag_ms"
+            FROM "pg_stat_replication""#;
+        let lag_ms: Option<i64> = match db
+            .query_one(Statement::from_string(db_backend, sql.to_owned()))
+            .await?
+        {
+            Some(row) => row.try_get("", "lag_ms")?,
+            None => None,
+        };
+        Ok(lag_ms.map(|lag_ms| Duration::from_millis(lag_ms.max(0) as u64)))
+    }
+}
+
+impl CallbackLagProbe {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn() -> Option<Duration> + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+}
+
+impl Debug for CallbackLagProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CallbackLagProbe").finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl LagProbe for CallbackLagProbe {
+    async fn lag(&self, _: &DbConn) -> Result<Option<Duration>, DbErr> {
+        Ok((self.0)())
+    }
+}
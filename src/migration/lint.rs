@@ -0,0 +1,677 @@
+use super::{destructive_kind, keywords, tokens, PlannedMigration};
+use crate::classification::Classifications;
+use sea_orm::DbBackend;
+use std::fmt::Display;
+
+/// Severity of a lint issue
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    Warning,
+    Error,
+}
+
+/// An issue found by a lint in the statements of a migration
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintIssue {
+    pub migration: String,
+    pub lint: &'static str,
+    pub level: LintLevel,
+    pub message: String,
+    /// The offending statement, if the issue concerns a single statement
+    pub sql: Option<String>,
+}
+
+/// A check performed on the statements built by a migration
+pub trait Lint: Send + Sync {
+    /// Name of the lint, reported along with its issues
+    fn name(&self) -> &'static str;
+
+    /// Check the statements built by the migration for the given backend
+    fn check(&self, migration: &PlannedMigration, db_backend: DbBackend) -> Vec<LintIssue>;
+}
+
+/// Flag statements dropping or truncating schema objects or data
+#[derive(Clone, Debug, Default)]
+pub struct DestructiveStatementLint;
+
+/// Flag migrations whose statements cannot be built without a database connection
+#[derive(Clone, Debug, Default)]
+pub struct UnplannedMigrationLint;
+
+/// Flag time-zone and locale dependent schema: timestamps without time zone, values relying on
+/// the time zone of the server or session and locale-dependent collations. Each check reports
+/// at its configured level, `None` disables it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeZoneLocaleLint {
+    pub timestamp_without_time_zone: Option<LintLevel>,
+    pub server_time_zone: Option<LintLevel>,
+    pub locale_collation: Option<LintLevel>,
+    /// Collations considered locale-independent, compared case-insensitively
+    pub allowed_collations: Vec<String>,
+    /// Only check migrations named after this one, e.g. to leave migrations applied before
+    /// adopting the policy alone
+    pub since: Option<String>,
+}
+
+/// Flag statements dropping classified columns, or copying them into another table,
+/// unless the migration acknowledges it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassificationLint {
+    pub classifications: Classifications,
+    /// Acknowledged (migration, table, column) triples, `*` acknowledging every column of the table
+    pub acknowledged: Vec<(String, String, String)>,
+    pub level: LintLevel,
+}
+
+/// The lints run by default
+pub fn default_lints() -> Vec<Box<dyn Lint>> {
+    vec![
+        Box::new(DestructiveStatementLint),
+        Box::new(UnplannedMigrationLint),
+        Box::new(TimeZoneLocaleLint::default()),
+    ]
+}
+
+/// Run the lints against the planned migrations
+pub fn run_lints(
+    lints: &[Box<dyn Lint>],
+    migrations: &[PlannedMigration],
+    db_backend: DbBackend,
+) -> Vec<LintIssue> {
+    migrations
+        .iter()
+        .flat_map(|migration| {
+            lints
+                .iter()
+                .flat_map(move |lint| lint.check(migration, db_backend))
+        })
+        .collect()
+}
+
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{}] {}: {}",
+            self.level, self.lint, self.migration, self.message
+        )?;
+        if let Some(sql) = &self.sql {
+            write!(f, "\n    {}", sql)?;
+        }
+        Ok(())
+    }
+}
+
+impl Lint for DestructiveStatementLint {
+    fn name(&self) -> &'static str {
+        "destructive_statement"
+    }
+
+    fn check(&self, migration: &PlannedMigration, _: DbBackend) -> Vec<LintIssue> {
+        migration
+            .statements
+            .iter()
+            .filter_map(|sql| {
+                destructive_kind(sql).map(|kind| LintIssue {
+                    migration: migration.name.clone(),
+                    lint: self.name(),
+                    level: LintLevel::Warning,
+                    message: format!("{} may lose data", kind),
+                    sql: Some(sql.clone()),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Lint for UnplannedMigrationLint {
+    fn name(&self) -> &'static str {
+        "unplanned_migration"
+    }
+
+    fn check(&self, migration: &PlannedMigration, _: DbBackend) -> Vec<LintIssue> {
+        match &migration.error {
+            Some(error) => vec![LintIssue {
+                migration: migration.name.clone(),
+                lint: self.name(),
+                level: LintLevel::Warning,
+                message: format!("statements cannot be built offline: {}", error),
+                sql: None,
+            }],
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Default for TimeZoneLocaleLint {
+    fn default() -> Self {
+        Self {
+            timestamp_without_time_zone: Some(LintLevel::Warning),
+            server_time_zone: Some(LintLevel::Warning),
+            locale_collation: Some(LintLevel::Warning),
+            allowed_collations: [
+                "C",
+                "POSIX",
+                "ucs_basic",
+                "pg_c_utf8",
+                "und-x-icu",
+                "unicode",
+                "BINARY",
+                "NOCASE",
+                "RTRIM",
+            ]
+            .iter()
+            .map(|collation| collation.to_string())
+            .collect(),
+            since: None,
+        }
+    }
+}
+
+impl TimeZoneLocaleLint {
+    pub fn timestamp_without_time_zone(mut self, level: Option<LintLevel>) -> Self {
+        self.timestamp_without_time_zone = level;
+        self
+    }
+
+    pub fn server_time_zone(mut self, level: Option<LintLevel>) -> Self {
+        self.server_time_zone = level;
+        self
+    }
+
+    pub fn locale_collation(mut self, level: Option<LintLevel>) -> Self {
+        self.locale_collation = level;
+        self
+    }
+
+    /// Consider a collation locale-independent
+    pub fn allow_collation<T>(mut self, collation: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.allowed_collations.push(collation.into());
+        self
+    }
+
+    pub fn since<T>(mut self, migration: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.since = Some(migration.into());
+        self
+    }
+
+    fn timestamp_without_time_zone_issue(
+        &self,
+        keywords: &[String],
+        db_backend: DbBackend,
+    ) -> Option<String> {
+        if !matches!(
+            (keyword(keywords, 0), keyword(keywords, 1)),
+            ("CREATE", "TABLE") | ("ALTER", "TABLE")
+        ) {
+            return None;
+        }
+        let naive = keywords.iter().enumerate().any(|(i, k)| match db_backend {
+            DbBackend::Postgres if k == "TIMESTAMP" => {
+                let mut next = i + 1;
+                // Skip the precision, e.g. `timestamp(6)`
+                if keyword(keywords, next) == "(" {
+                    next += 3;
+                }
+                // `TIMESTAMP '...'` is a literal, not a column type
+                !matches!(keyword(keywords, next), "WITH" | "?")
+            }
+            DbBackend::MySql => k == "DATETIME",
+            _ => false,
+        });
+        match (naive, db_backend) {
+            (true, DbBackend::MySql) => {
+                Some("DATETIME column stores local time without time zone".to_owned())
+            }
+            (true, _) => Some("timestamp column without time zone".to_owned()),
+            (false, _) => None,
+        }
+    }
+
+    fn server_time_zone_issue(
+        &self,
+        sql: &str,
+        keywords: &[String],
+        db_backend: DbBackend,
+    ) -> Option<String> {
+        let local = keywords.iter().find(|k| {
+            matches!(
+                k.as_str(),
+                "CURRENT_DATE"
+                    | "CURRENT_TIME"
+                    | "LOCALTIME"
+                    | "LOCALTIMESTAMP"
+                    | "CURDATE"
+                    | "CURTIME"
+            )
+        });
+        if let Some(function) = local {
+            return Some(format!(
+                "{} depends on the time zone of the session",
+                function
+            ));
+        }
+        match db_backend {
+            DbBackend::Postgres => keywords
+                .iter()
+                .enumerate()
+                .find(|(i, k)| {
+                    let mut next = i + 1;
+                    if keyword(keywords, next) == "(" && keyword(keywords, next + 1) == ")" {
+                        next += 2;
+                    }
+                    matches!(k.as_str(), "NOW" | "CURRENT_TIMESTAMP")
+                        && keyword(keywords, next) == ":"
+                        && keyword(keywords, next + 1) == ":"
+                        && matches!(keyword(keywords, next + 2), "DATE" | "TIME" | "TIMESTAMP")
+                })
+                .map(|(_, function)| {
+                    format!(
+                        "{} cast to a local date or time depends on the time zone of the session",
+                        function
+                    )
+                }),
+            DbBackend::MySql => keywords
+                .iter()
+                .find(|k| matches!(k.as_str(), "NOW" | "CURRENT_TIMESTAMP" | "SYSDATE"))
+                .filter(|_| keywords.iter().any(|k| k == "DATETIME"))
+                .map(|function| {
+                    format!(
+                        "{} stored as DATETIME depends on the time zone of the session",
+                        function
+                    )
+                }),
+            DbBackend::Sqlite => sql
+                .to_lowercase()
+                .contains("'localtime'")
+                .then(|| "'localtime' depends on the time zone of the server".to_owned()),
+        }
+    }
+
+    fn locale_collation_issue(&self, sql: &str, db_backend: DbBackend) -> Option<String> {
+        let tokens = tokens(sql, true);
+        let locale = tokens.iter().enumerate().find_map(|(i, token)| {
+            let name = match token.to_uppercase().as_str() {
+                "COLLATE" => tokens.get(i + 1)?,
+                // Collation of a database or a collation created from a locale
+                "LC_COLLATE" | "LOCALE" => return Some(token.to_uppercase()),
+                _ => return None,
+            };
+            // Unquoted names are folded, which `tokens` does to upper case
+            let name = match name.strip_prefix(|c| c == '"' || c == '`') {
+                Some(quoted) => quoted[..quoted.len() - 1].to_owned(),
+                None => name.to_lowercase(),
+            };
+            let allowed = self
+                .allowed_collations
+                .iter()
+                .any(|collation| collation.eq_ignore_ascii_case(&name));
+            let locale_dependent = match db_backend {
+                DbBackend::MySql => mysql_collation_has_locale(&name),
+                DbBackend::Postgres | DbBackend::Sqlite => true,
+            };
+            (!allowed && locale_dependent).then(|| format!("collation {}", name))
+        })?;
+        Some(format!("{} is locale-dependent", locale))
+    }
+}
+
+/// MySQL collations are named `<charset>_<language>_<attributes>`, language-neutral collations
+/// have no language, e.g. `utf8mb4_0900_ai_ci` but not `utf8mb4_de_pb_0900_ai_ci`
+fn mysql_collation_has_locale(name: &str) -> bool {
+    name.to_lowercase().split('_').skip(1).any(|part| {
+        !matches!(
+            part,
+            "general" | "unicode" | "bin" | "ci" | "cs" | "ai" | "as" | "ks" | "nopad" | "mysql500"
+        ) && !part.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+impl ClassificationLint {
+    pub fn new(classifications: Classifications) -> Self {
+        Self {
+            classifications,
+            acknowledged: Vec::new(),
+            level: LintLevel::Warning,
+        }
+    }
+
+    pub fn level(mut self, level: LintLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Acknowledge that a migration drops or copies a column, `*` for every column of the table
+    pub fn acknowledge<M, T, C>(mut self, migration: M, table: T, column: C) -> Self
+    where
+        M: Into<String>,
+        T: Into<String>,
+        C: Into<String>,
+    {
+        self.acknowledged
+            .push((migration.into(), table.into(), column.into()));
+        self
+    }
+
+    fn is_acknowledged(&self, migration: &str, table: &str, column: &str) -> bool {
+        self.acknowledged
+            .iter()
+            .any(|(m, t, c)| m == migration && t == table && (c == column || c == "*"))
+    }
+
+    /// Classified columns dropped or copied by a statement, with the way they are
+    fn affected_columns(&self, sql: &str) -> Vec<(String, String, &'static str)> {
+        let names: Vec<String> = tokens(sql, true).iter().map(|t| identifier(t)).collect();
+        let name = |i: usize| names.get(i).map(|n| n.as_str()).unwrap_or_default();
+        let mut affected = Vec::new();
+        match (name(0), name(1)) {
+            ("drop", "table") => {
+                let mut i = 2;
+                while i < names.len() {
+                    if !matches!(name(i), "if" | "exists" | "," | "cascade" | "restrict") {
+                        for (column, _) in self.classifications.table(name(i)) {
+                            affected.push((name(i).to_owned(), column, "drops"));
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            ("alter", "table") => {
+                let table = name(2);
+                for (i, n) in names.iter().enumerate() {
+                    let column = match (n.as_str(), name(i + 1)) {
+                        ("drop", "column") if name(i + 2) == "if" => name(i + 4),
+                        ("drop", "column") => name(i + 2),
+                        ("drop", column)
+                            if !matches!(
+                                column,
+                                "constraint"
+                                    | "foreign"
+                                    | "index"
+                                    | "key"
+                                    | "primary"
+                                    | "default"
+                                    | "not"
+                                    | "check"
+                            ) =>
+                        {
+                            column
+                        }
+                        _ => continue,
+                    };
+                    if !self.classifications.get(table, column).is_empty() {
+                        affected.push((table.to_owned(), column.to_owned(), "drops"));
+                    }
+                }
+            }
+            _ => {}
+        }
+        // Copies read the classified columns of a source table into another one
+        let is_copy = names.iter().any(|n| n == "select")
+            && matches!(
+                (name(0), name(1)),
+                ("insert", _) | ("create", "table") | ("select", _)
+            )
+            && names.iter().any(|n| n == "into" || n == "as");
+        if is_copy {
+            let sources: Vec<&str> = names
+                .windows(2)
+                .filter(|w| w[0] == "from" || w[0] == "join")
+                .map(|w| w[1].as_str())
+                .collect();
+            let selects_all = names.iter().any(|n| n == "*");
+            for source in sources {
+                for (column, _) in self.classifications.table(source) {
+                    if selects_all || names.contains(&column) {
+                        affected.push((source.to_owned(), column, "copies"));
+                    }
+                }
+            }
+        }
+        affected
+    }
+}
+
+/// Name of an identifier token, unquoted, or lower-cased if unquoted
+fn identifier(token: &str) -> String {
+    match token.chars().next() {
+        Some(quote @ ('"' | '`')) if token.len() > 1 => {
+            token[1..token.len() - 1].replace(&format!("{0}{0}", quote), &quote.to_string())
+        }
+        _ => token.to_lowercase(),
+    }
+}
+
+fn keyword(keywords: &[String], i: usize) -> &str {
+    keywords.get(i).map(|k| k.as_str()).unwrap_or_default()
+}
+
+impl Lint for TimeZoneLocaleLint {
+    fn name(&self) -> &'static str {
+        "time_zone_locale"
+    }
+
+    fn check(&self, migration: &PlannedMigration, db_backend: DbBackend) -> Vec<LintIssue> {
+        if let Some(since) = &self.since {
+            if migration.name.as_str() <= since.as_str() {
+                return Vec::new();
+            }
+        }
+        let mut issues = Vec::new();
+        for sql in migration.statements.iter() {
+            let keywords = keywords(sql);
+            let found = [
+                (
+                    self.timestamp_without_time_zone,
+                    self.timestamp_without_time_zone_issue(&keywords, db_backend),
+                ),
+                (
+                    self.server_time_zone,
+                    self.server_time_zone_issue(sql, &keywords, db_backend),
+                ),
+                (
+                    self.locale_collation,
+                    self.locale_collation_issue(sql, db_backend),
+                ),
+            ];
+            for (level, message) in found {
+                if let (Some(level), Some(message)) = (level, message) {
+                    issues.push(LintIssue {
+                        migration: migration.name.clone(),
+                        lint: self.name(),
+                        level,
+                        message,
+                        sql: Some(sql.clone()),
+                    });
+                }
+            }
+        }
+        issues
+    }
+}
+
+impl Lint for ClassificationLint {
+    fn name(&self) -> &'static str {
+        "classification"
+    }
+
+    fn check(&self, migration: &PlannedMigration, _: DbBackend) -> Vec<LintIssue> {
+        let mut issues = Vec::new();
+        for sql in migration.statements.iter() {
+            for (table, column, action) in self.affected_columns(sql) {
+                if self.is_acknowledged(&migration.name, &table, &column) {
+                    continue;
+                }
+                let classifications: Vec<String> = self
+                    .classifications
+                    .get(&table, &column)
+                    .iter()
+                    .map(|classification| classification.to_string())
+                    .collect();
+                issues.push(LintIssue {
+                    migration: migration.name.clone(),
+                    lint: self.name(),
+                    level: self.level,
+                    message: format!(
+                        "{} column {}.{} classified as {} without acknowledgment",
+                        action,
+                        table,
+                        column,
+                        classifications.join(", ")
+                    ),
+                    sql: Some(sql.clone()),
+                });
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(lint: &TimeZoneLocaleLint, db_backend: DbBackend, sql: &str) -> Vec<String> {
+        let migration = PlannedMigration {
+            name: "m20220101_000001_create_booking".to_owned(),
+            statements: vec![sql.to_owned()],
+            checksummed: vec![],
+            error: None,
+        };
+        lint.check(&migration, db_backend)
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_time_zone_locale_lint() {
+        let lint = TimeZoneLocaleLint::default();
+        assert_eq!(
+            messages(
+                &lint,
+                DbBackend::Postgres,
+                r#"CREATE TABLE "booking" ("starts_at" timestamp NOT NULL, "ends_at" timestamp(6) with time zone)"#
+            ),
+            vec!["timestamp column without time zone"]
+        );
+        assert!(messages(
+            &lint,
+            DbBackend::Postgres,
+            r#"CREATE TABLE "booking" ("starts_at" timestamp with time zone DEFAULT now())"#
+        )
+        .is_empty());
+        assert_eq!(
+            messages(
+                &lint,
+                DbBackend::Postgres,
+                r#"UPDATE "booking" SET "day" = now()::date"#
+            ),
+            vec!["NOW cast to a local date or time depends on the time zone of the session"]
+        );
+        assert_eq!(
+            messages(
+                &lint,
+                DbBackend::MySql,
+                "ALTER TABLE `booking` ADD COLUMN `day` date DEFAULT (CURRENT_DATE)"
+            ),
+            vec!["CURRENT_DATE depends on the time zone of the session"]
+        );
+        assert_eq!(
+            messages(
+                &lint,
+                DbBackend::Postgres,
+                r#"CREATE TABLE "guest" ("name" varchar COLLATE "de_DE", "code" varchar COLLATE "C")"#
+            ),
+            vec!["collation de_DE is locale-dependent"]
+        );
+        assert!(messages(
+            &lint,
+            DbBackend::MySql,
+            "CREATE TABLE `guest` (`name` varchar(255) COLLATE utf8mb4_0900_ai_ci)"
+        )
+        .is_empty());
+        assert_eq!(
+            messages(
+                &lint,
+                DbBackend::MySql,
+                "CREATE TABLE `guest` (`name` varchar(255) COLLATE utf8mb4_tr_0900_ai_ci)"
+            ),
+            vec!["collation utf8mb4_tr_0900_ai_ci is locale-dependent"]
+        );
+
+        let lint = TimeZoneLocaleLint::default()
+            .timestamp_without_time_zone(Some(LintLevel::Error))
+            .allow_collation("de_DE")
+            .since("m20220101_000000_init");
+        let migration = PlannedMigration {
+            name: "m20220101_000001_create_booking".to_owned(),
+            statements: vec![
+                r#"CREATE TABLE "booking" ("starts_at" timestamp, "name" varchar COLLATE "de_DE")"#
+                    .to_owned(),
+            ],
+            checksummed: vec![],
+            error: None,
+        };
+        let issues = lint.check(&migration, DbBackend::Postgres);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].level, LintLevel::Error);
+        assert!(lint
+            .since("m20220101_000001_create_booking")
+            .check(&migration, DbBackend::Postgres)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_classification_lint() {
+        use crate::classification::Classification;
+
+        let classifications = Classifications::default()
+            .tag("customer", "email", Classification::Pii)
+            .tag("customer", "api_key", Classification::Secret);
+        let lint = ClassificationLint::new(classifications).acknowledge(
+            "m20220101_000001_cleanup",
+            "customer",
+            "api_key",
+        );
+        let migration = PlannedMigration {
+            name: "m20220101_000001_cleanup".to_owned(),
+            statements: vec![
+                r#"ALTER TABLE "customer" DROP COLUMN "api_key", DROP COLUMN "email""#.to_owned(),
+                r#"INSERT INTO "lead" ("contact") SELECT "email" FROM "customer""#.to_owned(),
+                r#"INSERT INTO "lead" ("name") SELECT "name" FROM "customer""#.to_owned(),
+                "DROP TABLE IF EXISTS `customer`".to_owned(),
+            ],
+            checksummed: vec![],
+            error: None,
+        };
+        let messages: Vec<String> = lint
+            .check(&migration, DbBackend::Postgres)
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "drops column customer.email classified as pii without acknowledgment",
+                "copies column customer.email classified as pii without acknowledgment",
+                "drops column customer.email classified as pii without acknowledgment",
+            ]
+        );
+    }
+}
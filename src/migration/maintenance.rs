@@ -0,0 +1,248 @@
+use super::{get_schema_meta_value, quote, set_schema_meta_value};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use tracing::warn;
+
+/// Key of the `seaql_schema_meta` entry holding the maintenance statements enqueued by the last runs
+pub const META_PENDING_MAINTENANCE: &str = "pending_maintenance";
+
+/// Post-run maintenance report settings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Maintenance {
+    /// Rows a migration run has to touch in a table for the table to be reported
+    pub threshold: u64,
+    /// Enqueue the recommended statements, to be executed outside the main run by
+    /// [`MigratorTrait::run_maintenance`](super::MigratorTrait::run_maintenance)
+    pub enqueue: bool,
+}
+
+/// Rows of a table modified by the statements executed through [`SchemaManager`](super::SchemaManager)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableActivity {
+    pub inserted: u64,
+    pub updated: u64,
+    pub deleted: u64,
+}
+
+/// Kind of data modification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DmlKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A table likely needing maintenance after a migration run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaintenanceTask {
+    pub table: String,
+    pub activity: TableActivity,
+    /// Statements reclaiming space, rebuilding indexes or refreshing statistics
+    pub statements: Vec<String>,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            threshold: 100_000,
+            enqueue: false,
+        }
+    }
+}
+
+impl Maintenance {
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            threshold,
+            ..Default::default()
+        }
+    }
+
+    /// Enqueue the recommended statements instead of only reporting them
+    pub fn enqueue(mut self, enqueue: bool) -> Self {
+        self.enqueue = enqueue;
+        self
+    }
+
+    /// Recommend maintenance for the tables with at least `threshold` rows touched.
+    /// Updated and deleted rows leave dead tuples and fragmented indexes behind, inserted
+    /// rows only leave the planner statistics outdated.
+    pub fn report(
+        &self,
+        activity: &BTreeMap<String, TableActivity>,
+        db_backend: DbBackend,
+    ) -> Vec<MaintenanceTask> {
+        let mut tasks = Vec::new();
+        for (table, activity) in activity.iter() {
+            let churned = activity.updated + activity.deleted;
+            let name = quote(db_backend, table);
+            let statements = if churned >= self.threshold {
+                match db_backend {
+                    DbBackend::Postgres => vec![
+                        format!("VACUUM (FULL, ANALYZE) {}", name),
+                        format!("REINDEX TABLE {}", name),
+                    ],
+                    DbBackend::MySql => vec![format!("OPTIMIZE TABLE {}", name)],
+                    DbBackend::Sqlite => vec![format!("REINDEX {}", name), "VACUUM".to_owned()],
+                }
+            } else if churned + activity.inserted >= self.threshold {
+                match db_backend {
+                    DbBackend::MySql => vec![format!("ANALYZE TABLE {}", name)],
+                    DbBackend::Postgres | DbBackend::Sqlite => vec![format!("ANALYZE {}", name)],
+                }
+            } else {
+                continue;
+            };
+            tasks.push(MaintenanceTask {
+                table: table.clone(),
+                activity: *activity,
+                statements,
+            });
+        }
+        tasks
+    }
+}
+
+impl TableActivity {
+    pub(crate) fn add(&mut self, kind: DmlKind, rows: u64) {
+        match kind {
+            DmlKind::Insert => self.inserted += rows,
+            DmlKind::Update => self.updated += rows,
+            DmlKind::Delete => self.deleted += rows,
+        }
+    }
+}
+
+impl Display for MaintenanceTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Table '{}' ({} inserted, {} updated, {} deleted): {}",
+            self.table,
+            self.activity.inserted,
+            self.activity.updated,
+            self.activity.deleted,
+            self.statements.join("; ")
+        )
+    }
+}
+
+/// Maintenance statements enqueued by previous runs, in order
+pub async fn pending_maintenance(db: &DbConn) -> Result<Vec<String>, DbErr> {
+    Ok(get_schema_meta_value(db, META_PENDING_MAINTENANCE)
+        .await?
+        .map(|value| value.lines().map(|line| line.to_owned()).collect())
+        .unwrap_or_default())
+}
+
+/// Replace the maintenance statements enqueued in `seaql_schema_meta`
+pub(crate) async fn set_pending_maintenance(
+    db: &DbConn,
+    statements: &[String],
+) -> Result<(), DbErr> {
+    set_schema_meta_value(db, META_PENDING_MAINTENANCE, &statements.join("\n")).await
+}
+
+/// Report the tables likely needing maintenance after a run, enqueueing the statements if configured
+pub(crate) async fn report_maintenance(
+    db: &DbConn,
+    maintenance: &Maintenance,
+    activity: &BTreeMap<String, TableActivity>,
+) -> Result<(), DbErr> {
+    let tasks = maintenance.report(activity, db.get_database_backend());
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    for task in tasks.iter() {
+        warn!("Maintenance recommended: {}", task);
+    }
+    if maintenance.enqueue {
+        let mut pending = pending_maintenance(db).await?;
+        for statement in tasks.into_iter().flat_map(|task| task.statements) {
+            if !pending.contains(&statement) {
+                pending.push(statement);
+            }
+        }
+        set_pending_maintenance(db, &pending).await?;
+    }
+    Ok(())
+}
+
+/// The table modified by an `INSERT`, `UPDATE` or `DELETE` statement
+pub(crate) fn dml_target(sql: &str) -> Option<(String, DmlKind)> {
+    let tokens: Vec<&str> = sql.split_whitespace().collect();
+    let keyword = |i: usize| tokens.get(i).map(|t| t.to_uppercase()).unwrap_or_default();
+    let (kind, mut i) = match keyword(0).as_str() {
+        "INSERT" | "REPLACE" => (DmlKind::Insert, 1),
+        "UPDATE" => (DmlKind::Update, 1),
+        "DELETE" => (DmlKind::Delete, 1),
+        _ => return None,
+    };
+    while matches!(
+        keyword(i).as_str(),
+        "INTO" | "FROM" | "ONLY" | "IGNORE" | "LOW_PRIORITY" | "OR" | "REPLACE" | "ABORT"
+    ) {
+        i += 1;
+    }
+    let table = tokens.get(i)?.split('(').next()?;
+    let table = table.rsplit('.').next()?;
+    Some((
+        table.trim_matches(|c| c == '"' || c == '`').to_owned(),
+        kind,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dml_target() {
+        assert_eq!(
+            dml_target(r#"INSERT INTO "cake" ("name") VALUES ($1)"#),
+            Some(("cake".to_owned(), DmlKind::Insert))
+        );
+        assert_eq!(
+            dml_target("UPDATE `shop`.`cake` SET `name` = ?"),
+            Some(("cake".to_owned(), DmlKind::Update))
+        );
+        assert_eq!(
+            dml_target(r#"DELETE FROM "cake" WHERE "id" = $1"#),
+            Some(("cake".to_owned(), DmlKind::Delete))
+        );
+        assert_eq!(dml_target(r#"CREATE TABLE "cake" ()"#), None);
+    }
+
+    #[test]
+    fn test_report() {
+        let activity = BTreeMap::from([
+            (
+                "cake".to_owned(),
+                TableActivity {
+                    inserted: 0,
+                    updated: 60,
+                    deleted: 40,
+                },
+            ),
+            (
+                "fruit".to_owned(),
+                TableActivity {
+                    inserted: 100,
+                    ..Default::default()
+                },
+            ),
+            (
+                "vegetable".to_owned(),
+                TableActivity {
+                    inserted: 99,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let tasks = Maintenance::new(100).report(&activity, DbBackend::MySql);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].statements, vec!["OPTIMIZE TABLE `cake`"]);
+        assert_eq!(tasks[1].statements, vec!["ANALYZE TABLE `fruit`"]);
+    }
+}
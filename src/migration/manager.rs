@@ -1,56 +1,573 @@
 use sea_orm::sea_query::{
     extension::postgres::{TypeAlterStatement, TypeCreateStatement, TypeDropStatement},
     Alias, Expr, ForeignKeyCreateStatement, ForeignKeyDropStatement, IndexCreateStatement,
-    IndexDropStatement, Query, TableAlterStatement, TableCreateStatement, TableDropStatement,
-    TableRenameStatement, TableTruncateStatement,
+    IndexDropStatement, Query, SimpleExpr, TableAlterStatement, TableCreateStatement,
+    TableDropStatement, TableRenameStatement, TableTruncateStatement, Value,
+};
+use sea_orm::{
+    Condition, ConnectionTrait, DatabaseTransaction, DbBackend, DbConn, DbErr, QueryResult,
+    Statement, StatementBuilder, TransactionTrait,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{debug, warn, Instrument};
+
+use super::{
+    comment_statement, dml_target, ensure_allowed, ensure_no_lossy_change, managed_comment,
+    probe_columns, schema_probe, statement_class, statement_span, BlockingCheck, ColumnInfo,
+    MigrationWarning, PolicyContext, StatementClass, StatementPolicy, StatementRecorder,
+    TableActivity, Throttle, ValueCodecs, Watchdog, STATEMENT_TARGET,
 };
-use sea_orm::{Condition, ConnectionTrait, DbBackend, DbConn, DbErr, Statement, StatementBuilder};
 
-use super::query_tables;
+/// Connection handed to migrations in dry-run mode, any attempt to use it fails
+static DISCONNECTED: DbConn = DbConn::Disconnected;
 
 /// Helper struct for writing migration scripts in migration file
 pub struct SchemaManager<'c> {
     conn: &'c DbConn,
+    dml_conn: Option<&'c DbConn>,
+    db_backend: DbBackend,
+    watchdog: Option<Watchdog>,
+    blocking_check: Option<BlockingCheck>,
+    throttle: Option<Throttle>,
+    dry_run: Option<Mutex<Vec<Statement>>>,
+    activity: Mutex<BTreeMap<String, TableActivity>>,
+    value_codecs: ValueCodecs,
+    allow_lossy_alters: bool,
+    policy: Option<Box<dyn StatementPolicy>>,
+    policy_context: Mutex<PolicyContext>,
+    log_statements: bool,
+    tag_managed_objects: bool,
+    warnings: Mutex<Vec<MigrationWarning>>,
+    recorder: Option<StatementRecorder>,
+    /// Statements executed since the migrator last collected them, for the checksum of the
+    /// migration. Queries and the comments tagging managed objects are left out, as they vary
+    /// with the data and the version of the crate.
+    executed: Mutex<Vec<String>>,
+    /// The connection was handed out, statements may have been executed without the manager
+    connection_taken: AtomicBool,
+    held: Mutex<Held>,
+}
+
+/// Transactions begun with [`SchemaManager::begin`] on the connection and on the data
+/// connection, each holding a single connection of its pool
+#[derive(Default)]
+struct Held {
+    conn: Option<Arc<DatabaseTransaction>>,
+    dml_conn: Option<Arc<DatabaseTransaction>>,
+}
+
+/// Connection a statement is executed on: the transaction begun with [`SchemaManager::begin`],
+/// if any, or the pool
+enum Executor<'c> {
+    Pool(&'c DbConn),
+    Held(Arc<DatabaseTransaction>),
+}
+
+impl std::ops::Deref for Executor<'_> {
+    type Target = dyn ConnectionTrait;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Pool(conn) => *conn,
+            Self::Held(txn) => txn.as_ref(),
+        }
+    }
 }
 
 impl<'c> SchemaManager<'c> {
     pub fn new(conn: &'c DbConn) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            dml_conn: None,
+            db_backend: conn.get_database_backend(),
+            watchdog: None,
+            blocking_check: None,
+            throttle: None,
+            dry_run: None,
+            activity: Mutex::default(),
+            value_codecs: ValueCodecs::default(),
+            allow_lossy_alters: false,
+            policy: None,
+            policy_context: Mutex::default(),
+            log_statements: false,
+            tag_managed_objects: false,
+            warnings: Mutex::default(),
+            recorder: None,
+            executed: Mutex::default(),
+            connection_taken: AtomicBool::new(false),
+            held: Mutex::default(),
+        }
+    }
+
+    /// Create a schema manager that records statements instead of executing them.
+    /// Schema inspection still queries the database, but the connection returned by
+    /// [`SchemaManager::get_connection`] is disconnected.
+    pub fn dry_run(conn: &'c DbConn) -> Self {
+        Self {
+            dry_run: Some(Mutex::new(Vec::new())),
+            ..Self::new(conn)
+        }
+    }
+
+    /// Create a schema manager that builds statements for the given backend without
+    /// any database connection. Schema inspection is unavailable.
+    pub fn offline(db_backend: DbBackend) -> SchemaManager<'static> {
+        SchemaManager {
+            conn: &DISCONNECTED,
+            dml_conn: None,
+            db_backend,
+            watchdog: None,
+            blocking_check: None,
+            throttle: None,
+            dry_run: Some(Mutex::new(Vec::new())),
+            activity: Mutex::default(),
+            value_codecs: ValueCodecs::default(),
+            allow_lossy_alters: false,
+            policy: None,
+            policy_context: Mutex::default(),
+            log_statements: false,
+            tag_managed_objects: false,
+            warnings: Mutex::default(),
+            recorder: None,
+            executed: Mutex::default(),
+            connection_taken: AtomicBool::new(false),
+            held: Mutex::default(),
+        }
+    }
+
+    /// Return true if the schema manager has no database connection
+    pub fn is_offline(&self) -> bool {
+        std::ptr::eq(self.conn, &DISCONNECTED)
+    }
+
+    /// Return true if statements are recorded instead of executed
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.is_some()
+    }
+
+    /// Take the statements recorded in dry-run mode
+    pub fn take_statements(&self) -> Vec<Statement> {
+        match &self.dry_run {
+            Some(statements) => std::mem::take(&mut *statements.lock().unwrap()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Take the statements executed since the last call, also recorded in dry-run mode, `None`
+    /// if the connection was handed out in the meantime, as the statements executed on it
+    /// directly are unknown
+    pub(crate) fn take_executed(&self) -> Option<Vec<String>> {
+        let executed = std::mem::take(&mut *self.executed.lock().unwrap());
+        match self.connection_taken.swap(false, Ordering::Relaxed) {
+            true => None,
+            false => Some(executed),
+        }
+    }
+
+    /// Emit heartbeats and watch for blocked statements while executing
+    pub fn watchdog(&mut self, watchdog: Option<Watchdog>) -> &mut Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    /// Check for sessions blocking lock-heavy DDL statements before executing them
+    pub fn blocking_check(&mut self, blocking_check: Option<BlockingCheck>) -> &mut Self {
+        self.blocking_check = blocking_check;
+        self
+    }
+
+    /// Pause between statements and cap the number of statements executed concurrently
+    pub fn throttle(&mut self, throttle: Option<Throttle>) -> &mut Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Encoders and decoders of backend-specific column types used by data migrations
+    pub fn value_codecs(&mut self, value_codecs: ValueCodecs) -> &mut Self {
+        self.value_codecs = value_codecs;
+        self
+    }
+
+    /// Apply MySQL `ALTER TABLE` statements which may truncate or convert stored values
+    /// without failing, see [`SchemaManager::force_alter_table`]
+    pub fn allow_lossy_alters(&mut self, allow_lossy_alters: bool) -> &mut Self {
+        self.allow_lossy_alters = allow_lossy_alters;
+        self
+    }
+
+    /// Execute data statements and queries on a separate connection, e.g. one of a restricted
+    /// user lacking the privileges to change the schema, while every other statement and the
+    /// schema inspection keep using the privileged connection the schema manager was created with.
+    ///
+    /// Both connections are independent, a transaction begun on one is not seen by the other.
+    pub fn dml_connection(&mut self, dml_conn: Option<&'c DbConn>) -> &mut Self {
+        self.dml_conn = dml_conn;
+        self
+    }
+
+    /// Check every statement against the policy before executing or recording it
+    pub fn policy(&mut self, policy: Option<Box<dyn StatementPolicy>>) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Log every executed statement at debug level with the target [`STATEMENT_TARGET`],
+    /// along with its backend, its bound values and the migration executing it
+    pub fn log_statements(&mut self, log_statements: bool) -> &mut Self {
+        self.log_statements = log_statements;
+        self
+    }
+
+    /// Comment the tables, indexes, foreign keys and types created on Postgres with
+    /// [`managed_comment`], replacing any comment set by the statement creating them
+    pub fn tag_managed_objects(&mut self, tag_managed_objects: bool) -> &mut Self {
+        self.tag_managed_objects = tag_managed_objects;
+        self
+    }
+
+    /// Record every executed statement, with its timing and outcome, for it to be replayed later
+    pub fn record_statements(&mut self, recorder: Option<StatementRecorder>) -> &mut Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Begin a transaction on the connection, and on the data connection if any, in which the
+    /// statements of the schema manager are executed until [`SchemaManager::commit`] or
+    /// [`SchemaManager::rollback`]. Every statement then runs on the same connection of the pool,
+    /// e.g. for a session setting or deferred constraints to apply to all of them.
+    ///
+    /// Statements executed on [`SchemaManager::get_connection`] directly run outside of it.
+    /// Nothing is begun in dry-run mode.
+    pub async fn begin(&self) -> Result<(), DbErr> {
+        if self.dry_run.is_some() {
+            return Ok(());
+        }
+        if self.held.lock().unwrap().conn.is_some() {
+            return Err(DbErr::Custom(
+                "A transaction has already been begun by the schema manager".to_owned(),
+            ));
+        }
+        let conn = Arc::new(self.conn.begin().await?);
+        let dml_conn = match self.dml_conn {
+            Some(dml_conn) => Some(Arc::new(dml_conn.begin().await?)),
+            None => None,
+        };
+        *self.held.lock().unwrap() = Held {
+            conn: Some(conn),
+            dml_conn,
+        };
+        Ok(())
+    }
+
+    /// Return true if a transaction begun with [`SchemaManager::begin`] is held, e.g. by the
+    /// migrator for the session settings of the migration
+    pub fn in_transaction(&self) -> bool {
+        self.held.lock().unwrap().conn.is_some()
+    }
+
+    /// Commit the transactions begun with [`SchemaManager::begin`], if any
+    pub async fn commit(&self) -> Result<(), DbErr> {
+        for txn in self.take_held()? {
+            txn.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Roll back the transactions begun with [`SchemaManager::begin`], if any
+    pub async fn rollback(&self) -> Result<(), DbErr> {
+        for txn in self.take_held()? {
+            txn.rollback().await?;
+        }
+        Ok(())
+    }
+
+    fn take_held(&self) -> Result<Vec<DatabaseTransaction>, DbErr> {
+        let held = std::mem::take(&mut *self.held.lock().unwrap());
+        held.conn
+            .into_iter()
+            .chain(held.dml_conn)
+            .map(|txn| {
+                Arc::try_unwrap(txn).map_err(|_| {
+                    DbErr::Custom("The transaction is still executing a statement".to_owned())
+                })
+            })
+            .collect()
+    }
+
+    /// Set the migration the following statements are executed for, as given to the policy
+    pub(crate) fn set_policy_context(&self, context: PolicyContext) {
+        *self.policy_context.lock().unwrap() = context;
+    }
+
+    fn log_statement(&self, stmt: &Statement) {
+        if self.log_statements {
+            let context = self.policy_context.lock().unwrap();
+            debug!(
+                target: STATEMENT_TARGET,
+                backend = ?self.db_backend,
+                migration = context.migration.as_str(),
+                "{}",
+                stmt
+            );
+        }
+    }
+
+    fn track_executed(&self, stmt: &Statement) {
+        self.executed.lock().unwrap().push(stmt.to_string());
+    }
+
+    fn record_statement<T>(
+        &self,
+        stmt: Option<Statement>,
+        started: Instant,
+        res: &Result<T, DbErr>,
+    ) {
+        if let (Some(recorder), Some(stmt)) = (&self.recorder, stmt) {
+            let context = self.policy_context.lock().unwrap();
+            recorder.record(
+                &context.migration,
+                &stmt,
+                started.elapsed().as_millis() as u64,
+                res.as_ref().err(),
+            );
+        }
+    }
+
+    fn ensure_allowed(&self, sql: &str) -> Result<(), DbErr> {
+        match &self.policy {
+            Some(policy) => {
+                ensure_allowed(policy.as_ref(), &self.policy_context.lock().unwrap(), sql)
+            }
+            None => Ok(()),
+        }
     }
 
     pub async fn exec_stmt<S>(&self, stmt: S) -> Result<(), DbErr>
     where
         S: StatementBuilder,
     {
-        let builder = self.conn.get_database_backend();
-        self.conn.execute(builder.build(&stmt)).await.map(|_| ())
+        self.exec_raw(self.db_backend.build(&stmt)).await
+    }
+
+    /// Execute a statement built for the backend of the schema manager, e.g. raw SQL
+    /// that sea-query cannot express
+    pub async fn exec_raw(&self, stmt: Statement) -> Result<(), DbErr> {
+        self.exec_checked(stmt, !self.allow_lossy_alters, true)
+            .await
+    }
+
+    async fn exec_checked(
+        &self,
+        stmt: Statement,
+        check_lossy: bool,
+        checksummed: bool,
+    ) -> Result<(), DbErr> {
+        self.ensure_allowed(&stmt.sql)?;
+        if checksummed {
+            self.track_executed(&stmt);
+        }
+        if let Some(statements) = &self.dry_run {
+            statements.lock().unwrap().push(stmt);
+            return Ok(());
+        }
+        if check_lossy {
+            ensure_no_lossy_change(self.conn, &stmt.sql).await?;
+        }
+        let executor = self.executor_for(&stmt.sql);
+        if let Some(blocking_check) = &self.blocking_check {
+            // Locks held by the transaction of the schema manager are not blocking
+            blocking_check.ensure_clear(&*executor, &stmt.sql).await?;
+        }
+        self.log_statement(&stmt);
+        let conn = self.connection_for(&stmt.sql);
+        let target = dml_target(&stmt.sql);
+        let span = statement_span(self.db_backend, &stmt.sql);
+        let recorded = self.recorder.as_ref().map(|_| stmt.clone());
+        let started = Instant::now();
+        let exec = async {
+            match &self.watchdog {
+                Some(watchdog) => {
+                    let sql = stmt.sql.clone();
+                    watchdog.watch(conn, &sql, executor.execute(stmt)).await
+                }
+                None => executor.execute(stmt).await,
+            }
+        };
+        let res = match &self.throttle {
+            Some(throttle) => throttle.execute(conn, exec.instrument(span)).await,
+            None => exec.instrument(span).await,
+        };
+        self.record_statement(recorded, started, &res);
+        let res = res?;
+        if let Some((table, kind)) = target {
+            let mut activity = self.activity.lock().unwrap();
+            activity
+                .entry(table)
+                .or_default()
+                .add(kind, res.rows_affected());
+        }
+        Ok(())
+    }
+
+    /// Emit a non-fatal warning, e.g. `backfill skipped 12 rows with NULL owner`. Warnings are
+    /// reported as [`MigratorEvent::MigrationWarning`](super::MigratorEvent::MigrationWarning)
+    /// events once the migration completes, and recorded with the applied migration, see
+    /// [`get_migration_warnings`](super::get_migration_warnings).
+    pub fn warn<M>(&self, message: M)
+    where
+        M: Into<String>,
+    {
+        let warning = MigrationWarning {
+            migration: self.policy_context.lock().unwrap().migration.clone(),
+            message: message.into(),
+        };
+        warn!("{}", warning);
+        self.warnings.lock().unwrap().push(warning);
+    }
+
+    /// Warnings emitted since the migrator last collected them
+    pub fn warnings(&self) -> Vec<MigrationWarning> {
+        self.warnings.lock().unwrap().clone()
+    }
+
+    pub(crate) fn take_warnings(&self) -> Vec<MigrationWarning> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+
+    /// Rows modified per table by the statements executed through the schema manager.
+    /// Statements executed on [`SchemaManager::get_connection`] directly are not accounted for.
+    pub fn table_activity(&self) -> BTreeMap<String, TableActivity> {
+        self.activity.lock().unwrap().clone()
     }
 
     pub fn get_database_backend(&self) -> DbBackend {
-        self.conn.get_database_backend()
+        self.db_backend
     }
 
     pub fn get_connection(&self) -> &'c DbConn {
+        match self.dry_run {
+            Some(_) => &DISCONNECTED,
+            None => {
+                self.connection_taken.store(true, Ordering::Relaxed);
+                self.warn_held();
+                self.conn
+            }
+        }
+    }
+
+    /// Connection data statements are executed on, the one given to
+    /// [`SchemaManager::dml_connection`] if any
+    pub fn get_dml_connection(&self) -> &'c DbConn {
+        if self.dry_run.is_some() {
+            return &DISCONNECTED;
+        }
+        self.connection_taken.store(true, Ordering::Relaxed);
+        self.warn_held();
+        self.dml_conn.unwrap_or(self.conn)
+    }
+
+    fn warn_held(&self) {
+        if self.held.lock().unwrap().conn.is_some() {
+            warn!("Statements executed on the connection directly run outside of the transaction begun by the schema manager");
+        }
+    }
+
+    /// Connection the schema is inspected on, also in dry-run mode
+    pub(crate) fn schema_connection(&self) -> &'c DbConn {
         self.conn
     }
+
+    fn connection_for(&self, sql: &str) -> &'c DbConn {
+        match (statement_class(sql), self.dml_conn) {
+            (StatementClass::Dml, Some(dml_conn)) => dml_conn,
+            _ => self.conn,
+        }
+    }
+
+    fn executor_for(&self, sql: &str) -> Executor<'c> {
+        let held = self.held.lock().unwrap();
+        let txn = match (statement_class(sql), &held.dml_conn) {
+            (StatementClass::Dml, Some(dml_conn)) => Some(dml_conn),
+            _ => held.conn.as_ref(),
+        };
+        match txn {
+            Some(txn) => Executor::Held(txn.clone()),
+            None => Executor::Pool(self.connection_for(sql)),
+        }
+    }
+
+    /// Connection the schema is inspected on by the schema manager, the transaction begun
+    /// with [`SchemaManager::begin`] if any, which sees the changes it has not committed yet
+    fn inspector(&self) -> Executor<'c> {
+        match &self.held.lock().unwrap().conn {
+            Some(txn) => Executor::Held(txn.clone()),
+            None => Executor::Pool(self.conn),
+        }
+    }
+
+    /// Transactions begun with [`SchemaManager::begin`], on the connection and on the data
+    /// connection, e.g. to apply session settings on both
+    pub(crate) fn held_connections(&self) -> Vec<Arc<DatabaseTransaction>> {
+        let held = self.held.lock().unwrap();
+        held.conn
+            .iter()
+            .chain(held.dml_conn.iter())
+            .cloned()
+            .collect()
+    }
+
+    fn ensure_online(&self) -> Result<(), DbErr> {
+        match self.is_offline() {
+            true => Err(DbErr::Custom(
+                "Schema inspection is unavailable without a database connection".to_owned(),
+            )),
+            false => Ok(()),
+        }
+    }
 }
 
 /// Schema Creation
 impl<'c> SchemaManager<'c> {
     pub async fn create_table(&self, stmt: TableCreateStatement) -> Result<(), DbErr> {
-        self.exec_stmt(stmt).await
+        self.exec_create(stmt).await
     }
 
     pub async fn create_index(&self, stmt: IndexCreateStatement) -> Result<(), DbErr> {
-        self.exec_stmt(stmt).await
+        self.exec_create(stmt).await
     }
 
     pub async fn create_foreign_key(&self, stmt: ForeignKeyCreateStatement) -> Result<(), DbErr> {
-        self.exec_stmt(stmt).await
+        self.exec_create(stmt).await
     }
 
     pub async fn create_type(&self, stmt: TypeCreateStatement) -> Result<(), DbErr> {
-        self.exec_stmt(stmt).await
+        self.exec_create(stmt).await
+    }
+
+    /// Execute a statement creating an object, then tag the object if configured
+    async fn exec_create<S>(&self, stmt: S) -> Result<(), DbErr>
+    where
+        S: StatementBuilder,
+    {
+        let stmt = self.db_backend.build(&stmt);
+        let comment = match self.tag_managed_objects && self.db_backend == DbBackend::Postgres {
+            true => {
+                let migration = self.policy_context.lock().unwrap().migration.clone();
+                comment_statement(&stmt.sql, &managed_comment(&migration))
+            }
+            false => None,
+        };
+        self.exec_raw(stmt).await?;
+        match comment {
+            Some(sql) => {
+                let stmt = Statement::from_string(self.db_backend, sql);
+                self.exec_checked(stmt, false, false).await
+            }
+            None => Ok(()),
+        }
     }
 }
 
@@ -60,6 +577,13 @@ impl<'c> SchemaManager<'c> {
         self.exec_stmt(stmt).await
     }
 
+    /// Alter a table even if MySQL may silently truncate or convert stored values, e.g. when
+    /// shrinking a `VARCHAR` or changing its charset. Such changes fail with [`SchemaManager::alter_table`].
+    pub async fn force_alter_table(&self, stmt: TableAlterStatement) -> Result<(), DbErr> {
+        self.exec_checked(self.db_backend.build(&stmt), false, true)
+            .await
+    }
+
     pub async fn drop_table(&self, stmt: TableDropStatement) -> Result<(), DbErr> {
         self.exec_stmt(stmt).await
     }
@@ -89,21 +613,99 @@ impl<'c> SchemaManager<'c> {
     }
 }
 
+/// Data Manipulation
+impl<'c> SchemaManager<'c> {
+    /// Execute a query, e.g. `INSERT ... RETURNING`, and return the first row.
+    ///
+    /// Values bound to the statement, including those of `ON CONFLICT` clauses, are passed along.
+    /// MySQL has no `RETURNING` clause, use [`SchemaManager::exec_stmt`] with a follow-up query there.
+    /// In dry-run mode the statement is recorded and no row is returned.
+    pub async fn query_one<S>(&self, stmt: S) -> Result<Option<QueryResult>, DbErr>
+    where
+        S: StatementBuilder,
+    {
+        self.query_one_raw(self.db_backend.build(&stmt)).await
+    }
+
+    /// Execute a raw query and return the first row. In dry-run mode the statement is recorded and no row is returned.
+    pub async fn query_one_raw(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        self.ensure_allowed(&stmt.sql)?;
+        if let Some(statements) = &self.dry_run {
+            statements.lock().unwrap().push(stmt);
+            return Ok(None);
+        }
+        self.log_statement(&stmt);
+        let conn = self.connection_for(&stmt.sql);
+        let executor = self.executor_for(&stmt.sql);
+        let recorded = self.recorder.as_ref().map(|_| stmt.clone());
+        let started = Instant::now();
+        let res = match &self.throttle {
+            Some(throttle) => throttle.execute(conn, executor.query_one(stmt)).await,
+            None => executor.query_one(stmt).await,
+        };
+        self.record_statement(recorded, started, &res);
+        res
+    }
+
+    /// Expression binding `value` to a column of `column_type` with the registered codec, if any,
+    /// e.g. as a value of an insert or an update
+    pub fn encode_value(&self, column_type: &str, value: Value) -> SimpleExpr {
+        self.value_codecs
+            .encode(self.db_backend, column_type, value)
+    }
+
+    /// Expression selecting `column` of `column_type` with the registered codec, if any,
+    /// as a value that can be read from the returned [`QueryResult`]
+    pub fn decode_column(&self, column_type: &str, column: &str) -> SimpleExpr {
+        self.value_codecs
+            .decode(self.db_backend, column_type, column)
+    }
+
+    /// Execute a query and return all rows. In dry-run mode the statement is recorded and no row is returned.
+    pub async fn query_all<S>(&self, stmt: S) -> Result<Vec<QueryResult>, DbErr>
+    where
+        S: StatementBuilder,
+    {
+        self.query_all_raw(self.db_backend.build(&stmt)).await
+    }
+
+    /// Execute a raw query and return all rows. In dry-run mode the statement is recorded and no row is returned.
+    pub async fn query_all_raw(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        self.ensure_allowed(&stmt.sql)?;
+        if let Some(statements) = &self.dry_run {
+            statements.lock().unwrap().push(stmt);
+            return Ok(Vec::new());
+        }
+        self.log_statement(&stmt);
+        let conn = self.connection_for(&stmt.sql);
+        let executor = self.executor_for(&stmt.sql);
+        let recorded = self.recorder.as_ref().map(|_| stmt.clone());
+        let started = Instant::now();
+        let res = match &self.throttle {
+            Some(throttle) => throttle.execute(conn, executor.query_all(stmt)).await,
+            None => executor.query_all(stmt).await,
+        };
+        self.record_statement(recorded, started, &res);
+        res
+    }
+}
+
 /// Schema Inspection
 impl<'c> SchemaManager<'c> {
     pub async fn has_table<T>(&self, table: T) -> Result<bool, DbErr>
     where
         T: AsRef<str>,
     {
+        self.ensure_online()?;
         let mut stmt = Query::select();
-        let mut subquery = query_tables(self.conn);
+        let mut subquery = schema_probe(self.db_backend).query_tables();
         subquery.cond_where(Expr::col(Alias::new("table_name")).eq(table.as_ref()));
         stmt.expr_as(Expr::cust("COUNT(*)"), Alias::new("rows"))
             .from_subquery(subquery, Alias::new("subquery"));
 
         let builder = self.conn.get_database_backend();
         let res = self
-            .conn
+            .inspector()
             .query_one(builder.build(&stmt))
             .await?
             .ok_or_else(|| DbErr::Custom("Fail to check table exists".to_owned()))?;
@@ -117,7 +719,8 @@ impl<'c> SchemaManager<'c> {
         T: AsRef<str>,
         C: AsRef<str>,
     {
-        let db_backend = self.conn.get_database_backend();
+        self.ensure_online()?;
+        let db_backend = self.db_backend;
         let found = match db_backend {
             DbBackend::MySql | DbBackend::Postgres => {
                 let schema_name = match db_backend {
@@ -139,7 +742,7 @@ impl<'c> SchemaManager<'c> {
                     );
 
                 let res = self
-                    .conn
+                    .inspector()
                     .query_one(db_backend.build(&stmt))
                     .await?
                     .ok_or_else(|| DbErr::Custom("Fail to check column exists".to_owned()))?;
@@ -151,7 +754,7 @@ impl<'c> SchemaManager<'c> {
                     db_backend,
                     format!("PRAGMA table_info({})", table.as_ref()),
                 );
-                let results = self.conn.query_all(stmt).await?;
+                let results = self.inspector().query_all(stmt).await?;
                 let mut found = false;
                 for res in results {
                     let name: String = res.try_get("", "name")?;
@@ -164,4 +767,120 @@ impl<'c> SchemaManager<'c> {
         };
         Ok(found)
     }
+
+    /// Check the existence of several tables in a single query
+    pub async fn has_tables(&self, tables: &[&str]) -> Result<HashMap<String, bool>, DbErr> {
+        self.ensure_online()?;
+        let mut found: HashMap<String, bool> = tables
+            .iter()
+            .map(|table| (table.to_string(), false))
+            .collect();
+        if tables.is_empty() {
+            return Ok(found);
+        }
+        let mut stmt = Query::select();
+        stmt.column(Alias::new("table_name"))
+            .from_subquery(
+                schema_probe(self.db_backend).query_tables(),
+                Alias::new("subquery"),
+            )
+            .and_where(Expr::col(Alias::new("table_name")).is_in(tables.iter().copied()));
+        for row in self
+            .inspector()
+            .query_all(self.db_backend.build(&stmt))
+            .await?
+        {
+            found.insert(row.try_get("", "table_name")?, true);
+        }
+        Ok(found)
+    }
+
+    /// Check the existence of several columns of a table in a single query
+    pub async fn has_columns<T>(
+        &self,
+        table: T,
+        columns: &[&str],
+    ) -> Result<HashMap<String, bool>, DbErr>
+    where
+        T: AsRef<str>,
+    {
+        self.ensure_online()?;
+        let mut found: HashMap<String, bool> = columns
+            .iter()
+            .map(|column| (column.to_string(), false))
+            .collect();
+        let stmt = schema_probe(self.db_backend).query_columns(table.as_ref());
+        for row in self.inspector().query_all(stmt).await? {
+            let name: String = row.try_get("", "column_name")?;
+            if let Some(exists) = found.get_mut(&name) {
+                *exists = true;
+            }
+        }
+        Ok(found)
+    }
+
+    /// Read the type, nullability and default of the columns of a table, in ordinal order
+    pub async fn table_columns<T>(&self, table: T) -> Result<Vec<ColumnInfo>, DbErr>
+    where
+        T: AsRef<str>,
+    {
+        self.ensure_online()?;
+        probe_columns(&*self.inspector(), table.as_ref()).await
+    }
+
+    /// Read the type, nullability and default of a column, `None` if the column does not exist
+    pub async fn column_info<T, C>(&self, table: T, column: C) -> Result<Option<ColumnInfo>, DbErr>
+    where
+        T: AsRef<str>,
+        C: AsRef<str>,
+    {
+        self.ensure_online()?;
+        Ok(probe_columns(&*self.inspector(), table.as_ref())
+            .await?
+            .into_iter()
+            .find(|info| info.name == column.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::sea_query::{OnConflict, Value};
+
+    #[async_std::test]
+    async fn test_upsert_returning_values() {
+        for db_backend in [DbBackend::MySql, DbBackend::Postgres, DbBackend::Sqlite] {
+            let manager = SchemaManager::offline(db_backend);
+            let mut stmt = Query::insert();
+            stmt.into_table(Alias::new("cake"))
+                .columns([Alias::new("id"), Alias::new("name")])
+                .values_panic([1.into(), "Cheese".into()])
+                .on_conflict(
+                    OnConflict::column(Alias::new("id"))
+                        .update_column(Alias::new("name"))
+                        .to_owned(),
+                );
+            if db_backend == DbBackend::Postgres {
+                stmt.returning_col(Alias::new("id"));
+            }
+            assert!(manager.query_one(stmt).await.unwrap().is_none());
+
+            let statements = manager.take_statements();
+            assert_eq!(
+                statements[0].values.as_ref().map(|values| values.0.clone()),
+                Some(vec![
+                    Value::Int(Some(1)),
+                    Value::String(Some(Box::new("Cheese".to_owned())))
+                ])
+            );
+            let sql = &statements[0].sql;
+            match db_backend {
+                DbBackend::MySql => assert!(sql.contains("ON DUPLICATE KEY UPDATE")),
+                DbBackend::Postgres => {
+                    assert!(sql.contains("ON CONFLICT") && sql.ends_with(r#"RETURNING "id""#))
+                }
+                DbBackend::Sqlite => assert!(sql.contains("ON CONFLICT")),
+            }
+        }
+    }
 }
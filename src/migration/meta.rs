@@ -0,0 +1,73 @@
+use super::{seaql_schema_meta, SchemaSnapshot};
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, DbConn, DbErr, EntityTrait};
+use std::collections::BTreeMap;
+
+/// Version of sea-schema that last ran the migrator
+pub const META_CRATE_VERSION: &str = "crate_version";
+/// Id of the last migrator run which applied or rolled back a migration
+pub const META_LAST_RUN_ID: &str = "last_run_id";
+/// Unix timestamp of the last migrator run which applied or rolled back a migration
+pub const META_LAST_RUN_AT: &str = "last_run_at";
+/// Hash of the schema snapshot taken at the end of the last migrator run which applied or
+/// rolled back a migration
+pub const META_SCHEMA_HASH: &str = "schema_hash";
+
+/// Read all entries of the `seaql_schema_meta` table
+pub async fn get_schema_meta(db: &DbConn) -> Result<BTreeMap<String, String>, DbErr> {
+    Ok(seaql_schema_meta::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|model| (model.key, model.value))
+        .collect())
+}
+
+/// Read an entry of the `seaql_schema_meta` table
+pub async fn get_schema_meta_value(db: &DbConn, key: &str) -> Result<Option<String>, DbErr> {
+    Ok(seaql_schema_meta::Entity::find_by_id(key.to_owned())
+        .one(db)
+        .await?
+        .map(|model| model.value))
+}
+
+/// Insert or update an entry of the `seaql_schema_meta` table
+pub async fn set_schema_meta_value<C>(db: &C, key: &str, value: &str) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    let exists = seaql_schema_meta::Entity::find_by_id(key.to_owned())
+        .one(db)
+        .await?
+        .is_some();
+    let model = seaql_schema_meta::ActiveModel {
+        key: ActiveValue::Set(key.to_owned()),
+        value: ActiveValue::Set(value.to_owned()),
+    };
+    match exists {
+        true => model.update(db).await.map(|_| ()),
+        false => model.insert(db).await.map(|_| ()),
+    }
+}
+
+/// Delete an entry of the `seaql_schema_meta` table, if any
+pub async fn delete_schema_meta_value(db: &DbConn, key: &str) -> Result<(), DbErr> {
+    seaql_schema_meta::Entity::delete_by_id(key.to_owned())
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+/// Hash the tables and columns of the current schema, which changes whenever a table or
+/// column is added, dropped, renamed or retyped
+pub async fn schema_snapshot_hash(db: &DbConn) -> Result<String, DbErr> {
+    Ok(SchemaSnapshot::discover(db).await?.hash())
+}
+
+/// Update the `seaql_schema_meta` table at the end of a migrator run
+pub(crate) async fn record_run(db: &DbConn, run_id: &str, run_at: i64) -> Result<(), DbErr> {
+    set_schema_meta_value(db, META_CRATE_VERSION, env!("CARGO_PKG_VERSION")).await?;
+    set_schema_meta_value(db, META_LAST_RUN_ID, run_id).await?;
+    set_schema_meta_value(db, META_LAST_RUN_AT, &run_at.to_string()).await?;
+    let schema_hash = schema_snapshot_hash(db).await?;
+    set_schema_meta_value(db, META_SCHEMA_HASH, &schema_hash).await
+}
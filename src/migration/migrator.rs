@@ -1,16 +1,25 @@
-use super::{seaql_migrations, MigrationTrait, SchemaManager};
-use sea_orm::sea_query::{
-    Alias, Expr, ForeignKey, IntoTableRef, Query, SelectStatement, SimpleExpr, Table,
-};
-use sea_orm::{
-    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbBackend, DbConn,
-    DbErr, EntityTrait, QueryFilter, QueryOrder, Schema, Statement,
+use super::{
+    append_only_violations, apply_session_settings, check_adoption, default_lints,
+    destructive_kind, ensure_fast_only, ensure_not_in_run, enter_run, migration_files,
+    migration_span, pending_maintenance, plan_migrations, plan_offline, report_maintenance,
+    report_warnings, revert_session_settings, run_lints, run_span, seaql_migrations,
+    set_pending_maintenance, sort_records, store_warnings, AdoptionReport, AppliedAtFormat,
+    BlockingCheck, ChecksumOptions, DestructiveKind, DestructiveStatement, DropPlan, DurationClass,
+    HistoryStore, InRun, Lexicographic, Lint, LintIssue, Maintenance, MigrationTrait,
+    MigratorEvent, OrphanReport, Plan, PlannedMigration, PolicyContext, SchemaManager,
+    SchemaSnapshot, SessionSetting, StatementPolicy, StatementRecorder, TableHistoryStore,
+    Throttle, ValueCodecs, VersionOrder, Watchdog,
 };
+use sea_orm::{ConnectionTrait, Database, DbBackend, DbConn, DbErr, Statement};
+use std::collections::HashSet;
 use std::fmt::Display;
-use std::time::SystemTime;
-use tracing::info;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+use tracing::{info, warn, Instrument};
+use uuid::Uuid;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 /// Status of migration
 pub enum MigrationStatus {
     /// Not yet applied
@@ -19,11 +28,47 @@ pub enum MigrationStatus {
     Applied,
 }
 
+/// What the migrator does when [`MigratorTrait::migrations`] is empty, which in production
+/// almost always means the migrations were not compiled in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyMigrations {
+    /// Proceed silently, e.g. for a project without any migration yet
+    Ok,
+    /// Proceed, reporting a [`MigratorEvent::NoMigrations`] event
+    #[default]
+    Warn,
+    /// Report a [`MigratorEvent::NoMigrations`] event and fail the run
+    Error,
+}
+
+/// Counts of the applied and pending migrations, see [`MigratorTrait::summary`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationSummary {
+    pub applied: usize,
+    pub pending: usize,
+    /// Version of the last applied migration and when it was applied, as a unix timestamp
+    pub last_applied: Option<(String, i64)>,
+}
+
 pub struct Migration {
     migration: Box<dyn MigrationTrait>,
     status: MigrationStatus,
 }
 
+impl Migration {
+    pub fn name(&self) -> &str {
+        self.migration.name()
+    }
+
+    pub fn status(&self) -> MigrationStatus {
+        self.status
+    }
+
+    pub fn duration_class(&self) -> DurationClass {
+        self.migration.duration_class()
+    }
+}
+
 impl Display for MigrationStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let status = match self {
@@ -40,48 +85,259 @@ pub trait MigratorTrait: Send {
     /// Vector of migrations in time sequence
     fn migrations() -> Vec<Box<dyn MigrationTrait>>;
 
+    /// Sort [`MigratorTrait::migrations`] by version name instead of trusting the order of
+    /// registration, so that swapped registrations do not apply migrations out of sequence
+    fn sort_by_version() -> bool {
+        false
+    }
+
+    /// Order of the versions, sorting the migrations if [`MigratorTrait::sort_by_version`] is
+    /// enabled and the applied migrations read from the history store, e.g. [`Numeric`] for
+    /// legacy versions such as `9_add_index` and `10_drop_column`
+    fn version_order() -> Box<dyn VersionOrder> {
+        Box::new(Lexicographic)
+    }
+
+    /// The migrations in the order they are applied, sorted by version name if
+    /// [`MigratorTrait::sort_by_version`] is enabled
+    fn ordered_migrations() -> Result<Vec<Box<dyn MigrationTrait>>, DbErr> {
+        match Self::sort_by_version() {
+            true => sort_by_version_order(Self::migrations(), &*Self::version_order()),
+            false => Ok(Self::migrations()),
+        }
+    }
+
+    /// How `seaql_migrations.applied_at` is stored. Existing tables are converted on `install`.
+    fn applied_at_format() -> AppliedAtFormat {
+        AppliedAtFormat::default()
+    }
+
+    /// Where the applied migrations are persisted, the `seaql_migrations` table by default
+    fn history_store() -> Box<dyn HistoryStore> {
+        Box::new(TableHistoryStore::new(Self::applied_at_format()))
+    }
+
+    /// Build version of the application, recorded alongside each applied migration
+    fn app_version() -> Option<String> {
+        None
+    }
+
+    /// Release batch recorded alongside each applied migration, e.g. the deploy tag,
+    /// so that a release can be rolled back as a whole with [`MigratorTrait::down_batch`]
+    fn batch() -> Option<String> {
+        None
+    }
+
+    /// Who applies the migrations, recorded alongside each applied migration, e.g. the deploy
+    /// user or the `USER` environment variable. Nothing is recorded by default.
+    fn applied_by() -> Option<String> {
+        None
+    }
+
+    /// Heartbeat and watchdog settings applied to statements executed by migrations
+    fn watchdog() -> Option<Watchdog> {
+        None
+    }
+
+    /// Check for long-running transactions holding conflicting locks before executing lock-heavy DDL
+    fn blocking_check() -> Option<BlockingCheck> {
+        None
+    }
+
+    /// Execution profile pausing between statements and migrations, for heavy migrations
+    /// against busy production databases
+    fn throttle() -> Option<Throttle> {
+        None
+    }
+
+    /// OpenTelemetry tracer the CLI exports the spans of runs, migrations and statements with,
+    /// e.g. built by an OTLP pipeline installed by the application
+    #[cfg(feature = "with-opentelemetry")]
+    fn tracer() -> Option<opentelemetry::sdk::trace::Tracer> {
+        None
+    }
+
+    /// Claim every migration in the history store before applying it, so that instances applying
+    /// the migrations concurrently stop at the first migration claimed by another one, leaving
+    /// it and the following ones to that instance, complementing advisory locks or substituting
+    /// for them on backends lacking them. A migration failing to apply is released, but one
+    /// interrupted by a crash stays claimed and has to be removed from the history manually.
+    /// Otherwise, a migration already recorded once applied is reported as applied concurrently.
+    fn claim_before_apply() -> bool {
+        false
+    }
+
+    /// Report, and optionally enqueue, maintenance of the tables heavily modified by a run
+    fn maintenance() -> Option<Maintenance> {
+        Some(Maintenance::default())
+    }
+
+    /// Encoders and decoders of backend-specific column types, available to data migrations
+    /// through [`SchemaManager::encode_value`] and [`SchemaManager::decode_column`]
+    fn value_codecs() -> ValueCodecs {
+        ValueCodecs::default()
+    }
+
+    /// Apply MySQL `ALTER TABLE` statements which may truncate or convert stored values instead of
+    /// failing, as if every migration used [`SchemaManager::force_alter_table`]
+    fn allow_lossy_alters() -> bool {
+        false
+    }
+
+    /// Connection string of a restricted user the data statements and queries of migrations
+    /// are executed as, while the schema changes use the connection the migrator was given,
+    /// see [`SchemaManager::dml_connection`]. Defaults to the `DML_DATABASE_URL` environment variable.
+    fn dml_database_url() -> Option<String> {
+        std::env::var("DML_DATABASE_URL").ok()
+    }
+
+    /// Rules every statement executed by the migrations has to comply with, e.g.
+    /// [`StatementRules`](super::StatementRules) denying `GRANT` or `DROP TABLE` outside of tagged migrations
+    fn statement_policy() -> Option<Box<dyn StatementPolicy>> {
+        None
+    }
+
+    /// Log every statement executed by the migrations at debug level with the target
+    /// [`STATEMENT_TARGET`](super::STATEMENT_TARGET), e.g. to reconstruct what ran where the
+    /// logs of the database are not accessible
+    fn log_statements() -> bool {
+        false
+    }
+
+    /// File every statement executed by the migrations of a run is recorded to, with its timing
+    /// and outcome, e.g. to be attached to a bug report and replayed with the `replay` subcommand.
+    /// Runs are appended to the file. Defaults to the `SEA_SCHEMA_RECORD` environment variable.
+    fn record_statements() -> Option<PathBuf> {
+        std::env::var_os("SEA_SCHEMA_RECORD").map(PathBuf::from)
+    }
+
+    /// Comment the tables, indexes, foreign keys and types created through the schema manager
+    /// on Postgres with [`managed_comment`](super::managed_comment), e.g.
+    /// `managed-by: sea-schema 0.7.1 m20220101_000001_create_table`, so that objects created
+    /// outside the migrations can be told apart later
+    fn tag_managed_objects() -> bool {
+        false
+    }
+
+    /// Serialized [`SchemaSnapshot`] embedded in the binary, e.g.
+    /// `Some(include_str!("../schema.snapshot"))` with the output of the `snapshot` subcommand,
+    /// so that [`MigratorTrait::schema_snapshot`] does not have to discover the schema
+    fn schema_cache() -> Option<&'static str> {
+        None
+    }
+
+    /// What to do when no migration is registered
+    fn empty_migrations() -> EmptyMigrations {
+        EmptyMigrations::default()
+    }
+
+    /// Session-level settings applied while every migration runs.
+    ///
+    /// A setting only takes effect on the connection executing it, and `DbConn` is a pool. With
+    /// settings, every migration runs in a transaction, see [`SchemaManager::begin`], holding
+    /// a single connection on which the settings are applied, and reverted before the connection
+    /// returns to the pool. The migration is then atomic on Postgres and SQLite, where statements
+    /// that cannot run in a transaction, e.g. `CREATE INDEX CONCURRENTLY`, fail. Statements
+    /// executed on [`SchemaManager::get_connection`] directly are neither bound by the settings
+    /// nor see the uncommitted changes.
+    fn session_settings() -> Vec<SessionSetting> {
+        Vec::new()
+    }
+
+    /// Refuse to run the migrations of the [`DurationClass::Slow`] class unless
+    /// [`MigratorTrait::acknowledge_slow`], e.g. where migrations are applied automatically at boot
+    fn fast_only() -> bool {
+        false
+    }
+
+    /// Run slow migrations although the migrator is [`MigratorTrait::fast_only`]. Defaults to
+    /// the `ACKNOWLEDGE_SLOW_MIGRATIONS` environment variable being `true` or `1`.
+    fn acknowledge_slow() -> bool {
+        matches!(
+            std::env::var("ACKNOWLEDGE_SLOW_MIGRATIONS").as_deref(),
+            Ok("true" | "1")
+        )
+    }
+
     /// Get list of migrations wrapped in `Migration` struct
-    fn get_migration_files() -> Vec<Migration> {
-        Self::migrations()
+    fn get_migration_files() -> Result<Vec<Migration>, DbErr> {
+        Ok(Self::ordered_migrations()?
             .into_iter()
             .map(|migration| Migration {
                 migration,
                 status: MigrationStatus::Pending,
             })
-            .collect()
+            .collect())
     }
 
-    /// Get list of applied migrations from database
+    /// Migration files of `dir` whose version is not registered in [`MigratorTrait::migrations`],
+    /// e.g. checked by a test of the migration crate
+    fn unregistered_files<P>(dir: P) -> Result<Vec<String>, DbErr>
+    where
+        P: AsRef<Path>,
+    {
+        let files = migration_files(dir.as_ref()).map_err(|err| {
+            DbErr::Custom(format!(
+                "Fail to list the migrations in '{}': {}",
+                dir.as_ref().display(),
+                err
+            ))
+        })?;
+        let migrations = Self::migrations();
+        Ok(files
+            .into_iter()
+            .filter(|file| !migrations.iter().any(|migration| migration.name() == file))
+            .collect())
+    }
+
+    /// Get list of applied migrations from the history store
     async fn get_migration_models(db: &DbConn) -> Result<Vec<seaql_migrations::Model>, DbErr> {
         Self::install(db).await?;
-        seaql_migrations::Entity::find()
-            .order_by_asc(seaql_migrations::Column::Version)
-            .all(db)
-            .await
+        let applied = Self::history_store().applied(db).await?;
+        Ok(sort_records(applied, &*Self::version_order()))
     }
 
     /// Get list of migrations with status
     async fn get_migration_with_status(db: &DbConn) -> Result<Vec<Migration>, DbErr> {
-        Self::install(db).await?;
-        let mut migration_files = Self::get_migration_files();
         let migration_models = Self::get_migration_models(db).await?;
-        for (i, migration_model) in migration_models.into_iter().enumerate() {
-            if let Some(migration_file) = migration_files.get_mut(i) {
-                if migration_file.migration.name() == migration_model.version.as_str() {
-                    migration_file.status = MigrationStatus::Applied;
-                } else {
-                    return Err(DbErr::Custom(format!("Migration mismatch: applied migration != migration file, '{0}' != '{1}'\nMigration '{0}' has been applied but its corresponding migration file is missing.", migration_file.migration.name(), migration_model.version)));
-                }
-            } else {
-                return Err(DbErr::Custom(format!("Migration file of version '{}' is missing, this migration has been applied but its file is missing", migration_model.version)));
+        with_status(Self::get_migration_files()?, &migration_models)
+    }
+
+    /// Get list of migrations with status without installing or upgrading the history store,
+    /// reading the history written by earlier or later versions of the migrator alike
+    async fn read_migration_with_status(db: &DbConn) -> Result<Vec<Migration>, DbErr> {
+        let migration_models = Self::history_store().read_applied(db).await?;
+        let migration_models = sort_records(migration_models, &*Self::version_order());
+        with_status(Self::get_migration_files()?, &migration_models)
+    }
+
+    /// Count the applied and pending migrations with at most three queries, e.g. for a health
+    /// endpoint polled frequently. The history store is neither installed nor checked against
+    /// the migration files, see [`MigratorTrait::status`] for that. The whole history is read
+    /// unless the [`MigratorTrait::version_order`] is lexicographic.
+    async fn summary(db: &DbConn) -> Result<MigrationSummary, DbErr> {
+        let order = Self::version_order();
+        let registered: Vec<String> = Self::migrations()
+            .iter()
+            .map(|migration| migration.name().to_owned())
+            .collect();
+        match order.is_lexicographic() {
+            true => Self::history_store().summary(db, &registered).await,
+            false => {
+                let applied = sort_records(Self::history_store().applied(db).await?, &*order);
+                Ok(MigrationSummary {
+                    applied: applied.len(),
+                    pending: count_pending(&registered, &applied),
+                    last_applied: applied
+                        .last()
+                        .map(|model| (model.version.clone(), model.applied_at)),
+                })
             }
         }
-        Ok(migration_files)
     }
 
     /// Get list of pending migrations
     async fn get_pending_migrations(db: &DbConn) -> Result<Vec<Migration>, DbErr> {
-        Self::install(db).await?;
         Ok(Self::get_migration_with_status(db)
             .await?
             .into_iter()
@@ -91,7 +347,6 @@ pub trait MigratorTrait: Send {
 
     /// Get list of applied migrations
     async fn get_applied_migrations(db: &DbConn) -> Result<Vec<Migration>, DbErr> {
-        Self::install(db).await?;
         Ok(Self::get_migration_with_status(db)
             .await?
             .into_iter()
@@ -99,22 +354,73 @@ pub trait MigratorTrait: Send {
             .collect())
     }
 
-    /// Create migration table `seaql_migrations` in the database
+    /// Check that the database can be taken over from, and handed back to, the upstream
+    /// `sea-orm-migration`: `seaql_migrations` has the `version` and `applied_at` columns it
+    /// reads, `applied_at` is stored in unix seconds, no column unknown to it is required, and
+    /// the applied versions are the migrations registered, in order. Unless an incompatibility
+    /// is reported, the history store is then installed, only adding nullable columns.
+    async fn adopt_from_sea_orm(db: &DbConn) -> Result<AdoptionReport, DbErr> {
+        let registered: Vec<String> = Self::get_migration_files()?
+            .iter()
+            .map(|migration| migration.name().to_owned())
+            .collect();
+        let report = check_adoption(db, &registered, Self::applied_at_format()).await?;
+        for incompatibility in report.incompatibilities.iter() {
+            warn!("Cannot adopt from sea-orm-migration: {}", incompatibility);
+        }
+        if report.is_compatible() {
+            Self::install(db).await?;
+        }
+        Ok(report)
+    }
+
+    /// Prepare the history store, by default creating the migration table `seaql_migrations`
+    /// and metadata table `seaql_schema_meta` in the database
     async fn install(db: &DbConn) -> Result<(), DbErr> {
-        let builder = db.get_database_backend();
-        let schema = Schema::new(builder);
-        let mut stmt = schema.create_table_from_entity(seaql_migrations::Entity);
-        stmt.if_not_exists();
-        db.execute(builder.build(&stmt)).await.map(|_| ())
+        Self::history_store().install(db).await
+    }
+
+    /// Apply [`MigratorTrait::empty_migrations`] if no migration is registered
+    fn check_empty_migrations(run_id: &str, command: &str) -> Result<(), DbErr> {
+        let empty_migrations = Self::empty_migrations();
+        if empty_migrations == EmptyMigrations::Ok || !Self::migrations().is_empty() {
+            return Ok(());
+        }
+        MigratorEvent::NoMigrations {
+            run_id: run_id.to_owned(),
+            command: command.to_owned(),
+        }
+        .emit();
+        match empty_migrations {
+            EmptyMigrations::Error => Err(DbErr::Custom(
+                "No migration is registered, the migrations may not have been compiled in"
+                    .to_owned(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Connect to [`MigratorTrait::dml_database_url`], if any
+    async fn connect_dml() -> Result<Option<DbConn>, DbErr> {
+        let url = match Self::dml_database_url() {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        info!("Executing data statements on a separate connection");
+        Ok(Some(Database::connect(&url).await?))
     }
 
     /// Drop all tables from the database, then reapply all migrations
     async fn fresh(db: &DbConn) -> Result<(), DbErr> {
+        ensure_not_in_run(db, "fresh").await?;
         Self::install(db).await?;
         let db_backend = db.get_database_backend();
 
+        // Drop views, triggers, events, foreign keys forming a cycle, tables and types
+        let plan = DropPlan::discover(db).await?;
+
         // Temporarily disable the foreign key check
-        if db_backend == DbBackend::Sqlite {
+        if plan.disable_foreign_key_check {
             info!("Disabling foreign key check");
             db.execute(Statement::from_string(
                 db_backend,
@@ -124,61 +430,15 @@ pub trait MigratorTrait: Send {
             info!("Foreign key check disabled");
         }
 
-        // Drop all foreign keys
-        if db_backend == DbBackend::MySql {
-            info!("Dropping all foreign keys");
-            let mut stmt = Query::select();
-            stmt.columns([Alias::new("TABLE_NAME"), Alias::new("CONSTRAINT_NAME")])
-                .from((
-                    Alias::new("information_schema"),
-                    Alias::new("table_constraints"),
-                ))
-                .cond_where(
-                    Condition::all()
-                        .add(
-                            Expr::expr(get_current_schema(db)).equals(
-                                Alias::new("table_constraints"),
-                                Alias::new("table_schema"),
-                            ),
-                        )
-                        .add(Expr::expr(Expr::value("FOREIGN KEY")).equals(
-                            Alias::new("table_constraints"),
-                            Alias::new("constraint_type"),
-                        )),
-                );
-            let rows = db.query_all(db_backend.build(&stmt)).await?;
-            for row in rows.into_iter() {
-                let constraint_name: String = row.try_get("", "CONSTRAINT_NAME")?;
-                let table_name: String = row.try_get("", "TABLE_NAME")?;
-                info!(
-                    "Dropping foreign key '{}' from table '{}'",
-                    constraint_name, table_name
-                );
-                let mut stmt = ForeignKey::drop();
-                stmt.table(Alias::new(table_name.as_str()))
-                    .name(constraint_name.as_str());
-                db.execute(db_backend.build(&stmt)).await?;
-                info!("Foreign key '{}' has been dropped", constraint_name);
-            }
-            info!("All foreign keys dropped");
-        }
-
-        // Drop all tables
-        let stmt = query_tables(db);
-        let rows = db.query_all(db_backend.build(&stmt)).await?;
-        for row in rows.into_iter() {
-            let table_name: String = row.try_get("", "table_name")?;
-            info!("Dropping table '{}'", table_name);
-            let mut stmt = Table::drop();
-            stmt.table(Alias::new(table_name.as_str()))
-                .if_exists()
-                .cascade();
-            db.execute(db_backend.build(&stmt)).await?;
-            info!("Table '{}' has been dropped", table_name);
+        for drop in plan.drops.iter() {
+            info!("Dropping {} '{}'", drop.object, drop.name);
+            db.execute(Statement::from_string(db_backend, drop.sql.clone()))
+                .await?;
+            info!("Dropped {} '{}'", drop.object, drop.name);
         }
 
         // Restore the foreign key check
-        if db_backend == DbBackend::Sqlite {
+        if plan.disable_foreign_key_check {
             info!("Restoring foreign key check");
             db.execute(Statement::from_string(
                 db_backend,
@@ -188,10 +448,205 @@ pub trait MigratorTrait: Send {
             info!("Foreign key check restored");
         }
 
+        // The history may be kept outside the database
+        Self::history_store().clear(db).await?;
+
         // Reapply all migrations
         Self::up(db, None).await
     }
 
+    /// Preview `fresh`: the objects that would be dropped and the migrations that would be reapplied
+    async fn fresh_plan(db: &DbConn) -> Result<Plan, DbErr> {
+        Ok(Plan {
+            drops: DropPlan::discover(db).await?.drops,
+            applies: plan_migrations(db, Self::ordered_migrations()?, false).await,
+            ..Default::default()
+        })
+    }
+
+    /// Preview `refresh`: the migrations that would be rolled back and then reapplied
+    async fn refresh_plan(db: &DbConn) -> Result<Plan, DbErr> {
+        Ok(Plan {
+            applies: plan_migrations(db, Self::ordered_migrations()?, false).await,
+            ..Self::reset_plan(db).await?
+        })
+    }
+
+    /// Preview `up`: the pending migrations that would be applied
+    async fn up_plan(db: &DbConn) -> Result<Plan, DbErr> {
+        let migrations = Self::read_migration_with_status(db)
+            .await?
+            .into_iter()
+            .filter(|file| file.status == MigrationStatus::Pending)
+            .map(|Migration { migration, .. }| migration)
+            .collect();
+        Ok(Plan {
+            applies: plan_migrations(db, migrations, false).await,
+            ..Default::default()
+        })
+    }
+
+    /// Preview `reset`: the migrations that would be rolled back
+    async fn reset_plan(db: &DbConn) -> Result<Plan, DbErr> {
+        let migrations = Self::read_migration_with_status(db)
+            .await?
+            .into_iter()
+            .filter(|file| file.status == MigrationStatus::Applied)
+            .rev()
+            .map(|Migration { migration, .. }| migration)
+            .collect();
+        Ok(Plan {
+            rollbacks: plan_migrations(db, migrations, true).await,
+            ..Default::default()
+        })
+    }
+
+    /// List every destructive statement (DROP, TRUNCATE, dropping a column) the pending
+    /// migrations would execute. Pending migrations that cannot be run in dry-run mode are
+    /// listed as [`DestructiveKind::Unplanned`], as their statements cannot be known in advance.
+    async fn destructive_statements(db: &DbConn) -> Result<Vec<DestructiveStatement>, DbErr> {
+        let migrations = Self::get_pending_migrations(db)
+            .await?
+            .into_iter()
+            .map(|Migration { migration, .. }| migration)
+            .collect();
+        let mut destructive = Vec::new();
+        for planned in plan_migrations(db, migrations, false).await {
+            for sql in planned.statements.into_iter() {
+                if let Some(kind) = destructive_kind(&sql) {
+                    destructive.push(DestructiveStatement {
+                        migration: planned.name.clone(),
+                        kind,
+                        sql,
+                    });
+                }
+            }
+            if let Some(error) = planned.error {
+                destructive.push(DestructiveStatement {
+                    migration: planned.name,
+                    kind: DestructiveKind::Unplanned,
+                    sql: error,
+                });
+            }
+        }
+        Ok(destructive)
+    }
+
+    /// Lints run against the statements built by the migrations
+    fn lints() -> Vec<Box<dyn Lint>> {
+        default_lints()
+    }
+
+    /// Build the statements of every migration for the given backend, without a database connection.
+    /// Schema inspection is unavailable, migrations relying on it report an error.
+    async fn build_offline(db_backend: DbBackend) -> Vec<PlannedMigration> {
+        // Ties are reported once the migrations are applied, their statements can still be built
+        let migrations = Self::ordered_migrations().unwrap_or_else(|_| Self::migrations());
+        plan_offline(db_backend, migrations, false).await
+    }
+
+    /// Report the tables and indexes of the database that the applied migrations do not create,
+    /// replaying the statements the migrations build offline. Objects tagged as managed on
+    /// Postgres, see [`MigratorTrait::tag_managed_objects`], are never reported.
+    async fn orphaned_objects(db: &DbConn) -> Result<OrphanReport, DbErr> {
+        let applied = Self::get_applied_migrations(db)
+            .await?
+            .into_iter()
+            .map(|Migration { migration, .. }| migration)
+            .collect();
+        let planned = plan_offline(db.get_database_backend(), applied, false).await;
+        OrphanReport::discover(db, &planned).await
+    }
+
+    /// The schema, from the [`MigratorTrait::schema_cache`] if any. With a connection, the cache
+    /// is confirmed with a single query against the hash recorded by the last run, and the
+    /// schema is discovered if it is stale or missing. Without one, e.g. offline, the cache is
+    /// used as-is.
+    async fn schema_snapshot(db: Option<&DbConn>) -> Result<SchemaSnapshot, DbErr> {
+        let cached = Self::schema_cache()
+            .map(SchemaSnapshot::parse)
+            .transpose()?;
+        match (cached, db) {
+            (Some(cached), None) => Ok(cached),
+            (Some(cached), Some(db)) if cached.is_current(db).await? => Ok(cached),
+            (Some(_), Some(db)) => {
+                warn!("The schema cache is stale, discovering the schema");
+                SchemaSnapshot::discover(db).await
+            }
+            (None, Some(db)) => SchemaSnapshot::discover(db).await,
+            (None, None) => Err(DbErr::Custom(
+                "No schema cache is embedded and no connection is given".to_owned(),
+            )),
+        }
+    }
+
+    /// How the checksums of migrations are computed. Changing the options changes every checksum,
+    /// exported checksums have to be exported again.
+    fn checksum_options() -> ChecksumOptions {
+        ChecksumOptions::default()
+    }
+
+    /// Compute the checksum of every migration for the given backend, without a database connection
+    async fn checksums_offline(db_backend: DbBackend) -> Vec<(String, String)> {
+        Self::build_offline(db_backend)
+            .await
+            .into_iter()
+            .map(|migration| {
+                let checksum = migration.checksum_with(&Self::checksum_options());
+                (migration.name, checksum)
+            })
+            .collect()
+    }
+
+    /// Verify the migration set only appends to the base (version, checksum) pairs, e.g. exported
+    /// from the main branch with [`MigratorTrait::checksums_offline`] and [`format_checksums`]
+    async fn verify_append_only(
+        db_backend: DbBackend,
+        base: &[(String, String)],
+    ) -> Result<(), DbErr> {
+        let current = Self::checksums_offline(db_backend).await;
+        let violations = append_only_violations(base, &current);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let mut msg = "Migrations must only be appended:".to_owned();
+        for violation in violations.iter() {
+            msg.push_str(&format!("\n  {}", violation));
+        }
+        Err(DbErr::Custom(msg))
+    }
+
+    /// Run the lints against every migration for the given backend, without a database connection
+    async fn lint_offline(db_backend: DbBackend) -> Vec<LintIssue> {
+        let migrations = Self::build_offline(db_backend).await;
+        run_lints(&Self::lints(), &migrations, db_backend)
+    }
+
+    /// Render the plan applying every migration to an empty database of the given backend,
+    /// without a database connection
+    async fn plan_offline(db_backend: DbBackend) -> Plan {
+        Plan {
+            applies: Self::build_offline(db_backend).await,
+            ..Default::default()
+        }
+    }
+
+    /// Execute the maintenance statements enqueued by previous runs, e.g. from a scheduled job
+    /// outside the deployment. Statements are dequeued as soon as they succeed.
+    async fn run_maintenance(db: &DbConn) -> Result<(), DbErr> {
+        Self::install(db).await?;
+        let db_backend = db.get_database_backend();
+        let mut pending = pending_maintenance(db).await?;
+        while !pending.is_empty() {
+            info!("Executing maintenance: {}", pending[0]);
+            db.execute(Statement::from_string(db_backend, pending[0].clone()))
+                .await?;
+            pending.remove(0);
+            set_pending_maintenance(db, &pending).await?;
+        }
+        Ok(())
+    }
+
     /// Rollback all applied migrations, then reapply all migrations
     async fn refresh(db: &DbConn) -> Result<(), DbErr> {
         Self::down(db, None).await?;
@@ -205,12 +660,16 @@ pub trait MigratorTrait: Send {
 
     /// Check the status of all migrations
     async fn status(db: &DbConn) -> Result<(), DbErr> {
-        Self::install(db).await?;
-
         info!("Checking migration status");
+        Self::check_empty_migrations(&Uuid::new_v4().to_string(), "status")?;
 
-        for Migration { migration, status } in Self::get_migration_with_status(db).await? {
+        let migrations = Self::read_migration_with_status(db).await?;
+        let warnings = Self::history_store().warnings(db).await?;
+        for Migration { migration, status } in migrations {
             info!("Migration '{}'... {}", migration.name(), status);
+            for warning in warnings.get(migration.name()).into_iter().flatten() {
+                warn!("    warning: {}", warning);
+            }
         }
 
         Ok(())
@@ -218,117 +677,471 @@ pub trait MigratorTrait: Send {
 
     /// Apply pending migrations
     async fn up(db: &DbConn, mut steps: Option<u32>) -> Result<(), DbErr> {
-        Self::install(db).await?;
-        let manager = SchemaManager::new(db);
+        let run_id = Uuid::new_v4().to_string();
+        in_run(db, &run_id, "up", async {
+            Self::check_empty_migrations(&run_id, "up")?;
+            Self::install(db).await?;
+            let dml_db = Self::connect_dml().await?;
+            let recorder = Self::record_statements()
+                .map(|path| StatementRecorder::open(path, db.get_database_backend(), &run_id, "up"))
+                .transpose()?;
+            let mut manager = SchemaManager::new(db);
+            manager
+                .record_statements(recorder)
+                .dml_connection(dml_db.as_ref())
+                .watchdog(Self::watchdog())
+                .blocking_check(Self::blocking_check())
+                .throttle(Self::throttle())
+                .value_codecs(Self::value_codecs())
+                .allow_lossy_alters(Self::allow_lossy_alters())
+                .policy(Self::statement_policy())
+                .log_statements(Self::log_statements())
+                .tag_managed_objects(Self::tag_managed_objects());
 
-        if let Some(steps) = steps {
-            info!("Applying {} pending migrations", steps);
-        } else {
-            info!("Applying all pending migrations");
-        }
+            if let Some(steps) = steps {
+                info!("Applying {} pending migrations", steps);
+            } else {
+                info!("Applying all pending migrations");
+            }
 
-        let migrations = Self::get_pending_migrations(db).await?.into_iter();
-        if migrations.len() == 0 {
-            info!("No pending migrations");
-        }
-        for Migration { migration, .. } in migrations {
-            if let Some(steps) = steps.as_mut() {
-                if steps == &0 {
+            // The history store is installed above, the status is computed once for the run
+            let applied = Self::history_store().applied(db).await?;
+            let applied = sort_records(applied, &*Self::version_order());
+            let migrations: Vec<_> = with_status(Self::get_migration_files()?, &applied)?
+                .into_iter()
+                .filter(|file| file.status == MigrationStatus::Pending)
+                .collect();
+            if migrations.is_empty() {
+                info!("No pending migrations");
+            }
+            if Self::fast_only() {
+                let limit = steps.map(|steps| steps as usize).unwrap_or(usize::MAX);
+                ensure_fast_only(
+                    migrations
+                        .iter()
+                        .take(limit)
+                        .map(|file| file.migration.as_ref()),
+                    Self::acknowledge_slow(),
+                )?;
+            }
+            let mut ran = false;
+            for Migration { migration, .. } in migrations {
+                if let Some(steps) = steps.as_mut() {
+                    if steps == &0 {
+                        break;
+                    }
+                    *steps -= 1;
+                }
+                let history_store = Self::history_store();
+                let claim = Self::claim_before_apply();
+                let mut record = seaql_migrations::Model {
+                    version: migration.name().to_owned(),
+                    applied_at: Self::applied_at_format().now(),
+                    app_version: Self::app_version(),
+                    checksum: None,
+                    duration_ms: None,
+                    applied_by: Self::applied_by(),
+                    run_id: Some(run_id.clone()),
+                    batch: Self::batch(),
+                };
+                if claim && !history_store.claim(db, &record).await? {
+                    // The following migrations may depend on it while it is still being applied
+                    info!(
+                        "Migration '{}' has been claimed by another instance, stopping",
+                        migration.name()
+                    );
                     break;
                 }
-                *steps -= 1;
-            }
-            info!("Applying migration '{}'", migration.name());
-            migration.up(&manager).await?;
-            info!("Migration '{}' has been applied", migration.name());
-            let now = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("SystemTime before UNIX EPOCH!");
-            seaql_migrations::ActiveModel {
-                version: ActiveValue::Set(migration.name().to_owned()),
-                applied_at: ActiveValue::Set(now.as_secs() as i64),
+                MigratorEvent::MigrationStarted {
+                    run_id: run_id.clone(),
+                    command: "up".to_owned(),
+                    version: migration.name().to_owned(),
+                }
+                .emit();
+                manager.set_policy_context(PolicyContext {
+                    migration: migration.name().to_owned(),
+                    tags: migration.tags(),
+                    rollback: false,
+                });
+                // The settings of the migration override those of the migrator
+                let settings = [Self::session_settings(), migration.session_settings()].concat();
+                hold_session(&manager, &settings).await?;
+                manager.take_executed();
+                let started = Instant::now();
+                let res = migration
+                    .up(&manager)
+                    .instrument(migration_span(
+                        db.get_database_backend(),
+                        "up",
+                        migration.name(),
+                    ))
+                    .await;
+                let (res, restored) = release_session(&manager, &settings, res).await;
+                let warnings =
+                    report_warnings(&run_id, "up", migration.name(), manager.take_warnings());
+                if let Err(err) = res {
+                    log_restore_failure(migration.name(), restored);
+                    if claim {
+                        // Release the claim, the error of the migration is the one returned
+                        if let Err(released) =
+                            history_store.remove_applied(db, migration.name()).await
+                        {
+                            warn!(
+                                "Fail to release the claim on migration '{}': {}",
+                                migration.name(),
+                                released
+                            );
+                        }
+                    }
+                    return Err(err);
+                }
+                let duration_ms = started.elapsed().as_millis() as i64;
+                MigratorEvent::MigrationCompleted {
+                    run_id: run_id.clone(),
+                    command: "up".to_owned(),
+                    version: migration.name().to_owned(),
+                    duration_ms: duration_ms as u64,
+                }
+                .emit();
+                record.duration_ms = Some(duration_ms);
+                // Checksum of the statements executed, without the queries and the tagging
+                // comments, comparable with `checksums_offline` unless the migration executed
+                // statements on the connection directly
+                record.checksum = manager
+                    .take_executed()
+                    .map(|statements| Self::checksum_options().checksum(&statements));
+                if claim {
+                    history_store.update_applied(db, &record).await?;
+                } else {
+                    record.applied_at = Self::applied_at_format().now();
+                    if !history_store.claim(db, &record).await? {
+                        warn!(
+                            "Migration '{}' has also been applied by another instance",
+                            migration.name()
+                        );
+                    }
+                }
+                store_warnings(history_store.as_ref(), db, migration.name(), &warnings).await;
+                ran = true;
+                // The migration is recorded, the run stops with the sessions left as overridden
+                restored?;
+                if let Some(throttle) = Self::throttle() {
+                    throttle.pause_migration().await;
+                }
             }
-            .insert(db)
-            .await?;
-        }
 
-        Ok(())
+            if let Some(maintenance) = Self::maintenance() {
+                report_maintenance(db, &maintenance, &manager.table_activity()).await?;
+            }
+            // The schema hash is rediscovered only after a run changing the schema, e.g. not on
+            // every boot finding nothing pending
+            if ran {
+                Self::history_store()
+                    .record_run(db, &run_id, unix_timestamp())
+                    .await?;
+            }
+            Ok(())
+        })
+        .await
     }
 
     /// Rollback applied migrations
     async fn down(db: &DbConn, mut steps: Option<u32>) -> Result<(), DbErr> {
-        Self::install(db).await?;
-        let manager = SchemaManager::new(db);
+        let run_id = Uuid::new_v4().to_string();
+        in_run(db, &run_id, "down", async {
+            Self::check_empty_migrations(&run_id, "down")?;
+            Self::install(db).await?;
+            let dml_db = Self::connect_dml().await?;
+            let recorder = Self::record_statements()
+                .map(|path| {
+                    StatementRecorder::open(path, db.get_database_backend(), &run_id, "down")
+                })
+                .transpose()?;
+            let mut manager = SchemaManager::new(db);
+            manager
+                .record_statements(recorder)
+                .dml_connection(dml_db.as_ref())
+                .watchdog(Self::watchdog())
+                .blocking_check(Self::blocking_check())
+                .throttle(Self::throttle())
+                .value_codecs(Self::value_codecs())
+                .allow_lossy_alters(Self::allow_lossy_alters())
+                .policy(Self::statement_policy())
+                .log_statements(Self::log_statements())
+                .tag_managed_objects(Self::tag_managed_objects());
 
-        if let Some(steps) = steps {
-            info!("Rolling back {} applied migrations", steps);
-        } else {
-            info!("Rolling back all applied migrations");
-        }
+            if let Some(steps) = steps {
+                info!("Rolling back {} applied migrations", steps);
+            } else {
+                info!("Rolling back all applied migrations");
+            }
 
-        let migrations = Self::get_applied_migrations(db).await?.into_iter().rev();
-        if migrations.len() == 0 {
-            info!("No applied migrations");
-        }
-        for Migration { migration, .. } in migrations {
-            if let Some(steps) = steps.as_mut() {
-                if steps == &0 {
-                    break;
+            // The history store is installed above, the status is computed once for the run
+            let applied = Self::history_store().applied(db).await?;
+            let applied = sort_records(applied, &*Self::version_order());
+            let migrations: Vec<_> = with_status(Self::get_migration_files()?, &applied)?
+                .into_iter()
+                .filter(|file| file.status == MigrationStatus::Applied)
+                .rev()
+                .collect();
+            if migrations.is_empty() {
+                info!("No applied migrations");
+            }
+            if Self::fast_only() {
+                let limit = steps.map(|steps| steps as usize).unwrap_or(usize::MAX);
+                ensure_fast_only(
+                    migrations
+                        .iter()
+                        .take(limit)
+                        .map(|file| file.migration.as_ref()),
+                    Self::acknowledge_slow(),
+                )?;
+            }
+            let mut ran = false;
+            for Migration { migration, .. } in migrations {
+                if let Some(steps) = steps.as_mut() {
+                    if steps == &0 {
+                        break;
+                    }
+                    *steps -= 1;
+                }
+                MigratorEvent::MigrationStarted {
+                    run_id: run_id.clone(),
+                    command: "down".to_owned(),
+                    version: migration.name().to_owned(),
+                }
+                .emit();
+                manager.set_policy_context(PolicyContext {
+                    migration: migration.name().to_owned(),
+                    tags: migration.tags(),
+                    rollback: true,
+                });
+                // The settings of the migration override those of the migrator
+                let settings = [Self::session_settings(), migration.session_settings()].concat();
+                hold_session(&manager, &settings).await?;
+                let started = Instant::now();
+                let res = migration
+                    .down(&manager)
+                    .instrument(migration_span(
+                        db.get_database_backend(),
+                        "down",
+                        migration.name(),
+                    ))
+                    .await;
+                let (res, restored) = release_session(&manager, &settings, res).await;
+                let warnings =
+                    report_warnings(&run_id, "down", migration.name(), manager.take_warnings());
+                if let Err(err) = res {
+                    log_restore_failure(migration.name(), restored);
+                    return Err(err);
+                }
+                MigratorEvent::MigrationCompleted {
+                    run_id: run_id.clone(),
+                    command: "down".to_owned(),
+                    version: migration.name().to_owned(),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                }
+                .emit();
+                let history_store = Self::history_store();
+                history_store.remove_applied(db, migration.name()).await?;
+                store_warnings(history_store.as_ref(), db, migration.name(), &warnings).await;
+                ran = true;
+                // The rollback is recorded, the run stops with the sessions left as overridden
+                restored?;
+                if let Some(throttle) = Self::throttle() {
+                    throttle.pause_migration().await;
                 }
-                *steps -= 1;
             }
-            info!("Rolling back migration '{}'", migration.name());
-            migration.down(&manager).await?;
-            info!("Migration '{}' has been rollbacked", migration.name());
-            seaql_migrations::Entity::delete_many()
-                .filter(seaql_migrations::Column::Version.eq(migration.name()))
-                .exec(db)
-                .await?;
+
+            if let Some(maintenance) = Self::maintenance() {
+                report_maintenance(db, &maintenance, &manager.table_activity()).await?;
+            }
+            // The schema hash is rediscovered only after a run changing the schema, e.g. not on
+            // every boot finding nothing pending
+            if ran {
+                Self::history_store()
+                    .record_run(db, &run_id, unix_timestamp())
+                    .await?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Rollback every migration applied in a release batch, see [`MigratorTrait::batch`].
+    /// The batch has to be the last one applied, later batches are to be rolled back first.
+    async fn down_batch(db: &DbConn, batch: &str) -> Result<(), DbErr> {
+        let models = Self::get_migration_models(db).await?;
+        let in_batch = |model: &seaql_migrations::Model| model.batch.as_deref() == Some(batch);
+        let first = match models.iter().position(in_batch) {
+            Some(first) => first,
+            None => {
+                info!("No migrations applied in batch '{}'", batch);
+                return Ok(());
+            }
+        };
+        if let Some(model) = models[first..].iter().find(|model| !in_batch(model)) {
+            return Err(DbErr::Custom(format!(
+                "Migration '{}' has been applied after batch '{}', its batch has to be rolled back first",
+                model.version, batch
+            )));
         }
+        Self::down(db, Some((models.len() - first) as u32)).await
+    }
+}
 
+/// Hold a connection of the pool, and one of the data connection pool, for a migration with
+/// [`SchemaManager::begin`], and apply the session settings on them. Nothing is held without
+/// settings.
+async fn hold_session(
+    manager: &SchemaManager<'_>,
+    settings: &[SessionSetting],
+) -> Result<(), DbErr> {
+    if settings.is_empty() {
+        return Ok(());
+    }
+    manager.begin().await?;
+    let applied = async {
+        for conn in manager.held_connections() {
+            apply_session_settings(conn.as_ref(), settings).await?;
+        }
         Ok(())
     }
+    .await;
+    if applied.is_err() {
+        manager.rollback().await?;
+    }
+    applied
 }
 
-pub(crate) fn query_tables(db: &DbConn) -> SelectStatement {
-    let mut stmt = Query::select();
-    let (expr, tbl_ref, condition) = match db.get_database_backend() {
-        DbBackend::MySql => (
-            Expr::col(Alias::new("table_name")),
-            (Alias::new("information_schema"), Alias::new("tables")).into_table_ref(),
-            Condition::all().add(
-                Expr::expr(get_current_schema(db))
-                    .equals(Alias::new("tables"), Alias::new("table_schema")),
-            ),
-        ),
-        DbBackend::Postgres => (
-            Expr::col(Alias::new("table_name")),
-            (Alias::new("information_schema"), Alias::new("tables")).into_table_ref(),
-            Condition::all()
-                .add(
-                    Expr::expr(get_current_schema(db))
-                        .equals(Alias::new("tables"), Alias::new("table_schema")),
-                )
-                .add(Expr::col(Alias::new("table_type")).eq("BASE TABLE")),
-        ),
-        DbBackend::Sqlite => (
-            Expr::col(Alias::new("name")),
-            Alias::new("sqlite_master").into_table_ref(),
-            Condition::all()
-                .add(Expr::col(Alias::new("type")).eq("table"))
-                .add(Expr::col(Alias::new("name")).ne("sqlite_sequence")),
-        ),
+/// Revert the session settings on the connections held by [`hold_session`], then commit the
+/// transaction if the migration succeeded, or roll it back. Return the result of the migration,
+/// failing if it cannot be committed, and the result of reverting the settings.
+async fn release_session(
+    manager: &SchemaManager<'_>,
+    settings: &[SessionSetting],
+    res: Result<(), DbErr>,
+) -> (Result<(), DbErr>, Result<(), DbErr>) {
+    if settings.is_empty() {
+        return (res, Ok(()));
+    }
+    let mut reverted = Ok(());
+    for conn in manager.held_connections() {
+        reverted = reverted.and(revert_session_settings(conn.as_ref(), settings).await);
+    }
+    let res = match res {
+        Ok(()) => manager.commit().await,
+        Err(err) => {
+            if let Err(rollback) = manager.rollback().await {
+                warn!("Fail to roll back the migration: {}", rollback);
+            }
+            Err(err)
+        }
     };
-    stmt.expr_as(expr, Alias::new("table_name"))
-        .from(tbl_ref)
-        .cond_where(condition);
-    stmt
+    (res, reverted)
 }
 
-pub(crate) fn get_current_schema(db: &DbConn) -> SimpleExpr {
-    match db.get_database_backend() {
-        DbBackend::MySql => Expr::cust("DATABASE()"),
-        DbBackend::Postgres => Expr::cust("CURRENT_SCHEMA()"),
-        DbBackend::Sqlite => unimplemented!(),
+/// Log a failure to restore the session settings after a failed migration, whose error prevails
+fn log_restore_failure(version: &str, restored: Result<(), DbErr>) {
+    if let Err(err) = restored {
+        warn!(
+            "Fail to restore the session settings after migration '{}' failed: {}",
+            version, err
+        );
     }
 }
+
+/// Mark the migration files recorded as applied, in order, failing if the applied migrations
+/// do not match the files
+/// Number of the `registered` migrations missing from the `applied` ones
+pub(crate) fn count_pending(registered: &[String], applied: &[seaql_migrations::Model]) -> usize {
+    let applied: HashSet<&str> = applied.iter().map(|model| model.version.as_str()).collect();
+    registered
+        .iter()
+        .filter(|version| !applied.contains(version.as_str()))
+        .count()
+}
+
+fn with_status(
+    mut migration_files: Vec<Migration>,
+    migration_models: &[seaql_migrations::Model],
+) -> Result<Vec<Migration>, DbErr> {
+    for (i, migration_model) in migration_models.iter().enumerate() {
+        if let Some(migration_file) = migration_files.get_mut(i) {
+            if migration_file.migration.name() == migration_model.version.as_str() {
+                migration_file.status = MigrationStatus::Applied;
+            } else {
+                return Err(DbErr::Custom(format!("Migration mismatch: applied migration != migration file, '{0}' != '{1}'\nMigration '{0}' has been applied but its corresponding migration file is missing.", migration_file.migration.name(), migration_model.version)));
+            }
+        } else {
+            return Err(DbErr::Custom(format!("Migration file of version '{}' is missing, this migration has been applied but its file is missing", migration_model.version)));
+        }
+    }
+    Ok(migration_files)
+}
+
+/// Run a migrator command inside a span carrying the run id, so that every event, and the
+/// failure if any, can be correlated with the history rows and `seaql_schema_meta` entries of the run
+pub(crate) async fn in_run<F>(db: &DbConn, run_id: &str, command: &str, fut: F) -> Result<(), DbErr>
+where
+    F: Future<Output = Result<(), DbErr>>,
+{
+    let _marker = enter_run(db, run_id, command).await?;
+    let span = run_span(db.get_database_backend(), run_id, command);
+    let started = Instant::now();
+    span.in_scope(|| {
+        MigratorEvent::RunStarted {
+            run_id: run_id.to_owned(),
+            command: command.to_owned(),
+        }
+        .emit()
+    });
+    let res = InRun::new(run_id, command, fut.instrument(span.clone())).await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let event = match &res {
+        Ok(_) => MigratorEvent::RunCompleted {
+            run_id: run_id.to_owned(),
+            command: command.to_owned(),
+            duration_ms,
+        },
+        Err(err) => MigratorEvent::RunFailed {
+            run_id: run_id.to_owned(),
+            command: command.to_owned(),
+            duration_ms,
+            error: err.to_string(),
+        },
+    };
+    span.in_scope(|| event.emit());
+    res
+}
+
+/// Sort migrations by version name, failing if two migrations share a version
+pub fn sort_by_version(
+    migrations: Vec<Box<dyn MigrationTrait>>,
+) -> Result<Vec<Box<dyn MigrationTrait>>, DbErr> {
+    sort_by_version_order(migrations, &Lexicographic)
+}
+
+/// Sort migrations in the given order of versions, failing if two migrations share a version
+pub fn sort_by_version_order(
+    mut migrations: Vec<Box<dyn MigrationTrait>>,
+    order: &dyn VersionOrder,
+) -> Result<Vec<Box<dyn MigrationTrait>>, DbErr> {
+    migrations.sort_by(|a, b| order.cmp(a.name(), b.name()));
+    for pair in migrations.windows(2) {
+        if pair[0].name() == pair[1].name() {
+            return Err(DbErr::Custom(format!(
+                "Migration '{}' is registered more than once",
+                pair[0].name()
+            )));
+        }
+    }
+    Ok(migrations)
+}
+
+pub(crate) fn unix_timestamp() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("SystemTime before UNIX EPOCH!");
+    now.as_secs() as i64
+}
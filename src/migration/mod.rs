@@ -1,12 +1,103 @@
+pub mod activity;
+pub mod agent;
+pub mod applied_at;
+pub mod backfill;
+pub mod blob;
+pub mod blocking;
+pub mod boot;
+pub mod branch;
+pub mod bulk;
+pub mod checksum;
 pub mod cli;
+pub mod codec;
+pub mod coercion;
+pub mod compat;
+pub mod dependency;
+pub mod diff;
+pub mod dual_write;
+pub mod duration;
+pub mod event;
+pub mod fixture;
+mod guard;
+pub mod history;
+pub mod interop;
+pub mod lag;
+pub mod lint;
+pub mod maintenance;
 pub mod manager;
+pub mod meta;
 pub mod migrator;
+pub mod orphan;
+pub mod plan;
+pub mod policy;
 pub mod prelude;
+pub mod probe;
+pub mod registration;
+pub mod reindex;
+pub mod replay;
 pub mod seaql_migrations;
+pub mod seaql_schema_meta;
+pub mod session;
+pub mod setting;
+pub mod snapshot;
+pub mod statement;
+pub mod tagging;
+pub mod teardown;
+pub mod telemetry;
+pub mod throttle;
+pub mod verify;
+pub mod version;
+pub mod warning;
+pub mod watchdog;
 
+pub use activity::*;
+pub use agent::*;
+pub use applied_at::*;
+pub use backfill::*;
+pub use blob::*;
+pub use blocking::*;
+pub use boot::*;
+pub use branch::*;
+pub use bulk::*;
+pub use checksum::*;
 pub use cli::*;
+pub use codec::*;
+pub use coercion::*;
+pub use compat::*;
+pub use dependency::*;
+pub use diff::*;
+pub use dual_write::*;
+pub use duration::*;
+pub use event::*;
+pub use fixture::*;
+pub(crate) use guard::*;
+pub use history::*;
+pub use interop::*;
+pub use lag::*;
+pub use lint::*;
+pub use maintenance::*;
 pub use manager::*;
+pub use meta::*;
 pub use migrator::*;
+pub use orphan::*;
+pub use plan::*;
+pub use policy::*;
+pub use probe::*;
+pub use registration::*;
+pub use reindex::*;
+pub use replay::*;
+pub use session::*;
+pub use setting::*;
+pub use snapshot::*;
+pub use statement::*;
+pub use tagging::*;
+pub use teardown::*;
+pub use telemetry::*;
+pub use throttle::*;
+pub use verify::*;
+pub use version::*;
+pub use warning::*;
+pub use watchdog::*;
 
 pub use async_std;
 pub use async_trait;
@@ -26,4 +117,25 @@ pub trait MigrationTrait: MigrationName + Send + Sync {
 
     /// Define actions to perform when rolling back the migration
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr>;
+
+    /// Tags of the migration, e.g. `destructive`, exempting it from rules of the
+    /// [`MigratorTrait::statement_policy`]
+    fn tags(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Expected duration of the migration. Slow migrations only run where acknowledged if the
+    /// migrator is [`MigratorTrait::fast_only`].
+    fn duration_class(&self) -> DurationClass {
+        DurationClass::Fast
+    }
+
+    /// Session settings applied while the migration runs, overriding those of
+    /// [`MigratorTrait::session_settings`], e.g. a longer [`SessionSetting::StatementTimeout`]
+    /// for a slow migration. They are applied the same way, on the connection held for the
+    /// migration, and end with it on Postgres. Elsewhere they are reverted before the connection
+    /// returns to the pool, except [`SessionSetting::Custom`] and [`SessionSetting::BusyTimeout`].
+    fn session_settings(&self) -> Vec<SessionSetting> {
+        Vec::new()
+    }
 }
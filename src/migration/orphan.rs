@@ -0,0 +1,378 @@
+use super::{
+    parse_managed_comment, probe_tables, tokens, ObjectKind, PlannedMigration,
+    MIGRATOR_TABLE_PREFIX,
+};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+/// A table or an index of the database that the migrations do not create
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OrphanedObject {
+    pub object: ObjectKind,
+    pub name: String,
+    /// Table of an index
+    pub table: Option<String>,
+}
+
+/// Objects of the database not created by the applied migrations, the other direction of drift
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrphanReport {
+    pub orphaned: Vec<OrphanedObject>,
+    /// Applied migrations which could not be built offline, e.g. because they use the connection
+    /// directly; objects they create are reported as orphaned unless tagged as managed
+    pub unplanned: Vec<String>,
+}
+
+/// Tables and indexes the statements of the migrations leave behind, replaying their creations,
+/// renames and drops in order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExpectedObjects {
+    pub tables: BTreeSet<String>,
+    /// Indexes along with their table
+    pub indexes: BTreeMap<String, String>,
+}
+
+/// A table or an index found in the current schema
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LiveObject {
+    object: ObjectKind,
+    name: String,
+    table: Option<String>,
+    /// Comment of the object on Postgres
+    comment: Option<String>,
+}
+
+impl Display for OrphanedObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.table {
+            Some(table) => write!(f, "{} '{}' on '{}'", self.object, self.name, table),
+            None => write!(f, "{} '{}'", self.object, self.name),
+        }
+    }
+}
+
+impl OrphanReport {
+    /// Compare the objects of the current schema with the ones the applied migrations create
+    pub async fn discover(db: &DbConn, applied: &[PlannedMigration]) -> Result<Self, DbErr> {
+        let statements: Vec<&str> = applied
+            .iter()
+            .flat_map(|migration| migration.statements.iter().map(|sql| sql.as_str()))
+            .collect();
+        let expected = ExpectedObjects::replay(&statements);
+        let mut orphaned: Vec<OrphanedObject> = live_objects(db)
+            .await?
+            .into_iter()
+            .filter(|live| !live.is_expected(&expected))
+            .map(|live| OrphanedObject {
+                object: live.object,
+                name: live.name,
+                table: live.table,
+            })
+            .collect();
+        orphaned.sort();
+        Ok(Self {
+            orphaned,
+            unplanned: applied
+                .iter()
+                .filter(|migration| migration.error.is_some())
+                .map(|migration| migration.name.clone())
+                .collect(),
+        })
+    }
+
+    /// Return true if every object of the database is created by the migrations
+    pub fn is_empty(&self) -> bool {
+        self.orphaned.is_empty()
+    }
+}
+
+impl ExpectedObjects {
+    pub fn replay<S>(statements: &[S]) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let mut expected = Self::default();
+        for sql in statements.iter() {
+            expected.apply(sql.as_ref());
+        }
+        expected
+    }
+
+    fn apply(&mut self, sql: &str) {
+        let tokens = tokens(sql, true);
+        let token = |i: usize| tokens.get(i).map(|t| t.as_str()).unwrap_or_default();
+        match (token(0), token(1)) {
+            ("CREATE", "TABLE") => {
+                let i = skip(&tokens, 2, &["IF", "NOT", "EXISTS"]);
+                let table = match name_at(&tokens, i) {
+                    Some(table) => table,
+                    None => return,
+                };
+                // Indexes defined along with the table on MySQL, e.g. `UNIQUE KEY `idx` (...)`
+                for w in tokens[i..].windows(2) {
+                    if matches!(w[0].as_str(), "INDEX" | "KEY") && is_quoted(&w[1]) {
+                        self.indexes.insert(unquote(&w[1]), table.clone());
+                    }
+                }
+                self.tables.insert(table);
+            }
+            ("CREATE", "UNIQUE" | "INDEX") => {
+                let i = match tokens.iter().position(|t| t == "INDEX") {
+                    Some(i) => skip(
+                        &tokens,
+                        skip(&tokens, i + 1, &["CONCURRENTLY"]),
+                        &["IF", "NOT", "EXISTS"],
+                    ),
+                    None => return,
+                };
+                let on = match tokens[i..].iter().position(|t| t == "ON") {
+                    Some(on) => i + on,
+                    None => return,
+                };
+                if let (Some(index), Some(table)) = (name_at(&tokens, i), name_at(&tokens, on + 1))
+                {
+                    if on > i {
+                        self.indexes.insert(index, table);
+                    }
+                }
+            }
+            ("DROP", "TABLE") => {
+                let mut i = skip(&tokens, 2, &["IF", "EXISTS"]);
+                while let Some(table) = name_at(&tokens, i) {
+                    self.tables.remove(&table);
+                    self.indexes.retain(|_, t| t != &table);
+                    i = name_end(&tokens, i);
+                    match token(i) {
+                        "," => i += 1,
+                        _ => break,
+                    }
+                }
+            }
+            ("DROP", "INDEX") => {
+                let i = skip(
+                    &tokens,
+                    skip(&tokens, 2, &["CONCURRENTLY"]),
+                    &["IF", "EXISTS"],
+                );
+                if let Some(index) = name_at(&tokens, i) {
+                    self.indexes.remove(&index);
+                }
+            }
+            // MySQL
+            ("RENAME", "TABLE") => {
+                let i = name_end(&tokens, 2);
+                if let (Some(from), "TO", Some(to)) =
+                    (name_at(&tokens, 2), token(i), name_at(&tokens, i + 1))
+                {
+                    self.rename_table(from, to);
+                }
+            }
+            ("ALTER", "TABLE") => {
+                let i = skip(&tokens, 2, &["IF", "EXISTS"]);
+                let table = match name_at(&tokens, i) {
+                    Some(table) => table,
+                    None => return,
+                };
+                let j = name_end(&tokens, i);
+                match (token(j), token(j + 1)) {
+                    ("RENAME", "TO") => {
+                        if let Some(to) = name_at(&tokens, j + 2) {
+                            self.rename_table(table, to);
+                        }
+                    }
+                    ("DROP", "INDEX" | "KEY") => {
+                        if let Some(index) = name_at(&tokens, j + 2) {
+                            self.indexes.remove(&index);
+                        }
+                    }
+                    ("ADD", "INDEX" | "KEY" | "UNIQUE") => {
+                        let k = skip(&tokens, j + 2, &["INDEX"]);
+                        let k = skip(&tokens, k, &["KEY"]);
+                        if let Some(index) = name_at(&tokens, k) {
+                            self.indexes.insert(index, table);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn rename_table(&mut self, from: String, to: String) {
+        if self.tables.remove(&from) {
+            self.tables.insert(to.clone());
+        }
+        for table in self.indexes.values_mut() {
+            if table == &from {
+                *table = to.clone();
+            }
+        }
+    }
+}
+
+impl LiveObject {
+    fn is_expected(&self, expected: &ExpectedObjects) -> bool {
+        let managed = self
+            .comment
+            .as_deref()
+            .and_then(parse_managed_comment)
+            .is_some();
+        let table = self.table.as_deref().unwrap_or(&self.name);
+        managed
+            || table.starts_with(MIGRATOR_TABLE_PREFIX)
+            || match self.object {
+                ObjectKind::Index => expected.indexes.contains_key(&self.name),
+                _ => expected.tables.contains(&self.name),
+            }
+    }
+}
+
+/// Tables and indexes of the current schema, leaving out the indexes backing a primary key,
+/// unique constraint or foreign key
+async fn live_objects(db: &DbConn) -> Result<Vec<LiveObject>, DbErr> {
+    let db_backend = db.get_database_backend();
+    let mut objects = Vec::new();
+    let (tables_sql, indexes_sql) = match db_backend {
+        DbBackend::Postgres => (
+            Some(
+                r#"SELECT "c"."relname"::text AS "name", obj_description("c"."oid", 'pg_class') AS "comment"
+                FROM "pg_class" AS "c"
+                JOIN "pg_namespace" AS "n" ON "n"."oid" = "c"."relnamespace"
+                WHERE "n"."nspname" = CURRENT_SCHEMA() AND "c"."relkind" IN ('r', 'p')"#,
+            ),
+            r#"SELECT "i"."relname"::text AS "name", "t"."relname"::text AS "table_name",
+                obj_description("i"."oid", 'pg_class') AS "comment"
+            FROM "pg_index" AS "x"
+            JOIN "pg_class" AS "i" ON "i"."oid" = "x"."indexrelid"
+            JOIN "pg_class" AS "t" ON "t"."oid" = "x"."indrelid"
+            JOIN "pg_namespace" AS "n" ON "n"."oid" = "t"."relnamespace"
+            WHERE "n"."nspname" = CURRENT_SCHEMA()
+                AND NOT EXISTS (SELECT 1 FROM "pg_constraint" AS "k" WHERE "k"."conindid" = "i"."oid")"#,
+        ),
+        DbBackend::MySql => (
+            None,
+            r#"SELECT DISTINCT `s`.`INDEX_NAME` AS `name`, `s`.`TABLE_NAME` AS `table_name`,
+                CAST(NULL AS CHAR) AS `comment`
+            FROM `information_schema`.`STATISTICS` AS `s`
+            WHERE `s`.`TABLE_SCHEMA` = DATABASE() AND `s`.`INDEX_NAME` <> 'PRIMARY'
+                AND NOT EXISTS (
+                    SELECT 1 FROM `information_schema`.`TABLE_CONSTRAINTS` AS `k`
+                    WHERE `k`.`TABLE_SCHEMA` = `s`.`TABLE_SCHEMA` AND `k`.`TABLE_NAME` = `s`.`TABLE_NAME`
+                        AND `k`.`CONSTRAINT_NAME` = `s`.`INDEX_NAME`
+                )"#,
+        ),
+        DbBackend::Sqlite => (
+            None,
+            r#"SELECT "name", "tbl_name" AS "table_name", CAST(NULL AS TEXT) AS "comment"
+            FROM "sqlite_master"
+            WHERE "type" = 'index' AND "sql" IS NOT NULL"#,
+        ),
+    };
+    match tables_sql {
+        Some(sql) => {
+            let stmt = Statement::from_string(db_backend, sql.to_owned());
+            for row in db.query_all(stmt).await?.into_iter() {
+                objects.push(LiveObject {
+                    object: ObjectKind::Table,
+                    name: row.try_get("", "name")?,
+                    table: None,
+                    comment: row.try_get("", "comment")?,
+                });
+            }
+        }
+        None => {
+            for name in probe_tables(db).await? {
+                objects.push(LiveObject {
+                    object: ObjectKind::Table,
+                    name,
+                    table: None,
+                    comment: None,
+                });
+            }
+        }
+    }
+    let stmt = Statement::from_string(db_backend, indexes_sql.to_owned());
+    for row in db.query_all(stmt).await?.into_iter() {
+        objects.push(LiveObject {
+            object: ObjectKind::Index,
+            name: row.try_get("", "name")?,
+            table: Some(row.try_get("", "table_name")?),
+            comment: row.try_get("", "comment")?,
+        });
+    }
+    Ok(objects)
+}
+
+fn is_quoted(token: &str) -> bool {
+    token.starts_with('"') || token.starts_with('`')
+}
+
+/// Remove the quotes of an identifier, unquoted identifiers being upper-cased by [`tokens`]
+/// are folded to lower case
+fn unquote(token: &str) -> String {
+    match token.chars().next() {
+        Some(quote @ ('"' | '`')) => {
+            token[1..token.len() - 1].replace(&format!("{}{}", quote, quote), &quote.to_string())
+        }
+        _ => token.to_lowercase(),
+    }
+}
+
+/// Skip the keywords if they follow at `i`
+fn skip(tokens: &[String], i: usize, keywords: &[&str]) -> usize {
+    match tokens.get(i..i + keywords.len()) {
+        Some(found) if found.iter().zip(keywords).all(|(a, b)| a == b) => i + keywords.len(),
+        _ => i,
+    }
+}
+
+/// The possibly schema-qualified name at `i`, without its schema
+fn name_at(tokens: &[String], i: usize) -> Option<String> {
+    let token = tokens.get(name_end(tokens, i) - 1)?;
+    match is_quoted(token) || token.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        true => Some(unquote(token)),
+        false => None,
+    }
+}
+
+/// Index of the token following the possibly schema-qualified name at `i`
+fn name_end(tokens: &[String], i: usize) -> usize {
+    let mut j = i + 1;
+    while tokens.get(j).map(|t| t.as_str()) == Some(".") {
+        j += 2;
+    }
+    j
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay() {
+        let expected = ExpectedObjects::replay(&[
+            r#"CREATE TABLE IF NOT EXISTS "cake" ( "id" integer )"#,
+            r#"CREATE TABLE "fruit" ( "id" integer )"#,
+            r#"CREATE UNIQUE INDEX "idx-fruit-id" ON "fruit" ("id")"#,
+            r#"ALTER TABLE "fruit" RENAME TO "fruits""#,
+            r#"CREATE TABLE "tmp" ( "id" integer )"#,
+            r#"CREATE INDEX "idx-tmp-id" ON "tmp" ("id")"#,
+            r#"DROP TABLE "tmp""#,
+            "CREATE TABLE `baker` ( `id` int, UNIQUE KEY `idx-baker-id` (`id`) )",
+            "RENAME TABLE `baker` TO `bakers`",
+        ]);
+        assert_eq!(
+            expected.tables.iter().collect::<Vec<_>>(),
+            vec!["bakers", "cake", "fruits"]
+        );
+        assert_eq!(
+            expected.indexes.iter().collect::<Vec<_>>(),
+            vec![
+                (&"idx-baker-id".to_owned(), &"bakers".to_owned()),
+                (&"idx-fruit-id".to_owned(), &"fruits".to_owned())
+            ]
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use super::{
+    checksum, destructive_kind, json_string, schema_probe, statement_class, ChecksumOptions,
+    MigrationTrait, SchemaManager, StatementClass, TableGraph,
+};
+use futures::FutureExt;
+use sea_orm::sea_query::{Alias, Expr, ForeignKey, Query, Table};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::fmt::Display;
+use std::panic::AssertUnwindSafe;
+
+/// Version of the JSON format of [`Plan::to_json`], bumped whenever a field is removed or changes
+/// meaning. Fields may be added without bumping it, consumers should ignore unknown fields.
+pub const PLAN_FORMAT_VERSION: u32 = 1;
+
+/// Kind of database object dropped by the migrator
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObjectKind {
+    View,
+    Trigger,
+    Event,
+    ForeignKey,
+    Table,
+    Index,
+    Sequence,
+    Function,
+    Type,
+}
+
+/// A database object that would be dropped, along with the statement dropping it
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedDrop {
+    pub object: ObjectKind,
+    pub name: String,
+    pub sql: String,
+}
+
+/// A migration that would be applied or rolled back, along with the statements it would execute
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedMigration {
+    pub name: String,
+    pub statements: Vec<String>,
+    /// Statements the checksum is computed over: `statements` without the queries, as recorded
+    /// in the history when the migration is applied
+    pub checksummed: Vec<String>,
+    /// The migration failed to run in dry-run mode, e.g. it accessed the connection directly.
+    /// `statements` only contains the statements recorded before the failure.
+    pub error: Option<String>,
+}
+
+/// Preview of what a migrator operation would do, without modifying the database
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Plan {
+    /// Database objects that would be dropped, in order
+    pub drops: Vec<PlannedDrop>,
+    /// Migrations that would be rolled back, in order
+    pub rollbacks: Vec<PlannedMigration>,
+    /// Migrations that would be applied, in order
+    pub applies: Vec<PlannedMigration>,
+}
+
+/// Everything `fresh` drops from the database, in order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct DropPlan {
+    pub(crate) drops: Vec<PlannedDrop>,
+    /// SQLite cannot drop a foreign key, the foreign key check has to be disabled
+    /// when tables reference each other in a cycle
+    pub(crate) disable_foreign_key_check: bool,
+}
+
+impl ObjectKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::View => "view",
+            Self::Trigger => "trigger",
+            Self::Event => "event",
+            Self::ForeignKey => "foreign key",
+            Self::Table => "table",
+            Self::Index => "index",
+            Self::Sequence => "sequence",
+            Self::Function => "function",
+            Self::Type => "type",
+        }
+    }
+}
+
+impl Display for ObjectKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Display for PlannedDrop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Drop {} '{}': {}", self.object, self.name, self.sql)
+    }
+}
+
+impl Display for PlannedMigration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Migration '{}'", self.name)?;
+        for statement in self.statements.iter() {
+            write!(f, "\n    {}", statement)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, "\n    (cannot be fully planned: {})", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = Vec::new();
+        if !self.drops.is_empty() {
+            lines.push("Objects to be dropped:".to_owned());
+            lines.extend(self.drops.iter().map(|drop| format!("  {}", drop)));
+        }
+        if !self.rollbacks.is_empty() {
+            lines.push("Migrations to be rolled back:".to_owned());
+            lines.extend(self.rollbacks.iter().map(|m| format!("  {}", m)));
+        }
+        if !self.applies.is_empty() {
+            lines.push("Migrations to be applied:".to_owned());
+            lines.extend(self.applies.iter().map(|m| format!("  {}", m)));
+        }
+        if lines.is_empty() {
+            lines.push("Nothing to be done".to_owned());
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl PlannedDrop {
+    fn to_json(&self) -> String {
+        format!(
+            r#"{{"object":{},"name":{},"sql":{}}}"#,
+            json_string(self.object.as_str()),
+            json_string(&self.name),
+            json_string(&self.sql)
+        )
+    }
+}
+
+impl PlannedMigration {
+    /// Return true if a statement of the migration destroys schema or data, or if the
+    /// migration could not be fully planned
+    pub fn is_destructive(&self) -> bool {
+        self.error.is_some()
+            || self
+                .statements
+                .iter()
+                .any(|sql| destructive_kind(sql).is_some())
+    }
+
+    fn to_json(&self) -> String {
+        let statements: Vec<String> = self
+            .statements
+            .iter()
+            .map(|sql| {
+                let class = match statement_class(sql) {
+                    StatementClass::Ddl => "ddl",
+                    StatementClass::Dml => "dml",
+                };
+                let destructive = match destructive_kind(sql) {
+                    Some(kind) => json_string(&kind.to_string()),
+                    None => "null".to_owned(),
+                };
+                format!(
+                    r#"{{"sql":{},"class":{},"destructive":{}}}"#,
+                    json_string(sql),
+                    json_string(class),
+                    destructive
+                )
+            })
+            .collect();
+        let error = match &self.error {
+            Some(error) => json_string(error),
+            None => "null".to_owned(),
+        };
+        format!(
+            r#"{{"name":{},"checksum":{},"destructive":{},"statements":[{}],"error":{}}}"#,
+            json_string(&self.name),
+            json_string(&self.checksum()),
+            self.is_destructive(),
+            statements.join(","),
+            error
+        )
+    }
+
+    /// Checksum of the statements built by the migration, see [`PlannedMigration::checksummed`]
+    pub fn checksum(&self) -> String {
+        checksum(&self.checksummed)
+    }
+
+    /// Checksum of the statements built by the migration, computed as configured
+    pub fn checksum_with(&self, options: &ChecksumOptions) -> String {
+        options.checksum(&self.checksummed)
+    }
+}
+
+impl Plan {
+    /// Return true if the plan would not change the database
+    pub fn is_empty(&self) -> bool {
+        self.drops.is_empty() && self.rollbacks.is_empty() && self.applies.is_empty()
+    }
+
+    /// Return true if the plan drops an object, or if a migration it runs is destructive,
+    /// see [`PlannedMigration::is_destructive`]
+    pub fn is_destructive(&self) -> bool {
+        !self.drops.is_empty()
+            || self
+                .rollbacks
+                .iter()
+                .chain(self.applies.iter())
+                .any(|migration| migration.is_destructive())
+    }
+
+    /// Render the plan as a single JSON object, for external tooling to review and approve.
+    ///
+    /// The object holds `format_version`, see [`PLAN_FORMAT_VERSION`], `destructive`, and the
+    /// arrays `drops` (`object`, `name` and `sql`), `rollbacks` and `applies`. Every migration
+    /// holds `name`, `checksum`, `destructive`, `error` and its `statements`, each with its
+    /// `sql`, its `class` (`ddl` or `dml`) and the `destructive` kind, `null` if it is not.
+    pub fn to_json(&self) -> String {
+        let migrations = |migrations: &[PlannedMigration]| {
+            migrations
+                .iter()
+                .map(|m| m.to_json())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!(
+            r#"{{"format_version":{},"destructive":{},"drops":[{}],"rollbacks":[{}],"applies":[{}]}}"#,
+            PLAN_FORMAT_VERSION,
+            self.is_destructive(),
+            self.drops
+                .iter()
+                .map(|drop| drop.to_json())
+                .collect::<Vec<_>>()
+                .join(","),
+            migrations(&self.rollbacks),
+            migrations(&self.applies)
+        )
+    }
+}
+
+impl DropPlan {
+    /// Discover every object `fresh` has to drop from the current schema
+    pub(crate) async fn discover(db: &DbConn) -> Result<Self, DbErr> {
+        let db_backend = db.get_database_backend();
+        let mut plan = Self::default();
+
+        // Views, triggers and events
+        if db_backend == DbBackend::MySql {
+            let objects = [
+                (ObjectKind::View, "VIEWS", "TABLE_SCHEMA", "TABLE_NAME"),
+                (
+                    ObjectKind::Trigger,
+                    "TRIGGERS",
+                    "TRIGGER_SCHEMA",
+                    "TRIGGER_NAME",
+                ),
+                (ObjectKind::Event, "EVENTS", "EVENT_SCHEMA", "EVENT_NAME"),
+            ];
+            for (object, table, schema_col, name_col) in objects {
+                let mut stmt = Query::select();
+                stmt.expr_as(Expr::col(Alias::new(name_col)), Alias::new("name"))
+                    .from((Alias::new("information_schema"), Alias::new(table)))
+                    .cond_where(
+                        Expr::expr(schema_probe(db_backend).current_schema())
+                            .equals(Alias::new(table), Alias::new(schema_col)),
+                    );
+                for row in db.query_all(db_backend.build(&stmt)).await?.into_iter() {
+                    let name: String = row.try_get("", "name")?;
+                    let sql = format!(
+                        "DROP {} IF EXISTS `{}`",
+                        object.as_str().to_uppercase(),
+                        name.replace('`', "``")
+                    );
+                    plan.drops.push(PlannedDrop { object, name, sql });
+                }
+            }
+        }
+
+        // Views and materialized views, those created later usually depend on earlier ones
+        if db_backend == DbBackend::Postgres {
+            let sql = r#"SELECT "c"."relname"::text AS "name",
+                    'DROP ' || CASE WHEN "c"."relkind" = 'm' THEN 'MATERIALIZED ' ELSE '' END
+                        || 'VIEW IF EXISTS ' || quote_ident("c"."relname") AS "sql"
+                FROM "pg_class" AS "c"
+                JOIN "pg_namespace" AS "n" ON "n"."oid" = "c"."relnamespace"
+                WHERE "n"."nspname" = CURRENT_SCHEMA() AND "c"."relkind" IN ('v', 'm')
+                    AND NOT EXISTS (SELECT 1 FROM "pg_depend" AS "d" WHERE "d"."objid" = "c"."oid" AND "d"."deptype" = 'e')
+                ORDER BY "c"."oid" DESC"#;
+            let rows = db
+                .query_all(Statement::from_string(db_backend, sql.to_owned()))
+                .await?;
+            for row in rows.into_iter() {
+                plan.drops.push(PlannedDrop {
+                    object: ObjectKind::View,
+                    name: row.try_get("", "name")?,
+                    sql: row.try_get("", "sql")?,
+                });
+            }
+        }
+
+        let graph = TableGraph::discover(db).await?;
+        let order = graph.sort();
+
+        // Dropping tables in dependency order cannot satisfy foreign keys forming a cycle
+        if !order.cyclic.is_empty() {
+            match db_backend {
+                DbBackend::MySql | DbBackend::Postgres => {
+                    for fk in graph.foreign_keys_within(&order.cyclic) {
+                        let mut stmt = ForeignKey::drop();
+                        stmt.table(Alias::new(fk.table.as_str()))
+                            .name(fk.name.as_str());
+                        plan.drops.push(PlannedDrop {
+                            object: ObjectKind::ForeignKey,
+                            name: format!("{}.{}", fk.table, fk.name),
+                            sql: db_backend.build(&stmt).to_string(),
+                        });
+                    }
+                }
+                DbBackend::Sqlite => plan.disable_foreign_key_check = true,
+            }
+        }
+
+        // Referencing tables are dropped before the tables they reference, without `CASCADE`
+        // dropping objects left out of the plan
+        for table_name in order.cyclic.iter().chain(order.sorted.iter().rev()) {
+            let mut stmt = Table::drop();
+            stmt.table(Alias::new(table_name.as_str())).if_exists();
+            plan.drops.push(PlannedDrop {
+                object: ObjectKind::Table,
+                name: table_name.clone(),
+                sql: db_backend.build(&stmt).to_string(),
+            });
+        }
+
+        Ok(plan)
+    }
+}
+
+/// Run the migrations against a dry-run schema manager, recording the statements they would execute
+pub(crate) async fn plan_migrations(
+    db: &DbConn,
+    migrations: Vec<Box<dyn MigrationTrait>>,
+    rollback: bool,
+) -> Vec<PlannedMigration> {
+    plan_with(|| SchemaManager::dry_run(db), migrations, rollback).await
+}
+
+/// Run the migrations against an offline schema manager, recording the statements they build
+pub(crate) async fn plan_offline(
+    db_backend: DbBackend,
+    migrations: Vec<Box<dyn MigrationTrait>>,
+    rollback: bool,
+) -> Vec<PlannedMigration> {
+    plan_with(|| SchemaManager::offline(db_backend), migrations, rollback).await
+}
+
+async fn plan_with<'c, F>(
+    new_manager: F,
+    migrations: Vec<Box<dyn MigrationTrait>>,
+    rollback: bool,
+) -> Vec<PlannedMigration>
+where
+    F: Fn() -> SchemaManager<'c>,
+{
+    let mut planned = Vec::new();
+    for migration in migrations.iter() {
+        planned.push(plan_one(new_manager(), migration.as_ref(), rollback).await);
+    }
+    planned
+}
+
+/// Run a single migration against a dry-run or offline schema manager
+async fn plan_one(
+    manager: SchemaManager<'_>,
+    migration: &dyn MigrationTrait,
+    rollback: bool,
+) -> PlannedMigration {
+    let fut = match rollback {
+        true => migration.down(&manager),
+        false => migration.up(&manager),
+    };
+    // Using the disconnected connection may panic, e.g. when querying its backend
+    let res = match AssertUnwindSafe(fut).catch_unwind().await {
+        Ok(res) => res,
+        Err(_) => Err(DbErr::Custom(
+            "Migration accessed the database connection directly".to_owned(),
+        )),
+    };
+    PlannedMigration {
+        name: migration.name().to_owned(),
+        statements: manager
+            .take_statements()
+            .iter()
+            .map(|stmt| stmt.to_string())
+            .collect(),
+        checksummed: manager.take_executed().unwrap_or_default(),
+        error: res.err().map(|err| err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let plan = Plan {
+            drops: vec![PlannedDrop {
+                object: ObjectKind::Table,
+                name: "cake".to_owned(),
+                sql: r#"DROP TABLE IF EXISTS "cake""#.to_owned(),
+            }],
+            rollbacks: vec![],
+            applies: vec![PlannedMigration {
+                name: "m20220101_000001_create_cake".to_owned(),
+                statements: vec![r#"INSERT INTO "cake" ("name") VALUES ('a')"#.to_owned()],
+                checksummed: vec![r#"INSERT INTO "cake" ("name") VALUES ('a')"#.to_owned()],
+                error: None,
+            }],
+        };
+        assert_eq!(
+            plan.to_json(),
+            format!(
+                r#"{{"format_version":1,"destructive":true,"drops":[{{"object":"table","name":"cake","sql":"DROP TABLE IF EXISTS \"cake\""}}],"rollbacks":[],"applies":[{{"name":"m20220101_000001_create_cake","checksum":"{}","destructive":false,"statements":[{{"sql":"INSERT INTO \"cake\" (\"name\") VALUES ('a')","class":"dml","destructive":null}}],"error":null}}]}}"#,
+                plan.applies[0].checksum()
+            )
+        );
+    }
+}
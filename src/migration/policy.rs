@@ -0,0 +1,156 @@
+use super::keywords;
+
+/// Migration a statement is executed for, given to [`StatementPolicy::check`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PolicyContext {
+    /// Name of the migration, empty for statements executed outside of any migration
+    pub migration: String,
+    /// Tags of the migration, see [`MigrationTrait::tags`](super::MigrationTrait::tags)
+    pub tags: Vec<String>,
+    /// The migration is being rolled back
+    pub rollback: bool,
+}
+
+/// Organization rules every statement built by a migration has to comply with.
+/// A rejected statement fails the migration before being executed.
+pub trait StatementPolicy: Send + Sync {
+    /// Check a statement about to be executed, returning the reason it is rejected, if so
+    fn check(&self, context: &PolicyContext, sql: &str) -> Result<(), String>;
+}
+
+/// Statement policy made of rules matching the leading keywords of statements, e.g. `DROP TABLE`
+/// or `GRANT`. The first matching rule decides; statements no rule matches are allowed,
+/// unless [`StatementRules::deny_unmatched`] turns the rules into an allowlist.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatementRules {
+    rules: Vec<StatementRule>,
+    deny_unmatched: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct StatementRule {
+    keywords: Vec<String>,
+    allow: bool,
+    /// Tag exempting the migrations carrying it from a deny rule
+    unless_tagged: Option<String>,
+}
+
+impl StatementRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow statements starting with the given keywords
+    pub fn allow(self, prefix: &str) -> Self {
+        self.rule(prefix, true, None)
+    }
+
+    /// Deny statements starting with the given keywords
+    pub fn deny(self, prefix: &str) -> Self {
+        self.rule(prefix, false, None)
+    }
+
+    /// Deny statements starting with the given keywords, except in migrations tagged with `tag`
+    pub fn deny_unless_tagged(self, prefix: &str, tag: &str) -> Self {
+        self.rule(prefix, false, Some(tag.to_owned()))
+    }
+
+    /// Deny statements no rule matches
+    pub fn deny_unmatched(mut self) -> Self {
+        self.deny_unmatched = true;
+        self
+    }
+
+    fn rule(mut self, prefix: &str, allow: bool, unless_tagged: Option<String>) -> Self {
+        self.rules.push(StatementRule {
+            keywords: prefix.split_whitespace().map(str::to_uppercase).collect(),
+            allow,
+            unless_tagged,
+        });
+        self
+    }
+}
+
+impl StatementPolicy for StatementRules {
+    fn check(&self, context: &PolicyContext, sql: &str) -> Result<(), String> {
+        let keywords = keywords(sql);
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| keywords.starts_with(&rule.keywords));
+        match rule {
+            Some(rule) if rule.allow => Ok(()),
+            Some(rule) => match &rule.unless_tagged {
+                Some(tag) if context.tags.contains(tag) => Ok(()),
+                Some(tag) => Err(format!(
+                    "`{}` statements are denied outside of migrations tagged '{}'",
+                    rule.keywords.join(" "),
+                    tag
+                )),
+                None => Err(format!(
+                    "`{}` statements are denied",
+                    rule.keywords.join(" ")
+                )),
+            },
+            None if self.deny_unmatched => {
+                Err("The statement is not allowed by any rule".to_owned())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Check a statement against the policy, failing with the reason it is rejected
+pub(crate) fn ensure_allowed(
+    policy: &dyn StatementPolicy,
+    context: &PolicyContext,
+    sql: &str,
+) -> Result<(), sea_orm::DbErr> {
+    policy.check(context, sql).map_err(|reason| {
+        let migration = match context.migration.is_empty() {
+            true => String::new(),
+            false => format!(" in migration '{}'", context.migration),
+        };
+        sea_orm::DbErr::Custom(format!(
+            "Statement rejected by policy{}: {}\n    {}",
+            migration, reason, sql
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statement_rules() {
+        let rules = StatementRules::new()
+            .deny("GRANT")
+            .deny_unless_tagged("DROP TABLE", "destructive");
+        let context = PolicyContext {
+            migration: "m20220101_000001_create_table".to_owned(),
+            ..Default::default()
+        };
+        assert!(rules
+            .check(&context, "grant select on `cake` to app")
+            .is_err());
+        assert!(rules
+            .check(&context, r#"DROP TABLE IF EXISTS "cake""#)
+            .is_err());
+        assert!(rules.check(&context, r#"DROP INDEX "idx-cake""#).is_ok());
+        let tagged = PolicyContext {
+            tags: vec!["destructive".to_owned()],
+            ..context.clone()
+        };
+        assert!(rules.check(&tagged, r#"DROP TABLE "cake""#).is_ok());
+
+        let allowlist = StatementRules::new()
+            .allow("CREATE")
+            .allow("INSERT")
+            .deny_unmatched();
+        assert!(allowlist
+            .check(&context, r#"CREATE TABLE "cake" ()"#)
+            .is_ok());
+        assert!(allowlist.check(&context, r#"DELETE FROM "cake""#).is_err());
+    }
+}
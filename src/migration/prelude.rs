@@ -8,3 +8,90 @@ pub use sea_orm;
 pub use sea_orm::sea_query;
 pub use sea_orm::sea_query::*;
 pub use sea_orm::DbErr;
+
+/// Items of the backend preludes: the migration traits and the backend-agnostic schema builders
+mod common {
+    pub use super::super::cli;
+    pub use super::super::manager::SchemaManager;
+    pub use super::super::migrator::MigratorTrait;
+    pub use super::super::{MigrationName, MigrationTrait};
+    pub use async_std;
+    pub use async_trait;
+    pub use sea_orm;
+    pub use sea_orm::sea_query;
+    pub use sea_orm::sea_query::{
+        Alias, ColumnDef, Cond, Condition, DynIden, Expr, ForeignKey, ForeignKeyAction,
+        ForeignKeyCreateStatement, ForeignKeyDropStatement, Func, Iden, Index,
+        IndexCreateStatement, IndexDropStatement, Order, Query, SimpleExpr, Table,
+        TableAlterStatement, TableCreateStatement, TableDropStatement, TableRenameStatement,
+        TableTruncateStatement, Value,
+    };
+    pub use sea_orm::{DbBackend, DbErr};
+}
+
+/// Prelude of migrations targeting Postgres, adding the enum type statements and the
+/// Postgres-only helpers to the backend-agnostic items
+pub mod postgres {
+    pub use super::super::{
+        comment_statement, managed_comment, parse_managed_comment, ReplicationLagProbe,
+        MANAGED_BY_MARKER,
+    };
+    pub use super::common::*;
+    pub use sea_orm::sea_query::extension::postgres::{
+        Type, TypeAlterStatement, TypeCreateStatement, TypeDropStatement,
+    };
+
+    /// Query builder of the backend
+    pub use sea_orm::sea_query::PostgresQueryBuilder as Builder;
+
+    /// The backend the migrations run against
+    pub const DB_BACKEND: DbBackend = DbBackend::Postgres;
+}
+
+/// Prelude of migrations targeting MySQL, adding the MySQL-only helpers to the
+/// backend-agnostic items
+pub mod mysql {
+    pub use super::super::{lossy_changes, LossyChange};
+    pub use super::common::*;
+
+    /// Query builder of the backend
+    pub use sea_orm::sea_query::MysqlQueryBuilder as Builder;
+
+    /// The backend the migrations run against
+    pub const DB_BACKEND: DbBackend = DbBackend::MySql;
+}
+
+/// Prelude of migrations targeting SQLite, only the backend-agnostic items
+pub mod sqlite {
+    pub use super::common::*;
+
+    /// Query builder of the backend
+    pub use sea_orm::sea_query::SqliteQueryBuilder as Builder;
+
+    /// The backend the migrations run against
+    pub const DB_BACKEND: DbBackend = DbBackend::Sqlite;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::postgres::*;
+
+    #[test]
+    fn test_postgres_prelude() {
+        #[derive(Iden)]
+        enum Mood {
+            Table,
+            Happy,
+        }
+
+        let stmt = Type::create()
+            .as_enum(Mood::Table)
+            .values([Mood::Happy])
+            .to_owned();
+        assert_eq!(
+            stmt.to_string(Builder),
+            r#"CREATE TYPE "mood" AS ENUM ('happy')"#
+        );
+        assert_eq!(DB_BACKEND, DbBackend::Postgres);
+    }
+}
@@ -0,0 +1,179 @@
+use sea_orm::sea_query::{Alias, Expr, Query, SelectStatement, SimpleExpr};
+use sea_orm::{Condition, ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+
+/// Queries inspecting the current schema of a database.
+///
+/// These are the exact queries the migrator relies on, obtain the one matching
+/// a backend with [`schema_probe`].
+pub trait SchemaProbe: Send + Sync {
+    /// Expression evaluating to the current schema, i.e. the current database on MySQL
+    fn current_schema(&self) -> SimpleExpr;
+
+    /// Query listing the tables of the current schema as a `table_name` column
+    fn query_tables(&self) -> SelectStatement;
+
+    /// Query listing the columns of a table in the current schema, in ordinal order, as
+    /// `column_name`, `column_type`, `is_nullable` (either `YES` or `NO`) and `column_default` columns
+    fn query_columns(&self, table: &str) -> Statement;
+}
+
+/// Schema probe of MySQL
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MySqlProbe;
+
+/// Schema probe of Postgres
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PostgresProbe;
+
+/// Schema probe of SQLite
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SqliteProbe;
+
+/// A column listed by [`SchemaProbe::query_columns`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// Column type as reported by the database
+    pub column_type: String,
+    pub nullable: bool,
+    /// Default expression as reported by the database, e.g. `'pending'::text` on Postgres
+    pub default: Option<String>,
+}
+
+/// Get the schema probe of a backend
+pub fn schema_probe(db_backend: DbBackend) -> &'static dyn SchemaProbe {
+    match db_backend {
+        DbBackend::MySql => &MySqlProbe,
+        DbBackend::Postgres => &PostgresProbe,
+        DbBackend::Sqlite => &SqliteProbe,
+    }
+}
+
+/// List the tables of the current schema
+pub async fn probe_tables(db: &DbConn) -> Result<Vec<String>, DbErr> {
+    let db_backend = db.get_database_backend();
+    let stmt = db_backend.build(&schema_probe(db_backend).query_tables());
+    db.query_all(stmt)
+        .await?
+        .into_iter()
+        .map(|row| row.try_get("", "table_name"))
+        .collect()
+}
+
+/// List the columns of a table in the current schema, in ordinal order
+pub async fn probe_columns<C>(db: &C, table: &str) -> Result<Vec<ColumnInfo>, DbErr>
+where
+    C: ConnectionTrait + ?Sized,
+{
+    let stmt = schema_probe(db.get_database_backend()).query_columns(table);
+    let mut columns = Vec::new();
+    for row in db.query_all(stmt).await?.into_iter() {
+        columns.push(ColumnInfo {
+            name: row.try_get("", "column_name")?,
+            column_type: row.try_get("", "column_type")?,
+            nullable: row.try_get::<String>("", "is_nullable")? == "YES",
+            default: row.try_get("", "column_default")?,
+        });
+    }
+    Ok(columns)
+}
+
+impl SchemaProbe for MySqlProbe {
+    fn current_schema(&self) -> SimpleExpr {
+        Expr::cust("DATABASE()")
+    }
+
+    fn query_tables(&self) -> SelectStatement {
+        let mut stmt = Query::select();
+        stmt.expr_as(
+            Expr::col(Alias::new("table_name")),
+            Alias::new("table_name"),
+        )
+        .from((Alias::new("information_schema"), Alias::new("tables")))
+        .cond_where(
+            Condition::all().add(
+                Expr::expr(self.current_schema())
+                    .equals(Alias::new("tables"), Alias::new("table_schema")),
+            ),
+        );
+        stmt
+    }
+
+    fn query_columns(&self, table: &str) -> Statement {
+        Statement::from_sql_and_values(
+            DbBackend::MySql,
+            r#"SELECT `COLUMN_NAME` AS `column_name`, `COLUMN_TYPE` AS `column_type`,
+                `IS_NULLABLE` AS `is_nullable`, `COLUMN_DEFAULT` AS `column_default`
+            FROM `information_schema`.`COLUMNS`
+            WHERE `TABLE_SCHEMA` = DATABASE() AND `TABLE_NAME` = ?
+            ORDER BY `ORDINAL_POSITION`"#,
+            vec![table.into()],
+        )
+    }
+}
+
+impl SchemaProbe for PostgresProbe {
+    fn current_schema(&self) -> SimpleExpr {
+        Expr::cust("CURRENT_SCHEMA()")
+    }
+
+    fn query_tables(&self) -> SelectStatement {
+        let mut stmt = Query::select();
+        stmt.expr_as(
+            Expr::col(Alias::new("table_name")),
+            Alias::new("table_name"),
+        )
+        .from((Alias::new("information_schema"), Alias::new("tables")))
+        .cond_where(
+            Condition::all()
+                .add(
+                    Expr::expr(self.current_schema())
+                        .equals(Alias::new("tables"), Alias::new("table_schema")),
+                )
+                .add(Expr::col(Alias::new("table_type")).eq("BASE TABLE")),
+        );
+        stmt
+    }
+
+    fn query_columns(&self, table: &str) -> Statement {
+        Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"SELECT "column_name"::text AS "column_name", "udt_name"::text AS "column_type",
+                "is_nullable"::text AS "is_nullable", "column_default"::text AS "column_default"
+            FROM "information_schema"."columns"
+            WHERE "table_schema" = CURRENT_SCHEMA() AND "table_name" = $1
+            ORDER BY "ordinal_position""#,
+            vec![table.into()],
+        )
+    }
+}
+
+impl SchemaProbe for SqliteProbe {
+    /// SQLite has a single schema per database file
+    fn current_schema(&self) -> SimpleExpr {
+        Expr::cust("'main'")
+    }
+
+    fn query_tables(&self) -> SelectStatement {
+        let mut stmt = Query::select();
+        stmt.expr_as(Expr::col(Alias::new("name")), Alias::new("table_name"))
+            .from(Alias::new("sqlite_master"))
+            .cond_where(
+                Condition::all()
+                    .add(Expr::col(Alias::new("type")).eq("table"))
+                    .add(Expr::col(Alias::new("name")).ne("sqlite_sequence")),
+            );
+        stmt
+    }
+
+    fn query_columns(&self, table: &str) -> Statement {
+        Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            r#"SELECT "name" AS "column_name", "type" AS "column_type",
+                CASE "notnull" WHEN 0 THEN 'YES' ELSE 'NO' END AS "is_nullable",
+                "dflt_value" AS "column_default"
+            FROM pragma_table_info(?) ORDER BY "cid""#,
+            vec![table.into()],
+        )
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Source files of the migrations directory that do not define a migration
+const NON_MIGRATION_FILES: [&str; 3] = ["lib", "main", "mod"];
+
+/// Names of the migration files in a directory, i.e. the stems of the `.rs` files
+/// other than `lib.rs`, `main.rs` and `mod.rs`, in order
+pub fn migration_files<P>(dir: P) -> std::io::Result<Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    let mut files = BTreeSet::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            continue;
+        }
+        if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+            if !NON_MIGRATION_FILES.contains(&stem) {
+                files.insert(stem.to_owned());
+            }
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+/// Modules whose `Migration` is referenced in the source of a migrator,
+/// e.g. `m20220118_000001_create_cake_table` in `Box::new(m20220118_000001_create_cake_table::Migration)`
+pub fn registered_modules(source: &str) -> Vec<String> {
+    let mut modules = Vec::new();
+    let mut rest = source;
+    while let Some(i) = rest.find("::Migration") {
+        let module: String = rest[..i]
+            .chars()
+            .rev()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        rest = &rest[i + "::Migration".len()..];
+        // Skip paths such as `prelude::MigrationTrait`
+        let is_path_end = !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_');
+        if is_path_end && !module.is_empty() && !modules.contains(&module) {
+            modules.push(module);
+        }
+    }
+    modules
+}
+
+/// Migration files of `dir` not registered by the migrator defined in its `lib.rs` or `mod.rs`
+pub fn unregistered_migrations<P>(dir: P) -> std::io::Result<Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+    let lib = ["lib.rs", "mod.rs"]
+        .iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No lib.rs or mod.rs in '{}'", dir.display()),
+            )
+        })?;
+    let registered = registered_modules(&std::fs::read_to_string(lib)?);
+    Ok(migration_files(dir)?
+        .into_iter()
+        .filter(|file| !registered.contains(file))
+        .collect())
+}
+
+/// Fail the build if a migration file of `dir` is not registered in `MigratorTrait::migrations()`.
+/// Meant to be called from the `build.rs` of the migration crate:
+///
+/// ```ignore
+/// fn main() {
+///     sea_schema::migration::verify_migration_registration("src");
+/// }
+/// ```
+pub fn verify_migration_registration<P>(dir: P)
+where
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+    println!("cargo:rerun-if-changed={}", dir.display());
+    let unregistered = unregistered_migrations(dir).unwrap_or_else(|err| {
+        panic!(
+            "Fail to check the migrations in '{}': {}",
+            dir.display(),
+            err
+        )
+    });
+    if !unregistered.is_empty() {
+        panic!(
+            "Migration files not registered in `MigratorTrait::migrations()`: {}",
+            unregistered.join(", ")
+        );
+    }
+}
+
+/// Assert that a migrator registers the expected number of migrations, e.g. in a test of the
+/// migration crate, so that feature flags or module gating cannot silently exclude migrations:
+///
+/// ```ignore
+/// #[test]
+/// fn migration_count() {
+///     sea_schema::assert_migration_count!(Migrator, 42);
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_migration_count {
+    ($migrator:ty, $count:expr) => {{
+        let versions: Vec<String> = <$migrator as $crate::migration::MigratorTrait>::migrations()
+            .iter()
+            .map(|migration| $crate::migration::MigrationName::name(migration.as_ref()).to_owned())
+            .collect();
+        assert!(
+            versions.len() == $count,
+            "Expected {} migrations registered by `{}`, found {}: [{}]",
+            $count,
+            stringify!($migrator),
+            versions.len(),
+            versions.join(", ")
+        );
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registered_modules() {
+        let source = r#"
+            use sea_schema::migration::prelude::MigrationTrait;
+            mod m20220118_000001_create_cake_table;
+            mod m20220118_000002_create_fruit_table;
+
+            fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+                vec![
+                    Box::new(m20220118_000001_create_cake_table::Migration),
+                    Box::new(crate::m20220118_000002_create_fruit_table::Migration {}),
+                ]
+            }
+        "#;
+        assert_eq!(
+            registered_modules(source),
+            vec![
+                "m20220118_000001_create_cake_table",
+                "m20220118_000002_create_fruit_table",
+            ]
+        );
+    }
+
+    struct EmptyMigrator;
+
+    impl crate::migration::MigratorTrait for EmptyMigrator {
+        fn migrations() -> Vec<Box<dyn crate::migration::MigrationTrait>> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_assert_migration_count() {
+        crate::assert_migration_count!(EmptyMigrator, 0);
+        let res = std::panic::catch_unwind(|| crate::assert_migration_count!(EmptyMigrator, 1));
+        assert!(res.is_err());
+    }
+}
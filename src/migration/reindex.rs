@@ -0,0 +1,213 @@
+use super::{
+    delete_schema_meta_value, get_schema_meta_value, quote, set_schema_meta_value, SchemaManager,
+};
+use sea_orm::{DbBackend, DbErr, Statement};
+use tracing::info;
+
+/// Prefix of the `seaql_schema_meta` entries holding the definition of an index being rebuilt on SQLite
+pub const META_REBUILD_INDEX_PREFIX: &str = "rebuild_index:";
+
+/// Index Maintenance
+impl<'c> SchemaManager<'c> {
+    /// Rebuild an index without blocking writes where the backend allows it, e.g. to reclaim
+    /// the space of a bloated index. Statements are paced by the configured [`Throttle`](super::Throttle).
+    ///
+    /// - Postgres: `REINDEX INDEX CONCURRENTLY`, dropping the invalid copies left behind by an interrupted rebuild first
+    /// - MySQL: the index is recreated under a temporary name with an in-place, lock-free `ALTER TABLE`,
+    ///   then swapped with the original; an interrupted rebuild resumes from the temporary index
+    /// - SQLite: the index is dropped and recreated from its definition, kept in `seaql_schema_meta`
+    ///   until the index is recreated, so that an interrupted rebuild still recreates it
+    pub async fn rebuild_index(&self, table: &str, index: &str) -> Result<(), DbErr> {
+        info!("Rebuilding index '{}' of table '{}'", index, table);
+        match self.get_database_backend() {
+            DbBackend::Postgres => self.rebuild_index_postgres(index).await,
+            DbBackend::MySql => self.rebuild_index_mysql(table, index).await,
+            DbBackend::Sqlite => self.rebuild_index_sqlite(table, index).await,
+        }
+    }
+
+    async fn rebuild_index_postgres(&self, index: &str) -> Result<(), DbErr> {
+        let db_backend = DbBackend::Postgres;
+        let leftovers = self
+            .query_all_raw(Statement::from_sql_and_values(
+                db_backend,
+                r#"SELECT "c"."relname" AS "name"
+                    FROM "pg_index" AS "i"
+                    JOIN "pg_class" AS "c" ON "c"."oid" = "i"."indexrelid"
+                    JOIN "pg_namespace" AS "n" ON "n"."oid" = "c"."relnamespace"
+                    WHERE "n"."nspname" = current_schema()
+                        AND NOT "i"."indisvalid"
+                        AND "c"."relname" LIKE $1"#,
+                vec![format!("{}\\_cc%", index.replace('_', "\\_")).into()],
+            ))
+            .await?;
+        for row in leftovers {
+            let name: String = row.try_get("", "name")?;
+            info!(
+                "Dropping index '{}' left behind by an interrupted rebuild",
+                name
+            );
+            self.exec_raw(Statement::from_string(
+                db_backend,
+                format!(
+                    "DROP INDEX CONCURRENTLY IF EXISTS {}",
+                    quote(db_backend, &name)
+                ),
+            ))
+            .await?;
+        }
+        self.exec_raw(Statement::from_string(
+            db_backend,
+            format!("REINDEX INDEX CONCURRENTLY {}", quote(db_backend, index)),
+        ))
+        .await
+    }
+
+    async fn rebuild_index_mysql(&self, table: &str, index: &str) -> Result<(), DbErr> {
+        let db_backend = DbBackend::MySql;
+        let temporary = format!("{}_rebuild", index);
+        let has_original = self.has_index_mysql(table, index).await?;
+        let has_temporary = self.has_index_mysql(table, &temporary).await?;
+        let alter = |clauses: String| {
+            Statement::from_string(
+                db_backend,
+                format!(
+                    "ALTER TABLE {} {}, ALGORITHM=INPLACE, LOCK=NONE",
+                    quote(db_backend, table),
+                    clauses
+                ),
+            )
+        };
+        match (has_original, has_temporary) {
+            (false, false) => {
+                return Err(DbErr::Custom(format!(
+                    "Index '{}' of table '{}' does not exist",
+                    index, table
+                )))
+            }
+            // The rebuild was interrupted after dropping the original
+            (false, true) => {}
+            (true, has_temporary) => {
+                if !has_temporary {
+                    let definition = self.index_definition_mysql(table, index).await?;
+                    self.exec_raw(alter(format!(
+                        "ADD {}",
+                        definition.replacen("{name}", &quote(db_backend, &temporary), 1)
+                    )))
+                    .await?;
+                }
+                self.exec_raw(alter(format!("DROP INDEX {}", quote(db_backend, index))))
+                    .await?;
+            }
+        }
+        self.exec_raw(alter(format!(
+            "RENAME INDEX {} TO {}",
+            quote(db_backend, &temporary),
+            quote(db_backend, index)
+        )))
+        .await
+    }
+
+    async fn has_index_mysql(&self, table: &str, index: &str) -> Result<bool, DbErr> {
+        Ok(self
+            .query_one_raw(Statement::from_sql_and_values(
+                DbBackend::MySql,
+                "SELECT 1 AS `found` FROM `information_schema`.`STATISTICS` WHERE `TABLE_SCHEMA` = DATABASE() AND `TABLE_NAME` = ? AND `INDEX_NAME` = ? LIMIT 1",
+                vec![table.into(), index.into()],
+            ))
+            .await?
+            .is_some())
+    }
+
+    /// Definition of an index with a `{name}` placeholder, e.g. ``UNIQUE INDEX {name} (`name`(16) DESC)``
+    async fn index_definition_mysql(&self, table: &str, index: &str) -> Result<String, DbErr> {
+        let rows = self
+            .query_all_raw(Statement::from_sql_and_values(
+                DbBackend::MySql,
+                "SELECT `COLUMN_NAME` AS `column_name`, CAST(`SUB_PART` AS SIGNED) AS `sub_part`, CAST(`NON_UNIQUE` AS SIGNED) AS `non_unique`, `INDEX_TYPE` AS `index_type`, `COLLATION` AS `collation` FROM `information_schema`.`STATISTICS` WHERE `TABLE_SCHEMA` = DATABASE() AND `TABLE_NAME` = ? AND `INDEX_NAME` = ? ORDER BY `SEQ_IN_INDEX`",
+                vec![table.into(), index.into()],
+            ))
+            .await?;
+        let mut kind = "INDEX";
+        let mut parts = Vec::new();
+        for row in rows.iter() {
+            let column: Option<String> = row.try_get("", "column_name")?;
+            let column = column.ok_or_else(|| {
+                DbErr::Custom(format!(
+                    "Index '{}' is a functional index and cannot be rebuilt",
+                    index
+                ))
+            })?;
+            let sub_part: Option<i64> = row.try_get("", "sub_part")?;
+            let non_unique: i64 = row.try_get("", "non_unique")?;
+            let index_type: String = row.try_get("", "index_type")?;
+            let collation: Option<String> = row.try_get("", "collation")?;
+            kind = match (index_type.as_str(), non_unique) {
+                ("FULLTEXT", _) => "FULLTEXT INDEX",
+                ("SPATIAL", _) => "SPATIAL INDEX",
+                (_, 0) => "UNIQUE INDEX",
+                _ => "INDEX",
+            };
+            let mut part = quote(DbBackend::MySql, &column);
+            if let Some(sub_part) = sub_part {
+                part.push_str(&format!("({})", sub_part));
+            }
+            if collation.as_deref() == Some("D") {
+                part.push_str(" DESC");
+            }
+            parts.push(part);
+        }
+        if parts.is_empty() {
+            return Err(DbErr::Custom(format!(
+                "Index '{}' of table '{}' does not exist",
+                index, table
+            )));
+        }
+        Ok(format!("{} {{name}} ({})", kind, parts.join(", ")))
+    }
+
+    async fn rebuild_index_sqlite(&self, table: &str, index: &str) -> Result<(), DbErr> {
+        let db_backend = DbBackend::Sqlite;
+        let db = self.get_connection();
+        let key = format!("{}{}.{}", META_REBUILD_INDEX_PREFIX, table, index);
+        let definition: Option<Option<String>> = match self
+            .query_one_raw(Statement::from_sql_and_values(
+                db_backend,
+                "SELECT \"sql\" FROM \"sqlite_master\" WHERE \"type\" = 'index' AND \"tbl_name\" = ? AND \"name\" = ?",
+                vec![table.into(), index.into()],
+            ))
+            .await?
+        {
+            Some(row) => Some(row.try_get("", "sql")?),
+            None => None,
+        };
+        let definition = match definition {
+            // Indexes created by SQLite for `UNIQUE` and `PRIMARY KEY` constraints have no definition
+            Some(None) => {
+                return Err(DbErr::Custom(format!(
+                    "Index '{}' backs a constraint of table '{}' and cannot be rebuilt",
+                    index, table
+                )))
+            }
+            Some(Some(definition)) => {
+                set_schema_meta_value(db, &key, &definition).await?;
+                self.exec_raw(Statement::from_string(
+                    db_backend,
+                    format!("DROP INDEX {}", quote(db_backend, index)),
+                ))
+                .await?;
+                definition
+            }
+            // The rebuild was interrupted after dropping the index
+            None => get_schema_meta_value(db, &key).await?.ok_or_else(|| {
+                DbErr::Custom(format!(
+                    "Index '{}' of table '{}' does not exist",
+                    index, table
+                ))
+            })?,
+        };
+        self.exec_raw(Statement::from_string(db_backend, definition))
+            .await?;
+        delete_schema_meta_value(db, &key).await
+    }
+}
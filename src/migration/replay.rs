@@ -0,0 +1,285 @@
+use super::{escape, unescape};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use std::fmt::Display;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// First line of a recording, with the version of the format
+const RECORDING_HEADER: &str = "sea-schema-recording 1";
+
+/// A statement executed by the migrations of a run, as written by [`StatementRecorder`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedStatement {
+    /// Migration the statement was executed for
+    pub migration: String,
+    /// The statement with its bound values inlined
+    pub sql: String,
+    pub duration_ms: u64,
+    /// Error the statement failed with, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+/// Every statement the migrations of a run executed through the schema manager, in order, with
+/// their timings and outcomes, e.g. attached to a bug report and replayed against a scratch
+/// database with [`Recording::replay`].
+///
+/// The file is line-based: a header line, then for every run a `run` line with the run id, the
+/// command, the backend and the crate version, followed by a `statement` line per statement, with
+/// tab-separated fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recording {
+    pub run_id: String,
+    pub command: String,
+    pub backend: DbBackend,
+    /// Version of sea-schema that recorded the run
+    pub crate_version: String,
+    pub statements: Vec<RecordedStatement>,
+}
+
+/// Writes the statements of a run to a recording file as they complete, so that the statements
+/// leading to a failure are kept, see [`MigratorTrait::record_statements`](super::MigratorTrait::record_statements).
+/// Runs are appended to the file, e.g. both the `down` and the `up` run of a `refresh`.
+#[derive(Debug)]
+pub struct StatementRecorder {
+    path: PathBuf,
+    file: Mutex<std::fs::File>,
+}
+
+/// Result of [`Recording::replay`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayOutcome {
+    /// Every statement had the recorded outcome, including the recorded failures
+    Completed { failures: usize },
+    /// The statement at `index` succeeded where it failed when recorded, or the other way around
+    Diverged {
+        index: usize,
+        recorded: Option<String>,
+        replayed: Option<String>,
+    },
+}
+
+impl Display for ReplayOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Completed { failures: 0 } => write!(f, "Replayed every statement"),
+            Self::Completed { failures } => write!(
+                f,
+                "Replayed every statement, reproducing {} recorded failures",
+                failures
+            ),
+            Self::Diverged {
+                index,
+                recorded,
+                replayed,
+            } => write!(
+                f,
+                "Statement {} diverged: recorded {}, replayed {}",
+                index + 1,
+                recorded.as_deref().unwrap_or("success"),
+                replayed.as_deref().unwrap_or("success")
+            ),
+        }
+    }
+}
+
+impl StatementRecorder {
+    /// Append a run to the recording file, creating it if needed
+    pub fn open<P>(
+        path: P,
+        db_backend: DbBackend,
+        run_id: &str,
+        command: &str,
+    ) -> Result<Self, DbErr>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let io_err = |err: std::io::Error| {
+            DbErr::Custom(format!(
+                "Fail to open recording '{}': {}",
+                path.display(),
+                err
+            ))
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(io_err)?;
+        if file.metadata().map_err(io_err)?.len() == 0 {
+            writeln!(file, "{}", RECORDING_HEADER).map_err(io_err)?;
+        }
+        writeln!(
+            file,
+            "run\t{}\t{}\t{}\t{}",
+            escape(run_id),
+            escape(command),
+            backend_name(db_backend),
+            env!("CARGO_PKG_VERSION")
+        )
+        .map_err(io_err)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a statement, a failure to write is logged without failing the migration
+    pub(crate) fn record(
+        &self,
+        migration: &str,
+        stmt: &Statement,
+        duration_ms: u64,
+        error: Option<&DbErr>,
+    ) {
+        let line = format!(
+            "statement\t{}\t{}\t{}\t{}\n",
+            escape(migration),
+            duration_ms,
+            match error {
+                Some(err) => escape(&err.to_string()),
+                None => "\\N".to_owned(),
+            },
+            escape(&stmt.to_string())
+        );
+        let mut file = self.file.lock().unwrap();
+        if let Err(err) = file.write_all(line.as_bytes()).and_then(|_| file.flush()) {
+            warn!("Fail to write recording '{}': {}", self.path.display(), err);
+        }
+    }
+}
+
+impl Recording {
+    /// Parse the runs of a recording file written by [`StatementRecorder`], in order
+    pub fn parse(text: &str) -> Result<Vec<Self>, DbErr> {
+        let mut lines = text.lines();
+        if lines.next() != Some(RECORDING_HEADER) {
+            return Err(DbErr::Custom(format!(
+                "Recording does not start with '{}'",
+                RECORDING_HEADER
+            )));
+        }
+        let invalid = |line: usize| DbErr::Custom(format!("Invalid recording line {}", line));
+        let mut recordings: Vec<Self> = Vec::new();
+        for (i, line) in lines.enumerate().filter(|(_, line)| !line.is_empty()) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match (fields.as_slice(), recordings.last_mut()) {
+                (["run", run_id, command, backend, crate_version], _) => recordings.push(Self {
+                    run_id: unescape(run_id),
+                    command: unescape(command),
+                    backend: parse_backend(backend).ok_or_else(|| invalid(i + 2))?,
+                    crate_version: crate_version.to_string(),
+                    statements: Vec::new(),
+                }),
+                (["statement", migration, duration_ms, error, sql], Some(recording)) => {
+                    recording.statements.push(RecordedStatement {
+                        migration: unescape(migration),
+                        sql: unescape(sql),
+                        duration_ms: duration_ms.parse().map_err(|_| invalid(i + 2))?,
+                        error: match *error {
+                            "\\N" => None,
+                            error => Some(unescape(error)),
+                        },
+                    })
+                }
+                _ => return Err(invalid(i + 2)),
+            }
+        }
+        Ok(recordings)
+    }
+
+    /// Execute the recorded statements in order against a scratch database of the same backend,
+    /// e.g. restored from the schema the run started from, stopping at the first statement whose
+    /// outcome differs from the recorded one. Never replay a recording against the database it
+    /// was recorded on, its statements would be applied twice.
+    pub async fn replay(&self, db: &DbConn) -> Result<ReplayOutcome, DbErr> {
+        let db_backend = db.get_database_backend();
+        if db_backend != self.backend {
+            return Err(DbErr::Custom(format!(
+                "The recording was made on {}, it cannot be replayed on {}",
+                backend_name(self.backend),
+                backend_name(db_backend)
+            )));
+        }
+        let mut failures = 0;
+        for (index, recorded) in self.statements.iter().enumerate() {
+            let replayed = db
+                .execute(Statement::from_string(db_backend, recorded.sql.clone()))
+                .await
+                .err()
+                .map(|err| err.to_string());
+            match (&recorded.error, &replayed) {
+                (None, None) => {}
+                (Some(_), Some(_)) => failures += 1,
+                _ => {
+                    return Ok(ReplayOutcome::Diverged {
+                        index,
+                        recorded: recorded.error.clone(),
+                        replayed,
+                    })
+                }
+            }
+        }
+        Ok(ReplayOutcome::Completed { failures })
+    }
+}
+
+fn backend_name(db_backend: DbBackend) -> &'static str {
+    match db_backend {
+        DbBackend::MySql => "mysql",
+        DbBackend::Postgres => "postgres",
+        DbBackend::Sqlite => "sqlite",
+    }
+}
+
+fn parse_backend(name: &str) -> Option<DbBackend> {
+    match name {
+        "mysql" => Some(DbBackend::MySql),
+        "postgres" => Some(DbBackend::Postgres),
+        "sqlite" => Some(DbBackend::Sqlite),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("sea-schema-recording-{}.txt", std::process::id()));
+        let recorder = StatementRecorder::open(&path, DbBackend::Sqlite, "run-1", "down").unwrap();
+        let stmt = Statement::from_string(
+            DbBackend::Sqlite,
+            "CREATE TABLE \"cake\" (\n\"id\" integer\n)".to_owned(),
+        );
+        recorder.record("m1", &stmt, 3, None);
+        // A second run, e.g. the up of a refresh, is appended
+        let recorder = StatementRecorder::open(&path, DbBackend::Sqlite, "run-2", "up").unwrap();
+        let err = DbErr::Custom("table cake\texists".to_owned());
+        recorder.record("m1", &stmt, 1, Some(&err));
+
+        let recordings = Recording::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(recordings.len(), 2);
+        assert_eq!(recordings[0].run_id, "run-1");
+        assert_eq!(recordings[0].command, "down");
+        assert_eq!(recordings[0].backend, DbBackend::Sqlite);
+        assert_eq!(recordings[0].statements.len(), 1);
+        assert_eq!(recordings[0].statements[0].sql, stmt.sql);
+        assert_eq!(recordings[0].statements[0].error, None);
+        assert_eq!(recordings[1].run_id, "run-2");
+        assert_eq!(recordings[1].statements.len(), 1);
+        assert_eq!(
+            recordings[1].statements[0].error,
+            Some("Custom Error: table cake\texists".to_owned())
+        );
+    }
+}
@@ -6,6 +6,19 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub version: String,
     pub applied_at: i64,
+    /// Build version of the application that applied the migration
+    pub app_version: Option<String>,
+    /// Checksum of the statements executed by the migration, `None` if it executed statements
+    /// on the connection directly
+    pub checksum: Option<String>,
+    /// Time taken to apply the migration, in milliseconds
+    pub duration_ms: Option<i64>,
+    /// Who applied the migration, see [`MigratorTrait::applied_by`](super::MigratorTrait::applied_by)
+    pub applied_by: Option<String>,
+    /// Id of the migrator run that applied the migration
+    pub run_id: Option<String>,
+    /// Release batch the migration was applied in, see [`MigratorTrait::batch`](super::MigratorTrait::batch)
+    pub batch: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
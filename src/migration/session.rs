@@ -0,0 +1,174 @@
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, Statement};
+use std::time::Duration;
+use tracing::info;
+
+/// A session-level setting applied by the migrator at the start of every run
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionSetting {
+    /// Abort any statement that takes longer than the specified duration (Postgres, MySQL)
+    StatementTimeout(Duration),
+    /// Abort any statement waiting longer than the specified duration to acquire a lock (Postgres, MySQL)
+    LockTimeout(Duration),
+    /// Set the SQL mode of the session (MySQL)
+    SqlMode(String),
+    /// Set the client character set (Postgres, MySQL)
+    Names(String),
+    /// Wait for the specified duration when the database is locked before failing (SQLite)
+    BusyTimeout(Duration),
+    /// A raw statement executed as-is on every backend
+    Custom(String),
+}
+
+impl SessionSetting {
+    /// Build the statement applying this setting, `None` if the setting does not apply to the backend
+    pub fn to_statement(&self, db_backend: DbBackend) -> Option<Statement> {
+        let sql = match (self, db_backend) {
+            (Self::StatementTimeout(timeout), DbBackend::Postgres) => {
+                format!("SET statement_timeout = {}", timeout.as_millis())
+            }
+            (Self::StatementTimeout(timeout), DbBackend::MySql) => {
+                format!("SET SESSION max_execution_time = {}", timeout.as_millis())
+            }
+            (Self::LockTimeout(timeout), DbBackend::Postgres) => {
+                format!("SET lock_timeout = {}", timeout.as_millis())
+            }
+            (Self::LockTimeout(timeout), DbBackend::MySql) => format!(
+                "SET SESSION lock_wait_timeout = {}",
+                timeout.as_secs().max(1)
+            ),
+            (Self::SqlMode(mode), DbBackend::MySql) => {
+                format!("SET SESSION sql_mode = '{}'", escape(mode))
+            }
+            (Self::Names(charset), DbBackend::MySql) => format!("SET NAMES '{}'", escape(charset)),
+            (Self::Names(charset), DbBackend::Postgres) => {
+                format!("SET client_encoding = '{}'", escape(charset))
+            }
+            (Self::BusyTimeout(timeout), DbBackend::Sqlite) => {
+                format!("PRAGMA busy_timeout = {}", timeout.as_millis())
+            }
+            (Self::Custom(sql), _) => sql.to_owned(),
+            _ => return None,
+        };
+        Some(Statement::from_string(db_backend, sql))
+    }
+
+    /// Build the statement reverting this setting to the default of the server, `None` if the
+    /// setting does not apply to the backend or cannot be reverted
+    pub fn to_reset_statement(&self, db_backend: DbBackend) -> Option<Statement> {
+        let sql = match (self, db_backend) {
+            (Self::StatementTimeout(_), DbBackend::Postgres) => "RESET statement_timeout",
+            (Self::StatementTimeout(_), DbBackend::MySql) => {
+                "SET SESSION max_execution_time = DEFAULT"
+            }
+            (Self::LockTimeout(_), DbBackend::Postgres) => "RESET lock_timeout",
+            (Self::LockTimeout(_), DbBackend::MySql) => "SET SESSION lock_wait_timeout = DEFAULT",
+            (Self::SqlMode(_), DbBackend::MySql) => "SET SESSION sql_mode = DEFAULT",
+            (Self::Names(_), DbBackend::Postgres) => "RESET client_encoding",
+            _ => return None,
+        };
+        Some(Statement::from_string(db_backend, sql.to_owned()))
+    }
+}
+
+/// Apply session settings to a connection held by a transaction. On Postgres, settings are set
+/// with `SET LOCAL` and end with the transaction.
+pub(crate) async fn apply_session_settings(
+    db: &dyn ConnectionTrait,
+    settings: &[SessionSetting],
+) -> Result<(), DbErr> {
+    let db_backend = db.get_database_backend();
+    for setting in settings.iter() {
+        if let Some(mut stmt) = setting.to_statement(db_backend) {
+            if db_backend == DbBackend::Postgres && stmt.sql.starts_with("SET ") {
+                stmt.sql = stmt.sql.replacen("SET ", "SET LOCAL ", 1);
+            }
+            info!("Applying session setting: {}", stmt.sql);
+            db.execute(stmt).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Revert session settings applied with [`apply_session_settings`] to the defaults of the
+/// server, before the connection returns to the pool. Nothing is reverted on Postgres.
+pub(crate) async fn revert_session_settings(
+    db: &dyn ConnectionTrait,
+    settings: &[SessionSetting],
+) -> Result<(), DbErr> {
+    let db_backend = db.get_database_backend();
+    if db_backend == DbBackend::Postgres {
+        return Ok(());
+    }
+    for setting in settings.iter() {
+        if let Some(stmt) = setting.to_reset_statement(db_backend) {
+            info!("Reverting session setting: {}", stmt.sql);
+            db.execute(stmt).await?;
+        }
+    }
+    Ok(())
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_setting() {
+        let sql = |setting: SessionSetting, db_backend| {
+            setting
+                .to_statement(db_backend)
+                .map(|stmt| stmt.sql)
+                .unwrap_or_default()
+        };
+        assert_eq!(
+            sql(
+                SessionSetting::StatementTimeout(Duration::from_secs(5)),
+                DbBackend::Postgres
+            ),
+            "SET statement_timeout = 5000"
+        );
+        assert_eq!(
+            sql(
+                SessionSetting::LockTimeout(Duration::from_millis(100)),
+                DbBackend::MySql
+            ),
+            "SET SESSION lock_wait_timeout = 1"
+        );
+        assert_eq!(
+            sql(
+                SessionSetting::SqlMode("ANSI_QUOTES".to_owned()),
+                DbBackend::MySql
+            ),
+            "SET SESSION sql_mode = 'ANSI_QUOTES'"
+        );
+        assert_eq!(
+            sql(
+                SessionSetting::BusyTimeout(Duration::from_secs(1)),
+                DbBackend::Sqlite
+            ),
+            "PRAGMA busy_timeout = 1000"
+        );
+        assert_eq!(
+            sql(
+                SessionSetting::SqlMode("ANSI_QUOTES".to_owned()),
+                DbBackend::Postgres
+            ),
+            ""
+        );
+        assert_eq!(
+            SessionSetting::StatementTimeout(Duration::from_secs(5))
+                .to_reset_statement(DbBackend::MySql)
+                .map(|stmt| stmt.sql),
+            Some("SET SESSION max_execution_time = DEFAULT".to_owned())
+        );
+        assert_eq!(
+            SessionSetting::BusyTimeout(Duration::from_secs(1))
+                .to_reset_statement(DbBackend::Sqlite),
+            None
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use super::{quote, SchemaManager};
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, Statement};
+use tracing::info;
+
+/// Where a [`DatabaseSetting`] is applied
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingScope {
+    /// The current database, for the sessions opened afterwards: `ALTER DATABASE ... SET` (Postgres)
+    /// or `PRAGMA` (SQLite)
+    Database,
+    /// The whole server: `SET GLOBAL` (MySQL), lost on restart
+    Global,
+    /// The whole server, surviving restarts: `ALTER SYSTEM SET` (Postgres) or `SET PERSIST` (MySQL).
+    /// On Postgres the configuration has to be reloaded for the setting to take effect.
+    System,
+}
+
+/// A database-level or server-level setting changed by a migration, e.g. the time zone or the
+/// SQL mode, so that settings are versioned alongside the schema. Changing such settings usually
+/// requires elevated privileges.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseSetting {
+    pub name: String,
+    /// The value, `None` to reset the setting to its default
+    pub value: Option<String>,
+    pub scope: SettingScope,
+}
+
+impl DatabaseSetting {
+    pub fn new<N, V>(scope: SettingScope, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            value: Some(value.into()),
+            scope,
+        }
+    }
+
+    /// Reset a setting to its default
+    pub fn reset<N>(scope: SettingScope, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            value: None,
+            scope,
+        }
+    }
+
+    /// Time zone of the database on Postgres, of the server on MySQL
+    pub fn time_zone<V>(db_backend: DbBackend, time_zone: V) -> Self
+    where
+        V: Into<String>,
+    {
+        match db_backend {
+            DbBackend::MySql => Self::new(SettingScope::Global, "time_zone", time_zone),
+            _ => Self::new(SettingScope::Database, "timezone", time_zone),
+        }
+    }
+
+    /// SQL mode of the server (MySQL)
+    pub fn sql_mode<V>(sql_mode: V) -> Self
+    where
+        V: Into<String>,
+    {
+        Self::new(SettingScope::Global, "sql_mode", sql_mode)
+    }
+
+    /// Build the statement applying this setting to the database `database` (the current one),
+    /// failing if the backend has no such scope
+    pub fn to_statement(&self, db_backend: DbBackend, database: &str) -> Result<Statement, DbErr> {
+        ensure_setting_name(&self.name)?;
+        let name = &self.name;
+        let value = self.value.as_deref().map(literal);
+        let sql = match (self.scope, db_backend, value) {
+            (SettingScope::Database, DbBackend::Postgres, Some(value)) => format!(
+                "ALTER DATABASE {} SET {} = {}",
+                quote(db_backend, database),
+                name,
+                value
+            ),
+            (SettingScope::Database, DbBackend::Postgres, None) => format!(
+                "ALTER DATABASE {} RESET {}",
+                quote(db_backend, database),
+                name
+            ),
+            (SettingScope::Database, DbBackend::Sqlite, Some(value)) => {
+                format!("PRAGMA {} = {}", name, value)
+            }
+            (SettingScope::Global, DbBackend::MySql, Some(value)) => {
+                format!("SET GLOBAL {} = {}", name, value)
+            }
+            (SettingScope::Global, DbBackend::MySql, None) => {
+                format!("SET GLOBAL {} = DEFAULT", name)
+            }
+            (SettingScope::System, DbBackend::Postgres, Some(value)) => {
+                format!("ALTER SYSTEM SET {} = {}", name, value)
+            }
+            (SettingScope::System, DbBackend::Postgres, None) => {
+                format!("ALTER SYSTEM RESET {}", name)
+            }
+            (SettingScope::System, DbBackend::MySql, Some(value)) => {
+                format!("SET PERSIST {} = {}", name, value)
+            }
+            (SettingScope::System, DbBackend::MySql, None) => {
+                format!("RESET PERSIST IF EXISTS {}", name)
+            }
+            (scope, db_backend, _) => {
+                return Err(DbErr::Custom(format!(
+                    "Setting '{}' cannot be {} at the {:?} scope on {:?}",
+                    name,
+                    match self.value {
+                        Some(_) => "set",
+                        None => "reset",
+                    },
+                    scope,
+                    db_backend
+                )))
+            }
+        };
+        Ok(Statement::from_string(db_backend, sql))
+    }
+}
+
+/// Database Settings
+impl<'c> SchemaManager<'c> {
+    /// Change a database-level or server-level setting
+    pub async fn set_database_setting(&self, setting: DatabaseSetting) -> Result<(), DbErr> {
+        let db_backend = self.get_database_backend();
+        let database = match (db_backend, setting.scope) {
+            (DbBackend::Postgres, SettingScope::Database) => self.current_database().await?,
+            _ => String::new(),
+        };
+        let stmt = setting.to_statement(db_backend, &database)?;
+        info!("Changing database setting: {}", stmt.sql);
+        self.exec_raw(stmt).await
+    }
+
+    /// Current value of a setting as seen by the session, e.g. to restore it when rolling back
+    pub async fn get_database_setting(&self, name: &str) -> Result<Option<String>, DbErr> {
+        ensure_setting_name(name)?;
+        let db_backend = self.get_database_backend();
+        let stmt = match db_backend {
+            DbBackend::Postgres => Statement::from_sql_and_values(
+                db_backend,
+                "SELECT current_setting($1, true) AS value",
+                vec![name.into()],
+            ),
+            DbBackend::MySql => Statement::from_string(
+                db_backend,
+                format!("SELECT CAST(@@GLOBAL.{} AS CHAR) AS value", name),
+            ),
+            DbBackend::Sqlite => Statement::from_string(
+                db_backend,
+                format!(
+                    "SELECT CAST({} AS TEXT) AS value FROM pragma_{}()",
+                    name, name
+                ),
+            ),
+        };
+        match self.query_one_raw(stmt).await? {
+            Some(row) => row.try_get("", "value"),
+            None => Ok(None),
+        }
+    }
+
+    async fn current_database(&self) -> Result<String, DbErr> {
+        // In dry-run mode the statement is only recorded
+        if self.is_dry_run() {
+            return Ok("current_database".to_owned());
+        }
+        let row = self
+            .get_connection()
+            .query_one(Statement::from_string(
+                self.get_database_backend(),
+                "SELECT current_database() AS name".to_owned(),
+            ))
+            .await?;
+        match row {
+            Some(row) => row.try_get("", "name"),
+            None => Err(DbErr::Custom(
+                "Fail to query the current database".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Setting names are interpolated into statements, only allow identifiers such as `work_mem`
+/// or `pg_stat_statements.track`
+fn ensure_setting_name(name: &str) -> Result<(), DbErr> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    match valid {
+        true => Ok(()),
+        false => Err(DbErr::Custom(format!("Invalid setting name '{}'", name))),
+    }
+}
+
+/// Numbers are used as-is, any other value is quoted as a string
+fn literal(value: &str) -> String {
+    match value.parse::<f64>() {
+        Ok(_) => value.to_owned(),
+        Err(_) => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_statement() {
+        let sql = |setting: DatabaseSetting, db_backend| {
+            setting
+                .to_statement(db_backend, "shop")
+                .map(|stmt| stmt.sql)
+                .unwrap_or_default()
+        };
+        assert_eq!(
+            sql(
+                DatabaseSetting::time_zone(DbBackend::Postgres, "UTC"),
+                DbBackend::Postgres
+            ),
+            r#"ALTER DATABASE "shop" SET timezone = 'UTC'"#
+        );
+        assert_eq!(
+            sql(
+                DatabaseSetting::sql_mode("STRICT_ALL_TABLES"),
+                DbBackend::MySql
+            ),
+            "SET GLOBAL sql_mode = 'STRICT_ALL_TABLES'"
+        );
+        assert_eq!(
+            sql(
+                DatabaseSetting::new(SettingScope::System, "work_mem", "65536"),
+                DbBackend::Postgres
+            ),
+            "ALTER SYSTEM SET work_mem = 65536"
+        );
+        assert!(DatabaseSetting::sql_mode("ANSI")
+            .to_statement(DbBackend::Postgres, "shop")
+            .is_err());
+        assert!(
+            DatabaseSetting::new(SettingScope::Global, "sql_mode; DROP", "")
+                .to_statement(DbBackend::MySql, "shop")
+                .is_err()
+        );
+    }
+}
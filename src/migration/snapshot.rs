@@ -0,0 +1,182 @@
+use super::{get_schema_meta_value, probe_columns, probe_tables, ColumnInfo, META_SCHEMA_HASH};
+use sea_orm::{DbConn, DbErr};
+use sha2::{Digest, Sha256};
+
+/// First line of a serialized [`SchemaSnapshot`], with the version of the format
+const SNAPSHOT_HEADER: &str = "sea-schema-snapshot 1";
+
+/// The tables and columns of a schema, which can be serialized and embedded in a binary so that
+/// tools only needing schema knowledge, e.g. linting or rendering plans, start without
+/// discovering the schema, see [`MigratorTrait::schema_cache`](super::MigratorTrait::schema_cache).
+///
+/// The serialized form is line-based: a header line, then a `table` line per table followed by
+/// a `column` line per column, with tab-separated fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaSnapshot {
+    /// Tables sorted by name, with their columns in ordinal order
+    pub tables: Vec<(String, Vec<ColumnInfo>)>,
+}
+
+impl SchemaSnapshot {
+    /// Discover the tables and columns of the current schema
+    pub async fn discover(db: &DbConn) -> Result<Self, DbErr> {
+        let mut names = probe_tables(db).await?;
+        names.sort();
+        let mut tables = Vec::new();
+        for name in names.into_iter() {
+            let columns = probe_columns(db, &name).await?;
+            tables.push((name, columns));
+        }
+        Ok(Self { tables })
+    }
+
+    /// Columns of a table, `None` if the table is not in the snapshot
+    pub fn columns(&self, table: &str) -> Option<&[ColumnInfo]> {
+        self.tables
+            .iter()
+            .find(|(name, _)| name == table)
+            .map(|(_, columns)| columns.as_slice())
+    }
+
+    /// Hash of the snapshot, as recorded in `seaql_schema_meta` by the migrator, see
+    /// [`schema_snapshot_hash`](super::schema_snapshot_hash)
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (table, columns) in self.tables.iter() {
+            hasher.update(table.as_bytes());
+            hasher.update([0]);
+            for column in columns.iter() {
+                let nullable = match column.nullable {
+                    true => "YES",
+                    false => "NO",
+                };
+                for value in [column.name.as_str(), column.column_type.as_str(), nullable] {
+                    hasher.update(value.as_bytes());
+                    hasher.update([0]);
+                }
+            }
+            hasher.update([1]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Confirm the snapshot matches the schema recorded at the end of the last migrator run,
+    /// with a single query instead of discovering the schema
+    pub async fn is_current(&self, db: &DbConn) -> Result<bool, DbErr> {
+        Ok(get_schema_meta_value(db, META_SCHEMA_HASH).await? == Some(self.hash()))
+    }
+
+    /// Serialize the snapshot, to be parsed back with [`SchemaSnapshot::parse`]
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", SNAPSHOT_HEADER);
+        for (table, columns) in self.tables.iter() {
+            text.push_str(&format!("table\t{}\n", escape(table)));
+            for column in columns.iter() {
+                text.push_str(&format!(
+                    "column\t{}\t{}\t{}\t{}\n",
+                    escape(&column.name),
+                    escape(&column.column_type),
+                    match column.nullable {
+                        true => "YES",
+                        false => "NO",
+                    },
+                    match &column.default {
+                        Some(default) => escape(default),
+                        None => "\\N".to_owned(),
+                    }
+                ));
+            }
+        }
+        text
+    }
+
+    /// Parse a snapshot serialized with [`SchemaSnapshot::to_text`]
+    pub fn parse(text: &str) -> Result<Self, DbErr> {
+        let mut lines = text.lines();
+        if lines.next() != Some(SNAPSHOT_HEADER) {
+            return Err(DbErr::Custom(format!(
+                "Schema snapshot does not start with '{}'",
+                SNAPSHOT_HEADER
+            )));
+        }
+        let mut tables: Vec<(String, Vec<ColumnInfo>)> = Vec::new();
+        for (i, line) in lines.enumerate().filter(|(_, line)| !line.is_empty()) {
+            let invalid = || DbErr::Custom(format!("Invalid schema snapshot line {}", i + 2));
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                ["table", name] => tables.push((unescape(name), Vec::new())),
+                ["column", name, column_type, nullable, default] => {
+                    let (_, columns) = tables.last_mut().ok_or_else(invalid)?;
+                    columns.push(ColumnInfo {
+                        name: unescape(name),
+                        column_type: unescape(column_type),
+                        nullable: match *nullable {
+                            "YES" => true,
+                            "NO" => false,
+                            _ => return Err(invalid()),
+                        },
+                        default: match *default {
+                            "\\N" => None,
+                            default => Some(unescape(default)),
+                        },
+                    });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Self { tables })
+    }
+}
+
+/// Escape the characters separating the fields and lines of a serialized snapshot
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+pub(crate) fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let column = |name: &str, default: Option<&str>| ColumnInfo {
+            name: name.to_owned(),
+            column_type: "text".to_owned(),
+            nullable: default.is_none(),
+            default: default.map(|default| default.to_owned()),
+        };
+        let snapshot = SchemaSnapshot {
+            tables: vec![
+                ("cake".to_owned(), vec![column("name", Some("'a\tb\\n'"))]),
+                ("empty".to_owned(), vec![]),
+                ("fruit".to_owned(), vec![column("name", None)]),
+            ],
+        };
+        let parsed = SchemaSnapshot::parse(&snapshot.to_text()).unwrap();
+        assert_eq!(parsed, snapshot);
+        assert_eq!(parsed.hash(), snapshot.hash());
+        assert_eq!(parsed.columns("empty"), Some(&[][..]));
+        assert!(SchemaSnapshot::parse("column\tname").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt::Display;
+
+/// Target of the statements logged at debug level, see
+/// [`MigratorTrait::log_statements`](super::MigratorTrait::log_statements)
+pub const STATEMENT_TARGET: &str = "sea_schema::migration::statement";
+
+/// The way a statement destroys schema or data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DestructiveKind {
+    /// `DROP TABLE`, `DROP INDEX`, `DROP TYPE` and alike, holding the dropped object kind
+    Drop(String),
+    /// `TRUNCATE`
+    Truncate,
+    /// Dropping a column, constraint or foreign key with `ALTER TABLE`
+    AlterDrop(String),
+    /// The migration could not be run in dry-run mode, its statements are unknown
+    Unplanned,
+}
+
+/// Class of a statement, deciding the connection it is executed on when data statements
+/// run as a restricted user, see [`SchemaManager::dml_connection`](super::SchemaManager::dml_connection)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatementClass {
+    /// Data definition and anything else, e.g. `CREATE`, `ALTER`, `GRANT` or `SET`
+    Ddl,
+    /// Data manipulation and queries, e.g. `INSERT`, `UPDATE`, `DELETE` or `SELECT`
+    Dml,
+}
+
+/// A destructive statement executed by a migration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DestructiveStatement {
+    pub migration: String,
+    pub kind: DestructiveKind,
+    /// The statement, or the reason the migration could not be planned
+    pub sql: String,
+}
+
+impl Display for DestructiveKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Drop(object) => write!(f, "DROP {}", object),
+            Self::Truncate => write!(f, "TRUNCATE"),
+            Self::AlterDrop(object) => write!(f, "ALTER TABLE ... DROP {}", object),
+            Self::Unplanned => write!(f, "UNPLANNED"),
+        }
+    }
+}
+
+impl Display for DestructiveStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.migration, self.kind, self.sql)
+    }
+}
+
+/// Split a statement into upper-cased keywords, replacing string literals and
+/// quoted identifiers with a placeholder so their content is never mistaken for a keyword
+pub(crate) fn keywords(sql: &str) -> Vec<String> {
+    tokens(sql, false)
+}
+
+/// Split a statement into tokens like [`keywords`], keeping quoted identifiers with their quotes
+/// if `keep_quoted`; string literals are always replaced with a placeholder
+pub(crate) fn tokens(sql: &str, keep_quoted: bool) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                let mut quoted = c.to_string();
+                while let Some(next) = chars.next() {
+                    quoted.push(next);
+                    if next == c {
+                        // A doubled quote is an escaped quote
+                        if chars.peek() == Some(&c) {
+                            quoted.push(c);
+                            chars.next();
+                            continue;
+                        }
+                        break;
+                    }
+                }
+                if keep_quoted && c != '\'' {
+                    token.push_str(&quoted);
+                } else {
+                    token.push('?');
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' => token.extend(c.to_uppercase()),
+            c => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                if !c.is_whitespace() {
+                    tokens.push(c.to_string());
+                }
+            }
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Classify a statement as destructive, `None` if it does not drop or truncate anything
+pub fn destructive_kind(sql: &str) -> Option<DestructiveKind> {
+    let keywords = keywords(sql);
+    let keyword = |i: usize| keywords.get(i).map(|k| k.as_str()).unwrap_or_default();
+    match (keyword(0), keyword(1)) {
+        ("DROP", object) => Some(DestructiveKind::Drop(object.to_owned())),
+        ("TRUNCATE", _) => Some(DestructiveKind::Truncate),
+        ("ALTER", "TABLE") => keywords
+            .iter()
+            .enumerate()
+            .filter(|(_, k)| k.as_str() == "DROP")
+            .find_map(|(i, _)| match keyword(i + 1) {
+                // `ALTER COLUMN ... DROP DEFAULT` and `DROP NOT NULL` only relax the column
+                "DEFAULT" | "NOT" => None,
+                "COLUMN" | "CONSTRAINT" | "FOREIGN" | "INDEX" | "PRIMARY" => {
+                    Some(DestructiveKind::AlterDrop(keyword(i + 1).to_owned()))
+                }
+                // MySQL allows omitting the `COLUMN` keyword
+                _ => Some(DestructiveKind::AlterDrop("COLUMN".to_owned())),
+            }),
+        _ => None,
+    }
+}
+
+/// Classify a statement by its leading keyword, see [`StatementClass`]
+pub fn statement_class(sql: &str) -> StatementClass {
+    let keywords = keywords(sql);
+    match keywords
+        .iter()
+        .find(|k| k.as_str() != "(")
+        .map(|k| k.as_str())
+    {
+        Some(
+            "SELECT" | "INSERT" | "UPDATE" | "DELETE" | "REPLACE" | "MERGE" | "WITH" | "VALUES",
+        ) => StatementClass::Dml,
+        _ => StatementClass::Ddl,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keywords() {
+        assert_eq!(
+            keywords(r#"INSERT INTO "cake" ("name") VALUES ('drop ''table''')"#),
+            vec!["INSERT", "INTO", "?", "(", "?", ")", "VALUES", "(", "?", ")"]
+        );
+    }
+
+    #[test]
+    fn test_destructive_kind() {
+        assert_eq!(
+            destructive_kind(r#"DROP TABLE IF EXISTS "cake""#),
+            Some(DestructiveKind::Drop("TABLE".to_owned()))
+        );
+        assert_eq!(
+            destructive_kind("truncate table `cake`"),
+            Some(DestructiveKind::Truncate)
+        );
+        assert_eq!(
+            destructive_kind(r#"ALTER TABLE "cake" DROP COLUMN "name""#),
+            Some(DestructiveKind::AlterDrop("COLUMN".to_owned()))
+        );
+        assert_eq!(
+            destructive_kind("ALTER TABLE `cake` DROP FOREIGN KEY `fk-cake`"),
+            Some(DestructiveKind::AlterDrop("FOREIGN".to_owned()))
+        );
+        assert_eq!(
+            destructive_kind(r#"ALTER TABLE "cake" ALTER COLUMN "name" DROP DEFAULT"#),
+            None
+        );
+        assert_eq!(
+            destructive_kind(
+                r#"ALTER TABLE "cake" ALTER COLUMN "name" DROP DEFAULT, DROP COLUMN "price""#
+            ),
+            Some(DestructiveKind::AlterDrop("COLUMN".to_owned()))
+        );
+        assert_eq!(
+            destructive_kind(r#"ALTER TABLE "cake" ADD COLUMN "drop" integer"#),
+            None
+        );
+        assert_eq!(
+            destructive_kind(r#"UPDATE "cake" SET "name" = 'DROP TABLE'"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_statement_class() {
+        assert_eq!(
+            statement_class(r#"UPDATE "cake" SET "name" = 'CREATE TABLE'"#),
+            StatementClass::Dml
+        );
+        assert_eq!(
+            statement_class("WITH `t` AS (SELECT 1) SELECT * FROM `t`"),
+            StatementClass::Dml
+        );
+        assert_eq!(
+            statement_class(r#"CREATE TABLE "select" ("id" integer)"#),
+            StatementClass::Ddl
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use super::tokens;
+
+/// Start of the comment tagging the objects created by the migrations on Postgres, see
+/// [`MigratorTrait::tag_managed_objects`](super::MigratorTrait::tag_managed_objects)
+pub const MANAGED_BY_MARKER: &str = "managed-by: sea-schema";
+
+/// Comment tagging an object created by a migration:
+/// `managed-by: sea-schema <crate version> <migration>`
+pub fn managed_comment(migration: &str) -> String {
+    format!(
+        "{} {} {}",
+        MANAGED_BY_MARKER,
+        env!("CARGO_PKG_VERSION"),
+        migration
+    )
+}
+
+/// The crate version and the migration of a comment written by [`managed_comment`],
+/// `None` if the object is not tagged
+pub fn parse_managed_comment(comment: &str) -> Option<(String, String)> {
+    let rest = comment.strip_prefix(MANAGED_BY_MARKER)?.trim_start();
+    let (version, migration) = rest.split_once(' ')?;
+    Some((version.to_owned(), migration.trim().to_owned()))
+}
+
+/// Postgres statement commenting the object created by a `CREATE TABLE`, `CREATE INDEX`,
+/// `CREATE TYPE` or `ALTER TABLE ... ADD CONSTRAINT` statement, `None` for any other statement
+pub fn comment_statement(sql: &str, comment: &str) -> Option<String> {
+    let tokens = tokens(sql, true);
+    let token = |i: usize| tokens.get(i).map(|t| t.as_str()).unwrap_or_default();
+    let mut i = 2;
+    let (object, name, table) = match (token(0), token(1)) {
+        ("CREATE", "TABLE") => {
+            i = skip_if_not_exists(&tokens, i);
+            ("TABLE", qualified_name(&tokens, &mut i)?, None)
+        }
+        ("CREATE", "TYPE") => ("TYPE", qualified_name(&tokens, &mut i)?, None),
+        ("CREATE", "UNIQUE" | "INDEX") => {
+            i = tokens.iter().position(|t| t == "INDEX")? + 1;
+            if token(i) == "CONCURRENTLY" {
+                i += 1;
+            }
+            i = skip_if_not_exists(&tokens, i);
+            if token(i) == "ON" {
+                return None;
+            }
+            ("INDEX", qualified_name(&tokens, &mut i)?, None)
+        }
+        ("ALTER", "TABLE") => {
+            let table = qualified_name(&tokens, &mut i)?;
+            if token(i) != "ADD" || token(i + 1) != "CONSTRAINT" {
+                return None;
+            }
+            i += 2;
+            ("CONSTRAINT", qualified_name(&tokens, &mut i)?, Some(table))
+        }
+        _ => return None,
+    };
+    let comment = comment.replace('\'', "''");
+    Some(match table {
+        Some(table) => format!(
+            "COMMENT ON {} {} ON {} IS '{}'",
+            object, name, table, comment
+        ),
+        None => format!("COMMENT ON {} {} IS '{}'", object, name, comment),
+    })
+}
+
+fn skip_if_not_exists(tokens: &[String], i: usize) -> usize {
+    match tokens.get(i..i + 3) {
+        Some([a, b, c]) if a == "IF" && b == "NOT" && c == "EXISTS" => i + 3,
+        _ => i,
+    }
+}
+
+/// Read a possibly schema-qualified name at `i`, advancing past it. Unquoted identifiers,
+/// upper-cased by [`tokens`], are folded to lower case like Postgres does.
+fn qualified_name(tokens: &[String], i: &mut usize) -> Option<String> {
+    let ident = |token: &String| match token.starts_with('"') {
+        true => token.clone(),
+        false => token.to_lowercase(),
+    };
+    let mut name = ident(tokens.get(*i)?);
+    *i += 1;
+    while tokens.get(*i).map(|t| t.as_str()) == Some(".") {
+        name.push('.');
+        name.push_str(&ident(tokens.get(*i + 1)?));
+        *i += 2;
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_statement() {
+        let comment = managed_comment("m20220101_000001_create_cake");
+        assert_eq!(
+            parse_managed_comment(&comment),
+            Some((
+                env!("CARGO_PKG_VERSION").to_owned(),
+                "m20220101_000001_create_cake".to_owned()
+            ))
+        );
+        assert_eq!(
+            comment_statement(
+                r#"CREATE TABLE IF NOT EXISTS "shop"."cake" ( "id" integer )"#,
+                "it's"
+            ),
+            Some(r#"COMMENT ON TABLE "shop"."cake" IS 'it''s'"#.to_owned())
+        );
+        assert_eq!(
+            comment_statement(
+                r#"CREATE UNIQUE INDEX "idx-cake-name" ON "cake" ("name")"#,
+                "c"
+            ),
+            Some(r#"COMMENT ON INDEX "idx-cake-name" IS 'c'"#.to_owned())
+        );
+        assert_eq!(
+            comment_statement(
+                r#"ALTER TABLE "fruit" ADD CONSTRAINT "fk-fruit-cake" FOREIGN KEY ("cake_id") REFERENCES "cake" ("id")"#,
+                "c"
+            ),
+            Some(r#"COMMENT ON CONSTRAINT "fk-fruit-cake" ON "fruit" IS 'c'"#.to_owned())
+        );
+        assert_eq!(comment_statement(r#"DROP TABLE "cake""#, "c"), None);
+    }
+}
@@ -0,0 +1,314 @@
+use super::{quote, ObjectKind, PlannedDrop, TableGraph};
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use tracing::info;
+
+/// Kinds of objects removed by [`drop_schema_objects`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectKinds {
+    kinds: Vec<ObjectKind>,
+}
+
+impl ObjectKinds {
+    /// Every kind of object
+    pub fn all() -> Self {
+        Self {
+            kinds: vec![
+                ObjectKind::View,
+                ObjectKind::Trigger,
+                ObjectKind::Event,
+                ObjectKind::ForeignKey,
+                ObjectKind::Table,
+                ObjectKind::Sequence,
+                ObjectKind::Function,
+                ObjectKind::Type,
+            ],
+        }
+    }
+
+    /// No kind of object, to be extended with [`ObjectKinds::with`]
+    pub fn none() -> Self {
+        Self { kinds: Vec::new() }
+    }
+
+    pub fn with(mut self, kind: ObjectKind) -> Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    pub fn without(mut self, kind: ObjectKind) -> Self {
+        self.kinds.retain(|k| *k != kind);
+        self
+    }
+
+    pub fn contains(&self, kind: ObjectKind) -> bool {
+        self.kinds.contains(&kind)
+    }
+}
+
+impl From<ObjectKind> for ObjectKinds {
+    fn from(kind: ObjectKind) -> Self {
+        Self::none().with(kind)
+    }
+}
+
+/// Drop the objects of the given kinds from a schema, e.g. one created by a feature or a branch in
+/// a shared development database, and return the objects dropped in order. The schema itself is
+/// kept. Supported on Postgres, and on MySQL where the schema is a database.
+///
+/// Objects are dropped in dependency order without cascading, such that an object outside the
+/// schema depending on one inside it fails the teardown instead of being dropped along with it:
+/// views, then tables (referencing tables first, breaking foreign key cycles beforehand, their
+/// triggers, indexes and owned sequences going with them), then sequences, functions and types.
+/// Objects belonging to an extension are left alone.
+pub async fn drop_schema_objects(
+    db: &DbConn,
+    schema: &str,
+    kinds: ObjectKinds,
+) -> Result<Vec<PlannedDrop>, DbErr> {
+    let drops = plan_schema_drop(db, schema, &kinds).await?;
+    let db_backend = db.get_database_backend();
+    info!("Dropping {} objects of schema '{}'", drops.len(), schema);
+    for drop in drops.iter() {
+        info!("Dropping {} '{}'", drop.object, drop.name);
+        db.execute(Statement::from_string(db_backend, drop.sql.clone()))
+            .await?;
+    }
+    Ok(drops)
+}
+
+/// Objects [`drop_schema_objects`] would drop from a schema, in order, without dropping them
+pub async fn plan_schema_drop(
+    db: &DbConn,
+    schema: &str,
+    kinds: &ObjectKinds,
+) -> Result<Vec<PlannedDrop>, DbErr> {
+    let db_backend = db.get_database_backend();
+    if db_backend == DbBackend::Sqlite {
+        return Err(DbErr::Custom(
+            "Dropping the objects of a schema is not supported on SQLite".to_owned(),
+        ));
+    }
+    let mut drops = Vec::new();
+
+    if kinds.contains(ObjectKind::View) {
+        drops.extend(query_drops(db, schema, ObjectKind::View, views_sql(db_backend)).await?);
+    }
+    // Triggers go along with their tables
+    if kinds.contains(ObjectKind::Trigger) && !kinds.contains(ObjectKind::Table) {
+        drops.extend(query_drops(db, schema, ObjectKind::Trigger, triggers_sql(db_backend)).await?);
+    }
+    if kinds.contains(ObjectKind::Event) && db_backend == DbBackend::MySql {
+        drops.extend(query_drops(db, schema, ObjectKind::Event, EVENTS_SQL).await?);
+    }
+    if kinds.contains(ObjectKind::Table) {
+        let graph = TableGraph::discover_schema(db, schema).await?;
+        drops.extend(table_drops(db_backend, schema, &graph));
+    }
+    if db_backend == DbBackend::Postgres {
+        if kinds.contains(ObjectKind::Sequence) {
+            drops.extend(query_drops(db, schema, ObjectKind::Sequence, PG_SEQUENCES_SQL).await?);
+        }
+        if kinds.contains(ObjectKind::Function) {
+            drops.extend(query_drops(db, schema, ObjectKind::Function, PG_FUNCTIONS_SQL).await?);
+        }
+        if kinds.contains(ObjectKind::Type) {
+            drops.extend(query_drops(db, schema, ObjectKind::Type, PG_TYPES_SQL).await?);
+        }
+    } else if kinds.contains(ObjectKind::Function) {
+        drops.extend(query_drops(db, schema, ObjectKind::Function, MYSQL_ROUTINES_SQL).await?);
+    }
+    Ok(drops)
+}
+
+/// Drops of the tables of a schema, referencing tables first, preceded by the drops of the
+/// foreign keys forming cycles
+fn table_drops(db_backend: DbBackend, schema: &str, graph: &TableGraph) -> Vec<PlannedDrop> {
+    let qualified =
+        |name: &str| format!("{}.{}", quote(db_backend, schema), quote(db_backend, name));
+    let order = graph.sort();
+    let mut drops = Vec::new();
+    for fk in graph.foreign_keys_within(&order.cyclic) {
+        let sql = match db_backend {
+            DbBackend::MySql => format!(
+                "ALTER TABLE {} DROP FOREIGN KEY {}",
+                qualified(&fk.table),
+                quote(db_backend, &fk.name)
+            ),
+            _ => format!(
+                "ALTER TABLE {} DROP CONSTRAINT IF EXISTS {}",
+                qualified(&fk.table),
+                quote(db_backend, &fk.name)
+            ),
+        };
+        drops.push(PlannedDrop {
+            object: ObjectKind::ForeignKey,
+            name: format!("{}.{}.{}", schema, fk.table, fk.name),
+            sql,
+        });
+    }
+    for table in order.cyclic.iter().chain(order.sorted.iter().rev()) {
+        drops.push(PlannedDrop {
+            object: ObjectKind::Table,
+            name: format!("{}.{}", schema, table),
+            sql: format!("DROP TABLE IF EXISTS {}", qualified(table)),
+        });
+    }
+    drops
+}
+
+/// Query the objects of a kind in the schema, the query selecting their `name` and the `sql`
+/// dropping them given the schema and the quoted schema as parameters (in reverse on MySQL)
+async fn query_drops(
+    db: &DbConn,
+    schema: &str,
+    object: ObjectKind,
+    sql: &str,
+) -> Result<Vec<PlannedDrop>, DbErr> {
+    let db_backend = db.get_database_backend();
+    let values = match db_backend {
+        DbBackend::MySql => vec![quote(db_backend, schema).into(), schema.into()],
+        _ => vec![schema.into(), quote(db_backend, schema).into()],
+    };
+    let stmt = Statement::from_sql_and_values(db_backend, sql, values);
+    let mut drops = Vec::new();
+    for row in db.query_all(stmt).await?.into_iter() {
+        let name: String = row.try_get("", "name")?;
+        drops.push(PlannedDrop {
+            object,
+            name: format!("{}.{}", schema, name),
+            sql: row.try_get("", "sql")?,
+        });
+    }
+    Ok(drops)
+}
+
+fn views_sql(db_backend: DbBackend) -> &'static str {
+    match db_backend {
+        DbBackend::MySql => {
+            r#"SELECT `TABLE_NAME` AS `name`,
+                CONCAT('DROP VIEW IF EXISTS ', ?, '.`', REPLACE(`TABLE_NAME`, '`', '``'), '`') AS `sql`
+            FROM `information_schema`.`VIEWS`
+            WHERE `TABLE_SCHEMA` = ?
+            ORDER BY `TABLE_NAME`"#
+        }
+        // Views created later usually depend on views created earlier
+        _ => {
+            r#"SELECT "c"."relname"::text AS "name",
+                'DROP ' || CASE WHEN "c"."relkind" = 'm' THEN 'MATERIALIZED ' ELSE '' END
+                    || 'VIEW IF EXISTS ' || $2::text || '.' || quote_ident("c"."relname") AS "sql"
+            FROM "pg_class" AS "c"
+            JOIN "pg_namespace" AS "n" ON "n"."oid" = "c"."relnamespace"
+            WHERE "n"."nspname" = $1 AND "c"."relkind" IN ('v', 'm')
+                AND NOT EXISTS (SELECT 1 FROM "pg_depend" AS "d" WHERE "d"."objid" = "c"."oid" AND "d"."deptype" = 'e')
+            ORDER BY "c"."oid" DESC"#
+        }
+    }
+}
+
+fn triggers_sql(db_backend: DbBackend) -> &'static str {
+    match db_backend {
+        DbBackend::MySql => {
+            r#"SELECT `TRIGGER_NAME` AS `name`,
+                CONCAT('DROP TRIGGER IF EXISTS ', ?, '.`', REPLACE(`TRIGGER_NAME`, '`', '``'), '`') AS `sql`
+            FROM `information_schema`.`TRIGGERS`
+            WHERE `TRIGGER_SCHEMA` = ?
+            ORDER BY `TRIGGER_NAME`"#
+        }
+        _ => {
+            r#"SELECT "t"."tgname"::text AS "name",
+                'DROP TRIGGER IF EXISTS ' || quote_ident("t"."tgname") || ' ON ' || $2::text || '.' || quote_ident("c"."relname") AS "sql"
+            FROM "pg_trigger" AS "t"
+            JOIN "pg_class" AS "c" ON "c"."oid" = "t"."tgrelid"
+            JOIN "pg_namespace" AS "n" ON "n"."oid" = "c"."relnamespace"
+            WHERE "n"."nspname" = $1 AND NOT "t"."tgisinternal"
+            ORDER BY "t"."tgname""#
+        }
+    }
+}
+
+const EVENTS_SQL: &str = r#"SELECT `EVENT_NAME` AS `name`,
+        CONCAT('DROP EVENT IF EXISTS ', ?, '.`', REPLACE(`EVENT_NAME`, '`', '``'), '`') AS `sql`
+    FROM `information_schema`.`EVENTS`
+    WHERE `EVENT_SCHEMA` = ?
+    ORDER BY `EVENT_NAME`"#;
+
+const MYSQL_ROUTINES_SQL: &str = r#"SELECT `ROUTINE_NAME` AS `name`,
+        CONCAT('DROP ', `ROUTINE_TYPE`, ' IF EXISTS ', ?, '.`', REPLACE(`ROUTINE_NAME`, '`', '``'), '`') AS `sql`
+    FROM `information_schema`.`ROUTINES`
+    WHERE `ROUTINE_SCHEMA` = ?
+    ORDER BY `ROUTINE_NAME`"#;
+
+/// Sequences not owned by a column, which go along with their tables
+const PG_SEQUENCES_SQL: &str = r#"SELECT "c"."relname"::text AS "name",
+        'DROP SEQUENCE IF EXISTS ' || $2::text || '.' || quote_ident("c"."relname") AS "sql"
+    FROM "pg_class" AS "c"
+    JOIN "pg_namespace" AS "n" ON "n"."oid" = "c"."relnamespace"
+    WHERE "n"."nspname" = $1 AND "c"."relkind" = 'S'
+        AND NOT EXISTS (SELECT 1 FROM "pg_depend" AS "d" WHERE "d"."objid" = "c"."oid" AND "d"."deptype" IN ('a', 'i', 'e'))
+    ORDER BY "c"."relname""#;
+
+/// Functions and procedures, aggregates excluded
+const PG_FUNCTIONS_SQL: &str = r#"SELECT "p"."proname"::text AS "name",
+        'DROP ROUTINE IF EXISTS ' || $2::text || '.' || quote_ident("p"."proname")
+            || '(' || pg_get_function_identity_arguments("p"."oid") || ')' AS "sql"
+    FROM "pg_proc" AS "p"
+    JOIN "pg_namespace" AS "n" ON "n"."oid" = "p"."pronamespace"
+    WHERE "n"."nspname" = $1
+        AND NOT EXISTS (SELECT 1 FROM "pg_aggregate" AS "a" WHERE "a"."aggfnoid" = "p"."oid")
+        AND NOT EXISTS (SELECT 1 FROM "pg_depend" AS "d" WHERE "d"."objid" = "p"."oid" AND "d"."deptype" = 'e')
+    ORDER BY "p"."proname", "p"."oid""#;
+
+/// Composite types first, then domains, then enums, as each may be built on the latter
+const PG_TYPES_SQL: &str = r#"SELECT "t"."typname"::text AS "name",
+        'DROP ' || CASE WHEN "t"."typtype" = 'd' THEN 'DOMAIN' ELSE 'TYPE' END
+            || ' IF EXISTS ' || $2::text || '.' || quote_ident("t"."typname") AS "sql"
+    FROM "pg_type" AS "t"
+    JOIN "pg_namespace" AS "n" ON "n"."oid" = "t"."typnamespace"
+    WHERE "n"."nspname" = $1
+        AND ("t"."typtype" IN ('e', 'd')
+            OR ("t"."typtype" = 'c' AND EXISTS (SELECT 1 FROM "pg_class" AS "c" WHERE "c"."oid" = "t"."typrelid" AND "c"."relkind" = 'c')))
+        AND NOT EXISTS (SELECT 1 FROM "pg_depend" AS "d" WHERE "d"."objid" = "t"."oid" AND "d"."deptype" = 'e')
+    ORDER BY CASE "t"."typtype" WHEN 'c' THEN 0 WHEN 'd' THEN 1 ELSE 2 END, "t"."typname""#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::ForeignKeyEdge;
+
+    #[test]
+    fn test_table_drops() {
+        let fk = |table: &str, referenced_table: &str| ForeignKeyEdge {
+            table: table.to_owned(),
+            name: format!("fk-{}-{}", table, referenced_table),
+            referenced_table: referenced_table.to_owned(),
+        };
+        let graph = TableGraph {
+            tables: vec!["author".to_owned(), "book".to_owned(), "shelf".to_owned()],
+            foreign_keys: vec![
+                fk("book", "author"),
+                fk("author", "book"),
+                fk("book", "shelf"),
+            ],
+        };
+        let drops: Vec<String> = table_drops(DbBackend::Postgres, "feature", &graph)
+            .into_iter()
+            .map(|drop| drop.sql)
+            .collect();
+        assert_eq!(
+            drops,
+            vec![
+                r#"ALTER TABLE "feature"."book" DROP CONSTRAINT IF EXISTS "fk-book-author""#,
+                r#"ALTER TABLE "feature"."author" DROP CONSTRAINT IF EXISTS "fk-author-book""#,
+                r#"DROP TABLE IF EXISTS "feature"."author""#,
+                r#"DROP TABLE IF EXISTS "feature"."book""#,
+                r#"DROP TABLE IF EXISTS "feature"."shelf""#,
+            ]
+        );
+        assert!(!ObjectKinds::all()
+            .without(ObjectKind::Type)
+            .contains(ObjectKind::Type));
+        assert!(ObjectKinds::from(ObjectKind::Function).contains(ObjectKind::Function));
+    }
+}
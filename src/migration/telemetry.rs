@@ -0,0 +1,59 @@
+use super::checksum;
+use sea_orm::DbBackend;
+use tracing::{info_span, Span};
+
+/// Name of the database system following the OpenTelemetry semantic conventions (`db.system`)
+pub fn db_system(db_backend: DbBackend) -> &'static str {
+    match db_backend {
+        DbBackend::MySql => "mysql",
+        DbBackend::Postgres => "postgresql",
+        DbBackend::Sqlite => "sqlite",
+    }
+}
+
+/// Span of a migrator command, carrying the run id correlating its events and history rows
+pub(crate) fn run_span(db_backend: DbBackend, run_id: &str, command: &str) -> Span {
+    info_span!(
+        "migrator_run",
+        otel.name = %format!("migrator {}", command),
+        db.system = db_system(db_backend),
+        run_id,
+        command,
+    )
+}
+
+/// Span of a migration applied or rolled back
+pub(crate) fn migration_span(db_backend: DbBackend, command: &str, version: &str) -> Span {
+    info_span!(
+        "migration",
+        otel.name = %format!("migration {} {}", command, version),
+        db.system = db_system(db_backend),
+        migration.version = version,
+        migration.command = command,
+    )
+}
+
+/// Span of a statement executed by a migration. The statement is identified by the hash of
+/// its SQL, values are bound separately, so that no data ends up in the trace.
+pub(crate) fn statement_span(db_backend: DbBackend, sql: &str) -> Span {
+    info_span!(
+        "db_statement",
+        otel.kind = "client",
+        db.system = db_system(db_backend),
+        db.statement.hash = %checksum(&[sql]),
+    )
+}
+
+/// A [`tracing_subscriber`] layer exporting the spans of the migrator with an OpenTelemetry
+/// tracer, so that migration runs appear in the traces of the deploys triggering them.
+/// The CLI installs it with the tracer returned by
+/// [`MigratorTrait::tracer`](super::MigratorTrait::tracer).
+#[cfg(feature = "with-opentelemetry")]
+pub fn opentelemetry_layer<S>(
+    tracer: opentelemetry::sdk::trace::Tracer,
+) -> impl tracing_subscriber::Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    tracing_opentelemetry::layer().with_tracer(tracer)
+}
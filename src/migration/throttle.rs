@@ -0,0 +1,141 @@
+use super::LagProbe;
+use async_std::channel::{bounded, Receiver, Sender};
+use sea_orm::DbConn;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Execution profile for heavy migrations against busy production databases, bounding
+/// replication lag and I/O pressure by pausing between statements and migrations and
+/// capping the number of statements executed concurrently
+#[derive(Clone, Debug)]
+pub struct Throttle {
+    /// Pause after every statement executed by a migration
+    pub statement_pause: Duration,
+    /// Pause after every migration applied or rolled back
+    pub migration_pause: Duration,
+    /// Replication lag consulted before every statement
+    pub lag_probe: Option<Arc<dyn LagProbe>>,
+    /// Work is paused while the replication lag exceeds `max_lag`
+    pub max_lag: Duration,
+    /// Interval between lag probes while work is paused
+    pub lag_poll_interval: Duration,
+    max_concurrency: usize,
+    /// A bounded channel used as a semaphore, holding one message per running statement
+    permits: (Sender<()>, Receiver<()>),
+}
+
+/// Frees a slot of the semaphore once the statement completes, even if it fails or is cancelled
+struct Permit<'a>(&'a Receiver<()>);
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self {
+            statement_pause: Duration::from_millis(500),
+            migration_pause: Duration::from_secs(5),
+            lag_probe: None,
+            max_lag: Duration::from_secs(10),
+            lag_poll_interval: Duration::from_secs(5),
+            max_concurrency: 1,
+            permits: bounded(1),
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.try_recv();
+    }
+}
+
+impl Throttle {
+    /// Pause after every statement
+    pub fn statement_pause(mut self, statement_pause: Duration) -> Self {
+        self.statement_pause = statement_pause;
+        self
+    }
+
+    /// Pause after every migration
+    pub fn migration_pause(mut self, migration_pause: Duration) -> Self {
+        self.migration_pause = migration_pause;
+        self
+    }
+
+    /// Pause work while the replication lag reported by `lag_probe` exceeds `max_lag`
+    pub fn lag_probe<P>(mut self, lag_probe: P, max_lag: Duration) -> Self
+    where
+        P: LagProbe + 'static,
+    {
+        self.lag_probe = Some(Arc::new(lag_probe));
+        self.max_lag = max_lag;
+        self
+    }
+
+    /// Interval between lag probes while work is paused
+    pub fn lag_poll_interval(mut self, lag_poll_interval: Duration) -> Self {
+        self.lag_poll_interval = lag_poll_interval;
+        self
+    }
+
+    /// Execute at most `max_concurrency` statements at a time, e.g. when a migration
+    /// executes statements concurrently with `futures::future::join_all`
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self.permits = bounded(self.max_concurrency);
+        self
+    }
+
+    pub fn get_max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Wait until the replication lag drops to `max_lag`. A failing probe is logged and ignored.
+    pub async fn wait_for_lag(&self, db: &DbConn) {
+        let lag_probe = match &self.lag_probe {
+            Some(lag_probe) => lag_probe,
+            None => return,
+        };
+        loop {
+            match lag_probe.lag(db).await {
+                Ok(Some(lag)) if lag > self.max_lag => {
+                    warn!(
+                        lag_ms = lag.as_millis() as u64,
+                        "Replication lag of {:?} exceeds {:?}, pausing", lag, self.max_lag
+                    );
+                    async_std::task::sleep(self.lag_poll_interval).await;
+                }
+                Ok(_) => return,
+                Err(err) => {
+                    warn!("Fail to probe replication lag: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Run a statement once replicas caught up and a slot is free, then pause
+    pub(crate) async fn execute<F, T>(&self, db: &DbConn, fut: F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        self.wait_for_lag(db).await;
+        let res = {
+            // The receiver is held by `self`, the channel is never closed
+            let _ = self.permits.0.send(()).await;
+            let _permit = Permit(&self.permits.1);
+            fut.await
+        };
+        if !self.statement_pause.is_zero() {
+            async_std::task::sleep(self.statement_pause).await;
+        }
+        res
+    }
+
+    /// Pause after a migration
+    pub(crate) async fn pause_migration(&self) {
+        if !self.migration_pause.is_zero() {
+            async_std::task::sleep(self.migration_pause).await;
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use super::{cast_bigint, placeholder, quote, SchemaManager};
+use sea_orm::{DbBackend, DbErr, QueryResult, Statement};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Number of mismatching keys reported by a verification
+const MISMATCHED_KEYS_LIMIT: u64 = 10;
+
+/// Factor the rows sampled with `TABLESAMPLE` exceed the requested number by, as Postgres
+/// samples whole pages and the row estimate may be stale
+const TABLESAMPLE_OVERSAMPLING: f64 = 4.0;
+
+/// Comparison of a target column with its source after a backfill, e.g. of the new column with
+/// the old one during an expand/contract change, to run before the old column is dropped.
+///
+/// Values are compared null-safely. Every row is compared unless a sample is configured.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    pub table: String,
+    /// Integer column identifying the rows, usually the primary key
+    pub key: String,
+    /// Expression the target is expected to equal, e.g. `lower("name")` or a column
+    pub source: String,
+    /// Column populated by the backfill
+    pub target: String,
+    /// Only compare the rows whose key is a multiple of `sample_every`
+    pub sample_every: Option<u64>,
+}
+
+/// Outcome of a [`Verification`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+    pub table: String,
+    pub target: String,
+    pub rows_checked: i64,
+    pub mismatches: i64,
+    /// Keys of the first mismatching rows
+    pub mismatched_keys: Vec<i64>,
+    /// Only a sample of the rows was compared
+    pub sampled: bool,
+}
+
+impl Verification {
+    pub fn new<T, K, S, C>(table: T, key: K, source: S, target: C) -> Self
+    where
+        T: Into<String>,
+        K: Into<String>,
+        S: Into<String>,
+        C: Into<String>,
+    {
+        Self {
+            table: table.into(),
+            key: key.into(),
+            source: source.into(),
+            target: target.into(),
+            sample_every: None,
+        }
+    }
+
+    /// Compare one row out of `every`, selected by key, instead of every row
+    pub fn sample_every(mut self, every: u64) -> Self {
+        self.sample_every = Some(every.max(1));
+        self
+    }
+
+    /// Compare the columns and report the mismatches
+    pub async fn run(&self, manager: &SchemaManager<'_>) -> Result<VerificationReport, DbErr> {
+        let db_backend = manager.get_database_backend();
+        let table = quote(db_backend, &self.table);
+        let key = quote(db_backend, &self.key);
+        let mismatch = self.mismatch(db_backend);
+        let sample = match self.sample_every {
+            Some(every) => format!("{} % {} = 0", key, every),
+            None => "1 = 1".to_owned(),
+        };
+        info!(
+            "Verifying column '{}' of table '{}' against {}",
+            self.target, self.table, self.source
+        );
+        let counts = manager
+            .query_one_raw(Statement::from_string(
+                db_backend,
+                format!(
+                    "SELECT {} AS rows_checked, {} AS mismatches FROM {} WHERE {}",
+                    cast_bigint(db_backend, "COUNT(*)"),
+                    cast_bigint(
+                        db_backend,
+                        &format!("COALESCE(SUM(CASE WHEN {} THEN 1 ELSE 0 END), 0)", mismatch)
+                    ),
+                    table,
+                    sample
+                ),
+            ))
+            .await?;
+        let (rows_checked, mismatches) = match counts {
+            Some(row) => (
+                row.try_get("", "rows_checked")?,
+                row.try_get("", "mismatches")?,
+            ),
+            None => (0, 0),
+        };
+        let mut mismatched_keys = Vec::new();
+        if mismatches > 0 {
+            let rows = manager
+                .query_all_raw(Statement::from_string(
+                    db_backend,
+                    format!(
+                        "SELECT {} AS mismatched_key FROM {} WHERE {} AND {} ORDER BY {} LIMIT {}",
+                        cast_bigint(db_backend, &key),
+                        table,
+                        sample,
+                        mismatch,
+                        key,
+                        MISMATCHED_KEYS_LIMIT
+                    ),
+                ))
+                .await?;
+            for row in rows {
+                mismatched_keys.push(row.try_get("", "mismatched_key")?);
+            }
+        }
+        let report = VerificationReport {
+            table: self.table.clone(),
+            target: self.target.clone(),
+            rows_checked,
+            mismatches,
+            mismatched_keys,
+            sampled: self.sample_every.is_some(),
+        };
+        match report.is_clean() {
+            true => info!(
+                "Column '{}' of table '{}' matches in {} rows",
+                self.target, self.table, rows_checked
+            ),
+            false => warn!(
+                "Column '{}' of table '{}' mismatches in {} of {} rows, e.g. keys {:?}",
+                self.target, self.table, mismatches, rows_checked, report.mismatched_keys
+            ),
+        }
+        Ok(report)
+    }
+
+    /// Compare the columns and fail if any mismatch, e.g. to guard the contract step of a change
+    pub async fn ensure(&self, manager: &SchemaManager<'_>) -> Result<VerificationReport, DbErr> {
+        let report = self.run(manager).await?;
+        match report.is_clean() {
+            true => Ok(report),
+            false => Err(DbErr::Custom(format!(
+                "Column '{}' of table '{}' mismatches its source in {} of {} rows, e.g. keys {:?}",
+                report.target,
+                report.table,
+                report.mismatches,
+                report.rows_checked,
+                report.mismatched_keys
+            ))),
+        }
+    }
+
+    /// Condition of a row whose target differs from its source, `NULL` equal to `NULL`
+    fn mismatch(&self, db_backend: DbBackend) -> String {
+        let target = quote(db_backend, &self.target);
+        match db_backend {
+            DbBackend::MySql => format!("NOT ({} <=> ({}))", target, self.source),
+            DbBackend::Postgres => format!("{} IS DISTINCT FROM ({})", target, self.source),
+            DbBackend::Sqlite => format!("{} IS NOT ({})", target, self.source),
+        }
+    }
+}
+
+impl VerificationReport {
+    /// No compared row mismatches
+    pub fn is_clean(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+/// Row Sampling
+impl<'c> SchemaManager<'c> {
+    /// Select `n` random rows of a table, e.g. for smoke checks after a migration. Fewer rows are
+    /// returned if the table holds fewer.
+    ///
+    /// On Postgres, a `TABLESAMPLE SYSTEM` sized from the row estimate of the planner is taken.
+    /// On MySQL and SQLite, random keys are picked between the smallest and largest key of the
+    /// table: the integer primary key on MySQL and the `rowid` on SQLite. Rows after gaps in the
+    /// keys are more likely to be picked, which is fine for validation. Otherwise, or if too few
+    /// rows are sampled, rows are selected with `ORDER BY RANDOM()`, which scans the whole table.
+    pub async fn sample_rows(&self, table: &str, n: u64) -> Result<Vec<QueryResult>, DbErr> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let db_backend = self.get_database_backend();
+        let sampled = match db_backend {
+            DbBackend::Postgres => self.sample_by_tablesample(table, n).await?,
+            DbBackend::MySql => match self.integer_primary_key(table).await? {
+                Some(key) => self.sample_by_key(table, &key, n).await?,
+                None => None,
+            },
+            DbBackend::Sqlite => self.sample_by_key(table, "rowid", n).await?,
+        };
+        match sampled {
+            Some(rows) => Ok(rows),
+            None => {
+                let random = match db_backend {
+                    DbBackend::MySql => "RAND()",
+                    DbBackend::Postgres | DbBackend::Sqlite => "RANDOM()",
+                };
+                self.query_all_raw(Statement::from_string(
+                    db_backend,
+                    format!(
+                        "SELECT * FROM {} ORDER BY {} LIMIT {}",
+                        quote(db_backend, table),
+                        random,
+                        n
+                    ),
+                ))
+                .await
+            }
+        }
+    }
+
+    /// Sample with `TABLESAMPLE SYSTEM`, `None` if the table is too small or too few rows are sampled
+    async fn sample_by_tablesample(
+        &self,
+        table: &str,
+        n: u64,
+    ) -> Result<Option<Vec<QueryResult>>, DbErr> {
+        let db_backend = self.get_database_backend();
+        let table = quote(db_backend, table);
+        let estimate: f64 = match self
+            .query_one_raw(Statement::from_sql_and_values(
+                db_backend,
+                r#"SELECT "reltuples"::float8 AS "estimate" FROM "pg_class" WHERE "oid" = $1::regclass"#,
+                vec![table.as_str().into()],
+            ))
+            .await?
+        {
+            Some(row) => row.try_get("", "estimate")?,
+            None => return Ok(None),
+        };
+        let percent = n as f64 * TABLESAMPLE_OVERSAMPLING * 100.0 / estimate;
+        // The estimate is negative or zero for tables never analyzed
+        if estimate <= 0.0 || percent >= 100.0 {
+            return Ok(None);
+        }
+        let rows = self
+            .query_all_raw(Statement::from_string(
+                db_backend,
+                format!(
+                    "SELECT * FROM {} TABLESAMPLE SYSTEM ({:.6}) ORDER BY RANDOM() LIMIT {}",
+                    table, percent, n
+                ),
+            ))
+            .await?;
+        Ok(Some(rows).filter(|rows| rows.len() as u64 == n))
+    }
+
+    /// Sample by picking random keys between the bounds of an integer key, `None` if too few
+    /// distinct rows are picked
+    async fn sample_by_key(
+        &self,
+        table: &str,
+        key: &str,
+        n: u64,
+    ) -> Result<Option<Vec<QueryResult>>, DbErr> {
+        let db_backend = self.get_database_backend();
+        let table = quote(db_backend, table);
+        let key = quote(db_backend, key);
+        let bounds = self
+            .query_one_raw(Statement::from_string(
+                db_backend,
+                format!(
+                    "SELECT {} AS min_key, {} AS max_key FROM {}",
+                    cast_bigint(db_backend, &format!("MIN({})", key)),
+                    cast_bigint(db_backend, &format!("MAX({})", key)),
+                    table
+                ),
+            ))
+            .await?;
+        let (min, max): (i64, i64) = match bounds {
+            Some(row) => match (row.try_get("", "min_key")?, row.try_get("", "max_key")?) {
+                (Some(min), Some(max)) => (min, max),
+                _ => return Ok(Some(Vec::new())),
+            },
+            None => return Ok(None),
+        };
+        let span = (max as i128 - min as i128 + 1) as u128;
+        let mut keys: Vec<i64> = Vec::new();
+        // Collisions are rare on large tables, a small table is sampled with `ORDER BY` instead
+        for _ in 0..n * 2 {
+            if keys.len() as u64 == n {
+                break;
+            }
+            let pick = (min as i128 + (Uuid::new_v4().as_u128() % span) as i128) as i64;
+            let row = self
+                .query_one_raw(Statement::from_sql_and_values(
+                    db_backend,
+                    &format!(
+                        "SELECT {} AS sample_key FROM {} WHERE {} >= {} ORDER BY {} LIMIT 1",
+                        cast_bigint(db_backend, &key),
+                        table,
+                        key,
+                        placeholder(db_backend, 1),
+                        key
+                    ),
+                    vec![pick.into()],
+                ))
+                .await?;
+            if let Some(row) = row {
+                let picked: i64 = row.try_get("", "sample_key")?;
+                if !keys.contains(&picked) {
+                    keys.push(picked);
+                }
+            }
+        }
+        if (keys.len() as u64) < n {
+            return Ok(None);
+        }
+        let rows = self
+            .query_all_raw(Statement::from_string(
+                db_backend,
+                format!(
+                    "SELECT * FROM {} WHERE {} IN ({})",
+                    table,
+                    key,
+                    keys.iter()
+                        .map(|key| key.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ))
+            .await?;
+        Ok(Some(rows))
+    }
+
+    /// The primary key of a MySQL table if it is a single integer column
+    async fn integer_primary_key(&self, table: &str) -> Result<Option<String>, DbErr> {
+        let rows = self
+            .query_all_raw(Statement::from_sql_and_values(
+                self.get_database_backend(),
+                "SELECT `COLUMN_NAME` AS `name`, `DATA_TYPE` AS `data_type` FROM `information_schema`.`COLUMNS` WHERE `TABLE_SCHEMA` = DATABASE() AND `TABLE_NAME` = ? AND `COLUMN_KEY` = 'PRI'",
+                vec![table.into()],
+            ))
+            .await?;
+        match rows.as_slice() {
+            [row] => {
+                let data_type: String = row.try_get("", "data_type")?;
+                match data_type.to_lowercase().as_str() {
+                    "tinyint" | "smallint" | "mediumint" | "int" | "bigint" => {
+                        Ok(Some(row.try_get("", "name")?))
+                    }
+                    _ => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mismatch() {
+        let verification = Verification::new("cake", "id", "lower(name)", "slug");
+        assert_eq!(
+            verification.mismatch(DbBackend::Postgres),
+            r#""slug" IS DISTINCT FROM (lower(name))"#
+        );
+        assert_eq!(
+            verification.mismatch(DbBackend::MySql),
+            "NOT (`slug` <=> (lower(name)))"
+        );
+    }
+}
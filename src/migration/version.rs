@@ -0,0 +1,127 @@
+use super::seaql_migrations;
+use std::cmp::Ordering;
+
+/// Order of migration versions, used wherever the migrator sorts or compares versions: sorting
+/// the registered migrations, see [`MigratorTrait::sort_by_version`](super::MigratorTrait::sort_by_version),
+/// and ordering the applied ones read from the history store
+pub trait VersionOrder: Send + Sync {
+    fn cmp(&self, a: &str, b: &str) -> Ordering;
+
+    /// Whether the order is the byte order of the versions, so that the database can order
+    /// the history, e.g. to read the last applied migration alone
+    fn is_lexicographic(&self) -> bool {
+        false
+    }
+}
+
+/// Byte order of the versions, the order of the upstream `sea-orm-migration`, suited to
+/// zero-padded timestamps such as `m20220101_000001_create_table`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Lexicographic;
+
+/// Order by the first number in the version, e.g. `9_add_index` before `10_drop_column` or
+/// `V9__init` before `V10__seed`, then by byte order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Numeric;
+
+/// Order by the dot-separated numbers starting the version, compared one by one, e.g.
+/// `v1.2.9_add_index` before `v1.2.10_drop_column` before `v1.10_seed`, then by byte order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SemverLike;
+
+impl VersionOrder for Lexicographic {
+    fn cmp(&self, a: &str, b: &str) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn is_lexicographic(&self) -> bool {
+        true
+    }
+}
+
+impl VersionOrder for Numeric {
+    fn cmp(&self, a: &str, b: &str) -> Ordering {
+        let first = |version: &str| numbers(version).into_iter().next();
+        first(a).cmp(&first(b)).then_with(|| a.cmp(b))
+    }
+}
+
+impl VersionOrder for SemverLike {
+    fn cmp(&self, a: &str, b: &str) -> Ordering {
+        numbers(a).cmp(&numbers(b)).then_with(|| a.cmp(b))
+    }
+}
+
+/// The dot-separated numbers at the start of a version, after a non-numeric prefix such as `v`
+fn numbers(version: &str) -> Vec<u128> {
+    let start = match version.find(|c: char| c.is_ascii_digit()) {
+        Some(start) => start,
+        None => return Vec::new(),
+    };
+    let mut numbers = Vec::new();
+    for part in version[start..].split('.') {
+        let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+        match digits.parse() {
+            Ok(number) => numbers.push(number),
+            Err(_) => break,
+        }
+        // The number is followed by the rest of the version, e.g. `_add_index`
+        if digits.len() < part.len() {
+            break;
+        }
+    }
+    numbers
+}
+
+/// Sort history records by version
+pub(crate) fn sort_records(
+    mut records: Vec<seaql_migrations::Model>,
+    order: &dyn VersionOrder,
+) -> Vec<seaql_migrations::Model> {
+    if !order.is_lexicographic() {
+        records.sort_by(|a, b| order.cmp(&a.version, &b.version));
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(order: &dyn VersionOrder, versions: &[&str]) -> Vec<String> {
+        let mut versions: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+        versions.sort_by(|a, b| order.cmp(a, b));
+        versions
+    }
+
+    #[test]
+    fn test_version_order() {
+        let versions = [
+            "10_drop_column",
+            "9_add_index",
+            "V1.10_seed",
+            "v1.2.10_b",
+            "v1.2.9_a",
+        ];
+        assert_eq!(
+            sorted(&Lexicographic, &versions),
+            vec![
+                "10_drop_column",
+                "9_add_index",
+                "V1.10_seed",
+                "v1.2.10_b",
+                "v1.2.9_a"
+            ]
+        );
+        assert_eq!(
+            sorted(&Numeric, &versions[..2]),
+            vec!["9_add_index", "10_drop_column"]
+        );
+        assert_eq!(
+            sorted(&SemverLike, &versions[2..]),
+            vec!["v1.2.9_a", "v1.2.10_b", "V1.10_seed"]
+        );
+        assert_eq!(numbers("m20220101_000001_create_table"), vec![20220101]);
+        assert_eq!(numbers("create_table"), Vec::<u128>::new());
+    }
+}
@@ -0,0 +1,98 @@
+use super::{
+    delete_schema_meta_value, get_schema_meta, set_schema_meta_value, HistoryStore, MigratorEvent,
+};
+use sea_orm::{DbConn, DbErr};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use tracing::warn;
+
+/// Prefix of the `seaql_schema_meta` entries holding the warnings emitted by an applied
+/// migration, followed by its version
+pub const META_WARNINGS_PREFIX: &str = "warnings:";
+
+/// A non-fatal warning emitted by a migration with [`SchemaManager::warn`](super::SchemaManager::warn)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MigrationWarning {
+    pub migration: String,
+    pub message: String,
+}
+
+impl Display for MigrationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.migration, self.message)
+    }
+}
+
+/// Warnings emitted by the applied migrations when they were applied, by version, as recorded
+/// in `seaql_schema_meta` by the default history store
+pub async fn get_migration_warnings(db: &DbConn) -> Result<BTreeMap<String, Vec<String>>, DbErr> {
+    Ok(get_schema_meta(db)
+        .await?
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let version = key.strip_prefix(META_WARNINGS_PREFIX)?.to_owned();
+            Some((version, value.lines().map(|line| line.to_owned()).collect()))
+        })
+        .collect())
+}
+
+/// Record the warnings emitted by a migration in `seaql_schema_meta`, forgetting the
+/// earlier ones if there is none
+pub(crate) async fn record_warnings(
+    db: &DbConn,
+    version: &str,
+    warnings: &[String],
+) -> Result<(), DbErr> {
+    let key = format!("{}{}", META_WARNINGS_PREFIX, version);
+    match warnings.is_empty() {
+        true => delete_schema_meta_value(db, &key).await,
+        // Every line of the entry is a warning
+        false => {
+            let lines: Vec<String> = warnings
+                .iter()
+                .map(|warning| warning.replace(['\r', '\n'], " "))
+                .collect();
+            set_schema_meta_value(db, &key, &lines.join("\n")).await
+        }
+    }
+}
+
+/// Report the warnings emitted by a migration as lifecycle events, returning the ones to
+/// record with [`store_warnings`] once the migration is recorded as applied
+pub(crate) fn report_warnings(
+    run_id: &str,
+    command: &str,
+    version: &str,
+    warnings: Vec<MigrationWarning>,
+) -> Vec<String> {
+    let mut messages = Vec::new();
+    for warning in warnings.into_iter() {
+        MigratorEvent::MigrationWarning {
+            run_id: run_id.to_owned(),
+            command: command.to_owned(),
+            version: version.to_owned(),
+            message: warning.message.clone(),
+        }
+        .emit();
+        if command != "down" {
+            messages.push(warning.message);
+        }
+    }
+    messages
+}
+
+/// Record the warnings of a migration in the history store after it was applied, or forget
+/// them after it was rolled back. The migration is recorded by then, a failure is only logged.
+pub(crate) async fn store_warnings(
+    history_store: &dyn HistoryStore,
+    db: &DbConn,
+    version: &str,
+    messages: &[String],
+) {
+    if let Err(err) = history_store.record_warnings(db, version, messages).await {
+        warn!(
+            "Fail to record the warnings of migration '{}': {}",
+            version, err
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use super::{current_run, session_activity, MigratorEvent};
+use sea_orm::{ConnectionTrait, DbConn, DbErr, ExecResult, Statement};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Shortest interval between heartbeats, a shorter `heartbeat_interval` is raised to it
+pub const MIN_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Heartbeat and watchdog settings for statements executed by the migrator
+#[derive(Clone, Debug, PartialEq)]
+pub struct Watchdog {
+    /// Interval between [`MigratorEvent::Heartbeat`] events emitted while a statement is
+    /// running, at least [`MIN_HEARTBEAT_INTERVAL`]
+    pub heartbeat_interval: Duration,
+    /// Elapsed time after which a [`MigratorEvent::WatchdogWarning`], along with the sessions
+    /// currently active on the database server, is emitted
+    pub threshold: Option<Duration>,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_secs(10),
+            threshold: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl Watchdog {
+    pub fn new(heartbeat_interval: Duration) -> Self {
+        Self {
+            heartbeat_interval,
+            threshold: None,
+        }
+    }
+
+    /// Emit a [`MigratorEvent::WatchdogWarning`] once the statement has been running for longer than `threshold`
+    pub fn threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Execute the statement, emitting a heartbeat every `heartbeat_interval` until it completes
+    pub async fn execute(&self, db: &DbConn, stmt: Statement) -> Result<ExecResult, DbErr> {
+        let sql = stmt.sql.clone();
+        self.watch(db, &sql, db.execute(stmt)).await
+    }
+
+    /// Wait for the statement being executed, e.g. on a transaction, while querying the active
+    /// sessions on `db`
+    pub(crate) async fn watch<F>(&self, db: &DbConn, sql: &str, exec: F) -> F::Output
+    where
+        F: Future,
+    {
+        // A zero interval would poll the statement in a busy loop
+        let heartbeat_interval = self.heartbeat_interval.max(MIN_HEARTBEAT_INTERVAL);
+        let (run_id, command) = current_run().unwrap_or_default();
+        let start = Instant::now();
+        let mut warned = false;
+        let mut exec = Box::pin(exec);
+        loop {
+            match async_std::future::timeout(heartbeat_interval, &mut exec).await {
+                Ok(res) => return res,
+                Err(_) => {
+                    let elapsed = start.elapsed();
+                    MigratorEvent::Heartbeat {
+                        run_id: run_id.clone(),
+                        command: command.clone(),
+                        statement: sql.to_owned(),
+                        elapsed_ms: elapsed.as_millis() as u64,
+                    }
+                    .emit();
+                    match self.threshold {
+                        Some(threshold) if !warned && elapsed >= threshold => {
+                            warned = true;
+                            let sessions = match session_activity(db).await {
+                                Ok(sessions) => {
+                                    sessions.iter().map(|session| session.to_string()).collect()
+                                }
+                                Err(err) => {
+                                    warn!("Fail to query active sessions: {}", err);
+                                    Vec::new()
+                                }
+                            };
+                            MigratorEvent::WatchdogWarning {
+                                run_id: run_id.clone(),
+                                command: command.clone(),
+                                statement: sql.to_owned(),
+                                elapsed_ms: elapsed.as_millis() as u64,
+                                threshold_ms: threshold.as_millis() as u64,
+                                sessions,
+                            }
+                            .emit();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
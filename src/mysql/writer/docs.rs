@@ -0,0 +1,73 @@
+use crate::classification::Classification;
+use crate::docs::{ColumnDocs, ForeignKeyDocs, IndexDocs, SchemaDocs, TableDocs};
+use crate::mysql::def::{ForeignKeyAction, Schema, TableDef};
+use sea_query::Iden;
+
+impl From<&Schema> for SchemaDocs {
+    fn from(schema: &Schema) -> Self {
+        Self {
+            name: schema.schema.clone(),
+            tables: schema.tables.iter().map(TableDocs::from).collect(),
+        }
+    }
+}
+
+impl From<&TableDef> for TableDocs {
+    fn from(table: &TableDef) -> Self {
+        Self {
+            name: table.info.name.clone(),
+            comment: non_empty(&table.info.comment),
+            columns: table
+                .columns
+                .iter()
+                .map(|column| ColumnDocs {
+                    name: column.name.clone(),
+                    col_type: column.col_type.to_string(),
+                    nullable: column.null,
+                    default: column.default.as_ref().map(|default| default.expr.clone()),
+                    comment: non_empty(&column.comment),
+                    classifications: Classification::from_comment(&column.comment),
+                })
+                .collect(),
+            indexes: table
+                .indexes
+                .iter()
+                .map(|index| IndexDocs {
+                    name: index.name.clone(),
+                    columns: index.parts.iter().map(|part| part.column.clone()).collect(),
+                    primary: index.name == "PRIMARY",
+                    unique: index.unique,
+                })
+                .collect(),
+            foreign_keys: table
+                .foreign_keys
+                .iter()
+                .map(|foreign_key| ForeignKeyDocs {
+                    name: foreign_key.name.clone(),
+                    columns: foreign_key.columns.clone(),
+                    referenced_table: foreign_key.referenced_table.clone(),
+                    referenced_columns: foreign_key.referenced_columns.clone(),
+                    on_update: Some(action(&foreign_key.on_update).to_owned()),
+                    on_delete: Some(action(&foreign_key.on_delete).to_owned()),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn non_empty(comment: &str) -> Option<String> {
+    match comment.is_empty() {
+        true => None,
+        false => Some(comment.to_owned()),
+    }
+}
+
+fn action(action: &ForeignKeyAction) -> &'static str {
+    match action {
+        ForeignKeyAction::Cascade => "CASCADE",
+        ForeignKeyAction::SetNull => "SET NULL",
+        ForeignKeyAction::SetDefault => "SET DEFAULT",
+        ForeignKeyAction::Restrict => "RESTRICT",
+        ForeignKeyAction::NoAction => "NO ACTION",
+    }
+}
@@ -1,12 +1,14 @@
 //! To write [`mysql::Schema`] to SQL statements
 
 mod column;
+mod docs;
 mod foreign_key;
 mod index;
 mod table;
 mod types;
 
 pub use column::*;
+pub use docs::*;
 pub use foreign_key::*;
 pub use index::*;
 pub use table::*;
@@ -65,6 +65,8 @@ pub struct References {
     pub name: String,
     pub columns: Vec<String>,
     pub table: String,
+    /// Schema of the referenced table, `None` if it is the schema of the referencing table
+    pub foreign_schema: Option<String>,
     pub foreign_columns: Vec<String>,
     pub on_update: Option<ForeignKeyAction>,
     pub on_delete: Option<ForeignKeyAction>,
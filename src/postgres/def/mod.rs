@@ -1,11 +1,13 @@
 mod column;
 mod constraints;
+mod privilege;
 mod schema;
 mod table;
 mod types;
 
 pub use column::*;
 pub use constraints::*;
+pub use privilege::*;
 pub use schema::*;
 pub use table::*;
 pub use types::*;
@@ -0,0 +1,23 @@
+#[cfg(feature = "with-serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+/// Owner of a table and the privileges granted on it to other roles, to be restored when
+/// the schema is copied, e.g. into a staging database
+pub struct TablePrivileges {
+    pub table: String,
+    pub owner: String,
+    pub grants: Vec<Grant>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+/// A privilege granted on a table, e.g. `SELECT` to a read-only role
+pub struct Grant {
+    /// Role the privilege is granted to, `PUBLIC` for every role
+    pub grantee: String,
+    pub privilege: String,
+    /// The grantee may grant the privilege to other roles
+    pub grantable: bool,
+}
@@ -2,10 +2,10 @@
 
 use crate::debug_print;
 use crate::postgres::def::*;
-use crate::postgres::parser::parse_table_constraint_query_results;
+use crate::postgres::parser::{parse_table_constraint_query_results, yes_or_no_to_bool};
 use crate::postgres::query::{
     ColumnQueryResult, EnumQueryResult, SchemaQueryBuilder, TableConstraintsQueryResult,
-    TableQueryResult,
+    TableOwnerQueryResult, TablePrivilegeQueryResult, TableQueryResult,
 };
 use futures::future;
 use sea_query::{Alias, Iden, IntoIden, SeaRc};
@@ -187,6 +187,52 @@ impl SchemaDiscovery {
             .collect()
     }
 
+    /// Discover the owner of every table and the privileges granted on it to other roles
+    pub async fn discover_privileges(&self) -> Vec<TablePrivileges> {
+        let rows = self
+            .executor
+            .fetch_all(self.query.query_table_owners(self.schema.clone()))
+            .await;
+
+        let mut privileges: Vec<TablePrivileges> = rows
+            .iter()
+            .map(|row| {
+                let result: TableOwnerQueryResult = row.into();
+                debug_print!("{:?}", result);
+                TablePrivileges {
+                    table: result.table_name,
+                    owner: result.table_owner,
+                    grants: Vec::new(),
+                }
+            })
+            .collect();
+
+        let rows = self
+            .executor
+            .fetch_all(self.query.query_table_privileges(self.schema.clone()))
+            .await;
+
+        for row in rows.iter() {
+            let result: TablePrivilegeQueryResult = row.into();
+            debug_print!("{:?}", result);
+            let table = privileges
+                .iter_mut()
+                .find(|privileges| privileges.table == result.table_name);
+            if let Some(table) = table {
+                // Privileges of the owner come with the ownership
+                if table.owner != result.grantee {
+                    table.grants.push(Grant {
+                        grantee: result.grantee,
+                        privilege: result.privilege_type,
+                        grantable: yes_or_no_to_bool(&result.is_grantable),
+                    });
+                }
+            }
+        }
+
+        privileges
+    }
+
     pub async fn discover_enums(&self) -> Vec<EnumDef> {
         let rows = self.executor.fetch_all(self.query.query_enums()).await;
 
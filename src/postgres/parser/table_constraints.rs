@@ -45,6 +45,10 @@ impl Iterator for TableConstraintsQueryResultParser {
 
                 columns.push(result.column_name.unwrap());
                 let table = result.referential_key_table_name.unwrap();
+                let foreign_schema = match result.referential_key_table_schema {
+                    Some(schema) if schema != result.table_schema => Some(schema),
+                    _ => None,
+                };
                 foreign_columns.push(result.referential_key_column_name.unwrap());
                 let on_update =
                     ForeignKeyAction::from_str(&result.update_rule.clone().unwrap_or_default());
@@ -58,6 +62,7 @@ impl Iterator for TableConstraintsQueryResultParser {
                             name: constraint_name,
                             columns,
                             table,
+                            foreign_schema,
                             foreign_columns,
                             on_update,
                             on_delete,
@@ -75,6 +80,7 @@ impl Iterator for TableConstraintsQueryResultParser {
                     name: constraint_name,
                     columns,
                     table,
+                    foreign_schema,
                     foreign_columns,
                     on_update,
                     on_delete,
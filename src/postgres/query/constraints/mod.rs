@@ -41,6 +41,7 @@ pub struct TableConstraintsQueryResult {
     // From key_column_usage as part of subquery involving referential_constraints
     pub referential_key_table_name: Option<String>,
     pub referential_key_column_name: Option<String>,
+    pub referential_key_table_schema: Option<String>,
 }
 
 impl SchemaQueryBuilder {
@@ -83,6 +84,7 @@ impl SchemaQueryBuilder {
             .columns(vec![
                 (rcsq.clone(), Kcuf::TableName),
                 (rcsq.clone(), Kcuf::ColumnName),
+                (rcsq.clone(), Kcuf::TableSchema),
             ])
             .from((Schema::Schema, InformationSchema::TableConstraints))
             .join(
@@ -136,6 +138,7 @@ impl SchemaQueryBuilder {
                 Query::select()
                     .distinct()
                     .columns(vec![
+                        (Schema::ReferentialConstraints, RefC::ConstraintSchema),
                         (Schema::ReferentialConstraints, RefC::ConstraintName),
                         (Schema::ReferentialConstraints, RefC::UniqueConstraintSchema),
                         (Schema::ReferentialConstraints, RefC::UniqueConstraintName),
@@ -146,17 +149,30 @@ impl SchemaQueryBuilder {
                     .columns(vec![
                         (Schema::KeyColumnUsage, Kcuf::TableName),
                         (Schema::KeyColumnUsage, Kcuf::ColumnName),
+                        (Schema::KeyColumnUsage, Kcuf::TableSchema),
                     ])
                     .from((Schema::Schema, Schema::ReferentialConstraints))
                     .left_join(
                         (Schema::Schema, Schema::KeyColumnUsage),
+                        // The referenced key may live in another schema
                         Expr::tbl(Schema::ReferentialConstraints, RefC::UniqueConstraintName)
-                            .equals(Schema::KeyColumnUsage, Kcuf::ConstraintName),
+                            .equals(Schema::KeyColumnUsage, Kcuf::ConstraintName)
+                            .and(
+                                Expr::tbl(
+                                    Schema::ReferentialConstraints,
+                                    RefC::UniqueConstraintSchema,
+                                )
+                                .equals(Schema::KeyColumnUsage, Kcuf::ConstraintSchema),
+                            ),
                     )
                     .take(),
                 rcsq.clone(),
                 Expr::tbl(Schema::TableConstraints, Tcf::ConstraintName)
-                    .equals(rcsq.clone(), RefC::ConstraintName),
+                    .equals(rcsq.clone(), RefC::ConstraintName)
+                    .and(
+                        Expr::tbl(Schema::TableConstraints, Tcf::ConstraintSchema)
+                            .equals(rcsq.clone(), RefC::ConstraintSchema),
+                    ),
             )
             .and_where(
                 Expr::col((Schema::TableConstraints, Tcf::TableSchema)).eq(schema.to_string()),
@@ -197,6 +213,7 @@ impl From<&PgRow> for TableConstraintsQueryResult {
 
             referential_key_table_name: row.get(16),
             referential_key_column_name: row.get(17),
+            referential_key_table_schema: row.get(18),
         }
     }
 }
@@ -2,6 +2,7 @@ pub mod char_set;
 pub mod column;
 pub mod constraints;
 pub mod enumeration;
+pub mod privilege;
 pub mod schema;
 pub mod table;
 
@@ -9,5 +10,6 @@ pub use char_set::*;
 pub use column::*;
 pub use constraints::*;
 pub use enumeration::*;
+pub use privilege::*;
 pub use schema::*;
 pub use table::*;
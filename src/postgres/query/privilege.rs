@@ -0,0 +1,110 @@
+use super::{InformationSchema, SchemaQueryBuilder};
+use crate::sqlx_types::postgres::PgRow;
+use sea_query::{Expr, Iden, Order, Query, SeaRc, SelectStatement};
+
+#[derive(Debug, sea_query::Iden)]
+/// Ref: https://www.postgresql.org/docs/13/view-pg-tables.html
+pub enum PgTables {
+    #[iden = "pg_catalog"]
+    Schema,
+    #[iden = "pg_tables"]
+    Table,
+    #[iden = "schemaname"]
+    SchemaName,
+    #[iden = "tablename"]
+    TableName,
+    #[iden = "tableowner"]
+    TableOwner,
+}
+
+#[derive(Debug, sea_query::Iden)]
+/// Ref: https://www.postgresql.org/docs/13/infoschema-table-privileges.html
+pub enum TablePrivilegesFields {
+    Grantee,
+    TableSchema,
+    TableName,
+    PrivilegeType,
+    IsGrantable,
+}
+
+#[derive(Debug, Default)]
+pub struct TableOwnerQueryResult {
+    pub table_name: String,
+    pub table_owner: String,
+}
+
+#[derive(Debug, Default)]
+pub struct TablePrivilegeQueryResult {
+    pub table_name: String,
+    pub grantee: String,
+    pub privilege_type: String,
+    pub is_grantable: String,
+}
+
+impl SchemaQueryBuilder {
+    pub fn query_table_owners(&self, schema: SeaRc<dyn Iden>) -> SelectStatement {
+        Query::select()
+            .columns(vec![PgTables::TableName, PgTables::TableOwner])
+            .from((PgTables::Schema, PgTables::Table))
+            .and_where(Expr::col(PgTables::SchemaName).eq(schema.to_string()))
+            .order_by(PgTables::TableName, Order::Asc)
+            .take()
+    }
+
+    pub fn query_table_privileges(&self, schema: SeaRc<dyn Iden>) -> SelectStatement {
+        Query::select()
+            .columns(vec![
+                TablePrivilegesFields::TableName,
+                TablePrivilegesFields::Grantee,
+                TablePrivilegesFields::PrivilegeType,
+                TablePrivilegesFields::IsGrantable,
+            ])
+            .from((
+                InformationSchema::Schema,
+                InformationSchema::TablePrivileges,
+            ))
+            .and_where(Expr::col(TablePrivilegesFields::TableSchema).eq(schema.to_string()))
+            .order_by(TablePrivilegesFields::TableName, Order::Asc)
+            .order_by(TablePrivilegesFields::Grantee, Order::Asc)
+            .order_by(TablePrivilegesFields::PrivilegeType, Order::Asc)
+            .take()
+    }
+}
+
+#[cfg(feature = "sqlx-postgres")]
+impl From<&PgRow> for TableOwnerQueryResult {
+    fn from(row: &PgRow) -> Self {
+        use crate::sqlx_types::Row;
+        Self {
+            table_name: row.get(0),
+            table_owner: row.get(1),
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlx-postgres"))]
+impl From<&PgRow> for TableOwnerQueryResult {
+    fn from(row: &PgRow) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "sqlx-postgres")]
+impl From<&PgRow> for TablePrivilegeQueryResult {
+    fn from(row: &PgRow) -> Self {
+        use crate::sqlx_types::Row;
+        Self {
+            table_name: row.get(0),
+            grantee: row.get(1),
+            privilege_type: row.get(2),
+            is_grantable: row.get(3),
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlx-postgres"))]
+impl From<&PgRow> for TablePrivilegeQueryResult {
+    fn from(row: &PgRow) -> Self {
+        Self::default()
+    }
+}
@@ -27,7 +27,10 @@ impl References {
     pub fn write(&self) -> ForeignKeyCreateStatement {
         let mut key = ForeignKey::create();
         key.name(&self.name);
-        key.to_tbl(Alias::new(&self.table));
+        match &self.foreign_schema {
+            Some(schema) => key.to_tbl((Alias::new(schema), Alias::new(&self.table))),
+            None => key.to_tbl(Alias::new(&self.table)),
+        };
         for column in self.columns.iter() {
             key.from_col(Alias::new(column.as_str()));
         }
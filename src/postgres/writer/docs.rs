@@ -0,0 +1,85 @@
+use crate::docs::{ColumnDocs, ForeignKeyDocs, IndexDocs, SchemaDocs, TableDocs};
+use crate::postgres::def::{ColumnInfo, ForeignKeyAction, Schema, TableDef};
+use sea_query::{Alias, ColumnDef, PostgresQueryBuilder, Table};
+
+impl From<&Schema> for SchemaDocs {
+    fn from(schema: &Schema) -> Self {
+        Self {
+            name: schema.schema.clone(),
+            tables: schema.tables.iter().map(TableDocs::from).collect(),
+        }
+    }
+}
+
+impl From<&TableDef> for TableDocs {
+    fn from(table: &TableDef) -> Self {
+        let primary_keys = table.primary_key_constraints.iter().map(|key| IndexDocs {
+            name: key.name.clone(),
+            columns: key.columns.clone(),
+            primary: true,
+            unique: true,
+        });
+        let unique_keys = table.unique_constraints.iter().map(|key| IndexDocs {
+            name: key.name.clone(),
+            columns: key.columns.clone(),
+            primary: false,
+            unique: true,
+        });
+        Self {
+            name: table.info.name.clone(),
+            comment: None,
+            columns: table
+                .columns
+                .iter()
+                .map(|column| ColumnDocs {
+                    name: column.name.clone(),
+                    col_type: type_name(column),
+                    nullable: column.not_null.is_none(),
+                    default: column.default.as_ref().map(|default| default.0.clone()),
+                    comment: None,
+                    classifications: Vec::new(),
+                })
+                .collect(),
+            indexes: primary_keys.chain(unique_keys).collect(),
+            foreign_keys: table
+                .reference_constraints
+                .iter()
+                .map(|references| ForeignKeyDocs {
+                    name: references.name.clone(),
+                    columns: references.columns.clone(),
+                    referenced_table: match &references.foreign_schema {
+                        Some(schema) => format!("{}.{}", schema, references.table),
+                        None => references.table.clone(),
+                    },
+                    referenced_columns: references.foreign_columns.clone(),
+                    on_update: references.on_update.as_ref().map(action),
+                    on_delete: references.on_delete.as_ref().map(action),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Type of a column as written in `CREATE TABLE`, e.g. `varchar(255)`
+fn type_name(column: &ColumnInfo) -> String {
+    let mut col_def = column.write_col_type(ColumnDef::new(Alias::new("c")));
+    let sql = Table::create()
+        .table(Alias::new("t"))
+        .col(&mut col_def)
+        .to_string(PostgresQueryBuilder);
+    sql.split_once(r#""c" "#)
+        .map(|(_, col_type)| col_type.trim_end_matches(|c| c == ')' || c == ' '))
+        .unwrap_or_default()
+        .to_owned()
+}
+
+fn action(action: &ForeignKeyAction) -> String {
+    match action {
+        ForeignKeyAction::Cascade => "CASCADE",
+        ForeignKeyAction::SetNull => "SET NULL",
+        ForeignKeyAction::SetDefault => "SET DEFAULT",
+        ForeignKeyAction::Restrict => "RESTRICT",
+        ForeignKeyAction::NoAction => "NO ACTION",
+    }
+    .to_owned()
+}
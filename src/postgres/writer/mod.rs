@@ -1,13 +1,17 @@
 mod column;
 mod constraints;
+mod docs;
 mod enumeration;
+mod privilege;
 mod schema;
 mod table;
 mod types;
 
 pub use column::*;
 pub use constraints::*;
+pub use docs::*;
 pub use enumeration::*;
+pub use privilege::*;
 pub use schema::*;
 pub use table::*;
 pub use types::*;
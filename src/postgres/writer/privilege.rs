@@ -0,0 +1,36 @@
+use crate::postgres::def::TablePrivileges;
+
+impl TablePrivileges {
+    /// Statements restoring the owner and the grants of the table in `schema`. These are
+    /// optional when copying a schema, the roles have to exist in the target database.
+    pub fn write(&self, schema: &str) -> Vec<String> {
+        let table = format!("{}.{}", quote(schema), quote(&self.table));
+        let mut statements = vec![format!(
+            "ALTER TABLE {} OWNER TO {}",
+            table,
+            quote(&self.owner)
+        )];
+        for grant in self.grants.iter() {
+            let grantee = match grant.grantee.as_str() {
+                "PUBLIC" => "PUBLIC".to_owned(),
+                grantee => quote(grantee),
+            };
+            statements.push(format!(
+                "GRANT {} ON TABLE {} TO {}{}",
+                grant.privilege,
+                table,
+                grantee,
+                if grant.grantable {
+                    " WITH GRANT OPTION"
+                } else {
+                    ""
+                }
+            ));
+        }
+        statements
+    }
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
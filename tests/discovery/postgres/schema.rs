@@ -341,6 +341,7 @@ Schema {
                         "language_id",
                     ],
                     table: "language",
+                    foreign_schema: None,
                     foreign_columns: [
                         "language_id",
                     ],
@@ -357,6 +358,7 @@ Schema {
                         "original_language_id",
                     ],
                     table: "language",
+                    foreign_schema: None,
                     foreign_columns: [
                         "language_id",
                     ],
@@ -497,6 +499,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -513,6 +516,7 @@ Schema {
                         "rental_id",
                     ],
                     table: "rental",
+                    foreign_schema: None,
                     foreign_columns: [
                         "rental_id",
                     ],
@@ -529,6 +533,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -669,6 +674,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -685,6 +691,7 @@ Schema {
                         "rental_id",
                     ],
                     table: "rental",
+                    foreign_schema: None,
                     foreign_columns: [
                         "rental_id",
                     ],
@@ -701,6 +708,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -841,6 +849,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -857,6 +866,7 @@ Schema {
                         "rental_id",
                     ],
                     table: "rental",
+                    foreign_schema: None,
                     foreign_columns: [
                         "rental_id",
                     ],
@@ -873,6 +883,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -1013,6 +1024,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -1029,6 +1041,7 @@ Schema {
                         "rental_id",
                     ],
                     table: "rental",
+                    foreign_schema: None,
                     foreign_columns: [
                         "rental_id",
                     ],
@@ -1045,6 +1058,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -1185,6 +1199,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -1201,6 +1216,7 @@ Schema {
                         "rental_id",
                     ],
                     table: "rental",
+                    foreign_schema: None,
                     foreign_columns: [
                         "rental_id",
                     ],
@@ -1217,6 +1233,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -1357,6 +1374,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -1373,6 +1391,7 @@ Schema {
                         "rental_id",
                     ],
                     table: "rental",
+                    foreign_schema: None,
                     foreign_columns: [
                         "rental_id",
                     ],
@@ -1389,6 +1408,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -1570,6 +1590,7 @@ Schema {
                         "city_id",
                     ],
                     table: "city",
+                    foreign_schema: None,
                     foreign_columns: [
                         "city_id",
                     ],
@@ -1769,6 +1790,7 @@ Schema {
                         "country_id",
                     ],
                     table: "country",
+                    foreign_schema: None,
                     foreign_columns: [
                         "country_id",
                     ],
@@ -2051,6 +2073,7 @@ Schema {
                         "address_id",
                     ],
                     table: "address",
+                    foreign_schema: None,
                     foreign_columns: [
                         "address_id",
                     ],
@@ -2067,6 +2090,7 @@ Schema {
                         "store_id",
                     ],
                     table: "store",
+                    foreign_schema: None,
                     foreign_columns: [
                         "store_id",
                     ],
@@ -2159,6 +2183,7 @@ Schema {
                         "actor_id",
                     ],
                     table: "actor",
+                    foreign_schema: None,
                     foreign_columns: [
                         "actor_id",
                     ],
@@ -2175,6 +2200,7 @@ Schema {
                         "film_id",
                     ],
                     table: "film",
+                    foreign_schema: None,
                     foreign_columns: [
                         "film_id",
                     ],
@@ -2267,6 +2293,7 @@ Schema {
                         "category_id",
                     ],
                     table: "category",
+                    foreign_schema: None,
                     foreign_columns: [
                         "category_id",
                     ],
@@ -2283,6 +2310,7 @@ Schema {
                         "film_id",
                     ],
                     table: "film",
+                    foreign_schema: None,
                     foreign_columns: [
                         "film_id",
                     ],
@@ -2392,6 +2420,7 @@ Schema {
                         "film_id",
                     ],
                     table: "film",
+                    foreign_schema: None,
                     foreign_columns: [
                         "film_id",
                     ],
@@ -2408,6 +2437,7 @@ Schema {
                         "store_id",
                     ],
                     table: "store",
+                    foreign_schema: None,
                     foreign_columns: [
                         "store_id",
                     ],
@@ -2648,6 +2678,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -2664,6 +2695,7 @@ Schema {
                         "inventory_id",
                     ],
                     table: "inventory",
+                    foreign_schema: None,
                     foreign_columns: [
                         "inventory_id",
                     ],
@@ -2680,6 +2712,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -2900,6 +2933,7 @@ Schema {
                         "address_id",
                     ],
                     table: "address",
+                    foreign_schema: None,
                     foreign_columns: [
                         "address_id",
                     ],
@@ -2916,6 +2950,7 @@ Schema {
                         "store_id",
                     ],
                     table: "store",
+                    foreign_schema: None,
                     foreign_columns: [
                         "store_id",
                     ],
@@ -3025,6 +3060,7 @@ Schema {
                         "address_id",
                     ],
                     table: "address",
+                    foreign_schema: None,
                     foreign_columns: [
                         "address_id",
                     ],
@@ -3041,6 +3077,7 @@ Schema {
                         "manager_staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -3183,6 +3220,7 @@ Schema {
                         "customer_id",
                     ],
                     table: "customer",
+                    foreign_schema: None,
                     foreign_columns: [
                         "customer_id",
                     ],
@@ -3199,6 +3237,7 @@ Schema {
                         "rental_id",
                     ],
                     table: "rental",
+                    foreign_schema: None,
                     foreign_columns: [
                         "rental_id",
                     ],
@@ -3215,6 +3254,7 @@ Schema {
                         "staff_id",
                     ],
                     table: "staff",
+                    foreign_schema: None,
                     foreign_columns: [
                         "staff_id",
                     ],
@@ -93,6 +93,51 @@ async fn main() {
     dbg!(&enum_create_statements);
 
     assert_eq!(create_enum_stmt, enum_create_statements[0]);
+
+    let privileges = schema_discovery.discover_privileges().await;
+
+    dbg!(&privileges);
+
+    let bakery = privileges
+        .iter()
+        .find(|privileges| privileges.table == "bakery")
+        .unwrap();
+    let restore_statements = bakery.write("public");
+
+    dbg!(&restore_statements);
+
+    assert_eq!(
+        restore_statements[0],
+        format!(
+            r#"ALTER TABLE "public"."bakery" OWNER TO "{}""#,
+            bakery.owner
+        )
+    );
+
+    for sql in [
+        r#"CREATE SCHEMA "ref""#,
+        r#"CREATE TABLE "ref"."currency" ("code" char(3) PRIMARY KEY)"#,
+        r#"CREATE TABLE "price_list" ("id" integer PRIMARY KEY, "currency" char(3) NOT NULL, CONSTRAINT "FK_price_list_currency" FOREIGN KEY ("currency") REFERENCES "ref"."currency" ("code"))"#,
+    ] {
+        sqlx::query(sql).execute(&mut executor).await.unwrap();
+    }
+
+    let schema = schema_discovery.discover().await;
+    let price_list = schema
+        .tables
+        .iter()
+        .find(|table| table.info.name == "price_list")
+        .unwrap();
+    let references = &price_list.reference_constraints[0];
+
+    dbg!(references);
+
+    assert_eq!(references.table, "currency");
+    assert_eq!(references.foreign_schema.as_deref(), Some("ref"));
+    assert!(price_list
+        .write()
+        .to_string(PostgresQueryBuilder)
+        .contains(r#"REFERENCES "ref"."currency" ("code")"#));
 }
 
 async fn setup(base_url: &str, db_name: &str) -> Pool<Postgres> {
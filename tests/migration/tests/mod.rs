@@ -1,4 +1,5 @@
-use sea_orm::{Database, DbErr};
+use sea_orm::sea_query::{Alias, ColumnDef, ForeignKey, Query, Table};
+use sea_orm::{ConnectionTrait, Database, DbBackend, DbErr, Statement};
 use sea_schema::migration::*;
 use sea_schema_migration_test::Migrator;
 
@@ -27,6 +28,13 @@ async fn main() -> Result<(), DbErr> {
     assert!(!manager.has_table("cake").await?);
     assert!(!manager.has_table("fruit").await?);
 
+    println!("\nMigrator::destructive_statements");
+    let destructive = Migrator::destructive_statements(db).await?;
+
+    // The seed migration inserts with the connection directly and cannot be planned
+    assert_eq!(destructive.len(), 1);
+    assert_eq!(destructive[0].kind, DestructiveKind::Unplanned);
+
     println!("\nMigrator::up");
     Migrator::up(db, Some(0)).await?;
 
@@ -60,6 +68,83 @@ async fn main() -> Result<(), DbErr> {
     assert!(manager.has_table("cake").await?);
     assert!(manager.has_table("fruit").await?);
 
+    let summary = Migrator::summary(db).await?;
+    assert_eq!((summary.applied, summary.pending), (3, 0));
+    assert_eq!(
+        summary.last_applied.map(|(version, _)| version),
+        Some("m20220118_000003_seed_cake_table".to_owned())
+    );
+
+    let tables = manager.has_tables(&["cake", "fruit", "vegetable"]).await?;
+    assert!(tables["cake"]);
+    assert!(!tables["vegetable"]);
+    let columns = manager.has_columns("fruit", &["name", "color"]).await?;
+    assert!(columns["name"]);
+    assert!(!columns["color"]);
+    let cake_id = manager.column_info("fruit", "cake_id").await?.unwrap();
+    assert!(!cake_id.nullable);
+    assert_eq!(cake_id.default, None);
+    assert_eq!(manager.column_info("fruit", "color").await?, None);
+
+    // The seed migration inserts a single cake
+    assert_eq!(manager.sample_rows("cake", 5).await?.len(), 1);
+    assert!(manager.sample_rows("fruit", 5).await?.is_empty());
+
+    assert!(probe_tables(db).await?.contains(&"fruit".to_owned()));
+    assert!(probe_columns(db, "fruit")
+        .await?
+        .iter()
+        .any(|column| column.name == "cake_id"));
+
+    let models = Migrator::get_migration_models(db).await?;
+    assert!(models.iter().all(|model| model.duration_ms.is_some()));
+    assert!(models[0].checksum.is_some());
+    // The seed migration inserts with the connection directly
+    assert!(models[2].checksum.is_none());
+    let layout = HistoryLayout::detect(db).await?.unwrap();
+    assert!(layout.is_current());
+    let read = Migrator::history_store().read_applied(db).await?;
+    assert_eq!(read.len(), 3);
+    assert_eq!(read[2].run_id, models[2].run_id);
+    // An applied migration cannot be claimed again
+    assert!(!Migrator::history_store().claim(db, &models[0]).await?);
+
+    // No batch is configured, rolling back a batch leaves everything applied
+    assert!(models.iter().all(|model| model.batch.is_none()));
+    Migrator::down_batch(db, "v1.0.0").await?;
+    assert_eq!(Migrator::get_applied_migrations(db).await?.len(), 3);
+
+    let meta = get_schema_meta(db).await?;
+    assert!(meta.contains_key(META_LAST_RUN_ID));
+    // The last run applied the remaining migrations
+    assert_eq!(models[2].run_id.as_ref(), meta.get(META_LAST_RUN_ID));
+    assert_eq!(
+        meta.get(META_HISTORY_LAYOUT),
+        Some(&HISTORY_LAYOUT_VERSION.to_string())
+    );
+    assert_eq!(
+        meta.get(META_SCHEMA_HASH),
+        Some(&schema_snapshot_hash(db).await?)
+    );
+    let snapshot = SchemaSnapshot::discover(db).await?;
+    assert!(snapshot.is_current(db).await?);
+    assert_eq!(SchemaSnapshot::parse(&snapshot.to_text())?, snapshot);
+
+    // Reapply the seed migration in a batch, then roll the batch back
+    Migrator::down(db, Some(1)).await?;
+    BatchMigrator::up(db, None).await?;
+    let models = Migrator::get_migration_models(db).await?;
+    assert_eq!(models[2].batch.as_deref(), Some("v2.0.0"));
+    assert!(models[..2].iter().all(|model| model.batch.is_none()));
+    BatchMigrator::down_batch(db, "v2.0.0").await?;
+    assert_eq!(Migrator::get_applied_migrations(db).await?.len(), 2);
+    Migrator::up(db, None).await?;
+
+    // Nothing is pending, the boot takes and releases the lock only
+    let outcome = auto_migrate_on_boot::<Migrator>(db, &BootPolicy::default()).await?;
+    assert_eq!(outcome, BootOutcome::default());
+    assert!(manager.has_table(BOOT_LOCK_TABLE).await?);
+
     println!("\nMigrator::down");
     Migrator::down(db, None).await?;
 
@@ -67,12 +152,148 @@ async fn main() -> Result<(), DbErr> {
     assert!(!manager.has_table("cake").await?);
     assert!(!manager.has_table("fruit").await?);
 
+    println!("\nSchemaManager::truncate_all_except");
+    // Hens and eggs referencing each other in a cycle, SQLite cannot add a foreign key later
+    let fk_hen_egg = ForeignKey::create()
+        .name("fk-hen-egg_id")
+        .from(Alias::new("hen"), Alias::new("egg_id"))
+        .to(Alias::new("egg"), Alias::new("id"))
+        .to_owned();
+    let mut hen = Table::create();
+    hen.table(Alias::new("hen"))
+        .col(
+            ColumnDef::new(Alias::new("id"))
+                .integer()
+                .not_null()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(Alias::new("egg_id")).integer());
+    if db.get_database_backend() == DbBackend::Sqlite {
+        hen.foreign_key(&mut fk_hen_egg.clone());
+    }
+    manager.create_table(hen).await?;
+    manager
+        .create_table(
+            Table::create()
+                .table(Alias::new("egg"))
+                .col(
+                    ColumnDef::new(Alias::new("id"))
+                        .integer()
+                        .not_null()
+                        .primary_key(),
+                )
+                .col(ColumnDef::new(Alias::new("hen_id")).integer().not_null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .name("fk-egg-hen_id")
+                        .from(Alias::new("egg"), Alias::new("hen_id"))
+                        .to(Alias::new("hen"), Alias::new("id")),
+                )
+                .to_owned(),
+        )
+        .await?;
+    match db.get_database_backend() {
+        // Fixtures of the cycle are loaded with the constraints deferred
+        DbBackend::Postgres => {
+            manager
+                .exec_raw(Statement::from_string(
+                    DbBackend::Postgres,
+                    r#"ALTER TABLE "hen" ADD CONSTRAINT "fk-hen-egg_id" FOREIGN KEY ("egg_id") REFERENCES "egg" ("id") DEFERRABLE"#.to_owned(),
+                ))
+                .await?
+        }
+        DbBackend::MySql => manager.create_foreign_key(fk_hen_egg).await?,
+        DbBackend::Sqlite => {}
+    }
+    manager
+        .exec_stmt(
+            Query::insert()
+                .into_table(Alias::new("hen"))
+                .columns([Alias::new("id")])
+                .values_panic([1.into()])
+                .to_owned(),
+        )
+        .await?;
+    manager
+        .exec_stmt(
+            Query::insert()
+                .into_table(Alias::new("egg"))
+                .columns([Alias::new("id"), Alias::new("hen_id")])
+                .values_panic([1.into(), 1.into()])
+                .to_owned(),
+        )
+        .await?;
+    manager
+        .exec_stmt(
+            Query::update()
+                .table(Alias::new("hen"))
+                .value(Alias::new("egg_id"), 1)
+                .to_owned(),
+        )
+        .await?;
+
+    let emptied = manager.truncate_all_except(&[]).await?;
+    assert!(emptied.contains(&"hen".to_owned()) && emptied.contains(&"egg".to_owned()));
+    assert!(manager.sample_rows("hen", 5).await?.is_empty());
+    assert!(manager.sample_rows("egg", 5).await?.is_empty());
+
+    // Fixtures of tables in a cycle are loaded in a single transaction
+    let fixtures = Fixtures::new()
+        .rows(
+            BulkLoader::new("hen", ["id", "egg_id"]),
+            vec![vec![1.into(), 1.into()]],
+        )
+        .rows(
+            BulkLoader::new("egg", ["id", "hen_id"]),
+            vec![vec![1.into(), 1.into()]],
+        );
+    fixtures.load(&manager).await?;
+    assert_eq!(manager.sample_rows("hen", 5).await?.len(), 1);
+    assert_eq!(manager.sample_rows("egg", 5).await?.len(), 1);
+
+    // A view depending on a table is dropped before it
+    if db.get_database_backend() != DbBackend::Sqlite {
+        manager
+            .exec_raw(Statement::from_string(
+                db.get_database_backend(),
+                "CREATE VIEW hen_view AS SELECT id FROM hen".to_owned(),
+            ))
+            .await?;
+    }
+
+    println!("\nMigrator::fresh_plan");
+    let plan = Migrator::fresh_plan(db).await?;
+
+    assert!(plan
+        .drops
+        .iter()
+        .any(|drop| drop.name == "seaql_migrations"));
+    assert_eq!(plan.applies.len(), 3);
+    if db.get_database_backend() != DbBackend::Sqlite {
+        assert_eq!(plan.drops[0].name, "hen_view");
+    }
+
     println!("\nMigrator::fresh");
     Migrator::fresh(db).await?;
 
     assert!(manager.has_table("cake").await?);
     assert!(manager.has_table("fruit").await?);
 
+    println!("\nSchemaManager::truncate_all_except");
+    assert_eq!(manager.truncate_all_except(&["cake"]).await?, vec!["fruit"]);
+    assert_eq!(
+        manager.truncate_all_except(&[]).await?,
+        vec!["fruit", "cake"]
+    );
+    assert_eq!(Migrator::get_applied_migrations(db).await?.len(), 3);
+
+    println!("\nMigrator::refresh_plan");
+    let plan = Migrator::refresh_plan(db).await?;
+
+    assert_eq!(plan.rollbacks.len(), 3);
+    assert_eq!(plan.applies.len(), 3);
+    assert!(manager.has_table("cake").await?);
+
     println!("\nMigrator::refresh");
     Migrator::refresh(db).await?;
 
@@ -88,5 +309,110 @@ async fn main() -> Result<(), DbErr> {
     println!("\nMigrator::status");
     Migrator::status(db).await?;
 
+    println!("\nMigrator::adopt_from_sea_orm");
+    // The migration table as created by the upstream migrator, without metadata table
+    for table in ["seaql_migrations", "seaql_schema_meta"] {
+        let stmt = Table::drop().table(Alias::new(table)).to_owned();
+        db.execute(db.get_database_backend().build(&stmt)).await?;
+    }
+    let stmt = Table::create()
+        .table(Alias::new("seaql_migrations"))
+        .col(
+            ColumnDef::new(Alias::new("version"))
+                .string()
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("applied_at"))
+                .big_integer()
+                .not_null(),
+        )
+        .to_owned();
+    db.execute(db.get_database_backend().build(&stmt)).await?;
+
+    let layout = HistoryLayout::detect(db).await?.unwrap();
+    assert!(!layout.is_current());
+    assert_eq!(layout.recorded_version, None);
+    let report = Migrator::adopt_from_sea_orm(db).await?;
+    assert!(report.is_compatible());
+    assert!(HistoryLayout::detect(db).await?.unwrap().is_current());
+
+    // The taken over table remains readable and writable by the upstream migrator
+    Migrator::up(db, None).await?;
+    let report = Migrator::adopt_from_sea_orm(db).await?;
+    assert!(report.is_compatible());
+    assert_eq!(report.applied, 3);
+    Migrator::reset(db).await?;
+
     Ok(())
 }
+
+#[async_std::test]
+async fn offline() {
+    for db_backend in [DbBackend::MySql, DbBackend::Postgres, DbBackend::Sqlite] {
+        let migrations = Migrator::build_offline(db_backend).await;
+
+        assert_eq!(migrations.len(), 3);
+        assert_eq!(migrations[0].statements.len(), 1);
+        assert_eq!(migrations[1].statements.len(), 1);
+        // The seed migration inserts with the connection directly
+        assert!(migrations[2].error.is_some());
+
+        let checksums = Migrator::checksums_offline(db_backend).await;
+        assert_eq!(checksums[0].1, migrations[0].checksum());
+    }
+}
+
+#[test]
+fn registration() {
+    assert!(Migrator::unregistered_files("src").unwrap().is_empty());
+    assert!(unregistered_migrations("src").unwrap().is_empty());
+    sea_schema::assert_migration_count!(Migrator, 3);
+}
+
+/// The migrations of `Migrator` applied in the release batch `v2.0.0`
+struct BatchMigrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for BatchMigrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        Migrator::migrations()
+    }
+
+    fn batch() -> Option<String> {
+        Some("v2.0.0".to_owned())
+    }
+}
+
+/// The migrations of `Migrator` registered in reverse order
+struct SwappedMigrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for SwappedMigrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        Migrator::migrations().into_iter().rev().collect()
+    }
+
+    fn sort_by_version() -> bool {
+        true
+    }
+}
+
+#[test]
+fn sort_by_version() {
+    let names: Vec<String> = SwappedMigrator::ordered_migrations()
+        .unwrap()
+        .iter()
+        .map(|migration| migration.name().to_owned())
+        .collect();
+    let expected: Vec<String> = Migrator::migrations()
+        .iter()
+        .map(|migration| migration.name().to_owned())
+        .collect();
+    assert_eq!(names, expected);
+
+    let mut duplicated = Migrator::migrations();
+    duplicated.extend(Migrator::migrations());
+    assert!(sea_schema::migration::sort_by_version(duplicated).is_err());
+}